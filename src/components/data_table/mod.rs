@@ -1,9 +1,13 @@
 mod fk_card;
 mod render;
 mod resize;
+mod selection;
+mod sort;
 mod types;
 
 pub use render::DataTable;
+pub use sort::ColumnSort;
 pub use types::{
-    CellContextMenu, CellDoubleClicked, CellSaveRequested, DataTableColumn, DataTableState, FkDataRequest,
+    CellContextMenu, CellDoubleClicked, CellSaveRequested, CopySelectionRequested, DataTableColumn,
+    DataTableState, FkDataRequest, GridQueryChanged, SelectedCells,
 };
@@ -4,15 +4,37 @@ use gpui_component::checkbox::Checkbox;
 use std::sync::Arc;
 
 use crate::icons::icon_sm;
+use crate::postcommander::database::CellValue;
+use crate::postcommander::types::column_table;
 use crate::theme::ActiveTheme;
 
 use super::fk_card::render_fk_card;
 use super::resize::render_resize_handle;
+use super::selection::{GridCell, Movement};
 use super::types::{
-    CellContextMenu, CellDoubleClicked, CellSaveRequested, DataTableColumn, DataTableState,
+    CellContextMenu, CellDoubleClicked, CellSaveRequested, CopySelectionRequested, DataTableColumn, DataTableState,
     HEADER_HEIGHT, ROW_HEIGHT,
 };
 
+/// Backgrounds for rows and cells with changes waiting to be applied.
+#[derive(Clone, Copy)]
+struct RowMarkColors {
+    pending: u32,
+    selected: u32,
+    draft: u32,
+    deleted: u32,
+}
+
+#[derive(Clone, Copy)]
+enum CellMark {
+    Clean,
+    /// Edited and not yet written; holds the highlight color.
+    Pending(u32),
+    /// An untouched cell of a row that is not inserted yet.
+    Default,
+    Deleted,
+}
+
 #[derive(IntoElement)]
 pub struct DataTable {
     state: Entity<DataTableState>,
@@ -41,7 +63,7 @@ impl RenderOnce for DataTable {
 
         let columns = &state.columns;
         let rows = state.rows.clone();
-        let row_count = rows.len();
+        let row_count = state.visible_row_count();
 
         let col_widths: Vec<Pixels> = columns.iter().map(|c| c.width).collect();
         let columns_width: Pixels = col_widths.iter().copied().sum();
@@ -71,16 +93,30 @@ impl RenderOnce for DataTable {
 
         let row_hover_bg = colors.element_hover;
         let cell_hover_bg = colors.element_selected;
+        let row_marks = RowMarkColors {
+            pending: colors.status_warning_background,
+            selected: colors.element_active,
+            draft: colors.status_success_background,
+            deleted: colors.status_error_background,
+        };
 
         let column_names: Vec<SharedString> = columns.iter().map(|c| c.name.clone()).collect();
         let column_types: Vec<Option<SharedString>> =
             columns.iter().map(|c| c.type_name.clone()).collect();
 
-        let foreign_keys = state
-            .table_context
-            .as_ref()
-            .map(|ctx| ctx.foreign_keys.clone())
-            .unwrap_or_else(|| Arc::new(std::collections::HashMap::new()));
+        // Foreign keys are stored by table column; cells look them up by
+        // the name the result gives the column.
+        let foreign_keys = Arc::new(
+            columns
+                .iter()
+                .enumerate()
+                .filter_map(|(col_ix, col)| {
+                    let (_, context) = column_table(&state.table_contexts, col_ix)?;
+                    let fk = context.foreign_keys.get(context.source_column(col_ix)?)?;
+                    Some((col.name.to_string(), fk.clone()))
+                })
+                .collect::<std::collections::HashMap<_, _>>(),
+        );
 
         let visible_rows = render_visible_rows(
             first_visible_row,
@@ -99,13 +135,17 @@ impl RenderOnce for DataTable {
             accent,
             row_hover_bg,
             cell_hover_bg,
+            row_marks,
             column_names,
             column_types,
             foreign_keys,
+            &state,
             self.state.clone(),
         );
 
+        let focus_handle = state.focus_handle.clone();
         let state_for_scroll = self.state.clone();
+        let state_for_keys = self.state.clone();
         let state_for_measure = self.state.clone();
         let state_for_fk_card = self.state.clone();
         let active_fk_card = state.active_fk_card.clone();
@@ -116,8 +156,38 @@ impl RenderOnce for DataTable {
 
         div()
             .id("data-table-container")
+            .track_focus(&focus_handle)
             .size_full()
             .relative()
+            .on_key_down(move |event: &KeyDownEvent, _window, cx| {
+                let key = event.keystroke.key.as_str();
+                let modifiers = event.keystroke.modifiers;
+                let handled = state_for_keys.update(cx, |state, cx| {
+                    if let Some(movement) = Movement::from_key(key, modifiers.platform) {
+                        state.move_selection(movement, modifiers.shift, cx);
+                    } else if key == "a" && modifiers.platform && !modifiers.shift {
+                        state.select_all(cx);
+                    } else if key == "c" && modifiers.platform {
+                        let Some(selection) = state.selected_cells() else {
+                            return false;
+                        };
+                        cx.emit(CopySelectionRequested { selection });
+                    } else if key == "space" && (modifiers.shift || modifiers.control) {
+                        state.select_active_line(modifiers.control, cx);
+                    } else if key == "escape" {
+                        let Some(active) = state.selection.as_ref().map(|s| s.active()) else {
+                            return false;
+                        };
+                        state.select_cell(active, false, false, cx);
+                    } else {
+                        return false;
+                    }
+                    true
+                });
+                if handled {
+                    cx.stop_propagation();
+                }
+            })
             .child(
                 canvas(
                     move |bounds, _window, cx| {
@@ -174,6 +244,8 @@ fn render_header(
     panel_background: u32,
     border_variant: u32,
 ) -> Div {
+    let any_editable = state.table_contexts.iter().any(|c| c.is_editable());
+
     div()
         .absolute()
         .left(-scroll_offset.x)
@@ -186,13 +258,19 @@ fn render_header(
         .border_b_1()
         .border_color(rgb(border_variant))
         .children(columns.iter().enumerate().map(|(col_idx, col)| {
-            let is_pk = state
-                .table_context
-                .as_ref()
-                .map(|c| c.primary_keys.iter().any(|pk| pk == col.name.as_ref()))
-                .unwrap_or(false);
+            let source = column_table(&state.table_contexts, col_idx);
+            let is_pk = source.is_some_and(|(_, c)| {
+                c.source_column(col_idx)
+                    .is_some_and(|name| c.primary_keys.iter().any(|pk| pk == name))
+            });
+            // Only worth pointing out when other columns can be edited.
+            let is_read_only = any_editable && !source.is_some_and(|(_, c)| c.is_editable());
 
             let col_width = col.width;
+            let sort_rank = state.sort.iter().position(|s| s.col_index == col_idx);
+            let sort_descending = sort_rank.is_some_and(|rank| state.sort[rank].descending);
+            let show_rank = state.sort.len() > 1;
+            let state_for_sort = state_entity.clone();
 
             div()
                 .relative()
@@ -201,8 +279,20 @@ fn render_header(
                 .h(header_height)
                 .child(
                     div()
+                        .id(SharedString::from(format!("column-header-{}", col_idx)))
                         .size_full()
                         .px_3()
+                        .cursor_pointer()
+                        .on_click(move |event, _window, cx| {
+                            let modifiers = event.modifiers();
+                            state_for_sort.update(cx, |state, cx| {
+                                if modifiers.platform {
+                                    state.select_column(col_idx, modifiers.shift, cx);
+                                } else {
+                                    state.toggle_sort(col_idx, modifiers.shift, cx);
+                                }
+                            });
+                        })
                         .flex()
                         .flex_col()
                         .justify_center()
@@ -230,6 +320,21 @@ fn render_header(
                                             .text_color(rgb(accent))
                                             .child("PK"),
                                     )
+                                })
+                                .when(is_read_only, |el| el.child(icon_sm("lock", text_muted)))
+                                .when_some(sort_rank, |el, rank| {
+                                    el.child(icon_sm(
+                                        if sort_descending { "chevron-down" } else { "chevron-up" },
+                                        accent,
+                                    ))
+                                    .when(show_rank, |el| {
+                                        el.child(
+                                            div()
+                                                .text_xs()
+                                                .text_color(rgb(accent))
+                                                .child(format!("{}", rank + 1)),
+                                        )
+                                    })
                                 }),
                         )
                         .when_some(col.type_name.clone(), |el, type_name| {
@@ -251,7 +356,7 @@ fn render_header(
 fn render_visible_rows(
     first_visible_row: usize,
     last_visible_row: usize,
-    rows: &[Vec<SharedString>],
+    rows: &[Vec<CellValue>],
     col_widths: &[Pixels],
     columns_width: Pixels,
     row_height: Pixels,
@@ -265,16 +370,31 @@ fn render_visible_rows(
     accent: u32,
     row_hover_bg: u32,
     cell_hover_bg: u32,
+    row_marks: RowMarkColors,
     column_names: Vec<SharedString>,
     column_types: Vec<Option<SharedString>>,
     foreign_keys: Arc<std::collections::HashMap<String, crate::postcommander::types::ForeignKeyInfo>>,
+    table: &DataTableState,
     state: Entity<DataTableState>,
 ) -> Vec<Stateful<Div>> {
     (first_visible_row..last_visible_row)
-        .map(|row_ix| {
+        .map(|position| {
+            let row_ix = table.row_at(position);
             let row = &rows[row_ix];
-            let bg = if row_ix % 2 == 0 { background } else { panel_background };
-            let row_y = header_height + row_height * row_ix as f32 - scroll_offset.y;
+            let is_draft = table.draft_rows.contains(&row_ix);
+            let is_deleted = table.deleted_rows.contains(&row_ix);
+            let bg = if is_deleted {
+                row_marks.deleted
+            } else if is_draft {
+                row_marks.draft
+            } else if table.selection.as_ref().is_some_and(|s| s.is_whole_row(position)) {
+                row_marks.selected
+            } else if position % 2 == 0 {
+                background
+            } else {
+                panel_background
+            };
+            let row_y = header_height + row_height * position as f32 - scroll_offset.y;
             let state_for_row = state.clone();
             let column_names_for_row = column_names.clone();
             let column_types_for_row = column_types.clone();
@@ -298,9 +418,25 @@ fn render_visible_rows(
                     let column_type = column_types_for_row
                         .get(col_ix)
                         .and_then(|t| t.clone());
+                    let is_pending = table.pending_edits.contains_key(&(row_ix, col_ix));
+                    let grid_cell = GridCell::new(position, col_ix);
+                    let is_selected = table
+                        .selection
+                        .as_ref()
+                        .is_some_and(|s| s.contains(grid_cell) && !s.is_whole_row(position));
+                    let is_active = table.selection.as_ref().is_some_and(|s| s.active() == grid_cell);
+                    let mark = if is_deleted {
+                        CellMark::Deleted
+                    } else if is_pending {
+                        CellMark::Pending(row_marks.pending)
+                    } else if is_draft {
+                        CellMark::Default
+                    } else {
+                        CellMark::Clean
+                    };
                     render_cell(
                         row_ix,
-                        col_ix,
+                        grid_cell,
                         cell,
                         row,
                         &col_widths_for_row,
@@ -309,6 +445,9 @@ fn render_visible_rows(
                         text_muted,
                         accent,
                         cell_hover_bg,
+                        mark,
+                        is_selected.then_some(row_marks.selected),
+                        is_active,
                         &column_names_for_row,
                         column_type,
                         &foreign_keys_for_row,
@@ -332,36 +471,54 @@ fn is_boolean_type(type_name: &Option<SharedString>) -> bool {
 #[allow(clippy::too_many_arguments)]
 fn render_cell(
     row_ix: usize,
-    col_ix: usize,
-    cell: &SharedString,
-    row: &[SharedString],
+    grid_cell: GridCell,
+    cell: &CellValue,
+    row: &[CellValue],
     col_widths: &[Pixels],
     row_height: Pixels,
     text: u32,
     text_muted: u32,
     accent: u32,
     cell_hover_bg: u32,
+    mark: CellMark,
+    selected_bg: Option<u32>,
+    is_active: bool,
     column_names: &[SharedString],
     column_type: Option<SharedString>,
     foreign_keys: &Arc<std::collections::HashMap<String, crate::postcommander::types::ForeignKeyInfo>>,
     state: &Entity<DataTableState>,
 ) -> impl IntoElement {
-    let is_null = cell.as_ref() == "NULL";
+    let col_ix = grid_cell.col;
+    let is_null = cell.is_null();
     let width = col_widths.get(col_ix).copied().unwrap_or(px(150.));
     let cell_value = cell.clone();
+    let cell_text = cell.display();
     let column_name = column_names.get(col_ix).cloned().unwrap_or_else(|| "".into());
     let state_for_cell = state.clone();
     let state_for_context = state.clone();
     let row_data = row.to_vec();
-    let column_names_vec: Vec<String> = column_names.iter().map(|s| s.to_string()).collect();
 
     let fk_info: Option<crate::postcommander::types::ForeignKeyInfo> =
         foreign_keys.get(column_name.as_ref()).cloned();
     let is_fk = fk_info.is_some();
     let is_bool = is_boolean_type(&column_type);
+    let state_for_select = state.clone();
+    let pending_bg = match mark {
+        CellMark::Pending(bg) => Some(bg),
+        _ => None,
+    };
+    // Untouched cells of a new row show the column default, which the
+    // server fills in; cells of a row marked for deletion are struck out.
+    let is_placeholder = matches!(mark, CellMark::Default | CellMark::Deleted);
 
     let cell_content: AnyElement = if is_bool && !is_null {
-        let is_checked = cell.to_lowercase() == "true" || cell.as_ref() == "t";
+        let is_checked = match cell {
+            CellValue::Bool(b) => *b,
+            other => {
+                let text = other.display();
+                text.eq_ignore_ascii_case("true") || text.as_ref() == "t"
+            }
+        };
         let state_for_checkbox = state.clone();
         Checkbox::new(ElementId::Integer((row_ix as u64) << 32 | (col_ix as u64) | 0x8000_0000))
             .checked(is_checked)
@@ -371,7 +528,7 @@ fn render_cell(
                     cx.emit(CellSaveRequested {
                         row_index: row_ix,
                         col_index: col_ix,
-                        new_value: new_value.to_string(),
+                        new_value: Some(new_value.to_string()),
                     });
                 });
             })
@@ -388,18 +545,19 @@ fn render_cell(
                     .overflow_hidden()
                     .whitespace_nowrap()
                     .text_ellipsis()
-                    .text_color(rgb(if is_null {
+                    .text_color(rgb(if is_null || is_placeholder {
                         text_muted
                     } else if is_fk {
                         accent
                     } else {
                         text
                     }))
-                    .when(is_null, |el| el.italic())
+                    .when(is_null || matches!(mark, CellMark::Default), |el| el.italic())
+                    .when(matches!(mark, CellMark::Deleted), |el| el.line_through())
                     .child(if is_null {
                         SharedString::from("—")
                     } else {
-                        cell.clone()
+                        cell_text
                     }),
             )
             .when(is_fk && !is_null, |el| {
@@ -418,7 +576,17 @@ fn render_cell(
         .items_center()
         .overflow_hidden()
         .cursor_pointer()
+        .when_some(pending_bg.or(selected_bg), |el, bg| el.bg(rgb(bg)))
+        .when(is_active, |el| el.border_1().border_color(rgb(accent)))
         .hover(|s| s.bg(rgb(cell_hover_bg)))
+        .on_mouse_down(MouseButton::Left, move |event, window, cx| {
+            let modifiers = event.modifiers;
+            let focus_handle = state_for_select.update(cx, |state, cx| {
+                state.select_cell(grid_cell, modifiers.shift, modifiers.platform, cx);
+                state.focus_handle.clone()
+            });
+            window.focus(&focus_handle, cx);
+        })
         .when(!is_bool, |el| {
             let state_for_click = state_for_cell.clone();
             let cell_value_click = cell_value.clone();
@@ -441,7 +609,7 @@ fn render_cell(
                                 state.show_fk_card(
                                     row_ix,
                                     col_ix,
-                                    cell_value_click.clone(),
+                                    &cell_value_click,
                                     fk.clone(),
                                     cx,
                                 );
@@ -453,13 +621,17 @@ fn render_cell(
         })
         .on_mouse_down(MouseButton::Right, move |event, _window, cx| {
             let position = event.position;
-            state_for_context.update(cx, |_, cx| {
+            state_for_context.update(cx, |state, cx| {
+                if !state.selection.as_ref().is_some_and(|s| s.contains(grid_cell)) {
+                    state.select_cell(grid_cell, false, false, cx);
+                }
+                let selection = state.selected_cells().unwrap_or_default();
                 cx.emit(CellContextMenu {
                     row_index: row_ix,
                     col_index: col_ix,
-                    column_names: column_names_vec.clone(),
                     row_data: row_data.clone(),
                     position,
+                    selection,
                 });
             });
         })
@@ -0,0 +1,241 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// A cell by where it is shown: its row's position in the grid's display
+/// order, and its column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridCell {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl GridCell {
+    pub fn new(row: usize, col: usize) -> Self {
+        Self { row, col }
+    }
+}
+
+/// What is selected in the grid. Every kind has an active cell, which the
+/// keyboard moves from and which a shift-click or shift-move extends to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GridSelection {
+    /// The rectangle between `anchor` and `active`.
+    Cells { anchor: GridCell, active: GridCell },
+    /// Whole rows, by display position.
+    Rows {
+        rows: BTreeSet<usize>,
+        anchor: usize,
+        active: GridCell,
+    },
+    /// Whole columns from `anchor` to the active cell's.
+    Columns { anchor: usize, active: GridCell },
+}
+
+impl GridSelection {
+    pub fn cell(cell: GridCell) -> Self {
+        GridSelection::Cells { anchor: cell, active: cell }
+    }
+
+    pub fn row(cell: GridCell) -> Self {
+        GridSelection::Rows {
+            rows: BTreeSet::from([cell.row]),
+            anchor: cell.row,
+            active: cell,
+        }
+    }
+
+    pub fn column(cell: GridCell) -> Self {
+        GridSelection::Columns { anchor: cell.col, active: cell }
+    }
+
+    pub fn active(&self) -> GridCell {
+        match self {
+            GridSelection::Cells { active, .. }
+            | GridSelection::Rows { active, .. }
+            | GridSelection::Columns { active, .. } => *active,
+        }
+    }
+
+    /// Grows the selection from its anchor to `cell`, keeping its kind.
+    pub fn extend_to(&self, cell: GridCell) -> Self {
+        match self {
+            GridSelection::Cells { anchor, .. } => GridSelection::Cells { anchor: *anchor, active: cell },
+            GridSelection::Rows { anchor, .. } => GridSelection::Rows {
+                rows: span(*anchor, cell.row).collect(),
+                anchor: *anchor,
+                active: cell,
+            },
+            GridSelection::Columns { anchor, .. } => GridSelection::Columns { anchor: *anchor, active: cell },
+        }
+    }
+
+    /// Adds the cell's row to a row selection, or takes it out again.
+    pub fn toggle_row(&self, cell: GridCell) -> Self {
+        let mut rows = match self {
+            GridSelection::Rows { rows, .. } => rows.clone(),
+            _ => BTreeSet::new(),
+        };
+        if !rows.remove(&cell.row) {
+            rows.insert(cell.row);
+        }
+        GridSelection::Rows { rows, anchor: cell.row, active: cell }
+    }
+
+    /// Display positions of the rows with a selected cell, in order.
+    pub fn rows(&self, row_count: usize) -> Vec<usize> {
+        match self {
+            GridSelection::Cells { anchor, active } => {
+                span(anchor.row, active.row).filter(|&r| r < row_count).collect()
+            }
+            GridSelection::Rows { rows, .. } => rows.iter().copied().filter(|&r| r < row_count).collect(),
+            GridSelection::Columns { .. } => (0..row_count).collect(),
+        }
+    }
+
+    /// The columns with a selected cell.
+    pub fn columns(&self, col_count: usize) -> Range<usize> {
+        let (from, to) = match self {
+            GridSelection::Cells { anchor, active } => (anchor.col, active.col),
+            GridSelection::Rows { .. } => return 0..col_count,
+            GridSelection::Columns { anchor, active } => (*anchor, active.col),
+        };
+        from.min(to).min(col_count)..(from.max(to) + 1).min(col_count)
+    }
+
+    pub fn contains(&self, cell: GridCell) -> bool {
+        match self {
+            GridSelection::Cells { anchor, active } => {
+                span(anchor.row, active.row).contains(&cell.row) && span(anchor.col, active.col).contains(&cell.col)
+            }
+            GridSelection::Rows { rows, .. } => rows.contains(&cell.row),
+            GridSelection::Columns { anchor, active } => span(*anchor, active.col).contains(&cell.col),
+        }
+    }
+
+    /// Whether the whole row is selected, as opposed to some of its cells.
+    pub fn is_whole_row(&self, row: usize) -> bool {
+        matches!(self, GridSelection::Rows { rows, .. } if rows.contains(&row))
+    }
+}
+
+fn span(a: usize, b: usize) -> std::ops::RangeInclusive<usize> {
+    a.min(b)..=a.max(b)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Movement {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    RowStart,
+    RowEnd,
+    Top,
+    Bottom,
+}
+
+impl Movement {
+    /// The movement for a key: arrows, page up and down, home and end, and
+    /// with the platform modifier, the first or last row or column.
+    pub fn from_key(key: &str, platform: bool) -> Option<Self> {
+        Some(match (key, platform) {
+            ("up", false) => Movement::Up,
+            ("down", false) => Movement::Down,
+            ("left", false) => Movement::Left,
+            ("right", false) => Movement::Right,
+            ("up", true) | ("home", true) => Movement::Top,
+            ("down", true) | ("end", true) => Movement::Bottom,
+            ("left", true) | ("home", false) => Movement::RowStart,
+            ("right", true) | ("end", false) => Movement::RowEnd,
+            ("pageup", _) => Movement::PageUp,
+            ("pagedown", _) => Movement::PageDown,
+            _ => return None,
+        })
+    }
+}
+
+/// Where `movement` takes a cell in a grid of `row_count` by `col_count`,
+/// paging by `page` rows.
+pub fn move_cell(cell: GridCell, movement: Movement, row_count: usize, col_count: usize, page: usize) -> GridCell {
+    let last_row = row_count.saturating_sub(1);
+    let last_col = col_count.saturating_sub(1);
+    let (row, col) = match movement {
+        Movement::Up => (cell.row.saturating_sub(1), cell.col),
+        Movement::Down => (cell.row + 1, cell.col),
+        Movement::Left => (cell.row, cell.col.saturating_sub(1)),
+        Movement::Right => (cell.row, cell.col + 1),
+        Movement::PageUp => (cell.row.saturating_sub(page.max(1)), cell.col),
+        Movement::PageDown => (cell.row + page.max(1), cell.col),
+        Movement::RowStart => (cell.row, 0),
+        Movement::RowEnd => (cell.row, last_col),
+        Movement::Top => (0, cell.col),
+        Movement::Bottom => (last_row, cell.col),
+    };
+    GridCell::new(row.min(last_row), col.min(last_col))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(row: usize, col: usize) -> GridCell {
+        GridCell::new(row, col)
+    }
+
+    #[test]
+    fn test_cell_range() {
+        let selection = GridSelection::cell(cell(4, 3)).extend_to(cell(2, 1));
+        assert_eq!(selection.active(), cell(2, 1));
+        assert_eq!(selection.rows(10), vec![2, 3, 4]);
+        assert_eq!(selection.columns(5), 1..4);
+        assert!(selection.contains(cell(3, 2)));
+        assert!(!selection.contains(cell(3, 0)));
+        assert!(!selection.is_whole_row(3));
+    }
+
+    #[test]
+    fn test_rows() {
+        let selection = GridSelection::row(cell(1, 2)).extend_to(cell(3, 0));
+        assert_eq!(selection.rows(10), vec![1, 2, 3]);
+        assert_eq!(selection.columns(4), 0..4);
+        assert!(selection.is_whole_row(2));
+
+        let toggled = selection.toggle_row(cell(2, 0)).toggle_row(cell(7, 0));
+        assert_eq!(toggled.rows(10), vec![1, 3, 7]);
+        assert_eq!(GridSelection::cell(cell(5, 1)).toggle_row(cell(6, 1)).rows(10), vec![6]);
+    }
+
+    #[test]
+    fn test_columns() {
+        let selection = GridSelection::column(cell(0, 3)).extend_to(cell(8, 2));
+        assert_eq!(selection.rows(3), vec![0, 1, 2]);
+        assert_eq!(selection.columns(5), 2..4);
+        assert!(selection.contains(cell(1, 2)));
+        assert!(!selection.contains(cell(1, 4)));
+    }
+
+    #[test]
+    fn test_move_cell() {
+        let at = cell(5, 2);
+        assert_eq!(move_cell(at, Movement::Up, 10, 4, 3), cell(4, 2));
+        assert_eq!(move_cell(at, Movement::Right, 10, 4, 3), cell(5, 3));
+        assert_eq!(move_cell(cell(5, 3), Movement::Right, 10, 4, 3), cell(5, 3));
+        assert_eq!(move_cell(at, Movement::PageDown, 10, 4, 3), cell(8, 2));
+        assert_eq!(move_cell(at, Movement::PageDown, 7, 4, 3), cell(6, 2));
+        assert_eq!(move_cell(at, Movement::PageUp, 10, 4, 8), cell(0, 2));
+        assert_eq!(move_cell(at, Movement::RowEnd, 10, 4, 3), cell(5, 3));
+        assert_eq!(move_cell(at, Movement::Top, 10, 4, 3), cell(0, 2));
+        assert_eq!(move_cell(at, Movement::Bottom, 10, 4, 3), cell(9, 2));
+    }
+
+    #[test]
+    fn test_movement_keys() {
+        assert_eq!(Movement::from_key("down", false), Some(Movement::Down));
+        assert_eq!(Movement::from_key("down", true), Some(Movement::Bottom));
+        assert_eq!(Movement::from_key("home", false), Some(Movement::RowStart));
+        assert_eq!(Movement::from_key("pagedown", false), Some(Movement::PageDown));
+        assert_eq!(Movement::from_key("a", false), None);
+    }
+}
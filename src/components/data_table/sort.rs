@@ -0,0 +1,211 @@
+use std::cmp::Ordering;
+
+use crate::postcommander::database::CellValue;
+
+/// One column of the grid's sort, in the order the columns were clicked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColumnSort {
+    pub col_index: usize,
+    pub descending: bool,
+}
+
+/// The next sort after a header click. A plain click cycles the column
+/// through ascending, descending and unsorted on its own; with `extend` the
+/// column is cycled while keeping the others.
+pub(crate) fn toggle_sort(sort: &[ColumnSort], col_index: usize, extend: bool) -> Vec<ColumnSort> {
+    let current = sort.iter().find(|s| s.col_index == col_index).copied();
+    let mut next: Vec<ColumnSort> = if extend {
+        sort.iter().filter(|s| s.col_index != col_index).copied().collect()
+    } else {
+        vec![]
+    };
+    let cycled = match current {
+        None => Some(false),
+        Some(ColumnSort { descending: false, .. }) => Some(true),
+        Some(ColumnSort { descending: true, .. }) => None,
+    };
+    if let Some(descending) = cycled {
+        let column = ColumnSort { col_index, descending };
+        match sort.iter().position(|s| s.col_index == col_index).filter(|_| extend) {
+            Some(position) => next.insert(position, column),
+            None => next.push(column),
+        }
+    }
+    next
+}
+
+/// Orders two cells of a column the way Postgres would for its type, with
+/// NULL after every value, as `ORDER BY` does by default.
+pub(crate) fn compare_cells(a: &CellValue, b: &CellValue, type_name: Option<&str>) -> Ordering {
+    match (a, b) {
+        (CellValue::Null, CellValue::Null) => Ordering::Equal,
+        (CellValue::Null, _) => Ordering::Greater,
+        (_, CellValue::Null) => Ordering::Less,
+        (CellValue::Bool(a), CellValue::Bool(b)) => a.cmp(b),
+        (CellValue::Int(a), CellValue::Int(b)) => a.cmp(b),
+        (CellValue::Uuid(a), CellValue::Uuid(b)) => a.cmp(b),
+        (CellValue::Date(a), CellValue::Date(b)) => a.cmp(b),
+        (CellValue::Time(a), CellValue::Time(b)) => a.cmp(b),
+        (CellValue::Timestamp(a), CellValue::Timestamp(b)) => a.cmp(b),
+        (CellValue::TimestampTz(a), CellValue::TimestampTz(b)) => a.cmp(b),
+        (CellValue::Bytes(a), CellValue::Bytes(b)) => a.cmp(b),
+        (CellValue::Array(a), CellValue::Array(b)) => {
+            for (a, b) in a.iter().zip(b.iter()) {
+                let ordering = compare_cells(a, b, None);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+        _ if is_numeric(a, type_name) && is_numeric(b, type_name) => {
+            match (number(a), number(b)) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                _ => compare_text(a, b),
+            }
+        }
+        _ => compare_text(a, b),
+    }
+}
+
+fn is_numeric(cell: &CellValue, type_name: Option<&str>) -> bool {
+    match cell {
+        CellValue::Int(_) | CellValue::Float(_) | CellValue::Numeric(_) => true,
+        CellValue::Text(_) => type_name.is_some_and(|t| {
+            matches!(
+                t.to_uppercase().as_str(),
+                "INT2" | "INT4" | "INT8" | "FLOAT4" | "FLOAT8" | "NUMERIC" | "MONEY" | "OID"
+            )
+        }),
+        _ => false,
+    }
+}
+
+fn number(cell: &CellValue) -> Option<f64> {
+    match cell {
+        CellValue::Int(i) => Some(*i as f64),
+        CellValue::Float(f) => Some(*f),
+        other => {
+            let text: String = other
+                .display()
+                .chars()
+                .filter(|c| c.is_ascii_digit() || matches!(c, '-' | '.' | 'e' | 'E' | '+'))
+                .collect();
+            text.parse().ok()
+        }
+    }
+}
+
+/// Case-insensitive first, so `apple` sorts next to `Apple` rather than
+/// after `Zebra`.
+fn compare_text(a: &CellValue, b: &CellValue) -> Ordering {
+    let a = a.display();
+    let b = b.display();
+    a.to_lowercase().cmp(&b.to_lowercase()).then_with(|| a.cmp(&b))
+}
+
+/// Whether every word of the search appears in some cell of the row,
+/// ignoring case. NULL cells never match.
+pub(crate) fn row_matches(row: &[CellValue], terms: &[String]) -> bool {
+    terms.iter().all(|term| {
+        row.iter()
+            .any(|cell| !cell.is_null() && cell.display().to_lowercase().contains(term.as_str()))
+    })
+}
+
+pub(crate) fn search_terms(filter: &str) -> Vec<String> {
+    filter.split_whitespace().map(str::to_lowercase).collect()
+}
+
+/// Indices of the rows to show, in display order. Rows not yet inserted
+/// stay at the end whatever the sort or search.
+pub(crate) fn row_order(
+    rows: &[Vec<CellValue>],
+    sort: &[ColumnSort],
+    type_names: &[Option<&str>],
+    filter: &str,
+    is_draft: impl Fn(usize) -> bool,
+) -> Vec<usize> {
+    let terms = search_terms(filter);
+    let (mut order, drafts): (Vec<usize>, Vec<usize>) = (0..rows.len())
+        .filter(|&i| is_draft(i) || row_matches(&rows[i], &terms))
+        .partition(|&i| !is_draft(i));
+
+    if !sort.is_empty() {
+        order.sort_by(|&a, &b| {
+            for column in sort {
+                let type_name = type_names.get(column.col_index).copied().flatten();
+                let ordering = match (rows[a].get(column.col_index), rows[b].get(column.col_index)) {
+                    (Some(a), Some(b)) => compare_cells(a, b, type_name),
+                    _ => Ordering::Equal,
+                };
+                let ordering = if column.descending { ordering.reverse() } else { ordering };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            Ordering::Equal
+        });
+    }
+    order.extend(drafts);
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gpui::SharedString;
+
+    fn text(s: &str) -> CellValue {
+        CellValue::Text(SharedString::from(s.to_string()))
+    }
+
+    fn asc(col_index: usize) -> ColumnSort {
+        ColumnSort { col_index, descending: false }
+    }
+
+    fn desc(col_index: usize) -> ColumnSort {
+        ColumnSort { col_index, descending: true }
+    }
+
+    #[test]
+    fn test_toggle_sort() {
+        assert_eq!(toggle_sort(&[], 1, false), vec![asc(1)]);
+        assert_eq!(toggle_sort(&[asc(1)], 1, false), vec![desc(1)]);
+        assert_eq!(toggle_sort(&[desc(1)], 1, false), vec![]);
+        assert_eq!(toggle_sort(&[asc(1)], 2, false), vec![asc(2)]);
+        assert_eq!(toggle_sort(&[asc(1)], 2, true), vec![asc(1), asc(2)]);
+        assert_eq!(toggle_sort(&[asc(1), asc(2)], 1, true), vec![desc(1), asc(2)]);
+        assert_eq!(toggle_sort(&[desc(1), asc(2)], 1, true), vec![asc(2)]);
+    }
+
+    #[test]
+    fn test_compare_by_type() {
+        assert_eq!(compare_cells(&CellValue::Int(9), &CellValue::Int(10), None), Ordering::Less);
+        let nine = CellValue::Numeric("9.5".into());
+        let ten = CellValue::Numeric("10".into());
+        assert_eq!(compare_cells(&nine, &ten, Some("NUMERIC")), Ordering::Less);
+        assert_eq!(compare_cells(&text("9"), &text("10"), Some("int8")), Ordering::Less);
+        assert_eq!(compare_cells(&text("9"), &text("10"), Some("text")), Ordering::Greater);
+        assert_eq!(compare_cells(&text("apple"), &text("Banana"), None), Ordering::Less);
+        assert_eq!(compare_cells(&CellValue::Null, &CellValue::Int(1), None), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_row_order() {
+        let rows = vec![
+            vec![CellValue::Int(2), text("b")],
+            vec![CellValue::Int(1), text("a")],
+            vec![CellValue::Int(2), text("a")],
+            vec![CellValue::Null, text("c")],
+        ];
+        let types = [Some("int4"), Some("text")];
+        let no_drafts = |_| false;
+        assert_eq!(row_order(&rows, &[], &types, "", no_drafts), vec![0, 1, 2, 3]);
+        assert_eq!(row_order(&rows, &[asc(0)], &types, "", no_drafts), vec![1, 0, 2, 3]);
+        assert_eq!(row_order(&rows, &[desc(0), asc(1)], &types, "", no_drafts), vec![3, 2, 0, 1]);
+        assert_eq!(row_order(&rows, &[asc(0)], &types, "A", no_drafts), vec![1, 2]);
+        assert_eq!(row_order(&rows, &[asc(0)], &types, "2 b", no_drafts), vec![0]);
+        assert_eq!(row_order(&rows, &[asc(0)], &types, "a", |i| i == 3), vec![1, 2, 3]);
+    }
+}
@@ -1,8 +1,13 @@
 use gpui::*;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 
+use crate::postcommander::database::CellValue;
 use crate::postcommander::types::{ForeignKeyInfo, TableContext};
 
+use super::selection::{move_cell, GridCell, GridSelection, Movement};
+use super::sort::{row_order, toggle_sort, ColumnSort};
+
 pub(crate) const ROW_HEIGHT: f32 = 32.;
 pub(crate) const HEADER_HEIGHT: f32 = 48.;
 pub(crate) const MIN_COL_WIDTH: f32 = 50.;
@@ -56,7 +61,18 @@ impl DataTableColumn {
 pub struct CellSaveRequested {
     pub row_index: usize,
     pub col_index: usize,
-    pub new_value: String,
+    /// `None` sets the cell to NULL.
+    pub new_value: Option<String>,
+}
+
+/// A cell changed while edits are buffered, keyed by `(row, column)` in
+/// `DataTableState::pending_edits`.
+#[derive(Clone)]
+pub struct PendingCellEdit {
+    /// The value the cell had when the result was loaded.
+    pub original: CellValue,
+    /// The text to write; `None` writes NULL.
+    pub value: Option<String>,
 }
 
 #[derive(Clone)]
@@ -64,7 +80,14 @@ pub struct CellDoubleClicked {
     pub row_index: usize,
     pub col_index: usize,
     pub column_name: SharedString,
-    pub current_value: SharedString,
+    pub current_value: CellValue,
+}
+
+/// The sort or search changed on a grid that leaves them to the server.
+#[derive(Clone)]
+pub struct GridQueryChanged {
+    pub sort: Vec<ColumnSort>,
+    pub filter: String,
 }
 
 #[derive(Clone)]
@@ -77,9 +100,24 @@ pub struct FkDataRequest {
 pub struct CellContextMenu {
     pub row_index: usize,
     pub col_index: usize,
-    pub column_names: Vec<String>,
-    pub row_data: Vec<SharedString>,
+    pub row_data: Vec<CellValue>,
     pub position: Point<Pixels>,
+    /// The selection the click fell in, or just the clicked cell.
+    pub selection: SelectedCells,
+}
+
+/// The values of the selected cells: the columns the selection spans and,
+/// for each row with a selected cell, its values in those columns.
+#[derive(Clone, Default)]
+pub struct SelectedCells {
+    pub column_names: Vec<String>,
+    pub rows: Vec<Vec<CellValue>>,
+}
+
+/// The platform copy shortcut was pressed in the grid.
+#[derive(Clone)]
+pub struct CopySelectionRequested {
+    pub selection: SelectedCells,
 }
 
 #[derive(Clone)]
@@ -105,22 +143,43 @@ impl Render for DraggedFkCard {
 
 pub struct DataTableState {
     pub(crate) columns: Vec<DataTableColumn>,
-    pub(crate) rows: Arc<Vec<Vec<SharedString>>>,
-    pub(crate) table_context: Option<TableContext>,
+    pub(crate) rows: Arc<Vec<Vec<CellValue>>>,
+    pub(crate) table_contexts: Vec<TableContext>,
     pub(crate) scroll_offset: Point<Pixels>,
     pub(crate) viewport_size: Size<Pixels>,
     pub(crate) container_origin: Point<Pixels>,
     pub(crate) resize_drag: Option<ResizeDragState>,
     pub active_fk_card: Option<FkHoverCardData>,
     pub(crate) fk_card_drag_start: Option<Point<Pixels>>,
+    /// When set, edits are staged in `pending_edits` instead of being
+    /// written straight away.
+    pub(crate) buffer_edits: bool,
+    pub(crate) pending_edits: BTreeMap<(usize, usize), PendingCellEdit>,
+    /// Rows added in the grid and not yet inserted. They always sit at the
+    /// end of `rows`.
+    pub(crate) draft_rows: BTreeSet<usize>,
+    /// Rows marked for deletion and not yet deleted.
+    pub(crate) deleted_rows: BTreeSet<usize>,
+    pub(crate) selection: Option<GridSelection>,
+    pub(crate) focus_handle: FocusHandle,
+    pub(crate) sort: Vec<ColumnSort>,
+    pub(crate) filter: String,
+    /// Names of the columns `sort` was set on.
+    pub(crate) sorted_columns: Vec<SharedString>,
+    /// Indices into `rows` in the order they are shown, once sorted and
+    /// searched. `None` shows every row as loaded.
+    pub(crate) row_order: Option<Vec<usize>>,
+    /// When set, sorting and searching re-run the query instead of
+    /// reordering the loaded rows.
+    pub(crate) sort_on_server: bool,
 }
 
 impl DataTableState {
-    pub fn new(_cx: &mut Context<Self>) -> Self {
+    pub fn new(cx: &mut Context<Self>) -> Self {
         Self {
             columns: vec![],
             rows: Arc::new(vec![]),
-            table_context: None,
+            table_contexts: vec![],
             scroll_offset: Point::default(),
             viewport_size: Size {
                 width: px(100.),
@@ -130,30 +189,65 @@ impl DataTableState {
             resize_drag: None,
             active_fk_card: None,
             fk_card_drag_start: None,
+            buffer_edits: false,
+            pending_edits: BTreeMap::new(),
+            draft_rows: BTreeSet::new(),
+            deleted_rows: BTreeSet::new(),
+            selection: None,
+            focus_handle: cx.focus_handle(),
+            sort: vec![],
+            filter: String::new(),
+            sorted_columns: vec![],
+            row_order: None,
+            sort_on_server: false,
         }
     }
 
+    /// Sets the result's columns. The sort is kept when the columns are the
+    /// ones it was made on, as when the query is run again.
     pub fn set_columns(&mut self, columns: Vec<DataTableColumn>) {
+        let names: Vec<SharedString> = columns.iter().map(|c| c.name.clone()).collect();
+        if names != self.sorted_columns {
+            self.sort.clear();
+            self.sorted_columns = names;
+        }
         self.columns = columns;
     }
 
-    pub fn set_rows(&mut self, rows: Arc<Vec<Vec<SharedString>>>) {
+    pub fn set_rows(&mut self, rows: Arc<Vec<Vec<CellValue>>>) {
         self.rows = rows;
+        self.reset_row_state();
+        self.refresh_row_order();
     }
 
-    pub fn rows(&self) -> &Arc<Vec<Vec<SharedString>>> {
+    pub fn rows(&self) -> &Arc<Vec<Vec<CellValue>>> {
         &self.rows
     }
 
-    pub fn set_table_context(&mut self, context: Option<TableContext>) {
-        self.table_context = context;
+    /// Appends a batch of rows from a result that is still streaming in.
+    pub fn append_rows(&mut self, rows: Vec<Vec<CellValue>>) {
+        Arc::make_mut(&mut self.rows).extend(rows);
+        self.refresh_row_order();
+    }
+
+    pub fn set_table_contexts(&mut self, contexts: Vec<TableContext>) {
+        self.table_contexts = contexts;
     }
 
     pub fn clear(&mut self) {
         self.columns.clear();
         self.rows = Arc::new(vec![]);
-        self.table_context = None;
+        self.table_contexts.clear();
         self.scroll_offset = Point::default();
+        self.reset_row_state();
+        self.refresh_row_order();
+    }
+
+    fn reset_row_state(&mut self) {
+        self.pending_edits.clear();
+        self.draft_rows.clear();
+        self.deleted_rows.clear();
+        self.selection = None;
     }
 
     pub fn set_edit_saving(&mut self, _saving: bool, _cx: &mut Context<Self>) {}
@@ -164,7 +258,7 @@ impl DataTableState {
         &mut self,
         row_index: usize,
         col_index: usize,
-        new_value: SharedString,
+        new_value: CellValue,
     ) {
         let rows = Arc::make_mut(&mut self.rows);
         if let Some(row) = rows.get_mut(row_index) {
@@ -176,11 +270,393 @@ impl DataTableState {
 
     pub fn finish_editing(&mut self, _cx: &mut Context<Self>) {}
 
+    pub fn buffers_edits(&self) -> bool {
+        self.buffer_edits
+    }
+
+    pub fn set_buffer_edits(&mut self, buffer: bool, cx: &mut Context<Self>) {
+        self.buffer_edits = buffer;
+        cx.notify();
+    }
+
+    /// Shows `new_cell` in the grid and records the change. Editing a cell
+    /// back to its original value drops the change.
+    pub fn stage_edit(
+        &mut self,
+        row_index: usize,
+        col_index: usize,
+        value: Option<String>,
+        new_cell: CellValue,
+        cx: &mut Context<Self>,
+    ) {
+        let rows = Arc::make_mut(&mut self.rows);
+        let Some(cell) = rows.get_mut(row_index).and_then(|row| row.get_mut(col_index)) else {
+            return;
+        };
+        let key = (row_index, col_index);
+        let original = match self.pending_edits.remove(&key) {
+            Some(pending) => pending.original,
+            None => cell.clone(),
+        };
+        *cell = new_cell;
+        if *cell != original {
+            self.pending_edits.insert(key, PendingCellEdit { original, value });
+        }
+        cx.notify();
+    }
+
+    pub fn pending_edits(&self) -> &BTreeMap<(usize, usize), PendingCellEdit> {
+        &self.pending_edits
+    }
+
+    /// Staged edits, new rows or deletions waiting to be applied.
+    pub fn has_pending_changes(&self) -> bool {
+        !self.pending_edits.is_empty() || !self.draft_rows.is_empty() || !self.deleted_rows.is_empty()
+    }
+
+    /// Number of statements applying the pending changes will run: one per
+    /// edited, new or deleted row.
+    pub fn pending_change_count(&self) -> usize {
+        let edited: BTreeSet<usize> = self
+            .pending_edits
+            .keys()
+            .map(|(row_index, _)| *row_index)
+            .filter(|row_index| !self.draft_rows.contains(row_index) && !self.deleted_rows.contains(row_index))
+            .collect();
+        edited.len() + self.draft_rows.len() + self.deleted_rows.len()
+    }
+
+    /// The row as loaded, ignoring any staged edits. Primary key values for
+    /// a WHERE clause must come from here.
+    pub fn original_row(&self, row_index: usize) -> Option<Vec<CellValue>> {
+        let mut row = self.rows.get(row_index)?.clone();
+        for ((_, col_index), pending) in self
+            .pending_edits
+            .range((row_index, 0)..=(row_index, usize::MAX))
+        {
+            if let Some(cell) = row.get_mut(*col_index) {
+                *cell = pending.original.clone();
+            }
+        }
+        Some(row)
+    }
+
+    pub fn draft_rows(&self) -> &BTreeSet<usize> {
+        &self.draft_rows
+    }
+
+    pub fn is_draft_row(&self, row_index: usize) -> bool {
+        self.draft_rows.contains(&row_index)
+    }
+
+    pub fn deleted_rows(&self) -> &BTreeSet<usize> {
+        &self.deleted_rows
+    }
+
+    /// Appends a row to be inserted and selects it.
+    pub fn add_draft_row(&mut self, cells: Vec<CellValue>, cx: &mut Context<Self>) -> usize {
+        let rows = Arc::make_mut(&mut self.rows);
+        rows.push(cells);
+        let row_index = rows.len() - 1;
+        self.draft_rows.insert(row_index);
+        self.refresh_row_order();
+        self.selection = self
+            .display_position(row_index)
+            .map(|position| GridSelection::row(GridCell::new(position, 0)));
+        self.scroll_offset.y = (self.content_size().height - self.viewport_size.height).max(px(0.));
+        cx.notify();
+        row_index
+    }
+
+    /// Marks rows for deletion. Rows that were never inserted are simply
+    /// removed.
+    pub fn mark_rows_deleted(&mut self, row_indices: &BTreeSet<usize>, cx: &mut Context<Self>) {
+        for &row_index in row_indices.iter().rev() {
+            if self.draft_rows.contains(&row_index) {
+                self.remove_row(row_index);
+            } else if row_index < self.rows.len() {
+                self.deleted_rows.insert(row_index);
+            }
+        }
+        self.selection = None;
+        cx.notify();
+    }
+
+    /// Puts every staged cell back, drops new rows and unmarks deletions.
+    pub fn discard_pending_changes(&mut self, cx: &mut Context<Self>) {
+        let pending = std::mem::take(&mut self.pending_edits);
+        for ((row_index, col_index), edit) in pending {
+            self.update_cell_value(row_index, col_index, edit.original);
+        }
+        if let Some(&first_draft) = self.draft_rows.first() {
+            Arc::make_mut(&mut self.rows).truncate(first_draft);
+        }
+        self.reset_row_state();
+        self.refresh_row_order();
+        cx.notify();
+    }
+
+    /// Once the pending changes are written, shows the rows the server
+    /// returned and drops the deleted ones.
+    pub fn commit_pending_changes(
+        &mut self,
+        written: Vec<(usize, Vec<(usize, CellValue)>)>,
+        cx: &mut Context<Self>,
+    ) {
+        for (row_index, cells) in written {
+            for (col_index, cell) in cells {
+                self.update_cell_value(row_index, col_index, cell);
+            }
+        }
+        let rows = Arc::make_mut(&mut self.rows);
+        for &row_index in self.deleted_rows.iter().rev() {
+            if row_index < rows.len() {
+                rows.remove(row_index);
+            }
+        }
+        self.reset_row_state();
+        self.refresh_row_order();
+        cx.notify();
+    }
+
+    /// Removes one row, shifting the row indices kept for later rows.
+    fn remove_row(&mut self, row_index: usize) {
+        let rows = Arc::make_mut(&mut self.rows);
+        if row_index >= rows.len() {
+            return;
+        }
+        rows.remove(row_index);
+
+        let shift = |r: usize| if r > row_index { r - 1 } else { r };
+        let shift_set = |set: &BTreeSet<usize>| -> BTreeSet<usize> {
+            set.iter().filter(|&&r| r != row_index).map(|&r| shift(r)).collect()
+        };
+        self.pending_edits = std::mem::take(&mut self.pending_edits)
+            .into_iter()
+            .filter(|((r, _), _)| *r != row_index)
+            .map(|((r, c), edit)| ((shift(r), c), edit))
+            .collect();
+        self.draft_rows = shift_set(&self.draft_rows);
+        self.deleted_rows = shift_set(&self.deleted_rows);
+        self.selection = None;
+        self.refresh_row_order();
+    }
+
+    pub fn selection(&self) -> Option<&GridSelection> {
+        self.selection.as_ref()
+    }
+
+    /// Rows selected whole, as indices into `rows`. A range of cells or a
+    /// column selects none.
+    pub fn selected_rows(&self) -> BTreeSet<usize> {
+        self.selection
+            .iter()
+            .flat_map(|s| {
+                s.rows(self.visible_row_count())
+                    .into_iter()
+                    .filter(move |&position| s.is_whole_row(position))
+            })
+            .map(|position| self.row_at(position))
+            .collect()
+    }
+
+    /// Selects a cell on click. `extend` grows the selection to it from
+    /// where it started; `toggle` adds or removes the cell's whole row.
+    pub fn select_cell(&mut self, cell: GridCell, extend: bool, toggle: bool, cx: &mut Context<Self>) {
+        self.selection = Some(match &self.selection {
+            Some(selection) if extend => selection.extend_to(cell),
+            Some(selection) if toggle => selection.toggle_row(cell),
+            None if toggle => GridSelection::row(cell),
+            _ => GridSelection::cell(cell),
+        });
+        cx.notify();
+    }
+
+    /// Selects a whole column from its header; `extend` runs the selection
+    /// on to it from the column selected before.
+    pub fn select_column(&mut self, col_index: usize, extend: bool, cx: &mut Context<Self>) {
+        let row = self.selection.as_ref().map_or(0, |s| s.active().row);
+        let cell = GridCell::new(row, col_index);
+        self.selection = Some(match &self.selection {
+            Some(selection @ GridSelection::Columns { .. }) if extend => selection.extend_to(cell),
+            _ => GridSelection::column(cell),
+        });
+        cx.notify();
+    }
+
+    pub fn select_all(&mut self, cx: &mut Context<Self>) {
+        let (row_count, col_count) = (self.visible_row_count(), self.columns.len());
+        if row_count == 0 || col_count == 0 {
+            return;
+        }
+        self.selection = Some(
+            GridSelection::cell(GridCell::new(0, 0)).extend_to(GridCell::new(row_count - 1, col_count - 1)),
+        );
+        cx.notify();
+    }
+
+    /// Widens the selection to the whole rows or columns of the active cell.
+    pub fn select_active_line(&mut self, columns: bool, cx: &mut Context<Self>) {
+        let Some(active) = self.selection.as_ref().map(GridSelection::active) else {
+            return;
+        };
+        self.selection = Some(if columns {
+            GridSelection::column(active)
+        } else {
+            GridSelection::row(active)
+        });
+        cx.notify();
+    }
+
+    /// Moves the active cell from the keyboard; `extend` grows the
+    /// selection instead of starting a new one.
+    pub fn move_selection(&mut self, movement: Movement, extend: bool, cx: &mut Context<Self>) {
+        let (row_count, col_count) = (self.visible_row_count(), self.columns.len());
+        if row_count == 0 || col_count == 0 {
+            return;
+        }
+        let page = (f32::from(self.viewport_size.height - px(HEADER_HEIGHT)) / ROW_HEIGHT).floor() as usize;
+        let cell = match &self.selection {
+            Some(selection) => move_cell(selection.active(), movement, row_count, col_count, page),
+            None => GridCell::new(0, 0),
+        };
+        self.selection = Some(match &self.selection {
+            Some(selection) if extend => selection.extend_to(cell),
+            _ => GridSelection::cell(cell),
+        });
+        self.scroll_to_cell(cell);
+        cx.notify();
+    }
+
+    /// Scrolls just far enough for the cell to be in view.
+    fn scroll_to_cell(&mut self, cell: GridCell) {
+        let left: Pixels = self.columns.iter().take(cell.col).map(|c| c.width).sum();
+        let right = left + self.columns.get(cell.col).map_or(px(0.), |c| c.width);
+        if left < self.scroll_offset.x {
+            self.scroll_offset.x = left;
+        } else if right > self.scroll_offset.x + self.viewport_size.width {
+            self.scroll_offset.x = right - self.viewport_size.width;
+        }
+
+        let top = px(ROW_HEIGHT) * cell.row as f32;
+        let bottom = top + px(ROW_HEIGHT);
+        let rows_height = self.viewport_size.height - px(HEADER_HEIGHT);
+        if top < self.scroll_offset.y {
+            self.scroll_offset.y = top;
+        } else if bottom > self.scroll_offset.y + rows_height {
+            self.scroll_offset.y = bottom - rows_height;
+        }
+    }
+
+    /// The values under the selection, in display order.
+    pub fn selected_cells(&self) -> Option<SelectedCells> {
+        let selection = self.selection.as_ref()?;
+        let columns = selection.columns(self.columns.len());
+        let rows = selection
+            .rows(self.visible_row_count())
+            .into_iter()
+            .filter_map(|position| self.rows.get(self.row_at(position)))
+            .map(|row| row.get(columns.clone()).unwrap_or_default().to_vec())
+            .collect();
+        Some(SelectedCells {
+            column_names: self.columns[columns].iter().map(|c| c.name.to_string()).collect(),
+            rows,
+        })
+    }
+
+    pub fn focus_handle(&self) -> &FocusHandle {
+        &self.focus_handle
+    }
+
+    pub fn sort(&self) -> &[ColumnSort] {
+        &self.sort
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    /// Sorts by a column on a header click; `extend` adds it to the
+    /// current sort instead of replacing it.
+    pub fn toggle_sort(&mut self, col_index: usize, extend: bool, cx: &mut Context<Self>) {
+        self.sort = toggle_sort(&self.sort, col_index, extend);
+        self.sort_or_filter_changed(cx);
+    }
+
+    /// Shows only rows with a cell containing each word of `filter`.
+    pub fn set_filter(&mut self, filter: String, cx: &mut Context<Self>) {
+        if filter == self.filter {
+            return;
+        }
+        self.filter = filter;
+        self.sort_or_filter_changed(cx);
+    }
+
+    pub fn sorts_on_server(&self) -> bool {
+        self.sort_on_server
+    }
+
+    pub fn set_sort_on_server(&mut self, on_server: bool, cx: &mut Context<Self>) {
+        self.sort_on_server = on_server;
+        self.sort_or_filter_changed(cx);
+    }
+
+    fn sort_or_filter_changed(&mut self, cx: &mut Context<Self>) {
+        self.refresh_row_order();
+        self.selection = None;
+        self.scroll_offset.y = px(0.);
+        if self.sort_on_server {
+            cx.emit(GridQueryChanged {
+                sort: self.sort.clone(),
+                filter: self.filter.clone(),
+            });
+        }
+        cx.notify();
+    }
+
+    /// Rebuilds the display order after the rows, sort or search change.
+    /// Edits don't move a row, so it stays where the user is working on it.
+    fn refresh_row_order(&mut self) {
+        if self.sort_on_server || (self.sort.is_empty() && self.filter.trim().is_empty()) {
+            self.row_order = None;
+            return;
+        }
+        let type_names: Vec<Option<&str>> = self
+            .columns
+            .iter()
+            .map(|c| c.type_name.as_ref().map(|t| t.as_ref()))
+            .collect();
+        self.row_order = Some(row_order(
+            &self.rows,
+            &self.sort,
+            &type_names,
+            &self.filter,
+            |row_index| self.draft_rows.contains(&row_index),
+        ));
+    }
+
+    /// Number of rows shown once searched.
+    pub fn visible_row_count(&self) -> usize {
+        self.row_order.as_ref().map_or(self.rows.len(), Vec::len)
+    }
+
+    /// The index into `rows` of the row shown at `position`.
+    pub(crate) fn row_at(&self, position: usize) -> usize {
+        self.row_order.as_ref().map_or(position, |order| order[position])
+    }
+
+    fn display_position(&self, row_index: usize) -> Option<usize> {
+        match &self.row_order {
+            Some(order) => order.iter().position(|&r| r == row_index),
+            None => Some(row_index),
+        }
+    }
+
     pub(crate) fn content_size(&self) -> Size<Pixels> {
         let row_height = px(32.);
         let header_height = px(48.);
         let total_width: Pixels = self.columns.iter().map(|c| c.width).sum();
-        let total_height = header_height + (row_height * self.rows.len() as f32);
+        let total_height = header_height + (row_height * self.visible_row_count() as f32);
         Size {
             width: (total_width + px(END_PADDING)).max(px(100.)),
             height: total_height,
@@ -210,7 +686,8 @@ impl DataTableState {
     pub(crate) fn calculate_cell_position(&self, row_index: usize, col_index: usize) -> Point<Pixels> {
         let col_x: Pixels = self.columns.iter().take(col_index).map(|c| c.width).sum();
         let cell_x = col_x - self.scroll_offset.x;
-        let cell_y = px(HEADER_HEIGHT) + px(ROW_HEIGHT) * row_index as f32 - self.scroll_offset.y + px(ROW_HEIGHT);
+        let position = self.display_position(row_index).unwrap_or(row_index);
+        let cell_y = px(HEADER_HEIGHT) + px(ROW_HEIGHT) * position as f32 - self.scroll_offset.y + px(ROW_HEIGHT);
 
         point(
             self.container_origin.x + cell_x,
@@ -222,13 +699,14 @@ impl DataTableState {
         &mut self,
         row_index: usize,
         col_index: usize,
-        cell_value: SharedString,
+        cell: &CellValue,
         fk_info: ForeignKeyInfo,
         cx: &mut Context<Self>,
     ) {
-        if cell_value.as_ref() == "NULL" {
+        if cell.is_null() {
             return;
         }
+        let cell_value = cell.display();
 
         self.active_fk_card = Some(FkHoverCardData {
             fk_info: fk_info.clone(),
@@ -292,3 +770,5 @@ impl EventEmitter<CellSaveRequested> for DataTableState {}
 impl EventEmitter<CellDoubleClicked> for DataTableState {}
 impl EventEmitter<FkDataRequest> for DataTableState {}
 impl EventEmitter<CellContextMenu> for DataTableState {}
+impl EventEmitter<GridQueryChanged> for DataTableState {}
+impl EventEmitter<CopySelectionRequested> for DataTableState {}
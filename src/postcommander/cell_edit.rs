@@ -1,11 +1,221 @@
-use crate::components::{CellContextMenu, CellDoubleClicked, CellSaveRequested, DataTableState};
+use crate::components::{CellContextMenu, CellDoubleClicked, CellSaveRequested, CopySelectionRequested, DataTableState};
+use crate::postcommander::database::{CellValue, QueryResult};
 use crate::postcommander::page::PostCommanderPage;
-use crate::postcommander::types::CellEditState;
+use crate::postcommander::sql::{delete_statement, insert_statement, update_statement, ColumnValue};
+use crate::postcommander::types::{column_table, CellEditState, QueryTab, TableContext};
 use crate::theme::ActiveTheme;
 use gpui::prelude::FluentBuilder;
 use gpui::*;
 use gpui_component::input::InputState;
 
+/// An UPDATE of one row ready to send, and the values to show once it lands.
+pub(crate) struct RowUpdate {
+    pub(crate) connection_id: String,
+    /// The tab's manual-transaction session, which the update joins.
+    pub(crate) session_id: Option<String>,
+    pub(crate) sql: String,
+    pub(crate) params: Vec<Option<String>>,
+    pub(crate) new_cells: Vec<(usize, CellValue)>,
+}
+
+/// The table a result column was read from, and the result itself.
+fn edit_target(tab: &QueryTab, col_index: usize) -> Result<(&TableContext, &QueryResult), String> {
+    let result = tab.result.as_ref().ok_or("No result to edit")?;
+    let column = result.columns.get(col_index).ok_or("Column not found")?;
+    let (_, context) = column_table(&tab.table_contexts, col_index).ok_or_else(|| {
+        format!(
+            "Column \"{}\" is not read directly from a table, so it cannot be edited",
+            column.name
+        )
+    })?;
+    if context.read_only {
+        return Err("The connection is read-only".to_string());
+    }
+    Ok((context, result))
+}
+
+/// The table whole rows are inserted into and deleted from.
+fn row_target(tab: &QueryTab) -> Result<(&TableContext, &QueryResult), String> {
+    let result = tab.result.as_ref().ok_or("No result to edit")?;
+    let context = tab
+        .row_table()
+        .ok_or("Rows can only be added or deleted when the result comes from a single table")?;
+    if context.read_only {
+        return Err("The connection is read-only".to_string());
+    }
+    Ok((context, result))
+}
+
+/// Primary key values of `row` for a WHERE clause. `row` must hold the
+/// values as loaded, not any staged edits.
+fn row_key<'a>(
+    context: &'a TableContext,
+    result: &'a QueryResult,
+    row: &[CellValue],
+) -> Result<Vec<ColumnValue<'a>>, String> {
+    if context.primary_keys.is_empty() {
+        return Err("The table has no primary key, so the row cannot be identified".to_string());
+    }
+    let mut key = Vec::with_capacity(context.primary_keys.len());
+    for pk_col in &context.primary_keys {
+        let (pk_idx, pk_column) = context
+            .result_index(pk_col)
+            .and_then(|i| Some((i, result.columns.get(i)?)))
+            .ok_or_else(|| format!("Primary key column \"{}\" is not in the result", pk_col))?;
+        let pk_value = match row.get(pk_idx) {
+            Some(value) if !value.is_null() => value,
+            _ => return Err(format!("Primary key column \"{}\" is NULL", pk_col)),
+        };
+        key.push(ColumnValue {
+            name: pk_col,
+            type_name: &pk_column.type_name,
+            value: pk_value.to_param_text(),
+        });
+    }
+    Ok(key)
+}
+
+/// The value to show for text typed into a cell; `None` is NULL.
+fn edited_cell(result: &QueryResult, col_index: usize, value: &Option<String>) -> CellValue {
+    let type_name = result
+        .columns
+        .get(col_index)
+        .map(|c| c.type_name.as_str())
+        .unwrap_or_default();
+    match value {
+        Some(text) => CellValue::from_edit_text(text, type_name),
+        None => CellValue::Null,
+    }
+}
+
+/// Table columns and values for `changes`, which must all be read from
+/// `context`'s table.
+fn column_values<'a>(
+    context: &'a TableContext,
+    result: &'a QueryResult,
+    changes: Vec<(usize, Option<String>)>,
+) -> Result<Vec<ColumnValue<'a>>, String> {
+    changes
+        .into_iter()
+        .map(|(col_index, value)| {
+            let column = result.columns.get(col_index).ok_or("Column not found")?;
+            let name = context.source_column(col_index).ok_or_else(|| {
+                format!(
+                    "Column \"{}\" is not read from \"{}\", so it cannot be edited here",
+                    column.name, context.table
+                )
+            })?;
+            Ok(ColumnValue {
+                name,
+                type_name: &column.type_name,
+                value,
+            })
+        })
+        .collect()
+}
+
+/// Builds an UPDATE of the given columns of `row`, keyed on the table's
+/// primary key, with the new values and key values bound as parameters.
+/// The columns must all be read from the same table.
+pub(crate) fn build_row_update(
+    tab: &QueryTab,
+    row: &[CellValue],
+    changes: Vec<(usize, Option<String>)>,
+) -> Result<RowUpdate, String> {
+    let (first_col, _) = changes.first().ok_or("Nothing to update")?;
+    let (context, result) = edit_target(tab, *first_col)?;
+    let connection_id = tab.connection_id.clone().ok_or("Not connected")?;
+    let key = row_key(context, result, row)?;
+
+    let new_cells = changes
+        .iter()
+        .map(|(col_index, value)| (*col_index, edited_cell(result, *col_index, value)))
+        .collect();
+    let set = column_values(context, result, changes)?;
+    let (sql, params) = update_statement(&context.schema, &context.table, &set, &key);
+
+    Ok(RowUpdate {
+        connection_id,
+        session_id: tab.transaction.as_ref().map(|t| t.session_id.clone()),
+        sql,
+        params,
+        new_cells,
+    })
+}
+
+/// Builds an INSERT of a new row from the columns the user filled in.
+pub(crate) fn build_row_insert(
+    tab: &QueryTab,
+    changes: Vec<(usize, Option<String>)>,
+) -> Result<(String, Vec<Option<String>>), String> {
+    let (context, result) = row_target(tab)?;
+    let values = column_values(context, result, changes)?;
+    Ok(insert_statement(&context.schema, &context.table, &values))
+}
+
+/// Builds a DELETE of `row` by its primary key.
+pub(crate) fn build_row_delete(
+    tab: &QueryTab,
+    row: &[CellValue],
+) -> Result<(String, Vec<Option<String>>), String> {
+    let (context, result) = row_target(tab)?;
+    let key = row_key(context, result, row)?;
+    Ok(delete_statement(&context.schema, &context.table, &key))
+}
+
+/// Explains an update that did not touch exactly one row. Such updates are
+/// rolled back, so nothing was written.
+fn affected_rows_error(rows_affected: u64) -> Option<String> {
+    match rows_affected {
+        1 => None,
+        0 => Some(
+            "No row matched the primary key; it may have been changed or deleted. Nothing was saved."
+                .to_string(),
+        ),
+        n => Some(format!(
+            "The primary key matched {} rows. The update was rolled back.",
+            n
+        )),
+    }
+}
+
+/// Shows a single-cell update in the grid as a pending edit instead of
+/// writing it.
+fn stage_cell_update(
+    table_state: &Entity<DataTableState>,
+    row_index: usize,
+    update: RowUpdate,
+    value: Option<String>,
+    cx: &mut App,
+) {
+    let Some((col_index, cell)) = update.new_cells.into_iter().next() else {
+        return;
+    };
+    table_state.update(cx, |state, cx| {
+        state.stage_edit(row_index, col_index, value, cell, cx);
+    });
+}
+
+/// Cells of a row that is not inserted yet have nothing to update; the
+/// value is kept for the INSERT.
+fn stage_draft_cell(
+    tab: &QueryTab,
+    row_index: usize,
+    col_index: usize,
+    value: Option<String>,
+    cx: &mut App,
+) -> Result<(), String> {
+    let (context, result) = row_target(tab)?;
+    if context.source_column(col_index).is_none() {
+        return Err("Only columns read from the table can be filled in".to_string());
+    }
+    let cell = edited_cell(result, col_index, &value);
+    tab.table_state.update(cx, |state, cx| {
+        state.stage_edit(row_index, col_index, value, cell, cx);
+    });
+    Ok(())
+}
+
 impl PostCommanderPage {
     pub(crate) fn handle_cell_save(
         &mut self,
@@ -17,78 +227,107 @@ impl PostCommanderPage {
             return;
         };
 
-        let Some(context) = &tab.table_context else {
-            table_state.clone().update(cx, |state, cx| {
-                state.set_edit_error(Some("No table context for editing".to_string()), cx);
-            });
-            return;
-        };
-
-        let Some(result) = &tab.result else {
-            return;
-        };
-
+        let tab_id = tab.id;
         let row_index = event.row_index;
         let col_index = event.col_index;
-        let new_value = event.new_value.clone();
+        let value = event.new_value.clone();
 
-        let rows = table_state.read(cx).rows();
-        let Some(row) = rows.get(row_index) else {
+        if table_state.read(cx).is_draft_row(row_index) {
+            if let Err(e) = stage_draft_cell(tab, row_index, col_index, value, cx) {
+                self.show_cell_save_error(&table_state, row_index, col_index, e, cx);
+            }
+            return;
+        }
+        let Some(row) = table_state.read(cx).original_row(row_index) else {
             return;
         };
 
-        let mut pk_conditions = Vec::new();
-        for pk_col in &context.primary_keys {
-            if let Some((pk_idx, _)) = result.columns.iter().enumerate().find(|(_, c)| &c.name == pk_col) {
-                if let Some(pk_value) = row.get(pk_idx) {
-                    pk_conditions.push(format!("\"{}\" = '{}'", pk_col, pk_value.replace('\'', "''")));
-                }
+        let update = match build_row_update(tab, &row, vec![(col_index, value.clone())]) {
+            Ok(update) => update,
+            Err(e) => {
+                self.show_cell_save_error(&table_state, row_index, col_index, e, cx);
+                return;
             }
-        }
+        };
 
-        if pk_conditions.is_empty() {
-            table_state.clone().update(cx, |state, cx| {
-                state.set_edit_error(Some("No primary key values found".to_string()), cx);
-            });
+        if table_state.read(cx).buffers_edits() {
+            stage_cell_update(&table_state, row_index, update, value, cx);
             return;
         }
 
-        let column_name = result.columns.get(col_index).map(|c| c.name.clone()).unwrap_or_default();
-        let sql = format!(
-            "UPDATE \"{}\".\"{}\" SET \"{}\" = '{}' WHERE {}",
-            context.schema,
-            context.table,
-            column_name,
-            new_value.replace('\'', "''"),
-            pk_conditions.join(" AND ")
-        );
-
         table_state.clone().update(cx, |state, cx| {
             state.set_edit_saving(true, cx);
         });
 
-        let rx = self.db_manager.execute(sql);
+        let rx = self
+            .db_manager
+            .execute_single_row(&update.connection_id, update.sql, update.params, update.session_id);
+        let new_cells = update.new_cells;
 
-        cx.spawn(async move |_this, cx| {
-            let result = rx.await;
-            let _ = table_state.update(cx, |state, cx| {
-                match result {
-                    Ok(Ok(_)) => {
-                        state.update_cell_value(row_index, col_index, SharedString::from(new_value));
+        cx.spawn(async move |this, cx| {
+            let error = match rx.await {
+                Ok(Ok(rows_affected)) => affected_rows_error(rows_affected),
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_) => Some("Update failed".to_string()),
+            };
+            let _ = this.update(cx, |this, cx| match error {
+                None => {
+                    table_state.update(cx, |state, cx| {
+                        for (col_index, cell) in new_cells {
+                            state.update_cell_value(row_index, col_index, cell);
+                        }
                         state.finish_editing(cx);
-                    }
-                    Ok(Err(e)) => {
-                        state.set_edit_error(Some(e.to_string()), cx);
-                    }
-                    Err(_) => {
-                        state.set_edit_error(Some("Update failed".to_string()), cx);
-                    }
+                    });
+                    this.note_grid_edit(tab_id);
+                    cx.notify();
                 }
+                Some(e) => this.show_cell_save_error(&table_state, row_index, col_index, e, cx),
             });
         })
         .detach();
     }
 
+    /// Inline edits have nowhere to show an error, so a failed save opens
+    /// the cell in the edit dialog with the reason.
+    fn show_cell_save_error(
+        &mut self,
+        table_state: &Entity<DataTableState>,
+        row_index: usize,
+        col_index: usize,
+        error: String,
+        cx: &mut Context<Self>,
+    ) {
+        table_state.update(cx, |state, cx| {
+            state.set_edit_error(Some(error.clone()), cx);
+        });
+        let Some(tab) = self.tabs.iter().find(|t| &t.table_state == table_state) else {
+            return;
+        };
+        let column_name = tab
+            .result
+            .as_ref()
+            .and_then(|r| r.columns.get(col_index))
+            .map(|c| c.name.clone())
+            .unwrap_or_default();
+        let original_value = table_state
+            .read(cx)
+            .rows()
+            .get(row_index)
+            .and_then(|row| row.get(col_index))
+            .cloned()
+            .unwrap_or(CellValue::Null);
+        self.cell_edit = Some(CellEditState {
+            row_index,
+            col_index,
+            column_name,
+            original_value,
+            editor: None,
+            is_saving: false,
+            error: Some(error),
+        });
+        cx.notify();
+    }
+
     pub(crate) fn handle_cell_double_click(
         &mut self,
         _table_state: Entity<DataTableState>,
@@ -108,92 +347,98 @@ impl PostCommanderPage {
     }
 
     pub(crate) fn save_cell_edit(&mut self, cx: &mut Context<Self>) {
-        let Some(edit) = self.cell_edit.as_ref() else {
-            return;
-        };
-
-        let Some(tab) = self.active_tab_id.as_ref().and_then(|id| self.tabs.iter().find(|t| &t.id == id)) else {
+        let Some(editor) = self.cell_edit.as_ref().and_then(|e| e.editor.clone()) else {
             return;
         };
+        let value = editor.read(cx).value().to_string();
+        self.save_cell_edit_value(Some(value), cx);
+    }
 
-        let Some(context) = &tab.table_context else {
-            if let Some(ref mut edit) = self.cell_edit {
-                edit.error = Some("No table context for editing".to_string());
-            }
-            cx.notify();
-            return;
-        };
+    pub(crate) fn set_cell_null(&mut self, cx: &mut Context<Self>) {
+        self.save_cell_edit_value(None, cx);
+    }
 
-        let Some(result) = &tab.result else {
+    fn save_cell_edit_value(&mut self, value: Option<String>, cx: &mut Context<Self>) {
+        let Some(edit) = self.cell_edit.as_ref() else {
             return;
         };
-
-        let Some(editor) = &edit.editor else {
+        if edit.is_saving {
             return;
-        };
-        let new_value = editor.read(cx).value().to_string();
+        }
         let row_index = edit.row_index;
         let col_index = edit.col_index;
 
-        let table_state = tab.table_state.clone();
-        let rows = table_state.read(cx).rows();
-        let Some(row) = rows.get(row_index) else {
+        let Some(tab) = self.active_tab_id.as_ref().and_then(|id| self.tabs.iter().find(|t| &t.id == id)) else {
             return;
         };
 
-        let mut pk_conditions = Vec::new();
-        for pk_col in &context.primary_keys {
-            if let Some((pk_idx, _)) = result.columns.iter().enumerate().find(|(_, c)| &c.name == pk_col) {
-                if let Some(pk_value) = row.get(pk_idx) {
-                    pk_conditions.push(format!("\"{}\" = '{}'", pk_col, pk_value.replace('\'', "''")));
+        let tab_id = tab.id;
+        let table_state = tab.table_state.clone();
+        if table_state.read(cx).is_draft_row(row_index) {
+            match stage_draft_cell(tab, row_index, col_index, value, cx) {
+                Ok(()) => self.cell_edit = None,
+                Err(e) => {
+                    if let Some(edit) = self.cell_edit.as_mut() {
+                        edit.error = Some(e);
+                    }
                 }
             }
+            cx.notify();
+            return;
         }
+        let Some(row) = table_state.read(cx).original_row(row_index) else {
+            return;
+        };
 
-        if pk_conditions.is_empty() {
-            if let Some(ref mut edit) = self.cell_edit {
-                edit.error = Some("No primary key values found".to_string());
+        let update = build_row_update(tab, &row, vec![(col_index, value.clone())]);
+        let Some(edit) = self.cell_edit.as_mut() else {
+            return;
+        };
+        let update = match update {
+            Ok(update) => update,
+            Err(e) => {
+                edit.error = Some(e);
+                cx.notify();
+                return;
             }
+        };
+
+        if table_state.read(cx).buffers_edits() {
+            stage_cell_update(&table_state, row_index, update, value, cx);
+            self.cell_edit = None;
             cx.notify();
             return;
         }
 
-        let column_name = result.columns.get(col_index).map(|c| c.name.clone()).unwrap_or_default();
-        let sql = format!(
-            "UPDATE \"{}\".\"{}\" SET \"{}\" = '{}' WHERE {}",
-            context.schema,
-            context.table,
-            column_name,
-            new_value.replace('\'', "''"),
-            pk_conditions.join(" AND ")
-        );
-
-        if let Some(ref mut edit) = self.cell_edit {
-            edit.is_saving = true;
-        }
+        edit.is_saving = true;
+        edit.error = None;
         cx.notify();
 
-        let rx = self.db_manager.execute(sql);
+        let rx = self
+            .db_manager
+            .execute_single_row(&update.connection_id, update.sql, update.params, update.session_id);
+        let new_cells = update.new_cells;
 
         cx.spawn(async move |this, cx| {
-            let result = rx.await;
+            let error = match rx.await {
+                Ok(Ok(rows_affected)) => affected_rows_error(rows_affected),
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_) => Some("Update failed".to_string()),
+            };
             let _ = this.update(cx, |this, cx| {
-                match result {
-                    Ok(Ok(_)) => {
+                match error {
+                    None => {
                         table_state.update(cx, |state, _cx| {
-                            state.update_cell_value(row_index, col_index, SharedString::from(new_value));
+                            for (col_index, cell) in new_cells {
+                                state.update_cell_value(row_index, col_index, cell);
+                            }
                         });
+                        this.note_grid_edit(tab_id);
                         this.cell_edit = None;
                     }
-                    Ok(Err(e)) => {
-                        if let Some(ref mut edit) = this.cell_edit {
-                            edit.error = Some(e.to_string());
-                            edit.is_saving = false;
-                        }
-                    }
-                    Err(_) => {
+                    Some(e) => {
                         if let Some(ref mut edit) = this.cell_edit {
-                            edit.error = Some("Update failed".to_string());
+                            edit.error = Some(e);
                             edit.is_saving = false;
                         }
                     }
@@ -212,7 +457,11 @@ impl PostCommanderPage {
     pub(crate) fn render_cell_edit_modal(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         if let Some(ref mut edit) = self.cell_edit {
             if edit.editor.is_none() {
-                let original_value: String = edit.original_value.to_string();
+                let original_value: String = if edit.original_value.is_null() {
+                    String::new()
+                } else {
+                    edit.original_value.display().to_string()
+                };
                 let editor = cx.new(move |cx| {
                     let mut state = InputState::new(window, cx)
                         .placeholder("Enter value...")
@@ -367,6 +616,25 @@ impl PostCommanderPage {
                                             .child("Cancel"),
                                     ),
                             )
+                            .child(
+                                div()
+                                    .id("set-null-edit-btn")
+                                    .px_4()
+                                    .py_1()
+                                    .rounded_md()
+                                    .cursor_pointer()
+                                    .hover(move |s| s.bg(rgb(element_hover)))
+                                    .when(is_saving, |el| el.opacity(0.6))
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.set_cell_null(cx);
+                                    }))
+                                    .child(
+                                        div()
+                                            .text_sm()
+                                            .text_color(rgb(text_muted))
+                                            .child("Set NULL"),
+                                    ),
+                            )
                             .child(
                                 div()
                                     .id("save-edit-btn")
@@ -401,16 +669,25 @@ impl PostCommanderPage {
 
         let table_name = self.active_tab_id.as_ref()
             .and_then(|id| self.tabs.iter().find(|t| &t.id == id))
-            .and_then(|tab| tab.table_context.as_ref())
+            .and_then(|tab| tab.row_table())
             .map(|ctx| ctx.table.clone());
 
         self.overlays.pending_cell_context_menu = Some(PendingCellContextMenu {
             col_index: event.col_index,
-            column_names: event.column_names.clone(),
             row_data: event.row_data.clone(),
+            selection: event.selection.clone(),
             position: event.position,
             table_name,
         });
         cx.notify();
     }
+
+    pub(crate) fn handle_copy_selection(
+        &mut self,
+        _table_state: Entity<DataTableState>,
+        event: &CopySelectionRequested,
+        cx: &mut Context<Self>,
+    ) {
+        Self::copy_rows_as_tsv(&event.selection.rows, cx);
+    }
 }
@@ -1,6 +1,7 @@
 use crate::components::TextInput;
 use crate::icons::icon_sm;
 use crate::postcommander::page::PostCommanderPage;
+use crate::settings::{ConnectionAccess, ConnectionEnvironment};
 use crate::theme::ActiveTheme;
 use gpui::*;
 
@@ -50,7 +51,7 @@ impl PostCommanderPage {
                         .shadow_xl()
                         .occlude()
                         .child(self.render_dialog_header(cx, text, text_muted, element_hover))
-                        .child(self.render_dialog_form(cx, text_muted, text, surface, border_variant, accent))
+                        .child(self.render_dialog_form(cx, text_muted, text, surface, border_variant, accent, element_hover))
                         .child(self.render_dialog_buttons(
                             cx,
                             element,
@@ -70,6 +71,12 @@ impl PostCommanderPage {
         text_muted: u32,
         element_hover: u32,
     ) -> impl IntoElement {
+        let title = if self.connection_dialog.editing_id.is_some() {
+            "Edit Connection"
+        } else {
+            "New Connection"
+        };
+
         div()
             .flex()
             .items_center()
@@ -86,7 +93,7 @@ impl PostCommanderPage {
                             .text_lg()
                             .font_weight(FontWeight::SEMIBOLD)
                             .text_color(rgb(text))
-                            .child("Connect to PostgreSQL"),
+                            .child(title),
                     ),
             )
             .child(
@@ -115,11 +122,15 @@ impl PostCommanderPage {
         surface: u32,
         border_variant: u32,
         accent: u32,
+        element_hover: u32,
     ) -> impl IntoElement {
         div()
             .flex()
             .flex_col()
             .gap_4()
+            .child(self.render_input_field(cx, "Name", self.connection_dialog.input_name.clone(), text_muted, text, surface, border_variant, accent, false))
+            .child(self.render_environment_picker(cx, text_muted, border_variant, element_hover))
+            .child(self.render_access_picker(cx, text_muted, text, border_variant, accent, element_hover))
             .child(
                 div()
                     .flex()
@@ -132,6 +143,121 @@ impl PostCommanderPage {
             .child(self.render_input_field(cx, "Password", self.connection_dialog.input_password.clone(), text_muted, text, surface, border_variant, accent, false))
     }
 
+    fn render_environment_picker(
+        &self,
+        cx: &mut Context<Self>,
+        text_muted: u32,
+        border_variant: u32,
+        element_hover: u32,
+    ) -> impl IntoElement {
+        let selected = self.connection_dialog.environment;
+
+        div()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .child(
+                div()
+                    .text_xs()
+                    .font_weight(FontWeight::MEDIUM)
+                    .text_color(rgb(text_muted))
+                    .child("Environment"),
+            )
+            .child(
+                div()
+                    .flex()
+                    .gap_2()
+                    .children(ConnectionEnvironment::ALL.iter().map(|&env| {
+                        let is_selected = env == selected;
+                        let color = env.color();
+                        div()
+                            .id(SharedString::from(format!("env-{}", env.label().to_lowercase())))
+                            .flex_1()
+                            .h(px(30.))
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .gap_2()
+                            .rounded_lg()
+                            .border_1()
+                            .border_color(rgb(if is_selected { color } else { border_variant }))
+                            .cursor_pointer()
+                            .hover(move |s| s.bg(rgb(element_hover)))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.connection_dialog.environment = env;
+                                cx.notify();
+                            }))
+                            .child(div().size(px(8.)).rounded_full().bg(rgb(color)))
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .text_color(rgb(if is_selected { color } else { text_muted }))
+                                    .child(env.label()),
+                            )
+                    })),
+            )
+    }
+
+    fn render_access_picker(
+        &self,
+        cx: &mut Context<Self>,
+        text_muted: u32,
+        text: u32,
+        border_variant: u32,
+        accent: u32,
+        element_hover: u32,
+    ) -> impl IntoElement {
+        let selected = self.connection_dialog.access;
+        let hint = match selected {
+            ConnectionAccess::ReadWrite => "Risky statements ask before running.",
+            ConnectionAccess::Protected => "Risky statements need the database name typed to run.",
+            ConnectionAccess::ReadOnly => "Sessions are read-only and grid edits are off.",
+        };
+
+        div()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .child(
+                div()
+                    .text_xs()
+                    .font_weight(FontWeight::MEDIUM)
+                    .text_color(rgb(text_muted))
+                    .child("Access"),
+            )
+            .child(
+                div()
+                    .flex()
+                    .gap_2()
+                    .children(ConnectionAccess::ALL.iter().map(|&access| {
+                        let is_selected = access == selected;
+                        div()
+                            .id(SharedString::from(format!("access-{}", access.label().to_lowercase())))
+                            .flex_1()
+                            .h(px(30.))
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .rounded_lg()
+                            .border_1()
+                            .border_color(rgb(if is_selected { accent } else { border_variant }))
+                            .cursor_pointer()
+                            .hover(move |s| s.bg(rgb(element_hover)))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.connection_dialog.access = access;
+                                cx.notify();
+                            }))
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .text_color(rgb(if is_selected { text } else { text_muted }))
+                                    .child(access.label()),
+                            )
+                    })),
+            )
+            .child(div().text_xs().text_color(rgb(text_muted)).child(hint))
+    }
+
     fn render_input_field(
         &self,
         cx: &mut Context<Self>,
@@ -237,7 +363,7 @@ impl PostCommanderPage {
                     .cursor_pointer()
                     .hover(|s| s.opacity(0.9))
                     .on_click(cx.listener(|this, _, _, cx| {
-                        this.connect_to_database(cx);
+                        this.save_connection_dialog(cx);
                     }))
                     .child(icon_sm("plug", accent_foreground))
                     .child(
@@ -245,7 +371,7 @@ impl PostCommanderPage {
                             .text_sm()
                             .font_weight(FontWeight::MEDIUM)
                             .text_color(rgb(accent_foreground))
-                            .child("Connect"),
+                            .child("Save & Connect"),
                     ),
             )
     }
@@ -0,0 +1,599 @@
+use crate::components::TextInput;
+use crate::postcommander::database::ConnectionConfig;
+use crate::postcommander::page::PostCommanderPage;
+use crate::postcommander::sql::{split_statements, statement_scope};
+use crate::postcommander::types::{
+    CompletionCatalog, ConnectionSession, ConnectionState, SchemaMap, TabId, TableStructureInfo,
+    TransactionHold,
+};
+use crate::settings::{AppSettings, ConnectionAccess, ConnectionProfile, ConnectionSettings};
+use gpui::*;
+use gpui_component::menu::{PopupMenu, PopupMenuItem};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub(crate) fn connection_node_key(connection_id: &str) -> String {
+    format!("conn:{}", connection_id)
+}
+
+pub(crate) fn database_node_key(connection_id: &str) -> String {
+    format!("db:{}", connection_id)
+}
+
+fn connection_config(profile: &ConnectionProfile) -> ConnectionConfig {
+    let conn = &profile.connection;
+    ConnectionConfig {
+        name: profile.name.clone(),
+        host: conn.host.clone(),
+        port: conn.port.parse().unwrap_or(5432),
+        database: conn.database.clone(),
+        username: conn.username.clone(),
+        password: conn.password.clone(),
+        read_only: profile.access == ConnectionAccess::ReadOnly,
+    }
+}
+
+impl PostCommanderPage {
+    pub(crate) fn connection(&self, connection_id: &str) -> Option<&ConnectionSession> {
+        self.connections.iter().find(|c| c.id() == connection_id)
+    }
+
+    pub(crate) fn connection_mut(&mut self, connection_id: &str) -> Option<&mut ConnectionSession> {
+        self.connections.iter_mut().find(|c| c.id() == connection_id)
+    }
+
+    /// The profile of the connection a tab is bound to.
+    pub(crate) fn tab_profile(&self, tab_id: TabId) -> Option<&ConnectionProfile> {
+        let tab = self.tabs.iter().find(|t| t.id == tab_id)?;
+        Some(&self.connection(tab.connection_id.as_deref()?)?.profile)
+    }
+
+    /// The active tab's connection, or the last connection used when the
+    /// active tab isn't bound to one.
+    pub(crate) fn current_connection(&self) -> Option<&ConnectionSession> {
+        let tab_connection = self
+            .active_tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id))
+            .and_then(|t| t.connection_id.as_deref());
+        let id = tab_connection.or(self.active_connection_id.as_deref())?;
+        self.connection(id)
+    }
+
+    pub(crate) fn connect_profile(&mut self, connection_id: &str, cx: &mut Context<Self>) {
+        if self.hold_for_transactions(TransactionHold::Reconnect(connection_id.to_string()), cx) {
+            return;
+        }
+        let Some(session) = self.connection_mut(connection_id) else {
+            return;
+        };
+        let config = connection_config(&session.profile);
+        session.state = ConnectionState::Connecting;
+        session.attempt += 1;
+        let attempt = session.attempt;
+        self.forget_transaction_sessions(connection_id);
+        cx.notify();
+
+        let rx = self.db_manager.connect(connection_id, config);
+        let connection_id = connection_id.to_string();
+        cx.spawn(async move |this, cx| {
+            let result = rx.await;
+            let _ = this.update(cx, |this, cx| {
+                let state = match result {
+                    Ok(Ok(())) => ConnectionState::Connected,
+                    Ok(Err(e)) => ConnectionState::Error(e.to_string()),
+                    Err(_) => ConnectionState::Error("Connection failed".to_string()),
+                };
+                let connected = state == ConnectionState::Connected;
+                // Disconnected or connected again since; the manager drops
+                // the pool.
+                let Some(session) = this
+                    .connection_mut(&connection_id)
+                    .filter(|s| s.attempt == attempt)
+                else {
+                    return;
+                };
+                session.state = state;
+                session.schemas = Arc::new(SchemaMap::new());
+
+                if connected {
+                    this.fetch_completion_catalog(&connection_id, cx);
+                    this.expanded_nodes.insert(connection_node_key(&connection_id));
+                    let nodes: Vec<String> = this.expanded_nodes.iter().cloned().collect();
+                    AppSettings::update_global(cx, |settings| {
+                        let pc = settings.postcommander_mut();
+                        pc.set_connection_open(&connection_id, true);
+                        pc.expanded_nodes = Some(nodes);
+                    });
+                    AppSettings::get_global(cx).save();
+
+                    if this.active_connection_id.is_none() {
+                        this.active_connection_id = Some(connection_id.clone());
+                    }
+                    if this.expanded_nodes.contains(&database_node_key(&connection_id)) {
+                        this.fetch_schema_objects(&connection_id, cx);
+                    }
+                }
+                this.sync_completion_schemas();
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    pub(crate) fn disconnect_profile(&mut self, connection_id: &str, cx: &mut Context<Self>) {
+        if self.hold_for_transactions(TransactionHold::Disconnect(connection_id.to_string()), cx) {
+            return;
+        }
+        let _ = self.db_manager.disconnect(connection_id);
+        self.forget_transaction_sessions(connection_id);
+        if let Some(session) = self.connection_mut(connection_id) {
+            session.state = ConnectionState::Disconnected;
+            session.attempt += 1;
+            session.schemas = Arc::new(SchemaMap::new());
+            session.schemas_loading = false;
+            session.table_structures.clear();
+            session.structures_requested.clear();
+            session.catalog = Arc::new(CompletionCatalog::default());
+        }
+
+        AppSettings::update_global(cx, |settings| {
+            settings.postcommander_mut().set_connection_open(connection_id, false);
+        });
+        AppSettings::get_global(cx).save();
+
+        self.sync_completion_schemas();
+        cx.notify();
+    }
+
+    /// Shows the connection dialog, filled from `profile_id` when editing.
+    pub(crate) fn open_connection_dialog(&mut self, profile_id: Option<&str>, cx: &mut Context<Self>) {
+        let profile = profile_id.and_then(|id| self.connection(id)).map(|c| c.profile.clone());
+        let (name, environment, access, conn) = match &profile {
+            Some(p) => (p.name.clone(), p.environment, p.access, p.connection.clone()),
+            None => (String::new(), Default::default(), Default::default(), ConnectionSettings::defaults()),
+        };
+
+        let dialog = &mut self.connection_dialog;
+        dialog.editing_id = profile.map(|p| p.id);
+        dialog.environment = environment;
+        dialog.access = access;
+        for (input, value) in [
+            (&dialog.input_name, name),
+            (&dialog.input_host, conn.host),
+            (&dialog.input_port, conn.port),
+            (&dialog.input_database, conn.database),
+            (&dialog.input_username, conn.username),
+            (&dialog.input_password, conn.password),
+        ] {
+            input.update(cx, |input, _| input.set_content(value));
+        }
+        dialog.is_visible = true;
+        cx.notify();
+    }
+
+    /// Saves the profile in the connection dialog and (re)connects it.
+    pub(crate) fn save_connection_dialog(&mut self, cx: &mut Context<Self>) {
+        fn read(input: &Entity<TextInput>, cx: &App) -> String {
+            input.read(cx).content().to_string()
+        }
+        let dialog = &self.connection_dialog;
+
+        let connection = ConnectionSettings {
+            host: read(&dialog.input_host, cx),
+            port: read(&dialog.input_port, cx),
+            database: read(&dialog.input_database, cx),
+            username: read(&dialog.input_username, cx),
+            password: read(&dialog.input_password, cx),
+        };
+        let name = read(&dialog.input_name, cx).trim().to_string();
+        let name = if name.is_empty() {
+            format!("{}@{}", connection.database, connection.host)
+        } else {
+            name
+        };
+        let profile = ConnectionProfile {
+            id: dialog
+                .editing_id
+                .clone()
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            name,
+            environment: dialog.environment,
+            access: dialog.access,
+            connection,
+        };
+        let connection_id = profile.id.clone();
+
+        AppSettings::update_global(cx, |settings| {
+            settings.postcommander_mut().upsert_profile(profile.clone());
+        });
+        AppSettings::get_global(cx).save();
+
+        match self.connection_mut(&connection_id) {
+            Some(session) => session.profile = profile,
+            None => self.connections.push(ConnectionSession::new(profile)),
+        }
+
+        self.connection_dialog.is_visible = false;
+        self.connection_dialog.editing_id = None;
+        self.active_connection_id = Some(connection_id.clone());
+        self.connect_profile(&connection_id, cx);
+    }
+
+    pub(crate) fn delete_connection_profile(&mut self, connection_id: &str, cx: &mut Context<Self>) {
+        if self.hold_for_transactions(TransactionHold::DeleteConnection(connection_id.to_string()), cx) {
+            return;
+        }
+        let _ = self.db_manager.disconnect(connection_id);
+        self.forget_transaction_sessions(connection_id);
+        self.connections.retain(|c| c.id() != connection_id);
+        for tab in self.tabs.iter_mut() {
+            if tab.connection_id.as_deref() == Some(connection_id) {
+                tab.connection_id = None;
+            }
+        }
+        if self.active_connection_id.as_deref() == Some(connection_id) {
+            self.active_connection_id = self
+                .connections
+                .iter()
+                .find(|c| c.is_connected())
+                .map(|c| c.id().to_string());
+        }
+
+        AppSettings::update_global(cx, |settings| {
+            settings.postcommander_mut().remove_profile(connection_id);
+        });
+        AppSettings::get_global(cx).save();
+
+        self.sync_completion_schemas();
+        cx.notify();
+    }
+
+    /// Binds the active tab to another connection, connecting it if needed.
+    /// New tabs open on this connection too.
+    pub(crate) fn switch_connection(&mut self, connection_id: &str, cx: &mut Context<Self>) {
+        let Some(session) = self.connection(connection_id) else {
+            return;
+        };
+        let needs_connect = matches!(
+            session.state,
+            ConnectionState::Disconnected | ConnectionState::Error(_)
+        );
+
+        self.active_connection_id = Some(connection_id.to_string());
+        if let Some(tab_id) = self.active_tab_id {
+            self.rebind_tab(tab_id, connection_id, cx);
+        }
+
+        if needs_connect {
+            self.connect_profile(connection_id, cx);
+        }
+        self.sync_completion_schemas();
+        cx.notify();
+    }
+
+    fn rebind_tab(&mut self, tab_id: TabId, connection_id: &str, cx: &mut Context<Self>) {
+        if self.has_open_transaction(tab_id) {
+            if let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) {
+                tab.error = Some("Commit or roll back before switching connections".into());
+            }
+            return;
+        }
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+        if tab.is_loading || tab.connection_id.as_deref() == Some(connection_id) {
+            return;
+        }
+
+        let manual_transactions = tab.transaction.is_some();
+        self.close_transaction_session(tab_id, cx);
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+        tab.connection_id = Some(connection_id.to_string());
+        // Keys and FKs belong to the old server; don't edit through them.
+        tab.table_contexts.clear();
+        tab.table_state.update(cx, |state, _cx| {
+            state.set_table_contexts(vec![]);
+        });
+        if manual_transactions {
+            self.open_transaction_session(tab_id, cx);
+        }
+    }
+
+    /// Points SQL completion at the schemas and table structures of the
+    /// current connection.
+    pub(crate) fn sync_completion_schemas(&self) {
+        let schemas = self
+            .current_connection()
+            .map(|c| (*c.schemas).clone())
+            .unwrap_or_default();
+        let structures = self
+            .current_connection()
+            .map(|c| c.table_structures.values().cloned().collect())
+            .unwrap_or_default();
+        let catalog = self
+            .current_connection()
+            .map(|c| c.catalog.clone())
+            .unwrap_or_default();
+        *self.completion_schemas.borrow_mut() = schemas;
+        *self.completion_structures.borrow_mut() = structures;
+        *self.completion_catalog.borrow_mut() = catalog;
+    }
+
+    /// Loads the server's functions, types and keywords for completion.
+    /// Until they arrive, completion falls back to its built-in lists.
+    pub(crate) fn fetch_completion_catalog(&mut self, connection_id: &str, cx: &mut Context<Self>) {
+        let rx = self.db_manager.fetch_completion_catalog(connection_id);
+        let connection_id = connection_id.to_string();
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(catalog)) = rx.await else {
+                return;
+            };
+            let _ = this.update(cx, |this, _cx| {
+                if let Some(session) = this.connection_mut(&connection_id) {
+                    session.catalog = Arc::new(catalog);
+                }
+                this.sync_completion_schemas();
+            });
+        })
+        .detach();
+    }
+
+    /// Fetches the structure of each table the tab's SQL names that
+    /// completion has no columns for yet. Only tables in the loaded schema
+    /// list are fetched, so names still being typed cost nothing.
+    pub(crate) fn load_completion_structures(&mut self, tab_id: TabId, sql: &str, cx: &mut Context<Self>) {
+        let Some(connection_id) = self
+            .tabs
+            .iter()
+            .find(|t| t.id == tab_id)
+            .and_then(|t| t.connection_id.clone())
+        else {
+            return;
+        };
+        let Some(session) = self.connection_mut(&connection_id) else {
+            return;
+        };
+        if !session.is_connected() {
+            return;
+        }
+
+        let mut wanted = Vec::new();
+        for statement in split_statements(sql) {
+            for table in statement_scope(&statement.text).base_tables() {
+                let schema = match &table.schema {
+                    Some(schema) => schema.clone(),
+                    None => {
+                        let holds = |schema: &str| {
+                            session.schemas.get(schema).is_some_and(|objects| {
+                                objects.tables.contains(&table.name) || objects.views.contains(&table.name)
+                            })
+                        };
+                        if holds("public") {
+                            "public".to_string()
+                        } else if let Some(schema) = session.schemas.keys().find(|s| holds(s)) {
+                            schema.clone()
+                        } else {
+                            continue;
+                        }
+                    }
+                };
+                let known = session.schemas.get(&schema).is_some_and(|objects| {
+                    objects.tables.contains(&table.name) || objects.views.contains(&table.name)
+                });
+                let key = format!("{}.{}", schema, table.name);
+                if known && session.structures_requested.insert(key) {
+                    wanted.push((schema, table.name.clone()));
+                }
+            }
+        }
+        if wanted.is_empty() {
+            return;
+        }
+
+        let lookups: Vec<_> = wanted
+            .into_iter()
+            .map(|(schema, table)| self.db_manager.fetch_table_structure(&connection_id, schema, table))
+            .collect();
+        cx.spawn(async move |this, cx| {
+            let mut structures = Vec::new();
+            for rx in lookups {
+                if let Ok(Ok(structure)) = rx.await {
+                    structures.push(structure);
+                }
+            }
+            let _ = this.update(cx, |this, cx| {
+                this.cache_table_structures(&connection_id, structures, cx);
+            });
+        })
+        .detach();
+    }
+
+    /// Keeps fetched table structures for completion on the connection.
+    pub(crate) fn cache_table_structures(
+        &mut self,
+        connection_id: &str,
+        structures: Vec<TableStructureInfo>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(session) = self.connection_mut(connection_id) else {
+            return;
+        };
+        for structure in structures {
+            let key = format!("{}.{}", structure.schema, structure.table);
+            session.structures_requested.insert(key.clone());
+            session.table_structures.insert(key, structure);
+        }
+        self.sync_completion_schemas();
+        self.refresh_connection_diagnostics(connection_id, cx);
+    }
+
+    pub(crate) fn fetch_schema_objects(&mut self, connection_id: &str, cx: &mut Context<Self>) {
+        let Some(session) = self.connection_mut(connection_id) else {
+            return;
+        };
+        session.schemas_loading = true;
+        cx.notify();
+
+        let sql = r#"
+            SELECT
+                table_schema,
+                table_name,
+                table_type
+            FROM information_schema.tables
+            WHERE table_schema NOT IN ('pg_catalog', 'information_schema')
+            ORDER BY table_schema, table_type, table_name
+        "#
+        .to_string();
+
+        let rx = self.db_manager.execute(connection_id, sql);
+        let connection_id = connection_id.to_string();
+
+        cx.spawn(async move |this, cx| {
+            let result = rx.await;
+            let _ = this.update(cx, |this, cx| {
+                let mut schemas: SchemaMap = SchemaMap::new();
+                if let Ok(Ok(query_result)) = result {
+                    for row in query_result.rows.iter() {
+                        if row.len() >= 3 {
+                            let schema = row[0].display().to_string();
+                            let name = row[1].display().to_string();
+                            let obj_type = row[2].display();
+
+                            let entry = schemas.entry(schema).or_default();
+                            if obj_type.as_ref() == "VIEW" {
+                                entry.views.push(name);
+                            } else {
+                                entry.tables.push(name);
+                            }
+                        }
+                    }
+                }
+
+                if let Some(session) = this.connection_mut(&connection_id) {
+                    session.schemas_loading = false;
+                    session.schemas = Arc::new(schemas);
+                    // Tables may have changed along with the list.
+                    session.table_structures.clear();
+                    session.structures_requested.clear();
+                }
+                this.sync_completion_schemas();
+                this.refresh_connection_diagnostics(&connection_id, cx);
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Quick switcher listing every profile; picking one rebinds the active tab.
+    pub(crate) fn deploy_connection_switcher(
+        &mut self,
+        position: Point<Pixels>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let current_id = self.current_connection().map(|c| c.id().to_string());
+        let items: Vec<(String, String, bool)> = self
+            .connections
+            .iter()
+            .map(|c| {
+                let state = match &c.state {
+                    ConnectionState::Connected => "",
+                    ConnectionState::Connecting => " (connecting)",
+                    ConnectionState::Disconnected => " (disconnected)",
+                    ConnectionState::Error(_) => " (error)",
+                };
+                let label = format!("{} [{}]{}", c.profile.name, c.profile.environment.label(), state);
+                (c.id().to_string(), label, current_id.as_deref() == Some(c.id()))
+            })
+            .collect();
+        let entity = cx.entity().downgrade();
+
+        let menu = PopupMenu::build(window, cx, move |mut menu, _window, _cx| {
+            for (connection_id, label, checked) in items.iter().cloned() {
+                let entity = entity.clone();
+                menu = menu.item(PopupMenuItem::new(label).checked(checked).on_click(
+                    move |_, _window, cx| {
+                        if let Some(page) = entity.upgrade() {
+                            page.update(cx, |page, cx| {
+                                page.switch_connection(&connection_id, cx);
+                            });
+                        }
+                    },
+                ));
+            }
+
+            let entity = entity.clone();
+            menu.separator()
+                .item(PopupMenuItem::new("New Connection...").on_click(move |_, _window, cx| {
+                    if let Some(page) = entity.upgrade() {
+                        page.update(cx, |page, cx| {
+                            page.open_connection_dialog(None, cx);
+                        });
+                    }
+                }))
+        });
+
+        self.show_connection_menu(menu, position, cx);
+    }
+
+    pub(crate) fn deploy_connection_context_menu(
+        &mut self,
+        connection_id: String,
+        position: Point<Pixels>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(session) = self.connection(&connection_id) else {
+            return;
+        };
+        let is_connected = session.is_connected();
+        let entity = cx.entity().downgrade();
+
+        let menu = PopupMenu::build(window, cx, move |menu, _window, _cx| {
+            let action = |f: fn(&mut PostCommanderPage, &str, &mut Window, &mut Context<PostCommanderPage>)| {
+                let entity = entity.clone();
+                let connection_id = connection_id.clone();
+                move |_: &ClickEvent, window: &mut Window, cx: &mut App| {
+                    if let Some(page) = entity.upgrade() {
+                        page.update(cx, |page, cx| f(page, &connection_id, window, cx));
+                    }
+                }
+            };
+
+            menu.item(PopupMenuItem::new("New Query").on_click(action(|page, id, window, cx| {
+                page.add_tab_for_connection(Some(id.to_string()), window, cx);
+            })))
+            .separator()
+            .item(if is_connected {
+                PopupMenuItem::new("Disconnect").on_click(action(|page, id, _window, cx| {
+                    page.disconnect_profile(id, cx);
+                }))
+            } else {
+                PopupMenuItem::new("Connect").on_click(action(|page, id, _window, cx| {
+                    page.connect_profile(id, cx);
+                }))
+            })
+            .item(PopupMenuItem::new("Edit...").on_click(action(|page, id, _window, cx| {
+                page.open_connection_dialog(Some(id), cx);
+            })))
+            .separator()
+            .item(PopupMenuItem::new("Delete").on_click(action(|page, id, _window, cx| {
+                page.delete_connection_profile(id, cx);
+            })))
+        });
+
+        self.show_connection_menu(menu, position, cx);
+    }
+
+    fn show_connection_menu(&mut self, menu: Entity<PopupMenu>, position: Point<Pixels>, cx: &mut Context<Self>) {
+        let subscription = cx.subscribe(&menu, |this, _, _: &DismissEvent, cx| {
+            this.overlays.connection_menu = None;
+            cx.notify();
+        });
+
+        self.overlays.connection_menu = Some((menu, position, subscription));
+        cx.notify();
+    }
+}
@@ -1,11 +1,23 @@
-use crate::postcommander::types::{ForeignKeyInfo, ForeignKeyRef, TableColumn, TableStructureInfo};
+use crate::postcommander::pg_decode::{decode_value, reg_type_cast};
+use crate::postcommander::sql::ends_transaction;
+use crate::postcommander::types::{
+    CatalogFunction, CatalogType, CompletionCatalog, ForeignKeyInfo, ForeignKeyRef, FunctionKind,
+    TableColumn, TableStructureInfo,
+};
 use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use futures::TryStreamExt;
 use gpui::SharedString;
-use sqlx::postgres::{PgPool, PgRow};
-use sqlx::{Column, Row, TypeInfo, ValueRef};
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::{
+    PgColumn, PgConnectOptions, PgConnection, PgDatabaseError, PgErrorPosition, PgPool, PgRow,
+};
+use sqlx::{Column, Connection, Either, Executor, Postgres, Row, Statement, TypeInfo};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Mutex};
 
 #[derive(Debug, Clone)]
 pub struct ConnectionConfig {
@@ -16,6 +28,7 @@ pub struct ConnectionConfig {
     pub database: String,
     pub username: String,
     pub password: String,
+    pub read_only: bool,
 }
 
 impl ConnectionConfig {
@@ -31,53 +44,396 @@ impl ConnectionConfig {
 pub struct QueryColumn {
     pub name: String,
     pub type_name: String,
+    /// Table column the value was read from, as reported in the row
+    /// description; `None` for expressions.
+    pub source: Option<ColumnSource>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColumnSource {
+    pub table_oid: u32,
+    pub attnum: i16,
+}
+
+/// A relation result columns were read from, looked up by OID.
 #[derive(Debug, Clone)]
+pub struct SourceTable {
+    pub oid: u32,
+    pub schema: String,
+    pub table: String,
+    /// Ordinary or partitioned table, as opposed to a view or other relation.
+    pub is_base_table: bool,
+    /// Column names by attribute number.
+    pub columns: Vec<(i16, String)>,
+    pub primary_key: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum CellValue {
     Null,
     Bool(bool),
     Int(i64),
     Float(f64),
-    Text(String),
-    Bytes(Vec<u8>),
+    /// Exact decimal text, as sent by the server (NUMERIC, MONEY).
+    Numeric(SharedString),
+    Text(SharedString),
+    Bytes(Arc<Vec<u8>>),
+    Uuid(uuid::Uuid),
+    Json(Arc<serde_json::Value>),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    Timestamp(NaiveDateTime),
+    TimestampTz(DateTime<Utc>),
+    Array(Arc<Vec<CellValue>>),
 }
 
 impl CellValue {
-    pub fn display(&self) -> String {
+    pub fn is_null(&self) -> bool {
+        matches!(self, CellValue::Null)
+    }
+
+    pub fn display(&self) -> SharedString {
+        match self {
+            CellValue::Null => SharedString::from("NULL"),
+            CellValue::Bool(b) => SharedString::from(b.to_string()),
+            CellValue::Int(i) => SharedString::from(i.to_string()),
+            CellValue::Float(f) => SharedString::from(f.to_string()),
+            CellValue::Numeric(s) | CellValue::Text(s) => s.clone(),
+            CellValue::Bytes(b) => SharedString::from(format!("[{} bytes]", b.len())),
+            CellValue::Uuid(u) => SharedString::from(u.to_string()),
+            CellValue::Json(v) => SharedString::from(v.to_string()),
+            CellValue::Date(d) => SharedString::from(d.format("%Y-%m-%d").to_string()),
+            CellValue::Time(t) => SharedString::from(t.format("%H:%M:%S%.f").to_string()),
+            CellValue::Timestamp(ts) => {
+                SharedString::from(ts.format("%Y-%m-%d %H:%M:%S%.f").to_string())
+            }
+            CellValue::TimestampTz(ts) => SharedString::from(
+                ts.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S%.f%:z")
+                    .to_string(),
+            ),
+            CellValue::Array(items) => SharedString::from(format_array_literal(items)),
+        }
+    }
+
+    /// Rough number of bytes this value occupies in memory, used to cap
+    /// streamed results.
+    pub fn estimated_size(&self) -> usize {
+        let heap = match self {
+            CellValue::Numeric(s) | CellValue::Text(s) => s.len(),
+            CellValue::Bytes(b) => b.len(),
+            CellValue::Json(v) => v.to_string().len(),
+            CellValue::Array(items) => items.iter().map(CellValue::estimated_size).sum(),
+            _ => 0,
+        };
+        std::mem::size_of::<CellValue>() + heap
+    }
+
+    /// Builds a value from text typed into the grid, using the column type to
+    /// decide whether it should be kept as a number or boolean.
+    pub fn from_edit_text(text: &str, type_name: &str) -> Self {
+        match type_name.to_uppercase().as_str() {
+            "BOOL" | "BOOLEAN" => match text.trim().to_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" | "on" | "1" => CellValue::Bool(true),
+                "false" | "f" | "no" | "n" | "off" | "0" => CellValue::Bool(false),
+                _ => CellValue::Text(SharedString::from(text.to_string())),
+            },
+            "INT2" | "INT4" | "INT8" => text
+                .trim()
+                .parse::<i64>()
+                .map(CellValue::Int)
+                .unwrap_or_else(|_| CellValue::Text(SharedString::from(text.to_string()))),
+            "FLOAT4" | "FLOAT8" => text
+                .trim()
+                .parse::<f64>()
+                .map(CellValue::Float)
+                .unwrap_or_else(|_| CellValue::Text(SharedString::from(text.to_string()))),
+            "NUMERIC" | "MONEY" if text.trim().parse::<f64>().is_ok() => {
+                CellValue::Numeric(SharedString::from(text.trim().to_string()))
+            }
+            "UUID" => uuid::Uuid::parse_str(text.trim())
+                .map(CellValue::Uuid)
+                .unwrap_or_else(|_| CellValue::Text(SharedString::from(text.to_string()))),
+            "JSON" | "JSONB" => serde_json::from_str(text)
+                .map(|v| CellValue::Json(Arc::new(v)))
+                .unwrap_or_else(|_| CellValue::Text(SharedString::from(text.to_string()))),
+            _ => CellValue::Text(SharedString::from(text.to_string())),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            CellValue::Null => serde_json::Value::Null,
+            CellValue::Bool(b) => serde_json::Value::Bool(*b),
+            CellValue::Int(i) => serde_json::Value::from(*i),
+            CellValue::Float(f) => serde_json::Number::from_f64(*f)
+                .map(serde_json::Value::Number)
+                .unwrap_or_else(|| serde_json::Value::String(f.to_string())),
+            CellValue::Numeric(s) => numeric_to_json(s),
+            CellValue::Text(s) => serde_json::Value::String(s.to_string()),
+            CellValue::Bytes(b) => serde_json::Value::String(format!("\\x{}", hex_encode(b))),
+            CellValue::Json(v) => v.as_ref().clone(),
+            CellValue::TimestampTz(ts) => serde_json::Value::String(ts.to_rfc3339()),
+            CellValue::Array(items) => {
+                serde_json::Value::Array(items.iter().map(|item| item.to_json()).collect())
+            }
+            CellValue::Uuid(_)
+            | CellValue::Date(_)
+            | CellValue::Time(_)
+            | CellValue::Timestamp(_) => serde_json::Value::String(self.display().to_string()),
+        }
+    }
+
+    pub fn to_sql_literal(&self) -> String {
         match self {
             CellValue::Null => "NULL".to_string(),
-            CellValue::Bool(b) => b.to_string(),
+            CellValue::Bool(b) => if *b { "TRUE" } else { "FALSE" }.to_string(),
             CellValue::Int(i) => i.to_string(),
-            CellValue::Float(f) => f.to_string(),
-            CellValue::Text(s) => s.clone(),
-            CellValue::Bytes(b) => format!("[{} bytes]", b.len()),
+            CellValue::Float(f) if f.is_finite() => f.to_string(),
+            CellValue::Float(f) => format!("'{}'::float8", f),
+            CellValue::Numeric(s) if s.parse::<f64>().is_ok_and(|f| f.is_finite()) => s.to_string(),
+            CellValue::Numeric(s) => format!("'{}'::numeric", s),
+            CellValue::Text(s) => quote_literal(s),
+            CellValue::Bytes(b) => format!("'\\x{}'::bytea", hex_encode(b)),
+            CellValue::TimestampTz(ts) => {
+                quote_literal(&ts.format("%Y-%m-%d %H:%M:%S%.f+00").to_string())
+            }
+            CellValue::Uuid(_)
+            | CellValue::Json(_)
+            | CellValue::Date(_)
+            | CellValue::Time(_)
+            | CellValue::Timestamp(_)
+            | CellValue::Array(_) => quote_literal(&self.display()),
+        }
+    }
+
+    /// The value in PostgreSQL's text input form, for binding as a text
+    /// parameter and casting on the server. `None` binds NULL.
+    pub fn to_param_text(&self) -> Option<String> {
+        match self {
+            CellValue::Null => None,
+            CellValue::Bytes(b) => Some(format!("\\x{}", hex_encode(b))),
+            CellValue::TimestampTz(ts) => Some(ts.format("%Y-%m-%d %H:%M:%S%.f+00").to_string()),
+            other => Some(other.display().to_string()),
         }
     }
 }
 
+/// Emits a NUMERIC as a JSON number only when an f64 holds it exactly, so
+/// high-precision values survive as strings instead of being rounded.
+fn numeric_to_json(text: &str) -> serde_json::Value {
+    if let Ok(i) = text.parse::<i64>() {
+        return serde_json::Value::from(i);
+    }
+    let normalized = if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text
+    };
+    text.parse::<f64>()
+        .ok()
+        .filter(|f| f.is_finite() && f.to_string() == normalized)
+        .and_then(serde_json::Number::from_f64)
+        .map(serde_json::Value::Number)
+        .unwrap_or_else(|| serde_json::Value::String(text.to_string()))
+}
+
+/// Renders an array the way Postgres writes array literals, e.g. `{1,NULL,"a b"}`.
+fn format_array_literal(items: &[CellValue]) -> String {
+    let parts: Vec<String> = items
+        .iter()
+        .map(|item| match item {
+            CellValue::Null => "NULL".to_string(),
+            CellValue::Array(inner) => format_array_literal(inner),
+            other => {
+                let text = other.display();
+                let needs_quotes = text.is_empty()
+                    || text.eq_ignore_ascii_case("NULL")
+                    || text
+                        .chars()
+                        .any(|c| matches!(c, '{' | '}' | ',' | '"' | '\\') || c.is_whitespace());
+                if needs_quotes {
+                    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+                } else {
+                    text.to_string()
+                }
+            }
+        })
+        .collect();
+    format!("{{{}}}", parts.join(","))
+}
+
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 #[derive(Debug, Clone)]
 pub struct QueryResult {
     pub columns: Vec<QueryColumn>,
-    pub rows: Arc<Vec<Vec<SharedString>>>,
+    pub rows: Arc<Vec<Vec<CellValue>>>,
     #[allow(dead_code)]
     pub rows_affected: u64,
     pub execution_time_ms: u64,
+    pub truncated: Option<ResultTruncation>,
+}
+
+/// Caps applied while streaming a result set into a tab.
+#[derive(Debug, Clone, Copy)]
+pub struct ResultLimits {
+    pub max_rows: Option<usize>,
+    pub max_bytes: usize,
+}
+
+/// Why fetching stopped before the server ran out of rows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResultTruncation {
+    RowLimit(usize),
+    MemoryLimit(usize),
+}
+
+impl ResultTruncation {
+    pub fn message(&self) -> String {
+        match self {
+            ResultTruncation::RowLimit(rows) => {
+                format!("Result truncated at the {} row limit", rows)
+            }
+            ResultTruncation::MemoryLimit(bytes) => {
+                format!("Result truncated at the {} MB memory limit", bytes / (1024 * 1024))
+            }
+        }
+    }
+}
+
+pub enum QueryStreamEvent {
+    /// The statement is about to run on the connection with this backend PID.
+    Started { backend_pid: i32 },
+    Columns(Vec<QueryColumn>),
+    Rows(Vec<Vec<CellValue>>),
+    /// Transaction state of a session after the statement; only sent for
+    /// statements run in a session.
+    Transaction(TransactionStatus),
+    Finished(Result<QueryStreamSummary>),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransactionStatus {
+    #[default]
+    Idle,
+    InTransaction,
+    /// An error aborted the transaction; only ROLLBACK is accepted now.
+    Failed,
+}
+
+/// A connection pinned to one tab for manual transactions. Statements run
+/// in order on it, and a transaction is opened before the first one.
+pub struct Session {
+    conn: PoolConnection<Postgres>,
+    backend_pid: i32,
+    status: TransactionStatus,
+}
+
+type SharedSession = Arc<Mutex<Session>>;
+
+#[derive(Debug, Clone)]
+pub struct QueryStreamSummary {
+    pub row_count: u64,
+    pub execution_time_ms: u64,
+    pub truncated: Option<ResultTruncation>,
 }
 
+const STREAM_BATCH_ROWS: usize = 1000;
+const STREAM_BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
 pub enum DatabaseCommand {
     Connect {
         config: ConnectionConfig,
         response: tokio::sync::oneshot::Sender<Result<()>>,
     },
-    #[allow(dead_code)]
+    /// Sent back to the command loop once a spawned connect has a pool.
+    /// `attempt` tells whether a later connect or disconnect overtook it.
+    PoolReady {
+        pool: PgPool,
+        attempt: u64,
+        response: tokio::sync::oneshot::Sender<Result<()>>,
+    },
     Disconnect {
         response: tokio::sync::oneshot::Sender<Result<()>>,
     },
+    OpenSession {
+        session_id: String,
+        response: tokio::sync::oneshot::Sender<Result<()>>,
+    },
+    /// Sent back to the command loop once a spawned open has its connection.
+    SessionReady {
+        session_id: String,
+        session: Session,
+        response: tokio::sync::oneshot::Sender<Result<()>>,
+    },
+    CloseSession {
+        session_id: String,
+    },
+    EndTransaction {
+        session_id: String,
+        commit: bool,
+        response: tokio::sync::oneshot::Sender<Result<TransactionStatus>>,
+    },
     Execute {
         sql: String,
         response: tokio::sync::oneshot::Sender<Result<QueryResult>>,
     },
+    /// Runs `sql` in its own transaction and commits only if it touched
+    /// exactly one row. Responds with the number of rows it touched.
+    ExecuteSingleRow {
+        sql: String,
+        params: Vec<Option<String>>,
+        /// Joins this session's transaction instead, under a savepoint.
+        session_id: Option<String>,
+        response: tokio::sync::oneshot::Sender<Result<u64>>,
+    },
+    /// Runs every statement in one transaction. Each must end in
+    /// `RETURNING` and return exactly one row; otherwise, or on any error,
+    /// nothing is committed. Responds with each statement's row.
+    ExecuteSingleRowBatch {
+        statements: Vec<(String, Vec<Option<String>>)>,
+        session_id: Option<String>,
+        response: tokio::sync::oneshot::Sender<Result<Vec<QueryResult>>>,
+    },
+    ExecuteStreaming {
+        sql: String,
+        /// Bound to `$1..$n` as text; `None` binds NULL.
+        params: Vec<Option<String>>,
+        limits: ResultLimits,
+        /// Runs on this session's pinned connection instead of the pool.
+        session_id: Option<String>,
+        events: mpsc::UnboundedSender<QueryStreamEvent>,
+    },
+    /// Prepares `sql` without running it and returns the types the server
+    /// inferred for its parameters.
+    DescribeParameters {
+        sql: String,
+        session_id: Option<String>,
+        response: tokio::sync::oneshot::Sender<Result<Vec<String>>>,
+    },
+    /// The planner's estimate of the rows an UPDATE or DELETE would touch,
+    /// from `EXPLAIN` without running it.
+    EstimateRows {
+        sql: String,
+        session_id: Option<String>,
+        response: tokio::sync::oneshot::Sender<Result<i64>>,
+    },
+    /// Runs a `SELECT count(*)` read-only and with a time limit.
+    CountRows {
+        sql: String,
+        response: tokio::sync::oneshot::Sender<Result<i64>>,
+    },
+    CancelBackend {
+        backend_pid: i32,
+        response: tokio::sync::oneshot::Sender<Result<bool>>,
+    },
     FetchPrimaryKeys {
         schema: String,
         table: String,
@@ -100,12 +456,22 @@ pub enum DatabaseCommand {
         table: String,
         response: tokio::sync::oneshot::Sender<Result<TableStructureInfo>>,
     },
+    FetchSourceTables {
+        table_oids: Vec<u32>,
+        response: tokio::sync::oneshot::Sender<Result<Vec<SourceTable>>>,
+    },
+    FetchCompletionCatalog {
+        response: tokio::sync::oneshot::Sender<Result<CompletionCatalog>>,
+    },
 }
 
+/// Owns one pool per connected profile. Every command names the connection
+/// it runs on; work on a pool is spawned so a slow server never holds up
+/// commands for the others.
 pub struct DatabaseManager {
     #[allow(dead_code)]
     runtime: Arc<Runtime>,
-    command_tx: mpsc::UnboundedSender<DatabaseCommand>,
+    command_tx: mpsc::UnboundedSender<(String, DatabaseCommand)>,
 }
 
 impl DatabaseManager {
@@ -118,63 +484,259 @@ impl DatabaseManager {
                 .expect("Failed to create tokio runtime"),
         );
 
-        let (command_tx, mut command_rx) = mpsc::unbounded_channel::<DatabaseCommand>();
+        let (command_tx, mut command_rx) = mpsc::unbounded_channel::<(String, DatabaseCommand)>();
+        let loop_tx = command_tx.clone();
 
         let rt = runtime.clone();
         std::thread::spawn(move || {
             rt.block_on(async move {
-                let mut pool: Option<PgPool> = None;
+                let mut pools: HashMap<String, PgPool> = HashMap::new();
+                // Sessions by connection, then session id. Dropping one
+                // closes its connection, which rolls back an open transaction.
+                let mut sessions: HashMap<String, HashMap<String, SharedSession>> = HashMap::new();
+                // Bumped by every connect and disconnect, so a pool that
+                // arrives after either is stale.
+                let mut attempts: HashMap<String, u64> = HashMap::new();
 
-                while let Some(cmd) = command_rx.recv().await {
+                while let Some((connection_id, cmd)) = command_rx.recv().await {
+                    let pool = pools.get(&connection_id).cloned();
                     match cmd {
                         DatabaseCommand::Connect { config, response } => {
-                            let result = PgPool::connect(&config.connection_string()).await;
-                            match result {
-                                Ok(p) => {
-                                    pool = Some(p);
-                                    let _ = response.send(Ok(()));
-                                }
-                                Err(e) => {
-                                    let _ = response.send(Err(anyhow::anyhow!("{}", e)));
+                            let attempt = attempts.entry(connection_id.clone()).or_default();
+                            *attempt += 1;
+                            let attempt = *attempt;
+                            let loop_tx = loop_tx.clone();
+                            tokio::spawn(async move {
+                                match connect_pool(&config).await {
+                                    Ok(pool) => {
+                                        let _ = loop_tx.send((
+                                            connection_id,
+                                            DatabaseCommand::PoolReady { pool, attempt, response },
+                                        ));
+                                    }
+                                    Err(e) => {
+                                        let _ = response.send(Err(anyhow::anyhow!("{}", e)));
+                                    }
                                 }
+                            });
+                        }
+                        DatabaseCommand::PoolReady { pool: new_pool, attempt, response } => {
+                            // Closing waits for checked-out connections, so
+                            // don't hold up the loop on it.
+                            if attempts.get(&connection_id) != Some(&attempt) {
+                                tokio::spawn(async move { new_pool.close().await });
+                                let _ = response.send(Err(anyhow::anyhow!("Connection was closed while connecting")));
+                                continue;
                             }
+                            sessions.remove(&connection_id);
+                            if let Some(old) = pools.insert(connection_id, new_pool) {
+                                tokio::spawn(async move { old.close().await });
+                            }
+                            let _ = response.send(Ok(()));
                         }
                         DatabaseCommand::Disconnect { response } => {
-                            if let Some(p) = pool.take() {
-                                p.close().await;
+                            *attempts.entry(connection_id.clone()).or_default() += 1;
+                            sessions.remove(&connection_id);
+                            if let Some(p) = pools.remove(&connection_id) {
+                                tokio::spawn(async move { p.close().await });
                             }
                             let _ = response.send(Ok(()));
                         }
-                        DatabaseCommand::Execute { sql, response } => {
-                            if let Some(ref p) = pool {
-                                let start = std::time::Instant::now();
-                                let result = execute_query(p, &sql).await;
-                                let elapsed = start.elapsed().as_millis() as u64;
-                                match result {
-                                    Ok(mut qr) => {
-                                        qr.execution_time_ms = elapsed;
-                                        let _ = response.send(Ok(qr));
+                        DatabaseCommand::OpenSession { session_id, response } => {
+                            if let Some(p) = pool {
+                                let loop_tx = loop_tx.clone();
+                                tokio::spawn(async move {
+                                    match open_session(&p).await {
+                                        Ok(session) => {
+                                            let _ = loop_tx.send((
+                                                connection_id,
+                                                DatabaseCommand::SessionReady {
+                                                    session_id,
+                                                    session,
+                                                    response,
+                                                },
+                                            ));
+                                        }
+                                        Err(e) => {
+                                            let _ = response.send(Err(e));
+                                        }
                                     }
-                                    Err(e) => {
-                                        let _ = response.send(Err(e));
-                                    }
-                                }
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::SessionReady { session_id, session, response } => {
+                            // The pool may have been replaced or closed while
+                            // the connection was being acquired.
+                            if pool.is_some() {
+                                sessions
+                                    .entry(connection_id)
+                                    .or_default()
+                                    .insert(session_id, Arc::new(Mutex::new(session)));
+                                let _ = response.send(Ok(()));
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::CloseSession { session_id } => {
+                            if let Some(by_id) = sessions.get_mut(&connection_id) {
+                                by_id.remove(&session_id);
+                            }
+                        }
+                        DatabaseCommand::EndTransaction { session_id, commit, response } => {
+                            let session = sessions.get(&connection_id).and_then(|s| s.get(&session_id)).cloned();
+                            if let Some(session) = session {
+                                tokio::spawn(async move {
+                                    let _ = response.send(end_transaction(&session, commit).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Transaction session closed")));
+                            }
+                        }
+                        DatabaseCommand::Execute { sql, response } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let start = std::time::Instant::now();
+                                    let result = execute_query(&p, &sql).await.map(|mut qr| {
+                                        qr.execution_time_ms = start.elapsed().as_millis() as u64;
+                                        qr
+                                    });
+                                    let _ = response.send(result);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::ExecuteSingleRow { sql, params, session_id: Some(session_id), response } => {
+                            let session = sessions.get(&connection_id).and_then(|s| s.get(&session_id)).cloned();
+                            if let Some(session) = session {
+                                tokio::spawn(async move {
+                                    let _ = response.send(execute_session_single_row(&session, &sql, params).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Transaction session closed")));
+                            }
+                        }
+                        DatabaseCommand::ExecuteSingleRow { sql, params, session_id: None, response } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(execute_single_row(&p, &sql, params).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::ExecuteSingleRowBatch { statements, session_id: Some(session_id), response } => {
+                            let session = sessions.get(&connection_id).and_then(|s| s.get(&session_id)).cloned();
+                            if let Some(session) = session {
+                                tokio::spawn(async move {
+                                    let _ = response.send(execute_session_single_row_batch(&session, pool, statements).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Transaction session closed")));
+                            }
+                        }
+                        DatabaseCommand::ExecuteSingleRowBatch { statements, session_id: None, response } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(execute_single_row_batch(&p, statements).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::ExecuteStreaming { sql, params, limits, session_id: Some(session_id), events } => {
+                            let session = sessions.get(&connection_id).and_then(|s| s.get(&session_id)).cloned();
+                            if let Some(session) = session {
+                                tokio::spawn(stream_session_query(session, pool, sql, params, limits, events));
+                            } else {
+                                let _ = events.send(QueryStreamEvent::Finished(Err(
+                                    anyhow::anyhow!("Transaction session closed"),
+                                )));
+                            }
+                        }
+                        DatabaseCommand::ExecuteStreaming { sql, params, limits, session_id: None, events } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(stream_query(p, sql, params, limits, events));
+                            } else {
+                                let _ = events.send(QueryStreamEvent::Finished(Err(
+                                    anyhow::anyhow!("Not connected"),
+                                )));
+                            }
+                        }
+                        DatabaseCommand::DescribeParameters { sql, session_id: Some(session_id), response } => {
+                            let session = sessions.get(&connection_id).and_then(|s| s.get(&session_id)).cloned();
+                            if let Some(session) = session {
+                                tokio::spawn(async move {
+                                    let mut session = session.lock().await;
+                                    let _ = response.send(describe_parameters(&mut *session.conn, &sql).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Transaction session closed")));
+                            }
+                        }
+                        DatabaseCommand::DescribeParameters { sql, session_id: None, response } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(describe_parameters(&p, &sql).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::EstimateRows { sql, session_id: Some(session_id), response } => {
+                            let session = sessions.get(&connection_id).and_then(|s| s.get(&session_id)).cloned();
+                            if let Some(session) = session {
+                                tokio::spawn(async move {
+                                    let mut session = session.lock().await;
+                                    let _ = response.send(estimate_rows(&mut *session.conn, &sql).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Transaction session closed")));
+                            }
+                        }
+                        DatabaseCommand::EstimateRows { sql, session_id: None, response } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(estimate_rows(&p, &sql).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::CountRows { sql, response } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(count_rows(&p, &sql).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::CancelBackend { backend_pid, response } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(cancel_backend(&p, backend_pid).await);
+                                });
                             } else {
                                 let _ = response.send(Err(anyhow::anyhow!("Not connected")));
                             }
                         }
                         DatabaseCommand::FetchPrimaryKeys { schema, table, response } => {
-                            if let Some(ref p) = pool {
-                                let result = fetch_primary_keys(p, &schema, &table).await;
-                                let _ = response.send(result);
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(fetch_primary_keys(&p, &schema, &table).await);
+                                });
                             } else {
                                 let _ = response.send(Err(anyhow::anyhow!("Not connected")));
                             }
                         }
                         DatabaseCommand::FetchForeignKeys { schema, table, response } => {
-                            if let Some(ref p) = pool {
-                                let result = fetch_foreign_keys(p, &schema, &table).await;
-                                let _ = response.send(result);
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(fetch_foreign_keys(&p, &schema, &table).await);
+                                });
                             } else {
                                 let _ = response.send(Err(anyhow::anyhow!("Not connected")));
                             }
@@ -186,24 +748,45 @@ impl DatabaseManager {
                             value,
                             response,
                         } => {
-                            if let Some(ref p) = pool {
-                                let result = fetch_fk_referenced_row(
-                                    p,
-                                    &referenced_schema,
-                                    &referenced_table,
-                                    &referenced_column,
-                                    &value,
-                                )
-                                .await;
-                                let _ = response.send(result);
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let result = fetch_fk_referenced_row(
+                                        &p,
+                                        &referenced_schema,
+                                        &referenced_table,
+                                        &referenced_column,
+                                        &value,
+                                    )
+                                    .await;
+                                    let _ = response.send(result);
+                                });
                             } else {
                                 let _ = response.send(Err(anyhow::anyhow!("Not connected")));
                             }
                         }
                         DatabaseCommand::FetchTableStructure { schema, table, response } => {
-                            if let Some(ref p) = pool {
-                                let result = fetch_table_structure(p, &schema, &table).await;
-                                let _ = response.send(result);
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(fetch_table_structure(&p, &schema, &table).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::FetchSourceTables { table_oids, response } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(fetch_source_tables(&p, &table_oids).await);
+                                });
+                            } else {
+                                let _ = response.send(Err(anyhow::anyhow!("Not connected")));
+                            }
+                        }
+                        DatabaseCommand::FetchCompletionCatalog { response } => {
+                            if let Some(p) = pool {
+                                tokio::spawn(async move {
+                                    let _ = response.send(fetch_completion_catalog(&p).await);
+                                });
                             } else {
                                 let _ = response.send(Err(anyhow::anyhow!("Not connected")));
                             }
@@ -219,38 +802,191 @@ impl DatabaseManager {
         }
     }
 
+    fn send(&self, connection_id: &str, command: DatabaseCommand) {
+        let _ = self.command_tx.send((connection_id.to_string(), command));
+    }
+
+    /// Opens a pool for `connection_id`, replacing any pool it already has.
     pub fn connect(
         &self,
+        connection_id: &str,
         config: ConnectionConfig,
     ) -> tokio::sync::oneshot::Receiver<Result<()>> {
         let (tx, rx) = tokio::sync::oneshot::channel();
-        let _ = self.command_tx.send(DatabaseCommand::Connect {
+        self.send(connection_id, DatabaseCommand::Connect {
             config,
             response: tx,
         });
         rx
     }
 
-    #[allow(dead_code)]
-    pub fn disconnect(&self) -> tokio::sync::oneshot::Receiver<Result<()>> {
+    pub fn disconnect(&self, connection_id: &str) -> tokio::sync::oneshot::Receiver<Result<()>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::Disconnect { response: tx });
+        rx
+    }
+
+    pub fn execute(
+        &self,
+        connection_id: &str,
+        sql: String,
+    ) -> tokio::sync::oneshot::Receiver<Result<QueryResult>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::Execute { sql, response: tx });
+        rx
+    }
+
+    pub fn execute_single_row(
+        &self,
+        connection_id: &str,
+        sql: String,
+        params: Vec<Option<String>>,
+        session_id: Option<String>,
+    ) -> tokio::sync::oneshot::Receiver<Result<u64>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::ExecuteSingleRow {
+            sql,
+            params,
+            session_id,
+            response: tx,
+        });
+        rx
+    }
+
+    pub fn execute_single_row_batch(
+        &self,
+        connection_id: &str,
+        statements: Vec<(String, Vec<Option<String>>)>,
+        session_id: Option<String>,
+    ) -> tokio::sync::oneshot::Receiver<Result<Vec<QueryResult>>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::ExecuteSingleRowBatch {
+            statements,
+            session_id,
+            response: tx,
+        });
+        rx
+    }
+
+    /// Runs a query and streams its rows back in batches. The stream ends
+    /// with a `Finished` event; dropping the receiver stops the fetch.
+    pub fn execute_streaming(
+        &self,
+        connection_id: &str,
+        sql: String,
+        params: Vec<Option<String>>,
+        limits: ResultLimits,
+        session_id: Option<String>,
+    ) -> mpsc::UnboundedReceiver<QueryStreamEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.send(connection_id, DatabaseCommand::ExecuteStreaming {
+            sql,
+            params,
+            limits,
+            session_id,
+            events: tx,
+        });
+        rx
+    }
+
+    /// Pins a pooled connection under `session_id` for manual transactions.
+    pub fn open_session(
+        &self,
+        connection_id: &str,
+        session_id: String,
+    ) -> tokio::sync::oneshot::Receiver<Result<()>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::OpenSession {
+            session_id,
+            response: tx,
+        });
+        rx
+    }
+
+    /// Releases a session's connection, rolling back anything uncommitted.
+    pub fn close_session(&self, connection_id: &str, session_id: String) {
+        self.send(connection_id, DatabaseCommand::CloseSession { session_id });
+    }
+
+    /// Commits or rolls back the session's transaction.
+    pub fn end_transaction(
+        &self,
+        connection_id: &str,
+        session_id: String,
+        commit: bool,
+    ) -> tokio::sync::oneshot::Receiver<Result<TransactionStatus>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::EndTransaction {
+            session_id,
+            commit,
+            response: tx,
+        });
+        rx
+    }
+
+    pub fn describe_parameters(
+        &self,
+        connection_id: &str,
+        sql: String,
+        session_id: Option<String>,
+    ) -> tokio::sync::oneshot::Receiver<Result<Vec<String>>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::DescribeParameters {
+            sql,
+            session_id,
+            response: tx,
+        });
+        rx
+    }
+
+    pub fn estimate_rows(
+        &self,
+        connection_id: &str,
+        sql: String,
+        session_id: Option<String>,
+    ) -> tokio::sync::oneshot::Receiver<Result<i64>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::EstimateRows {
+            sql,
+            session_id,
+            response: tx,
+        });
+        rx
+    }
+
+    pub fn count_rows(
+        &self,
+        connection_id: &str,
+        sql: String,
+    ) -> tokio::sync::oneshot::Receiver<Result<i64>> {
         let (tx, rx) = tokio::sync::oneshot::channel();
-        let _ = self.command_tx.send(DatabaseCommand::Disconnect { response: tx });
+        self.send(connection_id, DatabaseCommand::CountRows { sql, response: tx });
         rx
     }
 
-    pub fn execute(&self, sql: String) -> tokio::sync::oneshot::Receiver<Result<QueryResult>> {
+    /// Asks the server to cancel the statement running on `backend_pid`.
+    /// Resolves to whether the server confirmed the cancel request.
+    pub fn cancel_backend(
+        &self,
+        connection_id: &str,
+        backend_pid: i32,
+    ) -> tokio::sync::oneshot::Receiver<Result<bool>> {
         let (tx, rx) = tokio::sync::oneshot::channel();
-        let _ = self.command_tx.send(DatabaseCommand::Execute { sql, response: tx });
+        self.send(connection_id, DatabaseCommand::CancelBackend {
+            backend_pid,
+            response: tx,
+        });
         rx
     }
 
     pub fn fetch_primary_keys(
         &self,
+        connection_id: &str,
         schema: String,
         table: String,
     ) -> tokio::sync::oneshot::Receiver<Result<Vec<String>>> {
         let (tx, rx) = tokio::sync::oneshot::channel();
-        let _ = self.command_tx.send(DatabaseCommand::FetchPrimaryKeys {
+        self.send(connection_id, DatabaseCommand::FetchPrimaryKeys {
             schema,
             table,
             response: tx,
@@ -260,11 +996,12 @@ impl DatabaseManager {
 
     pub fn fetch_foreign_keys(
         &self,
+        connection_id: &str,
         schema: String,
         table: String,
     ) -> tokio::sync::oneshot::Receiver<Result<Vec<ForeignKeyInfo>>> {
         let (tx, rx) = tokio::sync::oneshot::channel();
-        let _ = self.command_tx.send(DatabaseCommand::FetchForeignKeys {
+        self.send(connection_id, DatabaseCommand::FetchForeignKeys {
             schema,
             table,
             response: tx,
@@ -274,13 +1011,14 @@ impl DatabaseManager {
 
     pub fn fetch_fk_referenced_row(
         &self,
+        connection_id: &str,
         referenced_schema: String,
         referenced_table: String,
         referenced_column: String,
         value: String,
     ) -> tokio::sync::oneshot::Receiver<Result<Vec<(String, String)>>> {
         let (tx, rx) = tokio::sync::oneshot::channel();
-        let _ = self.command_tx.send(DatabaseCommand::FetchFkReferencedRow {
+        self.send(connection_id, DatabaseCommand::FetchFkReferencedRow {
             referenced_schema,
             referenced_table,
             referenced_column,
@@ -292,64 +1030,635 @@ impl DatabaseManager {
 
     pub fn fetch_table_structure(
         &self,
+        connection_id: &str,
         schema: String,
         table: String,
     ) -> tokio::sync::oneshot::Receiver<Result<TableStructureInfo>> {
         let (tx, rx) = tokio::sync::oneshot::channel();
-        let _ = self.command_tx.send(DatabaseCommand::FetchTableStructure {
+        self.send(connection_id, DatabaseCommand::FetchTableStructure {
             schema,
             table,
             response: tx,
         });
         rx
     }
+
+    pub fn fetch_source_tables(
+        &self,
+        connection_id: &str,
+        table_oids: Vec<u32>,
+    ) -> tokio::sync::oneshot::Receiver<Result<Vec<SourceTable>>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::FetchSourceTables {
+            table_oids,
+            response: tx,
+        });
+        rx
+    }
+
+    pub fn fetch_completion_catalog(
+        &self,
+        connection_id: &str,
+    ) -> tokio::sync::oneshot::Receiver<Result<CompletionCatalog>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(connection_id, DatabaseCommand::FetchCompletionCatalog { response: tx });
+        rx
+    }
+}
+
+async fn connect_pool(config: &ConnectionConfig) -> Result<PgPool> {
+    let mut options: PgConnectOptions = config.connection_string().parse()?;
+    if config.read_only {
+        options = options.options([("default_transaction_read_only", "on")]);
+    }
+    Ok(PgPool::connect_with(options).await?)
+}
+
+async fn execute_single_row(pool: &PgPool, sql: &str, params: Vec<Option<String>>) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    let rows_affected = execute_row_change(&mut tx, sql, params).await?;
+    if rows_affected == 1 {
+        tx.commit().await?;
+    } else {
+        tx.rollback().await?;
+    }
+    Ok(rows_affected)
+}
+
+async fn execute_row_change(conn: &mut PgConnection, sql: &str, params: Vec<Option<String>>) -> Result<u64> {
+    Ok(params
+        .into_iter()
+        .fold(sqlx::query(sql), |query, value| query.bind(value))
+        .execute(conn)
+        .await?
+        .rows_affected())
+}
+
+/// Errors name the failing statement by its 1-based position. Returning
+/// early drops the transaction, which rolls it back.
+async fn execute_single_row_batch(
+    pool: &PgPool,
+    statements: Vec<(String, Vec<Option<String>>)>,
+) -> Result<Vec<QueryResult>> {
+    let mut tx = pool.begin().await?;
+    let results = execute_row_changes(&mut tx, statements).await?;
+    tx.commit().await?;
+    Ok(name_row_changes(Some(pool), results).await)
+}
+
+async fn execute_row_changes(
+    conn: &mut PgConnection,
+    statements: Vec<(String, Vec<Option<String>>)>,
+) -> Result<Vec<(QueryResult, Vec<Option<&'static str>>)>> {
+    let total = statements.len();
+    let mut results = Vec::with_capacity(total);
+    for (i, (sql, params)) in statements.into_iter().enumerate() {
+        let rows: Vec<PgRow> = params
+            .into_iter()
+            .fold(sqlx::query(&sql), |query, value| query.bind(value))
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| anyhow::anyhow!("Change {} of {}: {}", i + 1, total, e))?;
+        if rows.len() != 1 {
+            anyhow::bail!(
+                "Change {} of {} matched {} rows instead of 1",
+                i + 1,
+                total,
+                rows.len()
+            );
+        }
+        results.push((query_result(&rows), reg_casts(&rows[0])));
+    }
+    Ok(results)
+}
+
+async fn name_row_changes(
+    pool: Option<&PgPool>,
+    results: Vec<(QueryResult, Vec<Option<&'static str>>)>,
+) -> Vec<QueryResult> {
+    let mut names = RegNames::new();
+    let mut named = Vec::with_capacity(results.len());
+    for (mut result, casts) in results {
+        if let Some(pool) = pool {
+            let rows = Arc::make_mut(&mut result.rows);
+            name_reg_values(pool, &casts, rows, &mut names).await;
+        }
+        named.push(result);
+    }
+    named
+}
+
+/// Runs a grid edit in the session's transaction, opening one if none is
+/// open, so it is kept or undone along with the tab's other statements.
+async fn execute_session_single_row(
+    session: &SharedSession,
+    sql: &str,
+    params: Vec<Option<String>>,
+) -> Result<u64> {
+    let mut session = session.lock().await;
+    let began = begin_session_edit(&mut session).await?;
+    let result = execute_row_change(&mut session.conn, sql, params).await;
+    finish_session_edit(&mut session, began, matches!(result, Ok(1))).await?;
+    result
+}
+
+async fn execute_session_single_row_batch(
+    session: &SharedSession,
+    pool: Option<PgPool>,
+    statements: Vec<(String, Vec<Option<String>>)>,
+) -> Result<Vec<QueryResult>> {
+    let mut session = session.lock().await;
+    let began = begin_session_edit(&mut session).await?;
+    let result = execute_row_changes(&mut session.conn, statements).await;
+    finish_session_edit(&mut session, began, result.is_ok()).await?;
+    Ok(name_row_changes(pool.as_ref(), result?).await)
+}
+
+/// Sets a savepoint for an edit, beginning a transaction first if the
+/// session has none. Returns whether it began one.
+async fn begin_session_edit(session: &mut Session) -> Result<bool> {
+    let began = match session.status {
+        TransactionStatus::Failed => {
+            anyhow::bail!("The transaction failed. Roll it back before editing.")
+        }
+        TransactionStatus::Idle => {
+            sqlx::query("BEGIN").execute(&mut *session.conn).await?;
+            session.status = TransactionStatus::InTransaction;
+            true
+        }
+        TransactionStatus::InTransaction => false,
+    };
+    sqlx::query("SAVEPOINT grid_edit").execute(&mut *session.conn).await?;
+    Ok(began)
+}
+
+/// Keeps the edit, or undoes only it: a transaction begun for the edit is
+/// rolled back whole, an earlier one back to the savepoint.
+async fn finish_session_edit(session: &mut Session, began: bool, keep: bool) -> Result<()> {
+    let sql = match (keep, began) {
+        (true, _) => "RELEASE SAVEPOINT grid_edit",
+        (false, true) => "ROLLBACK",
+        (false, false) => "ROLLBACK TO SAVEPOINT grid_edit; RELEASE SAVEPOINT grid_edit",
+    };
+    match session.conn.execute(sqlx::raw_sql(sql)).await {
+        Ok(_) => {
+            if !keep && began {
+                session.status = TransactionStatus::Idle;
+            }
+            Ok(())
+        }
+        Err(e) => {
+            let e = e.into();
+            session.status = status_after_error(sql, session.status, &e);
+            Err(e)
+        }
+    }
 }
 
 async fn execute_query(pool: &PgPool, sql: &str) -> Result<QueryResult> {
     let rows: Vec<PgRow> = sqlx::query(sql).fetch_all(pool).await?;
+    let mut result = query_result(&rows);
+    if let Some(row) = rows.first() {
+        let rows = Arc::make_mut(&mut result.rows);
+        name_reg_values(pool, &reg_casts(row), rows, &mut RegNames::new()).await;
+    }
+    Ok(result)
+}
 
+fn query_column(column: &PgColumn) -> QueryColumn {
+    QueryColumn {
+        name: column.name().to_string(),
+        type_name: column.type_info().name().to_string(),
+        source: column
+            .relation_id()
+            .zip(column.relation_attribute_no())
+            .map(|(oid, attnum)| ColumnSource {
+                table_oid: oid.0,
+                attnum,
+            }),
+    }
+}
+
+fn query_result(rows: &[PgRow]) -> QueryResult {
     if rows.is_empty() {
-        return Ok(QueryResult {
+        return QueryResult {
             columns: vec![],
             rows: Arc::new(vec![]),
             rows_affected: 0,
             execution_time_ms: 0,
-        });
+            truncated: None,
+        };
     }
 
     let columns: Vec<QueryColumn> = rows[0]
         .columns()
         .iter()
-        .map(|c| QueryColumn {
-            name: c.name().to_string(),
-            type_name: c.type_info().name().to_string(),
-        })
+        .map(query_column)
         .collect();
 
-    let result_rows: Vec<Vec<SharedString>> = rows
+    let result_rows: Vec<Vec<CellValue>> = rows
         .iter()
         .map(|row| {
             row.columns()
                 .iter()
                 .enumerate()
-                .map(|(i, col)| {
-                    let cell = extract_cell_value(row, i, col.type_info().name());
-                    SharedString::from(cell.display())
-                })
+                .map(|(i, _)| extract_cell_value(row, i))
                 .collect()
         })
         .collect();
 
     let row_count = result_rows.len() as u64;
 
-    Ok(QueryResult {
+    QueryResult {
         columns,
         rows: Arc::new(result_rows),
         rows_affected: row_count,
         execution_time_ms: 0,
+        truncated: None,
+    }
+}
+
+async fn stream_query(
+    pool: PgPool,
+    sql: String,
+    params: Vec<Option<String>>,
+    limits: ResultLimits,
+    events: mpsc::UnboundedSender<QueryStreamEvent>,
+) {
+    let start = Instant::now();
+    let result = stream_pooled_query(&pool, &sql, params, limits, &events).await;
+    let _ = events.send(QueryStreamEvent::Finished(stream_summary(result, start)));
+}
+
+async fn stream_session_query(
+    session: SharedSession,
+    pool: Option<PgPool>,
+    sql: String,
+    params: Vec<Option<String>>,
+    limits: ResultLimits,
+    events: mpsc::UnboundedSender<QueryStreamEvent>,
+) {
+    let mut session = session.lock().await;
+    let start = Instant::now();
+
+    let result = async {
+        if session.status == TransactionStatus::Idle {
+            sqlx::query("BEGIN").execute(&mut *session.conn).await?;
+            session.status = TransactionStatus::InTransaction;
+        }
+        let backend_pid = session.backend_pid;
+        // Rows left unread after a truncation are skipped by sqlx before the
+        // next statement; closing would lose the transaction.
+        stream_query_rows(&mut session.conn, pool.as_ref(), backend_pid, &sql, params, limits, &events)
+            .await
+            .map(|(row_count, truncated, _)| (row_count, truncated))
+    }
+    .await;
+
+    session.status = match &result {
+        Ok(_) if ends_transaction(&sql) => TransactionStatus::Idle,
+        Ok(_) => TransactionStatus::InTransaction,
+        Err(e) => status_after_error(&sql, session.status, e),
+    };
+    let _ = events.send(QueryStreamEvent::Transaction(session.status));
+    let _ = events.send(QueryStreamEvent::Finished(stream_summary(result, start)));
+}
+
+fn stream_summary(
+    result: Result<(u64, Option<ResultTruncation>)>,
+    start: Instant,
+) -> Result<QueryStreamSummary> {
+    result.map(|(row_count, truncated)| QueryStreamSummary {
+        row_count,
+        execution_time_ms: start.elapsed().as_millis() as u64,
+        truncated,
+    })
+}
+
+async fn stream_pooled_query(
+    pool: &PgPool,
+    sql: &str,
+    params: Vec<Option<String>>,
+    limits: ResultLimits,
+    events: &mpsc::UnboundedSender<QueryStreamEvent>,
+) -> Result<(u64, Option<ResultTruncation>)> {
+    let mut conn = pool.acquire().await?;
+    let backend_pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let (row_count, truncated, complete) =
+        stream_query_rows(&mut conn, Some(pool), backend_pid, sql, params, limits, events).await?;
+
+    if !complete {
+        // The server keeps sending the remaining rows, so close the
+        // connection rather than draining it back into the pool.
+        let _ = conn.close().await;
+    }
+
+    Ok((row_count, truncated))
+}
+
+/// Streams the statement's rows to `events`. The returned flag is false
+/// when fetching stopped before the server finished sending.
+async fn stream_query_rows(
+    conn: &mut PgConnection,
+    pool: Option<&PgPool>,
+    backend_pid: i32,
+    sql: &str,
+    params: Vec<Option<String>>,
+    limits: ResultLimits,
+    events: &mpsc::UnboundedSender<QueryStreamEvent>,
+) -> Result<(u64, Option<ResultTruncation>, bool)> {
+    let _ = events.send(QueryStreamEvent::Started { backend_pid });
+
+    let mut row_count: u64 = 0;
+    let mut truncated = None;
+    let mut stopped_early = false;
+
+    {
+        let query = params
+            .into_iter()
+            .fold(sqlx::query(sql), |query, value| query.bind(value));
+        let mut stream = query.fetch(&mut *conn);
+        let mut batch: Vec<Vec<CellValue>> = Vec::new();
+        let mut bytes = 0usize;
+        let mut last_flush = Instant::now();
+        let mut casts = vec![];
+        let mut names = RegNames::new();
+
+        while let Some(row) = stream.try_next().await? {
+            if row_count == 0 {
+                let columns = row
+                    .columns()
+                    .iter()
+                    .map(query_column)
+                    .collect();
+                let _ = events.send(QueryStreamEvent::Columns(columns));
+                casts = reg_casts(&row);
+            }
+
+            if let Some(max_rows) = limits.max_rows {
+                if row_count as usize >= max_rows {
+                    truncated = Some(ResultTruncation::RowLimit(max_rows));
+                    break;
+                }
+            }
+            if bytes >= limits.max_bytes {
+                truncated = Some(ResultTruncation::MemoryLimit(limits.max_bytes));
+                break;
+            }
+
+            let cells: Vec<CellValue> = (0..row.len()).map(|i| extract_cell_value(&row, i)).collect();
+            bytes += cells.iter().map(CellValue::estimated_size).sum::<usize>();
+            batch.push(cells);
+            row_count += 1;
+
+            if batch.len() >= STREAM_BATCH_ROWS || last_flush.elapsed() >= STREAM_BATCH_INTERVAL {
+                if let Some(pool) = pool {
+                    name_reg_values(pool, &casts, &mut batch, &mut names).await;
+                }
+                if events.send(QueryStreamEvent::Rows(std::mem::take(&mut batch))).is_err() {
+                    stopped_early = true;
+                    break;
+                }
+                last_flush = Instant::now();
+            }
+        }
+
+        if !batch.is_empty() {
+            if let Some(pool) = pool {
+                name_reg_values(pool, &casts, &mut batch, &mut names).await;
+            }
+            let _ = events.send(QueryStreamEvent::Rows(batch));
+        }
+    }
+
+    Ok((row_count, truncated, truncated.is_none() && !stopped_early))
+}
+
+async fn describe_parameters<'c, E>(executor: E, sql: &str) -> Result<Vec<String>>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let statement = executor.prepare(sql).await?;
+    Ok(match statement.parameters() {
+        Some(Either::Left(types)) => types.iter().map(|t| t.name().to_string()).collect(),
+        _ => vec![],
+    })
+}
+
+/// Reads the estimate off the plan under the ModifyTable node; the node
+/// itself reports the rows it returns, which is none without RETURNING.
+async fn estimate_rows<'c, E>(executor: E, sql: &str) -> Result<i64>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let explain = format!("EXPLAIN (FORMAT JSON) {}", sql);
+    let plan: serde_json::Value = sqlx::query_scalar(&explain)
+        .persistent(false)
+        .fetch_one(executor)
+        .await?;
+    let plan = &plan[0]["Plan"];
+    let scan = match plan["Node Type"].as_str() {
+        Some("ModifyTable") => plan["Plans"]
+            .as_array()
+            .and_then(|plans| plans.iter().find(|p| p["Parent Relationship"] == "Outer"))
+            .unwrap_or(plan),
+        _ => plan,
+    };
+    scan["Plan Rows"]
+        .as_f64()
+        .map(|rows| rows as i64)
+        .ok_or_else(|| anyhow::anyhow!("The plan has no row estimate"))
+}
+
+/// Read-only so a function in the condition can't write, and on the pool
+/// so a slow count never holds up the tab's transaction.
+async fn count_rows(pool: &PgPool, sql: &str) -> Result<i64> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION READ ONLY").execute(&mut *tx).await?;
+    sqlx::query("SET LOCAL statement_timeout = '30s'")
+        .execute(&mut *tx)
+        .await?;
+    let count: i64 = sqlx::query_scalar(sql)
+        .persistent(false)
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.rollback().await?;
+    Ok(count)
+}
+
+async fn open_session(pool: &PgPool) -> Result<Session> {
+    let mut conn = pool.acquire().await?;
+    // Never hand a connection with a half-finished transaction back to
+    // the pool.
+    conn.close_on_drop();
+    let backend_pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(Session {
+        conn,
+        backend_pid,
+        status: TransactionStatus::Idle,
     })
 }
 
+async fn end_transaction(session: &SharedSession, commit: bool) -> Result<TransactionStatus> {
+    let mut session = session.lock().await;
+    let sql = if commit { "COMMIT" } else { "ROLLBACK" };
+    match sqlx::query(sql).execute(&mut *session.conn).await {
+        Ok(_) => {
+            session.status = TransactionStatus::Idle;
+            Ok(session.status)
+        }
+        Err(e) => {
+            let e = e.into();
+            session.status = status_after_error(sql, session.status, &e);
+            Err(e)
+        }
+    }
+}
+
+/// The session's state after `sql` failed. An error from the server aborts
+/// the transaction, or ends it when `sql` was ending it anyway. Any other
+/// error, such as a lost connection, says nothing about the transaction.
+fn status_after_error(sql: &str, status: TransactionStatus, error: &anyhow::Error) -> TransactionStatus {
+    let rejected = error
+        .downcast_ref::<sqlx::Error>()
+        .is_some_and(|e| e.as_database_error().is_some());
+    if !rejected {
+        status
+    } else if ends_transaction(sql) {
+        TransactionStatus::Idle
+    } else {
+        TransactionStatus::Failed
+    }
+}
+
+/// True when the server aborted the statement because of a cancel request
+/// (SQLSTATE 57014 `query_canceled`).
+pub fn is_query_cancelled(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<sqlx::Error>()
+        .and_then(|e| e.as_database_error())
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == "57014")
+}
+
+/// A failed statement with the fields the server reported. Errors that
+/// never reached the server carry only the message.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryError {
+    pub message: String,
+    /// SQLSTATE, such as `23505`.
+    pub code: Option<String>,
+    pub detail: Option<String>,
+    pub hint: Option<String>,
+    pub schema: Option<String>,
+    pub table: Option<String>,
+    pub column: Option<String>,
+    pub constraint: Option<String>,
+    /// Where in the statement the server placed the error, in characters
+    /// from 1. Positions inside a function body the statement called are
+    /// left out.
+    pub position: Option<usize>,
+}
+
+impl QueryError {
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        let database_error = error
+            .downcast_ref::<sqlx::Error>()
+            .and_then(|e| e.as_database_error())
+            .and_then(|e| e.try_downcast_ref::<PgDatabaseError>());
+        let Some(e) = database_error else {
+            return Self::from(error.to_string());
+        };
+        let owned = |value: Option<&str>| value.map(str::to_string);
+        Self {
+            message: e.message().to_string(),
+            code: Some(e.code().to_string()),
+            detail: owned(e.detail()),
+            hint: owned(e.hint()),
+            schema: owned(e.schema()),
+            table: owned(e.table()),
+            column: owned(e.column()),
+            constraint: owned(e.constraint()),
+            position: match e.position() {
+                Some(PgErrorPosition::Original(position)) => Some(position),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn is_unique_violation(&self) -> bool {
+        self.code.as_deref() == Some("23505")
+    }
+
+    /// Condition name of the SQLSTATE, for the codes people commonly hit.
+    pub fn condition(&self) -> Option<&'static str> {
+        Some(match self.code.as_deref()? {
+            "23505" => "unique_violation",
+            "23503" => "foreign_key_violation",
+            "23502" => "not_null_violation",
+            "23514" => "check_violation",
+            "23P01" => "exclusion_violation",
+            "22P02" => "invalid_text_representation",
+            "22001" => "string_data_right_truncation",
+            "22003" => "numeric_value_out_of_range",
+            "22012" => "division_by_zero",
+            "25006" => "read_only_sql_transaction",
+            "25P02" => "in_failed_sql_transaction",
+            "40001" => "serialization_failure",
+            "40P01" => "deadlock_detected",
+            "42501" => "insufficient_privilege",
+            "42601" => "syntax_error",
+            "42703" => "undefined_column",
+            "42704" => "undefined_object",
+            "42804" => "datatype_mismatch",
+            "42883" => "undefined_function",
+            "42P01" => "undefined_table",
+            "42P07" => "duplicate_table",
+            "55P03" => "lock_not_available",
+            "57014" => "query_canceled",
+            _ => return None,
+        })
+    }
+}
+
+impl From<String> for QueryError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&str> for QueryError {
+    fn from(message: &str) -> Self {
+        Self::from(message.to_string())
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Cancels over a fresh connection: the pooled ones may all be busy, and the
+/// connection running the statement can't take another command until it ends.
+async fn cancel_backend(pool: &PgPool, backend_pid: i32) -> Result<bool> {
+    let mut conn = PgConnection::connect_with(&pool.connect_options()).await?;
+    let result = sqlx::query_scalar::<_, bool>("SELECT pg_cancel_backend($1)")
+        .bind(backend_pid)
+        .fetch_one(&mut conn)
+        .await;
+    let _ = conn.close().await;
+    Ok(result?)
+}
+
 async fn fetch_primary_keys(pool: &PgPool, schema: &str, table: &str) -> Result<Vec<String>> {
     let sql = r#"
         SELECT kcu.column_name
@@ -377,6 +1686,137 @@ async fn fetch_primary_keys(pool: &PgPool, schema: &str, table: &str) -> Result<
     Ok(pk_columns)
 }
 
+async fn fetch_source_tables(pool: &PgPool, table_oids: &[u32]) -> Result<Vec<SourceTable>> {
+    let sql = r#"
+        SELECT
+            c.oid::int8 AS oid,
+            n.nspname::text AS schema,
+            c.relname::text AS table,
+            c.relkind IN ('r', 'p') AS is_base_table,
+            ARRAY(
+                SELECT a.attnum FROM pg_attribute a
+                WHERE a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+                ORDER BY a.attnum
+            ) AS attnums,
+            ARRAY(
+                SELECT a.attname::text FROM pg_attribute a
+                WHERE a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+                ORDER BY a.attnum
+            ) AS attnames,
+            ARRAY(
+                SELECT a.attname::text
+                FROM pg_index i
+                JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+                WHERE i.indrelid = c.oid AND i.indisprimary
+                ORDER BY array_position(i.indkey::int2[], a.attnum)
+            ) AS primary_key
+        FROM pg_class c
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE c.oid::int8 = ANY($1)
+    "#;
+
+    let oids: Vec<i64> = table_oids.iter().map(|&oid| oid as i64).collect();
+    let rows: Vec<PgRow> = sqlx::query(sql).bind(oids).fetch_all(pool).await?;
+
+    rows.iter()
+        .map(|row| {
+            let attnums: Vec<i16> = row.try_get("attnums")?;
+            let attnames: Vec<String> = row.try_get("attnames")?;
+            Ok(SourceTable {
+                oid: row.try_get::<i64, _>("oid")? as u32,
+                schema: row.try_get("schema")?,
+                table: row.try_get("table")?,
+                is_base_table: row.try_get("is_base_table")?,
+                columns: attnums.into_iter().zip(attnames).collect(),
+                primary_key: row.try_get("primary_key")?,
+            })
+        })
+        .collect()
+}
+
+/// Every function the user may call, every type they can name and the
+/// server's keywords. Functions that only take or return `internal` and
+/// trigger functions can't be called from SQL and are left out.
+async fn fetch_completion_catalog(pool: &PgPool) -> Result<CompletionCatalog> {
+    let functions_sql = r#"
+        SELECT
+            n.nspname::text AS schema,
+            p.proname::text AS name,
+            pg_get_function_arguments(p.oid) AS arguments,
+            pg_get_function_result(p.oid) AS result,
+            p.prokind::text AS kind
+        FROM pg_proc p
+        JOIN pg_namespace n ON n.oid = p.pronamespace
+        WHERE p.prokind IN ('f', 'a', 'w')
+            AND n.nspname NOT IN ('information_schema')
+            AND n.nspname NOT LIKE 'pg\_toast%'
+            AND n.nspname NOT LIKE 'pg\_temp%'
+            AND p.prorettype NOT IN ('internal'::regtype, 'trigger'::regtype, 'event_trigger'::regtype)
+            AND NOT 'internal'::regtype = ANY(p.proargtypes)
+            AND has_function_privilege(p.oid, 'EXECUTE')
+        ORDER BY p.proname, n.nspname
+    "#;
+    let types_sql = r#"
+        SELECT
+            n.nspname::text AS schema,
+            t.typname::text AS name,
+            format_type(t.oid, NULL) AS display_name,
+            ARRAY(
+                SELECT e.enumlabel::text FROM pg_enum e
+                WHERE e.enumtypid = t.oid
+                ORDER BY e.enumsortorder
+            ) AS enum_values
+        FROM pg_type t
+        JOIN pg_namespace n ON n.oid = t.typnamespace
+        WHERE t.typtype IN ('b', 'd', 'e', 'r', 'm')
+            AND t.typcategory <> 'A'
+            AND n.nspname NOT IN ('information_schema')
+            AND n.nspname NOT LIKE 'pg\_toast%'
+            AND n.nspname NOT LIKE 'pg\_temp%'
+        ORDER BY t.typname
+    "#;
+    let keywords_sql = "SELECT word::text FROM pg_get_keywords() ORDER BY word";
+
+    let function_rows: Vec<PgRow> = sqlx::query(functions_sql).fetch_all(pool).await?;
+    let type_rows: Vec<PgRow> = sqlx::query(types_sql).fetch_all(pool).await?;
+    let keywords: Vec<String> = sqlx::query_scalar(keywords_sql).fetch_all(pool).await?;
+
+    let functions = function_rows
+        .iter()
+        .map(|row| {
+            let kind: String = row.try_get("kind")?;
+            Ok(CatalogFunction {
+                schema: row.try_get("schema")?,
+                name: row.try_get("name")?,
+                arguments: row.try_get("arguments")?,
+                result: row.try_get("result")?,
+                kind: match kind.as_str() {
+                    "a" => FunctionKind::Aggregate,
+                    "w" => FunctionKind::Window,
+                    _ => FunctionKind::Normal,
+                },
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let types = type_rows
+        .iter()
+        .map(|row| {
+            Ok(CatalogType {
+                schema: row.try_get("schema")?,
+                name: row.try_get("name")?,
+                display_name: row.try_get("display_name")?,
+                enum_values: row.try_get("enum_values")?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(CompletionCatalog {
+        functions,
+        types,
+        keywords,
+    })
+}
+
 async fn fetch_foreign_keys(
     pool: &PgPool,
     schema: &str,
@@ -445,44 +1885,89 @@ async fn fetch_fk_referenced_row(
         .enumerate()
         .map(|(i, col)| {
             let col_name = col.name().to_string();
-            let cell = extract_cell_value(row, i, col.type_info().name());
-            (col_name, cell.display())
+            let cell = extract_cell_value(row, i);
+            (col_name, cell.display().to_string())
         })
         .collect();
 
     Ok(result)
 }
 
-fn extract_cell_value(row: &PgRow, index: usize, type_name: &str) -> CellValue {
-    if row.try_get_raw(index).map(|v| v.is_null()).unwrap_or(true) {
-        return CellValue::Null;
+fn extract_cell_value(row: &PgRow, index: usize) -> CellValue {
+    row.try_get_raw(index)
+        .map(decode_value)
+        .unwrap_or(CellValue::Null)
+}
+
+/// Names already looked up for reg* values, by cast and OID.
+type RegNames = HashMap<(&'static str, i64), SharedString>;
+
+/// The cast naming each column's values, for the reg* columns of a row.
+fn reg_casts(row: &PgRow) -> Vec<Option<&'static str>> {
+    row.columns().iter().map(|c| reg_type_cast(c.type_info())).collect()
+}
+
+/// Replaces the OIDs in reg* columns with the names Postgres would print,
+/// such as `pg_class` for a `regclass`. The values only carry the OID, so
+/// the names are looked up on the pool; an object only visible inside an
+/// open transaction keeps its number, as it would once dropped.
+async fn name_reg_values(
+    pool: &PgPool,
+    casts: &[Option<&'static str>],
+    rows: &mut [Vec<CellValue>],
+    names: &mut RegNames,
+) {
+    for (index, cast) in casts.iter().enumerate() {
+        let Some(cast) = *cast else {
+            continue;
+        };
+        let mut missing: Vec<i64> = rows
+            .iter()
+            .filter_map(|row| row.get(index))
+            .flat_map(reg_oids)
+            .filter(|oid| !names.contains_key(&(cast, *oid)))
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        if !missing.is_empty() {
+            let sql = format!("SELECT o, o::oid::{}::text FROM unnest($1::int8[]) AS o", cast);
+            if let Ok(found) = sqlx::query_as::<_, (i64, String)>(&sql)
+                .bind(&missing)
+                .fetch_all(pool)
+                .await
+            {
+                names.extend(found.into_iter().map(|(oid, name)| ((cast, oid), SharedString::from(name))));
+            }
+        }
+        for row in rows.iter_mut() {
+            if let Some(cell) = row.get_mut(index) {
+                name_reg_value(cell, cast, names);
+            }
+        }
     }
+}
 
-    match type_name {
-        "BOOL" => row
-            .try_get::<bool, _>(index)
-            .map(CellValue::Bool)
-            .unwrap_or(CellValue::Null),
-        "INT2" | "INT4" | "INT8" => row
-            .try_get::<i64, _>(index)
-            .map(CellValue::Int)
-            .unwrap_or_else(|_| {
-                row.try_get::<i32, _>(index)
-                    .map(|v| CellValue::Int(v as i64))
-                    .unwrap_or(CellValue::Null)
-            }),
-        "FLOAT4" | "FLOAT8" | "NUMERIC" => row
-            .try_get::<f64, _>(index)
-            .map(CellValue::Float)
-            .unwrap_or(CellValue::Null),
-        "BYTEA" => row
-            .try_get::<Vec<u8>, _>(index)
-            .map(CellValue::Bytes)
-            .unwrap_or(CellValue::Null),
-        _ => row
-            .try_get::<String, _>(index)
-            .map(CellValue::Text)
-            .unwrap_or(CellValue::Null),
+fn reg_oids(cell: &CellValue) -> Vec<i64> {
+    match cell {
+        CellValue::Int(oid) => vec![*oid],
+        CellValue::Array(items) => items.iter().flat_map(reg_oids).collect(),
+        _ => vec![],
+    }
+}
+
+fn name_reg_value(cell: &mut CellValue, cast: &'static str, names: &RegNames) {
+    match cell {
+        CellValue::Int(oid) => {
+            if let Some(name) = names.get(&(cast, *oid)) {
+                *cell = CellValue::Text(name.clone());
+            }
+        }
+        CellValue::Array(items) => {
+            for item in Arc::make_mut(items) {
+                name_reg_value(item, cast, names);
+            }
+        }
+        _ => {}
     }
 }
 
@@ -494,7 +1979,7 @@ async fn fetch_table_structure(
     let sql = r#"
         SELECT
             c.column_name,
-            c.data_type,
+            CASE WHEN c.data_type = 'USER-DEFINED' THEN c.udt_name ELSE c.data_type END::text AS data_type,
             c.is_nullable,
             c.column_default,
             CASE WHEN pk.column_name IS NOT NULL THEN true ELSE false END as is_primary_key,
@@ -577,3 +2062,148 @@ async fn fetch_table_structure(
         columns,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The server in `DATABASE_URL`; tests that need one are ignored by
+    /// default, so run them with `--include-ignored`.
+    async fn test_pool() -> PgPool {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL");
+        PgPool::connect(&url).await.expect("connect to DATABASE_URL")
+    }
+
+    async fn test_session() -> SharedSession {
+        let session = open_session(&test_pool().await).await.expect("open session");
+        Arc::new(Mutex::new(session))
+    }
+
+    async fn run(session: &SharedSession, sql: &str) -> TransactionStatus {
+        let (result, status) = try_run(session, sql).await;
+        result.expect(sql);
+        status
+    }
+
+    async fn try_run(session: &SharedSession, sql: &str) -> (Result<QueryStreamSummary>, TransactionStatus) {
+        let (events, mut received) = mpsc::unbounded_channel();
+        let limits = ResultLimits { max_rows: None, max_bytes: usize::MAX };
+        stream_session_query(session.clone(), None, sql.to_string(), vec![], limits, events).await;
+        let (mut result, mut status) = (None, None);
+        while let Ok(event) = received.try_recv() {
+            match event {
+                QueryStreamEvent::Transaction(s) => status = Some(s),
+                QueryStreamEvent::Finished(r) => result = Some(r),
+                _ => {}
+            }
+        }
+        (result.expect("finished"), status.expect("transaction status"))
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_transaction_status_after_commit() {
+        let session = test_session().await;
+        assert_eq!(session.lock().await.status, TransactionStatus::Idle);
+        assert_eq!(run(&session, "SELECT 1").await, TransactionStatus::InTransaction);
+        assert_eq!(end_transaction(&session, true).await.unwrap(), TransactionStatus::Idle);
+
+        // The next statement has to open a transaction again.
+        assert_eq!(run(&session, "SELECT 1").await, TransactionStatus::InTransaction);
+        assert_eq!(end_transaction(&session, false).await.unwrap(), TransactionStatus::Idle);
+
+        // Typed COMMIT and ROLLBACK count too.
+        assert_eq!(run(&session, "SELECT 1").await, TransactionStatus::InTransaction);
+        assert_eq!(run(&session, "COMMIT").await, TransactionStatus::Idle);
+        assert_eq!(run(&session, "SELECT 1").await, TransactionStatus::InTransaction);
+        assert_eq!(run(&session, "ROLLBACK AND CHAIN").await, TransactionStatus::InTransaction);
+        assert_eq!(run(&session, "END").await, TransactionStatus::Idle);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_transaction_status_failed() {
+        let session = test_session().await;
+        assert_eq!(run(&session, "SAVEPOINT s").await, TransactionStatus::InTransaction);
+        let (result, status) = try_run(&session, "SELECT 1 / 0").await;
+        assert!(result.is_err());
+        assert_eq!(status, TransactionStatus::Failed);
+        assert!(try_run(&session, "SELECT 1").await.0.is_err());
+        assert_eq!(run(&session, "ROLLBACK TO s").await, TransactionStatus::InTransaction);
+
+        assert!(try_run(&session, "SELECT 1 / 0").await.0.is_err());
+        // The server rolls back instead of committing.
+        assert_eq!(run(&session, "COMMIT").await, TransactionStatus::Idle);
+
+        // A COMMIT the server refuses still ends the transaction.
+        run(&session, "CREATE TEMP TABLE deferred (id int UNIQUE DEFERRABLE INITIALLY DEFERRED)").await;
+        run(&session, "COMMIT").await;
+        run(&session, "INSERT INTO deferred VALUES (1), (1)").await;
+        assert!(end_transaction(&session, true).await.is_err());
+        assert_eq!(session.lock().await.status, TransactionStatus::Idle);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_grid_edits_join_session_transaction() {
+        let session = test_session().await;
+        run(&session, "CREATE TEMP TABLE grid_edit (id int PRIMARY KEY, a int)").await;
+        run(&session, "INSERT INTO grid_edit VALUES (1, 0), (2, 0)").await;
+
+        // Touching two rows undoes only the edit, not the inserts before it.
+        let update = "UPDATE grid_edit SET a = 1";
+        assert_eq!(execute_session_single_row(&session, update, vec![]).await.unwrap(), 2);
+        let update = "UPDATE grid_edit SET a = 1 WHERE id = $1::int";
+        assert_eq!(execute_session_single_row(&session, update, vec![Some("1".into())]).await.unwrap(), 1);
+        let batch = vec![
+            ("UPDATE grid_edit SET a = 2 WHERE id = 2 RETURNING a".to_string(), vec![]),
+            ("DELETE FROM grid_edit WHERE id = 3 RETURNING id".to_string(), vec![]),
+        ];
+        assert!(execute_session_single_row_batch(&session, None, batch).await.is_err());
+        assert_eq!(session.lock().await.status, TransactionStatus::InTransaction);
+
+        let mut locked = session.lock().await;
+        let sums: (i64, i64) = sqlx::query_as("SELECT count(*), sum(a) FROM grid_edit")
+            .fetch_one(&mut *locked.conn)
+            .await
+            .unwrap();
+        assert_eq!(sums, (2, 1));
+        drop(locked);
+
+        // Nothing is committed until the tab commits.
+        assert_eq!(end_transaction(&session, false).await.unwrap(), TransactionStatus::Idle);
+        let update = "UPDATE grid_edit SET a = 1 WHERE id = 1";
+        assert!(execute_session_single_row(&session, update, vec![]).await.is_err());
+        assert_eq!(session.lock().await.status, TransactionStatus::Idle);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_count_rows_of_one_to_many_join() {
+        let pool = test_pool().await;
+        // pg_catalog matches both rows of the list but would be deleted once.
+        let delete = "DELETE FROM pg_namespace n USING (VALUES ('pg_catalog'), ('pg_catalog')) v(name) \
+                      WHERE n.nspname = v.name";
+        let sql = crate::postcommander::sql::count_query(delete).expect("count query");
+        assert_eq!(count_rows(&pool, &sql).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DATABASE_URL"]
+    async fn test_reg_types_show_names() {
+        let pool = test_pool().await;
+        let (events, mut received) = mpsc::unbounded_channel();
+        let limits = ResultLimits { max_rows: None, max_bytes: usize::MAX };
+        let sql = "SELECT 'pg_class'::regclass, 'int4'::regtype, ARRAY['pg_type'::regclass], 1259::oid";
+        stream_query(pool, sql.to_string(), vec![], limits, events).await;
+
+        let mut rows = vec![];
+        while let Ok(event) = received.try_recv() {
+            if let QueryStreamEvent::Rows(batch) = event {
+                rows.extend(batch);
+            }
+        }
+        let shown: Vec<String> = rows[0].iter().map(|c| c.display().to_string()).collect();
+        assert_eq!(shown, vec!["pg_class", "integer", "{pg_type}", "1259"]);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::postcommander::page::PostCommanderPage;
+use crate::postcommander::sql::{lint_script, LintContext, SqlLint};
+use crate::postcommander::types::{SchemaMap, TabId};
+use gpui::*;
+use gpui_component::highlighter::{Diagnostic, DiagnosticSeverity};
+use gpui_component::input::Position;
+use std::collections::HashMap;
+
+impl PostCommanderPage {
+    /// Puts the lint warnings for the tab's text, and the error of its last
+    /// run, on its editor as squiggles with the message on hover.
+    pub(crate) fn refresh_diagnostics(&mut self, tab_id: TabId, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return;
+        };
+        let text = tab.editor.read(cx).value().to_string();
+        let session = tab.connection_id.as_deref().and_then(|id| self.connection(id));
+        let no_schemas = SchemaMap::new();
+        let no_structures = HashMap::new();
+        let context = LintContext {
+            schemas: session.map_or(&no_schemas, |s| s.schemas.as_ref()),
+            structures: session.map_or(&no_structures, |s| &s.table_structures),
+            saved_query: tab.saved_query_id.is_some(),
+        };
+
+        let diagnostics: Vec<Diagnostic> = lint_script(&text, &context)
+            .iter()
+            .map(|lint| diagnostic(&text, lint, DiagnosticSeverity::Warning))
+            .chain(
+                tab.error_diagnostic
+                    .iter()
+                    .filter(|error| error.range.end <= text.len())
+                    .map(|error| diagnostic(&text, error, DiagnosticSeverity::Error)),
+            )
+            .collect();
+
+        tab.editor.update(cx, |editor, cx| {
+            if let Some(set) = editor.diagnostics_mut() {
+                set.clear();
+                set.extend(diagnostics);
+                cx.notify();
+            }
+        });
+    }
+
+    /// Re-lints the tabs on a connection once more of its schema is known.
+    pub(crate) fn refresh_connection_diagnostics(&mut self, connection_id: &str, cx: &mut Context<Self>) {
+        let tab_ids: Vec<TabId> = self
+            .tabs
+            .iter()
+            .filter(|t| t.connection_id.as_deref() == Some(connection_id))
+            .map(|t| t.id)
+            .collect();
+        for tab_id in tab_ids {
+            self.refresh_diagnostics(tab_id, cx);
+        }
+    }
+}
+
+fn diagnostic(text: &str, lint: &SqlLint, severity: DiagnosticSeverity) -> Diagnostic {
+    let range = position(text, lint.range.start)..position(text, lint.range.end);
+    Diagnostic::new(range, lint.message.clone()).with_severity(severity)
+}
+
+/// Line and UTF-16 column of a byte offset.
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
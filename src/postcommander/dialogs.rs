@@ -1,6 +1,9 @@
+use crate::postcommander::database::TransactionStatus;
 use crate::postcommander::sql::SqlDangerLevel;
+use crate::postcommander::types::TransactionHold;
 use crate::postcommander::PostCommanderPage;
 use crate::theme::ActiveTheme;
+use gpui::prelude::FluentBuilder;
 use gpui::*;
 
 impl PostCommanderPage {
@@ -8,13 +11,92 @@ impl PostCommanderPage {
         let theme = cx.theme();
         let colors = theme.colors();
 
-        let Some((danger_level, message)) = &self.safety_warning else {
+        let Some(warning) = &self.safety_warning else {
             return div().into_any_element();
         };
 
-        let is_dangerous = matches!(danger_level, SqlDangerLevel::Dangerous(_));
+        let is_dangerous = warning
+            .findings
+            .iter()
+            .any(|f| matches!(f.level, SqlDangerLevel::Dangerous(_)));
         let title = if is_dangerous { "Dangerous Query" } else { "Warning" };
         let icon_color = if is_dangerous { colors.status_error } else { colors.status_warning };
+        let confirmed = warning.is_confirmed(cx);
+
+        let items: Vec<AnyElement> = warning
+            .findings
+            .iter()
+            .zip(&warning.impacts)
+            .enumerate()
+            .map(|(index, (finding, impact))| {
+                let (message, color) = match &finding.level {
+                    SqlDangerLevel::Dangerous(message) => (message.clone(), colors.status_error),
+                    SqlDangerLevel::Warning(message) => (message.clone(), colors.status_warning),
+                    SqlDangerLevel::Safe => (String::new(), colors.text_muted),
+                };
+                let statement = finding.statement.split_whitespace().collect::<Vec<_>>().join(" ");
+                div()
+                    .flex()
+                    .flex_col()
+                    .gap_1()
+                    .pl_2()
+                    .border_l_2()
+                    .border_color(rgb(color))
+                    .child(
+                        div()
+                            .text_sm()
+                            .text_color(rgb(colors.text))
+                            .child(message)
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .font_family("monospace")
+                            .text_color(rgb(colors.text_muted))
+                            .truncate()
+                            .child(statement)
+                    )
+                    .when_some(impact.as_ref(), |el, impact| {
+                        let estimate = match &impact.estimated {
+                            None => "Estimating rows…".to_string(),
+                            Some(Ok(rows)) => format!("About {} rows (planner estimate)", rows),
+                            Some(Err(e)) => format!("No estimate: {}", e),
+                        };
+                        let count = match &impact.counted {
+                            _ if impact.counting => Some("Counting…".to_string()),
+                            Some(Ok(rows)) => Some(format!("{} rows match", rows)),
+                            Some(Err(e)) => Some(format!("Count failed: {}", e)),
+                            None => None,
+                        };
+                        el.child(
+                            div()
+                                .flex()
+                                .items_center()
+                                .gap_2()
+                                .text_xs()
+                                .text_color(rgb(colors.text_muted))
+                                .child(estimate)
+                                .child(match count {
+                                    Some(count) => div()
+                                        .text_color(rgb(colors.text))
+                                        .child(count)
+                                        .into_any_element(),
+                                    None => div()
+                                        .id(SharedString::from(format!("count-impact-{}", index)))
+                                        .text_color(rgb(colors.accent))
+                                        .cursor_pointer()
+                                        .hover(|s| s.opacity(0.8))
+                                        .on_click(cx.listener(move |this, _, _, cx| {
+                                            this.count_impact_rows(index, cx);
+                                        }))
+                                        .child("Count exactly")
+                                        .into_any_element(),
+                                })
+                        )
+                    })
+                    .into_any_element()
+            })
+            .collect();
 
         div()
             .id("safety-warning-overlay")
@@ -27,7 +109,7 @@ impl PostCommanderPage {
             .on_mouse_down(MouseButton::Left, |_, _, _| {})
             .child(
                 div()
-                    .w(px(420.0))
+                    .w(px(480.0))
                     .bg(rgb(colors.surface))
                     .border_1()
                     .border_color(rgb(colors.border))
@@ -55,10 +137,45 @@ impl PostCommanderPage {
                     )
                     .child(
                         div()
-                            .text_sm()
-                            .text_color(rgb(colors.text_muted))
-                            .child(message.clone())
+                            .id("safety-findings")
+                            .max_h(px(280.0))
+                            .overflow_y_scroll()
+                            .flex()
+                            .flex_col()
+                            .gap_3()
+                            .children(items)
                     )
+                    .when_some(warning.confirm.as_ref(), |el, confirm| {
+                        el.child(
+                            div()
+                                .flex()
+                                .flex_col()
+                                .gap_1()
+                                .child(
+                                    div()
+                                        .text_xs()
+                                        .text_color(rgb(colors.text_muted))
+                                        .child(format!(
+                                            "This connection is protected. Type {} to run the script.",
+                                            confirm.database
+                                        ))
+                                )
+                                .child(
+                                    div()
+                                        .h(px(32.0))
+                                        .px_3()
+                                        .flex()
+                                        .items_center()
+                                        .rounded_md()
+                                        .bg(rgb(colors.background))
+                                        .border_1()
+                                        .border_color(rgb(if confirmed { icon_color } else { colors.border }))
+                                        .text_sm()
+                                        .font_family("monospace")
+                                        .child(confirm.input.clone())
+                                )
+                        )
+                    })
                     .child(
                         div()
                             .flex()
@@ -89,13 +206,168 @@ impl PostCommanderPage {
                                     .bg(rgb(icon_color))
                                     .text_sm()
                                     .text_color(rgb(colors.accent_foreground))
+                                    .when(!confirmed, |el| el.opacity(0.5))
+                                    .when(confirmed, |el| {
+                                        el.cursor_pointer()
+                                            .hover(|s| s.opacity(0.9))
+                                            .on_click(cx.listener(|this, _, _, cx| {
+                                                this.execute_query_force(cx);
+                                            }))
+                                    })
+                                    .child("Execute Anyway")
+                            )
+                    )
+            )
+            .into_any_element()
+    }
+
+    pub(crate) fn render_transaction_hold_dialog(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+
+        let Some(hold) = &self.transaction_hold else {
+            return div().into_any_element();
+        };
+        let tab_ids = self.held_transaction_tabs(hold);
+        let failed = self
+            .tabs
+            .iter()
+            .filter(|t| tab_ids.contains(&t.id))
+            .filter_map(|t| t.transaction.as_ref())
+            .any(|t| t.status == TransactionStatus::Failed);
+
+        let (action, verb) = match hold {
+            TransactionHold::CloseTab(_) => ("Closing the tab", "Close"),
+            TransactionHold::Reconnect(_) => ("Reconnecting", "Reconnect"),
+            TransactionHold::Disconnect(_) => ("Disconnecting", "Disconnect"),
+            TransactionHold::DeleteConnection(_) => ("Deleting the connection", "Delete"),
+        };
+        let subject = match hold {
+            TransactionHold::CloseTab(tab_id) => {
+                let name = self.tabs.iter().find(|t| t.id == *tab_id).map(|t| t.name.clone());
+                format!("\"{}\"", name.unwrap_or_default())
+            }
+            TransactionHold::Reconnect(id)
+            | TransactionHold::Disconnect(id)
+            | TransactionHold::DeleteConnection(id) => {
+                let name = self.connection(id).map(|c| c.profile.name.clone());
+                match tab_ids.len() {
+                    1 => format!("A tab on \"{}\"", name.unwrap_or_default()),
+                    n => format!("{} tabs on \"{}\"", n, name.unwrap_or_default()),
+                }
+            }
+        };
+        let (has, them) = if tab_ids.len() == 1 { ("has", "it") } else { ("have", "them") };
+        let message = if failed {
+            format!(
+                "{} {} a failed transaction. {} rolls {} back.",
+                subject, has, action, them
+            )
+        } else {
+            format!(
+                "{} {} uncommitted changes. {} rolls them back.",
+                subject, has, action
+            )
+        };
+
+        div()
+            .id("close-transaction-overlay")
+            .absolute()
+            .inset_0()
+            .flex()
+            .items_center()
+            .justify_center()
+            .bg(rgba(0x00000080))
+            .on_mouse_down(MouseButton::Left, |_, _, _| {})
+            .child(
+                div()
+                    .w(px(420.0))
+                    .bg(rgb(colors.surface))
+                    .border_1()
+                    .border_color(rgb(colors.border))
+                    .rounded_lg()
+                    .shadow_lg()
+                    .p_4()
+                    .flex()
+                    .flex_col()
+                    .gap_4()
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .gap_2()
+                            .child(
+                                crate::icons::icon("alert-triangle", px(24.0), colors.status_warning)
+                            )
+                            .child(
+                                div()
+                                    .text_lg()
+                                    .font_weight(gpui::FontWeight::SEMIBOLD)
+                                    .text_color(rgb(colors.text))
+                                    .child("Open Transaction")
+                            )
+                    )
+                    .child(
+                        div()
+                            .text_sm()
+                            .text_color(rgb(colors.text_muted))
+                            .child(message)
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .justify_end()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .id("keep-open-btn")
+                                    .px_3()
+                                    .py_1p5()
+                                    .rounded_md()
+                                    .bg(rgb(colors.element))
+                                    .text_sm()
+                                    .text_color(rgb(colors.text))
                                     .cursor_pointer()
-                                    .hover(|s| s.opacity(0.9))
+                                    .hover(|s| s.bg(rgb(colors.element_hover)))
                                     .on_click(cx.listener(|this, _, _, cx| {
-                                        this.execute_query_force(cx);
+                                        this.cancel_transaction_hold(cx);
                                     }))
-                                    .child("Execute Anyway")
+                                    .child("Cancel")
+                            )
+                            .child(
+                                div()
+                                    .id("rollback-close-btn")
+                                    .px_3()
+                                    .py_1p5()
+                                    .rounded_md()
+                                    .bg(rgb(colors.status_error))
+                                    .text_sm()
+                                    .text_color(rgb(colors.accent_foreground))
+                                    .cursor_pointer()
+                                    .hover(|s| s.opacity(0.9))
+                                    .on_click(cx.listener(|this, _, window, cx| {
+                                        this.confirm_transaction_hold(false, window, cx);
+                                    }))
+                                    .child(format!("Roll Back & {}", verb))
                             )
+                            .when(!failed, |el| {
+                                el.child(
+                                    div()
+                                        .id("commit-close-btn")
+                                        .px_3()
+                                        .py_1p5()
+                                        .rounded_md()
+                                        .bg(rgb(colors.accent))
+                                        .text_sm()
+                                        .text_color(rgb(colors.accent_foreground))
+                                        .cursor_pointer()
+                                        .hover(|s| s.opacity(0.9))
+                                        .on_click(cx.listener(|this, _, window, cx| {
+                                            this.confirm_transaction_hold(true, window, cx);
+                                        }))
+                                        .child(format!("Commit & {}", verb))
+                                )
+                            })
                     )
             )
             .into_any_element()
@@ -1,3 +1,4 @@
+use crate::postcommander::database::CellValue;
 use crate::postcommander::page::PostCommanderPage;
 use gpui::*;
 use std::fs;
@@ -10,20 +11,10 @@ impl PostCommanderPage {
             .and_then(|id| self.tabs.iter().find(|t| &t.id == id))?;
 
         let result = tab.result.as_ref()?;
-        let mut csv = String::new();
-
-        let headers: Vec<String> = result.columns.iter().map(|c| escape_csv(&c.name)).collect();
-        csv.push_str(&headers.join(","));
-        csv.push('\n');
+        let column_names: Vec<&str> = result.columns.iter().map(|c| c.name.as_str()).collect();
 
         let rows = tab.table_state.read(cx).rows();
-        for row in rows.iter() {
-            let cells: Vec<String> = row.iter().map(|c| escape_csv(c.as_ref())).collect();
-            csv.push_str(&cells.join(","));
-            csv.push('\n');
-        }
-
-        Some(csv)
+        Some(csv_text(&column_names, rows))
     }
 
     pub(crate) fn export_to_json(&self, cx: &mut Context<Self>) -> Option<String> {
@@ -34,21 +25,10 @@ impl PostCommanderPage {
         let column_names: Vec<&str> = result.columns.iter().map(|c| c.name.as_str()).collect();
 
         let rows = tab.table_state.read(cx).rows();
-        let mut json_rows: Vec<String> = Vec::new();
-
-        for row in rows.iter() {
-            let mut obj_parts: Vec<String> = Vec::new();
-            for (i, cell) in row.iter().enumerate() {
-                let key = column_names.get(i).unwrap_or(&"");
-                let value = if cell.as_ref() == "NULL" {
-                    "null".to_string()
-                } else {
-                    format!("\"{}\"", escape_json(cell.as_ref()))
-                };
-                obj_parts.push(format!("\"{}\":{}", key, value));
-            }
-            json_rows.push(format!("{{{}}}", obj_parts.join(",")));
-        }
+        let json_rows: Vec<String> = rows
+            .iter()
+            .map(|row| row_to_json_object(&column_names, row))
+            .collect();
 
         Some(format!("[\n  {}\n]", json_rows.join(",\n  ")))
     }
@@ -58,26 +38,10 @@ impl PostCommanderPage {
             .and_then(|id| self.tabs.iter().find(|t| &t.id == id))?;
 
         let result = tab.result.as_ref()?;
-        let mut md = String::new();
-
         let headers: Vec<&str> = result.columns.iter().map(|c| c.name.as_str()).collect();
-        md.push_str("| ");
-        md.push_str(&headers.join(" | "));
-        md.push_str(" |\n");
-
-        md.push_str("| ");
-        md.push_str(&headers.iter().map(|_| "---").collect::<Vec<_>>().join(" | "));
-        md.push_str(" |\n");
 
         let rows = tab.table_state.read(cx).rows();
-        for row in rows.iter().take(100) {
-            md.push_str("| ");
-            let cells: Vec<String> = row.iter()
-                .map(|c| escape_markdown(c.as_ref()))
-                .collect();
-            md.push_str(&cells.join(" | "));
-            md.push_str(" |\n");
-        }
+        let mut md = markdown_table(&headers, &rows[..rows.len().min(100)]);
 
         if rows.len() > 100 {
             md.push_str(&format!("\n_({} more rows...)_\n", rows.len() - 100));
@@ -94,54 +58,103 @@ impl PostCommanderPage {
         cx.write_to_clipboard(ClipboardItem::new_string(value.to_string()));
     }
 
-    pub(crate) fn copy_row_as_tsv(_columns: &[String], row: &[SharedString], cx: &mut App) {
-        let tsv: Vec<String> = row.iter().map(|cell| {
-            let s = cell.as_ref();
-            if s.contains('\t') || s.contains('\n') || s.contains('\r') {
-                format!("\"{}\"", s.replace('"', "\"\""))
-            } else {
-                s.to_string()
-            }
-        }).collect();
-        cx.write_to_clipboard(ClipboardItem::new_string(tsv.join("\t")));
+    /// Tab-separated values without a header, as spreadsheets paste them.
+    pub(crate) fn copy_rows_as_tsv(rows: &[Vec<CellValue>], cx: &mut App) {
+        let lines: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| {
+                        let display = cell.display();
+                        let s: &str = &display;
+                        if s.contains('\t') || s.contains('\n') || s.contains('\r') {
+                            format!("\"{}\"", s.replace('"', "\"\""))
+                        } else {
+                            s.to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            })
+            .collect();
+        cx.write_to_clipboard(ClipboardItem::new_string(lines.join("\n")));
     }
 
-    pub(crate) fn copy_row_as_json(columns: &[String], row: &[SharedString], cx: &mut App) {
-        let mut obj_parts: Vec<String> = Vec::new();
-        for (i, cell) in row.iter().enumerate() {
-            let key = columns.get(i).map(|s| s.as_str()).unwrap_or("");
-            let value = if cell.as_ref() == "NULL" {
-                "null".to_string()
-            } else {
-                format!("\"{}\"", escape_json(cell.as_ref()))
-            };
-            obj_parts.push(format!("\"{}\":{}", key, value));
-        }
-        let json = format!("{{{}}}", obj_parts.join(","));
+    pub(crate) fn copy_rows_as_csv(columns: &[String], rows: &[Vec<CellValue>], cx: &mut App) {
+        let column_names: Vec<&str> = columns.iter().map(|s| s.as_str()).collect();
+        cx.write_to_clipboard(ClipboardItem::new_string(csv_text(&column_names, rows)));
+    }
+
+    /// One row copies as an object, several as an array of them.
+    pub(crate) fn copy_rows_as_json(columns: &[String], rows: &[Vec<CellValue>], cx: &mut App) {
+        let column_names: Vec<&str> = columns.iter().map(|s| s.as_str()).collect();
+        let json = match rows {
+            [row] => row_to_json_object(&column_names, row),
+            rows => {
+                let objects: Vec<String> = rows.iter().map(|row| row_to_json_object(&column_names, row)).collect();
+                format!("[\n  {}\n]", objects.join(",\n  "))
+            }
+        };
         cx.write_to_clipboard(ClipboardItem::new_string(json));
     }
 
-    pub(crate) fn copy_row_as_insert(
+    pub(crate) fn copy_rows_as_insert(
         table_name: Option<&str>,
         columns: &[String],
-        row: &[SharedString],
+        rows: &[Vec<CellValue>],
         cx: &mut App,
     ) {
         let table = table_name.unwrap_or("table_name");
         let col_list = columns.join(", ");
-        let values: Vec<String> = row.iter().map(|cell| {
-            let s = cell.as_ref();
-            if s == "NULL" {
-                "NULL".to_string()
-            } else if s.parse::<i64>().is_ok() || s.parse::<f64>().is_ok() {
-                s.to_string()
-            } else {
-                format!("'{}'", s.replace('\'', "''"))
-            }
-        }).collect();
-        let sql = format!("INSERT INTO {} ({}) VALUES ({});", table, col_list, values.join(", "));
+        let values: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                let values: Vec<String> = row.iter().map(|cell| cell.to_sql_literal()).collect();
+                format!("({})", values.join(", "))
+            })
+            .collect();
+        let sql = format!("INSERT INTO {} ({}) VALUES {};", table, col_list, values.join(",\n  "));
         cx.write_to_clipboard(ClipboardItem::new_string(sql));
     }
+
+    pub(crate) fn copy_rows_as_markdown(columns: &[String], rows: &[Vec<CellValue>], cx: &mut App) {
+        let column_names: Vec<&str> = columns.iter().map(|s| s.as_str()).collect();
+        cx.write_to_clipboard(ClipboardItem::new_string(markdown_table(&column_names, rows)));
+    }
+}
+
+fn csv_text(column_names: &[&str], rows: &[Vec<CellValue>]) -> String {
+    let mut csv = String::new();
+    let headers: Vec<String> = column_names.iter().map(|name| escape_csv(name)).collect();
+    csv.push_str(&headers.join(","));
+    csv.push('\n');
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|c| escape_csv(&c.display())).collect();
+        csv.push_str(&cells.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn markdown_table(column_names: &[&str], rows: &[Vec<CellValue>]) -> String {
+    let mut md = String::new();
+    md.push_str("| ");
+    md.push_str(&column_names.join(" | "));
+    md.push_str(" |\n");
+
+    md.push_str("| ");
+    md.push_str(&column_names.iter().map(|_| "---").collect::<Vec<_>>().join(" | "));
+    md.push_str(" |\n");
+
+    for row in rows {
+        md.push_str("| ");
+        let cells: Vec<String> = row.iter()
+            .map(|c| escape_markdown(&c.display()))
+            .collect();
+        md.push_str(&cells.join(" | "));
+        md.push_str(" |\n");
+    }
+    md
 }
 
 fn escape_csv(s: &str) -> String {
@@ -152,12 +165,17 @@ fn escape_csv(s: &str) -> String {
     }
 }
 
-fn escape_json(s: &str) -> String {
-    s.replace('\\', "\\\\")
-        .replace('"', "\\\"")
-        .replace('\n', "\\n")
-        .replace('\r', "\\r")
-        .replace('\t', "\\t")
+/// Serializes a row as a JSON object, keeping the result column order.
+fn row_to_json_object(column_names: &[&str], row: &[CellValue]) -> String {
+    let obj_parts: Vec<String> = row
+        .iter()
+        .enumerate()
+        .map(|(i, cell)| {
+            let key = column_names.get(i).copied().unwrap_or("");
+            format!("{}:{}", serde_json::Value::from(key), cell.to_json())
+        })
+        .collect();
+    format!("{{{}}}", obj_parts.join(","))
 }
 
 fn escape_markdown(s: &str) -> String {
@@ -0,0 +1,145 @@
+use crate::components::{DataTableState, GridQueryChanged, TextInputElement};
+use crate::icons::icon_sm;
+use crate::postcommander::page::PostCommanderPage;
+use crate::postcommander::query_execution::statement_run;
+use crate::postcommander::sql::browse_query;
+use crate::postcommander::types::TabId;
+use crate::theme::ActiveTheme;
+use gpui::prelude::FluentBuilder;
+use gpui::*;
+use std::time::Duration;
+
+/// How long a search waits for more typing before the query is re-run.
+const RERUN_DELAY: Duration = Duration::from_millis(400);
+
+impl PostCommanderPage {
+    /// Re-runs a single-table browse with the grid's sort as `ORDER BY` and
+    /// its search as `WHERE`, after a pause so a search runs once.
+    pub(crate) fn handle_grid_query_changed(
+        &mut self,
+        table_state: Entity<DataTableState>,
+        event: &GridQueryChanged,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.table_state == table_state) else {
+            return;
+        };
+        let Some(base) = tab.browse_sql().map(str::to_string) else {
+            return;
+        };
+        let search_columns: Vec<String> = tab
+            .row_table()
+            .map(|c| c.result_columns.iter().flatten().cloned().collect())
+            .unwrap_or_default();
+        let order_by: Vec<(usize, bool)> = event.sort.iter().map(|s| (s.col_index, s.descending)).collect();
+        let Some(sql) = browse_query(&base, &order_by, &search_columns, &event.filter) else {
+            return;
+        };
+
+        let tab_id = tab.id;
+        tab.grid_rerun = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(RERUN_DELAY).await;
+            let _ = this.update(cx, |this, cx| {
+                this.rerun_browse(tab_id, base, sql, cx);
+            });
+        }));
+    }
+
+    fn rerun_browse(&mut self, tab_id: TabId, base: String, sql: String, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return;
+        };
+        if tab.is_loading || tab.table_state.read(cx).has_pending_changes() {
+            return;
+        }
+        self.run_script(tab_id, vec![statement_run(&sql, None, None)], cx);
+        if let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) {
+            tab.grid_base_sql = Some(base);
+        }
+    }
+
+    fn toggle_sort_on_server(&mut self, tab_id: TabId, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return;
+        };
+        tab.table_state.update(cx, |state, cx| {
+            let on_server = !state.sorts_on_server();
+            state.set_sort_on_server(on_server, cx);
+        });
+        cx.notify();
+    }
+
+    /// The search box over the result, and the switch that hands the sort
+    /// and search to the server for a single-table browse.
+    pub(crate) fn render_grid_query_controls(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+        let surface = colors.surface;
+        let border_variant = colors.border_variant;
+        let text = colors.text;
+        let text_muted = colors.text_muted;
+        let text_placeholder = colors.text_placeholder;
+        let element_hover = colors.element_hover;
+        let element_active = colors.element_active;
+        let accent = colors.accent;
+
+        let Some(tab) = self
+            .active_tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id))
+        else {
+            return div();
+        };
+        let tab_id = tab.id;
+        let can_sort_on_server = tab.browse_sql().is_some();
+        let sorts_on_server = tab.table_state.read(cx).sorts_on_server();
+
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .child(
+                div()
+                    .w(px(180.))
+                    .h(px(24.))
+                    .px_2()
+                    .flex()
+                    .items_center()
+                    .gap_1()
+                    .rounded_md()
+                    .bg(rgb(surface))
+                    .border_1()
+                    .border_color(rgb(border_variant))
+                    .child(icon_sm("search", text_muted))
+                    .child(div().flex_1().text_xs().child(TextInputElement::new(
+                        tab.grid_search_input.clone(),
+                        text,
+                        text_placeholder,
+                    ))),
+            )
+            .when(can_sort_on_server, |el| {
+                el.child(
+                    div()
+                        .id("sort-on-server-btn")
+                        .h(px(24.))
+                        .px_2()
+                        .flex()
+                        .items_center()
+                        .gap_1()
+                        .rounded_md()
+                        .cursor_pointer()
+                        .when(sorts_on_server, |el| el.bg(rgb(element_active)))
+                        .hover(move |s| s.bg(rgb(element_hover)))
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            this.toggle_sort_on_server(tab_id, cx);
+                        }))
+                        .child(icon_sm("server", if sorts_on_server { accent } else { text_muted }))
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(if sorts_on_server { accent } else { text_muted }))
+                                .child("Sort on server"),
+                        ),
+                )
+            })
+    }
+}
@@ -1,9 +1,17 @@
 mod cell_edit;
 mod connection_dialog;
+mod connections;
 pub mod database;
+mod diagnostics;
 mod dialogs;
 mod export;
+mod grid_query;
 mod page;
+mod parameter_dialog;
+mod pending_changes;
+mod pg_decode;
+mod plan;
+mod plan_view;
 mod query_execution;
 mod query_history_panel;
 mod resize_handlers;
@@ -16,6 +24,7 @@ mod state;
 mod structure_panel;
 mod tabs;
 mod theme_colors;
+mod transactions;
 pub mod types;
 mod ui_helpers;
 
@@ -1,9 +1,12 @@
-use crate::components::TextInput;
-use crate::postcommander::database::{ConnectionConfig, DatabaseManager};
-use crate::postcommander::sql::{SqlCompletionProvider, SqlDangerLevel};
-use crate::postcommander::state::{ActiveOverlays, ConnectionDialogState, ResizeState, SaveQueryDialogState};
-use crate::postcommander::types::{CellEditState, ConnectionState, QueryTab, SchemaMap, SidebarTab, TabId, TableStructureInfo};
-use crate::settings::{AppSettings, ConnectionSettings};
+use crate::components::{SelectedCells, TextInput};
+use crate::postcommander::database::{CellValue, DatabaseManager};
+use crate::postcommander::sql::{SqlCompletionProvider, SqlStatement};
+use crate::postcommander::state::{
+    ActiveOverlays, ChangesReviewState, ConnectionDialogState, ParameterPromptState, ResizeState,
+    SaveQueryDialogState,
+};
+use crate::postcommander::types::{CellEditState, CompletionCatalog, ConnectionSession, QueryTab, RunScope, SafetyWarning, SchemaMap, SidebarTab, TabId, TableStructureInfo, TransactionHold};
+use crate::settings::{AppSettings, ConnectionAccess};
 use crate::theme::ActiveTheme;
 use gpui::prelude::FluentBuilder;
 use gpui::*;
@@ -21,16 +24,24 @@ pub struct PostCommanderPage {
     pub(crate) tabs: Vec<QueryTab>,
     pub(crate) active_tab_id: Option<TabId>,
     pub(crate) db_manager: Arc<DatabaseManager>,
-    pub(crate) connection_state: ConnectionState,
+    /// One session per saved profile, in the order they were added.
+    pub(crate) connections: Vec<ConnectionSession>,
+    /// Connection new tabs bind to; follows the last one picked.
+    pub(crate) active_connection_id: Option<String>,
     pub(crate) expanded_nodes: HashSet<String>,
-    pub(crate) schemas: Arc<SchemaMap>,
-    pub(crate) schemas_loading: bool,
     pub(crate) cell_edit: Option<CellEditState>,
     pub(crate) _subscriptions: Vec<Subscription>,
     pub(crate) completion_provider: Rc<SqlCompletionProvider>,
     pub(crate) completion_schemas: Rc<RefCell<SchemaMap>>,
     pub(crate) completion_structures: Rc<RefCell<Vec<TableStructureInfo>>>,
-    pub(crate) safety_warning: Option<(SqlDangerLevel, String)>,
+    pub(crate) completion_catalog: Rc<RefCell<Arc<CompletionCatalog>>>,
+    pub(crate) safety_warning: Option<SafetyWarning>,
+    /// Statements held back by the safety warning until it is confirmed.
+    pub(crate) pending_script: Option<(TabId, Vec<SqlStatement>)>,
+    pub(crate) parameter_prompt: Option<ParameterPromptState>,
+    pub(crate) changes_review: Option<ChangesReviewState>,
+    /// Action waiting on a decision about the open transactions it would end.
+    pub(crate) transaction_hold: Option<TransactionHold>,
     pub(crate) pending_capitalization: Option<(TabId, usize, usize, String)>,
     pub(crate) pending_undo_newline: Option<TabId>,
     pub(crate) current_sidebar_tab: SidebarTab,
@@ -39,75 +50,44 @@ pub struct PostCommanderPage {
     pub(crate) saved_queries_search_filter: String,
     pub(crate) saved_queries_search_input: Entity<TextInput>,
     pub(crate) save_query_dialog: SaveQueryDialogState,
-    cached_connection: ConnectionInfo,
     pub(crate) temporary_message: Option<(String, Task<()>)>,
     pub(crate) pending_file_open: Option<(String, String)>,
 }
 
-#[derive(Default, Clone)]
-struct ConnectionInfo {
-    host: String,
-    port: String,
-    database: String,
-    username: String,
-    password: String,
-}
-
 impl PostCommanderPage {
     pub fn new(_window: &mut Window, cx: &mut Context<Self>) -> Self {
+        AppSettings::update_global(cx, |settings| {
+            if settings.postcommander_mut().migrate_legacy_connection() {
+                settings.save();
+            }
+        });
+
         let settings = AppSettings::get_global(cx);
         let pc_settings = settings.postcommander();
-        let saved_conn = pc_settings.connection.clone();
+        let profiles = pc_settings.profiles().to_vec();
+        let open_connections = pc_settings.open_connections.clone().unwrap_or_default();
         let saved_expanded = pc_settings.expanded_nodes.clone();
         let saved_sidebar_width = pc_settings.sidebar_width;
         let saved_editor_height = pc_settings.editor_height;
         let saved_structure_panel_width = pc_settings.structure_panel_width;
-        let conn = saved_conn.clone().unwrap_or_else(ConnectionSettings::defaults);
-
-        let has_saved_connection = saved_conn.is_some()
-            && !conn.host.is_empty()
-            && !conn.database.is_empty()
-            && !conn.username.is_empty();
 
-        let cached_connection = ConnectionInfo {
-            host: conn.host.clone(),
-            port: conn.port.clone(),
-            database: conn.database.clone(),
-            username: conn.username.clone(),
-            password: conn.password.clone(),
-        };
-
-        let input_host = cx.new(|cx| {
-            let mut input = TextInput::new(cx, "localhost");
-            input.set_content(conn.host.clone());
-            input
-        });
-        let input_port = cx.new(|cx| {
-            let mut input = TextInput::new(cx, "5432");
-            input.set_content(conn.port.clone());
-            input
-        });
-        let input_database = cx.new(|cx| {
-            let mut input = TextInput::new(cx, "database");
-            input.set_content(conn.database.clone());
-            input
-        });
-        let input_username = cx.new(|cx| {
-            let mut input = TextInput::new(cx, "username");
-            input.set_content(conn.username.clone());
-            input
-        });
+        let input_name = cx.new(|cx| TextInput::new(cx, "Connection name"));
+        let input_host = cx.new(|cx| TextInput::new(cx, "localhost"));
+        let input_port = cx.new(|cx| TextInput::new(cx, "5432"));
+        let input_database = cx.new(|cx| TextInput::new(cx, "database"));
+        let input_username = cx.new(|cx| TextInput::new(cx, "username"));
         let input_password = cx.new(|cx| {
             let mut input = TextInput::new(cx, "password");
-            input.set_content(conn.password.clone());
             input.set_masked(true);
             input
         });
 
-        if has_saved_connection {
+        if !open_connections.is_empty() {
             cx.spawn(async move |this, cx| {
                 let _ = this.update(cx, |this, cx| {
-                    this.connect_to_database(cx);
+                    for connection_id in &open_connections {
+                        this.connect_profile(connection_id, cx);
+                    }
                 });
             })
             .detach();
@@ -116,6 +96,7 @@ impl PostCommanderPage {
         let completion_provider = Rc::new(SqlCompletionProvider::new());
         let completion_schemas = completion_provider.schemas_ref();
         let completion_structures = completion_provider.table_structures_ref();
+        let completion_catalog = completion_provider.catalog_ref();
 
         let page_entity = cx.entity();
         let history_search_input = cx.new(|cx| {
@@ -153,6 +134,7 @@ impl PostCommanderPage {
                 saved_structure_panel_width.unwrap_or(280.0),
             ),
             connection_dialog: ConnectionDialogState::new(
+                input_name,
                 input_host,
                 input_port,
                 input_database,
@@ -163,22 +145,22 @@ impl PostCommanderPage {
             tabs: vec![],
             active_tab_id: None,
             db_manager: Arc::new(DatabaseManager::new()),
-            connection_state: if has_saved_connection {
-                ConnectionState::Connecting
-            } else {
-                ConnectionState::Disconnected
-            },
+            connections: profiles.into_iter().map(ConnectionSession::new).collect(),
+            active_connection_id: None,
             expanded_nodes: saved_expanded
                 .map(|v| v.into_iter().collect())
                 .unwrap_or_default(),
-            schemas: Arc::new(SchemaMap::new()),
-            schemas_loading: false,
             cell_edit: None,
             _subscriptions: vec![],
             completion_provider,
             completion_schemas,
             completion_structures,
+            completion_catalog,
             safety_warning: None,
+            pending_script: None,
+            parameter_prompt: None,
+            changes_review: None,
+            transaction_hold: None,
             pending_capitalization: None,
             pending_undo_newline: None,
             current_sidebar_tab: SidebarTab::Schema,
@@ -191,101 +173,11 @@ impl PostCommanderPage {
                 input_query_folder,
                 input_query_description,
             ),
-            cached_connection,
             temporary_message: None,
             pending_file_open: None,
         }
     }
 
-    pub(crate) fn get_conn_host(&self) -> &str {
-        &self.cached_connection.host
-    }
-
-    pub(crate) fn get_conn_port(&self) -> &str {
-        &self.cached_connection.port
-    }
-
-    pub(crate) fn get_conn_database(&self) -> &str {
-        &self.cached_connection.database
-    }
-
-    pub(crate) fn get_conn_username(&self) -> &str {
-        &self.cached_connection.username
-    }
-
-    pub(crate) fn get_conn_password(&self) -> &str {
-        &self.cached_connection.password
-    }
-
-    fn update_cached_connection(&mut self, cx: &App) {
-        self.cached_connection.host = self.connection_dialog.input_host.read(cx).content().to_string();
-        self.cached_connection.port = self.connection_dialog.input_port.read(cx).content().to_string();
-        self.cached_connection.database = self.connection_dialog.input_database.read(cx).content().to_string();
-        self.cached_connection.username = self.connection_dialog.input_username.read(cx).content().to_string();
-        self.cached_connection.password = self.connection_dialog.input_password.read(cx).content().to_string();
-    }
-
-    pub(crate) fn connect_to_database(&mut self, cx: &mut Context<Self>) {
-        self.update_cached_connection(cx);
-        let host = self.get_conn_host();
-        let port_str = self.get_conn_port();
-        let database = self.get_conn_database();
-        let username = self.get_conn_username();
-        let password = self.get_conn_password();
-
-        let config = ConnectionConfig {
-            name: "Local PostgreSQL".to_string(),
-            host: host.to_string(),
-            port: port_str.parse().unwrap_or(5432),
-            database: database.to_string(),
-            username: username.to_string(),
-            password: password.to_string(),
-        };
-
-        let conn_settings = ConnectionSettings {
-            host: host.to_string(),
-            port: port_str.to_string(),
-            database: database.to_string(),
-            username: username.to_string(),
-            password: password.to_string(),
-        };
-
-        self.connection_state = ConnectionState::Connecting;
-        self.connection_dialog.is_visible = false;
-        cx.notify();
-
-        let rx = self.db_manager.connect(config);
-        cx.spawn(async move |this, cx| {
-            let result = rx.await;
-            let _ = this.update(cx, |this, cx| {
-                match result {
-                    Ok(Ok(())) => {
-                        this.connection_state = ConnectionState::Connected;
-                        this.expanded_nodes.insert("server".to_string());
-                        let nodes: Vec<String> = this.expanded_nodes.iter().cloned().collect();
-                        AppSettings::update_global(cx, |settings| {
-                            let pc = settings.postcommander_mut();
-                            pc.connection = Some(conn_settings.clone());
-                            pc.expanded_nodes = Some(nodes);
-                        });
-                        AppSettings::get_global(cx).save();
-                        if this.expanded_nodes.contains("database") && this.schemas.is_empty() {
-                            this.fetch_schema_objects(cx);
-                        }
-                    }
-                    Ok(Err(e)) => {
-                        this.connection_state = ConnectionState::Error(e.to_string());
-                    }
-                    Err(_) => {
-                        this.connection_state = ConnectionState::Error("Connection failed".to_string());
-                    }
-                }
-                cx.notify();
-            });
-        })
-        .detach();
-    }
-
     pub(crate) fn deploy_export_menu(
         &mut self,
         position: Point<Pixels>,
@@ -392,9 +284,48 @@ impl PostCommanderPage {
         cx.notify();
     }
 
+    pub(crate) fn deploy_run_menu(
+        &mut self,
+        position: Point<Pixels>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        use gpui_component::menu::PopupMenuItem;
+
+        let entity = cx.entity().downgrade();
+        let scopes = [
+            ("Run Script  ⌘↵", RunScope::Script),
+            ("Run Statement at Cursor  ⌘R", RunScope::StatementAtCursor),
+            ("Run Selection  ⇧⌘R", RunScope::Selection),
+        ];
+
+        let menu = PopupMenu::build(window, cx, move |mut menu, _window, _cx| {
+            for (label, scope) in scopes {
+                let entity = entity.clone();
+                menu = menu.item(PopupMenuItem::new(label).on_click(move |_, window, cx| {
+                    if let Some(page) = entity.upgrade() {
+                        page.update(cx, |page, cx| {
+                            page.run_query(scope, window, cx);
+                        });
+                    }
+                }));
+            }
+            menu
+        });
+
+        let subscription = cx.subscribe(&menu, |this, _, _: &DismissEvent, cx| {
+            this.overlays.run_menu = None;
+            cx.notify();
+        });
+
+        self.overlays.run_menu = Some((menu, position, subscription));
+        cx.notify();
+    }
+
     pub(crate) fn deploy_table_context_menu(
         &mut self,
         position: Point<Pixels>,
+        connection_id: String,
         schema: String,
         table: String,
         window: &mut Window,
@@ -402,6 +333,7 @@ impl PostCommanderPage {
     ) {
         use gpui_component::menu::PopupMenuItem;
 
+        let menu_key = format!("{}:{}.{}", connection_id, schema, table);
         let schema_clone = schema.clone();
         let table_clone = table.clone();
         let entity_select = cx.entity().downgrade();
@@ -413,16 +345,18 @@ impl PostCommanderPage {
         let menu = PopupMenu::build(window, cx, move |menu, _window, _cx| {
             let schema = schema_clone.clone();
             let table = table_clone.clone();
+            let connection_id = connection_id.clone();
 
             menu.item(
                 PopupMenuItem::new("Select Top 100").on_click({
                     let entity = entity_select.clone();
+                    let connection_id = connection_id.clone();
                     let schema = schema.clone();
                     let table = table.clone();
                     move |_, window, cx| {
                         if let Some(page) = entity.upgrade() {
                             page.update(cx, |page, cx| {
-                                page.query_table(&schema, &table, window, cx);
+                                page.query_table(&connection_id, &schema, &table, window, cx);
                             });
                         }
                     }
@@ -460,12 +394,13 @@ impl PostCommanderPage {
             .item(
                 PopupMenuItem::new("Count Rows").on_click({
                     let entity = entity_count.clone();
+                    let connection_id = connection_id.clone();
                     let schema = schema.clone();
                     let table = table.clone();
                     move |_, window, cx| {
                         if let Some(page) = entity.upgrade() {
                             page.update(cx, |page, cx| {
-                                page.count_table_rows(&schema, &table, window, cx);
+                                page.count_table_rows(&connection_id, &schema, &table, window, cx);
                             });
                         }
                     }
@@ -474,12 +409,13 @@ impl PostCommanderPage {
             .item(
                 PopupMenuItem::new("Generate SELECT").on_click({
                     let entity = entity_generate.clone();
+                    let connection_id = connection_id.clone();
                     let schema = schema.clone();
                     let table = table.clone();
                     move |_, window, cx| {
                         if let Some(page) = entity.upgrade() {
                             page.update(cx, |page, cx| {
-                                page.generate_select_statement(&schema, &table, window, cx);
+                                page.generate_select_statement(&connection_id, &schema, &table, window, cx);
                             });
                         }
                     }
@@ -492,15 +428,15 @@ impl PostCommanderPage {
             cx.notify();
         });
 
-        self.overlays.context_menu = Some((menu, position, table, subscription));
+        self.overlays.context_menu = Some((menu, position, menu_key, subscription));
         cx.notify();
     }
 
     pub(crate) fn deploy_cell_context_menu(
         &mut self,
         col_index: usize,
-        column_names: Vec<String>,
-        row_data: Vec<SharedString>,
+        row_data: Vec<CellValue>,
+        selection: SelectedCells,
         position: Point<Pixels>,
         table_name: Option<String>,
         window: &mut Window,
@@ -508,9 +444,8 @@ impl PostCommanderPage {
     ) {
         use gpui_component::menu::PopupMenuItem;
 
-        let cell_value = row_data.get(col_index).map(|s| s.to_string()).unwrap_or_default();
-        let col_names = column_names.clone();
-        let row_clone = row_data.clone();
+        let cell_value = row_data.get(col_index).map(|c| c.display().to_string()).unwrap_or_default();
+        let selection = Rc::new(selection);
 
         let menu = gpui_component::menu::PopupMenu::build(window, cx, move |menu, _window, _cx| {
             menu.item(
@@ -521,39 +456,53 @@ impl PostCommanderPage {
                     }
                 }),
             )
+            .separator()
+            .item(
+                PopupMenuItem::new("Copy as TSV").on_click({
+                    let selection = selection.clone();
+                    move |_, _window, cx| {
+                        PostCommanderPage::copy_rows_as_tsv(&selection.rows, cx);
+                    }
+                }),
+            )
             .item(
-                PopupMenuItem::new("Copy Row (TSV)").on_click({
-                    let cols = col_names.clone();
-                    let row = row_clone.clone();
+                PopupMenuItem::new("Copy as CSV").on_click({
+                    let selection = selection.clone();
                     move |_, _window, cx| {
-                        PostCommanderPage::copy_row_as_tsv(&cols, &row, cx);
+                        PostCommanderPage::copy_rows_as_csv(&selection.column_names, &selection.rows, cx);
                     }
                 }),
             )
             .item(
-                PopupMenuItem::new("Copy Row (JSON)").on_click({
-                    let cols = col_names.clone();
-                    let row = row_clone.clone();
+                PopupMenuItem::new("Copy as JSON").on_click({
+                    let selection = selection.clone();
                     move |_, _window, cx| {
-                        PostCommanderPage::copy_row_as_json(&cols, &row, cx);
+                        PostCommanderPage::copy_rows_as_json(&selection.column_names, &selection.rows, cx);
                     }
                 }),
             )
             .item(
-                PopupMenuItem::new("Copy Row (INSERT)").on_click({
-                    let cols = col_names.clone();
-                    let row = row_clone.clone();
+                PopupMenuItem::new("Copy as INSERT").on_click({
+                    let selection = selection.clone();
                     let table = table_name.clone();
                     move |_, _window, cx| {
-                        PostCommanderPage::copy_row_as_insert(
+                        PostCommanderPage::copy_rows_as_insert(
                             table.as_deref(),
-                            &cols,
-                            &row,
+                            &selection.column_names,
+                            &selection.rows,
                             cx,
                         );
                     }
                 }),
             )
+            .item(
+                PopupMenuItem::new("Copy as Markdown").on_click({
+                    let selection = selection.clone();
+                    move |_, _window, cx| {
+                        PostCommanderPage::copy_rows_as_markdown(&selection.column_names, &selection.rows, cx);
+                    }
+                }),
+            )
         });
 
         let subscription = cx.subscribe(&menu, |this, _, _: &gpui::DismissEvent, cx| {
@@ -692,10 +641,11 @@ impl PostCommanderPage {
                 input.set_content(description);
             });
 
-            if let Some(tab) = self.tabs.iter().find(|t| Some(t.id) == self.active_tab_id) {
+            if let Some(tab) = self.tabs.iter_mut().find(|t| Some(t.id) == self.active_tab_id) {
                 tab.editor.update(cx, |editor, cx| {
                     editor.set_value(sql, window, cx);
                 });
+                tab.saved_query_id = Some(id.to_string());
             }
 
             self.save_query_dialog.editing_id = Some(id.to_string());
@@ -720,8 +670,13 @@ impl PostCommanderPage {
             self.expanded_nodes.remove(node_id);
         } else {
             self.expanded_nodes.insert(node_id.to_string());
-            if node_id == "database" && self.schemas.is_empty() && !self.schemas_loading {
-                self.fetch_schema_objects(cx);
+            if let Some(connection_id) = node_id.strip_prefix("db:") {
+                let needs_fetch = self
+                    .connection(connection_id)
+                    .is_some_and(|c| c.is_connected() && c.schemas.is_empty() && !c.schemas_loading);
+                if needs_fetch {
+                    self.fetch_schema_objects(connection_id, cx);
+                }
             }
         }
         self.save_expanded_nodes(cx);
@@ -742,58 +697,6 @@ impl PostCommanderPage {
         });
         AppSettings::get_global(cx).save();
     }
-
-    fn fetch_schema_objects(&mut self, cx: &mut Context<Self>) {
-        self.schemas_loading = true;
-        cx.notify();
-
-        let sql = r#"
-            SELECT
-                table_schema,
-                table_name,
-                table_type
-            FROM information_schema.tables
-            WHERE table_schema NOT IN ('pg_catalog', 'information_schema')
-            ORDER BY table_schema, table_type, table_name
-        "#
-        .to_string();
-
-        let rx = self.db_manager.execute(sql);
-
-        cx.spawn(async move |this, cx| {
-            let result = rx.await;
-            let _ = this.update(cx, |this, cx| {
-                this.schemas_loading = false;
-                match result {
-                    Ok(Ok(query_result)) => {
-                        let mut schemas: SchemaMap = SchemaMap::new();
-                        for row in query_result.rows.iter() {
-                            if row.len() >= 3 {
-                                let schema = row[0].to_string();
-                                let name = row[1].to_string();
-                                let obj_type = row[2].as_ref();
-
-                                let entry = schemas.entry(schema).or_default();
-                                if obj_type == "VIEW" {
-                                    entry.views.push(name);
-                                } else {
-                                    entry.tables.push(name);
-                                }
-                            }
-                        }
-                        *this.completion_schemas.borrow_mut() = schemas.clone();
-                        this.schemas = Arc::new(schemas);
-                    }
-                    _ => {
-                        *this.completion_schemas.borrow_mut() = SchemaMap::new();
-                        this.schemas = Arc::new(SchemaMap::new());
-                    }
-                }
-                cx.notify();
-            });
-        })
-        .detach();
-    }
 }
 
 impl Render for PostCommanderPage {
@@ -853,7 +756,15 @@ impl Render for PostCommanderPage {
         let is_resizing_structure = self.resize.is_resizing_structure;
         let show_cell_edit = self.cell_edit.is_some();
         let show_safety_warning = self.safety_warning.is_some();
+        let show_transaction_hold = self.transaction_hold.is_some();
+        let show_parameter_prompt = self.parameter_prompt.is_some();
+        let show_changes_review = self.changes_review.is_some();
         let show_save_dialog = self.save_query_dialog.is_visible;
+        let protected_color = self
+            .active_tab_id
+            .and_then(|id| self.tab_profile(id))
+            .filter(|p| p.access == ConnectionAccess::Protected)
+            .map(|p| p.environment.color());
         let context_menu = self
             .overlays.context_menu
             .as_ref()
@@ -862,6 +773,10 @@ impl Render for PostCommanderPage {
             .overlays.export_menu
             .as_ref()
             .map(|(menu, pos, _)| (menu.clone(), *pos));
+        let run_menu = self
+            .overlays.run_menu
+            .as_ref()
+            .map(|(menu, pos, _)| (menu.clone(), *pos));
         let cell_context_menu = self
             .overlays.cell_context_menu
             .as_ref()
@@ -870,12 +785,16 @@ impl Render for PostCommanderPage {
             .overlays.saved_query_menu
             .as_ref()
             .map(|(menu, pos, _, _)| (menu.clone(), *pos));
+        let connection_menu = self
+            .overlays.connection_menu
+            .as_ref()
+            .map(|(menu, pos, _)| (menu.clone(), *pos));
 
         if let Some(pending) = self.overlays.pending_cell_context_menu.take() {
             self.deploy_cell_context_menu(
                 pending.col_index,
-                pending.column_names,
                 pending.row_data,
+                pending.selection,
                 pending.position,
                 pending.table_name,
                 window,
@@ -893,6 +812,12 @@ impl Render for PostCommanderPage {
             .on_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
                 if event.keystroke.key == "enter" && event.keystroke.modifiers.platform {
                     this.execute_query(cx);
+                } else if event.keystroke.key == "r"
+                    && event.keystroke.modifiers.platform
+                    && event.keystroke.modifiers.shift {
+                    this.run_query(RunScope::Selection, window, cx);
+                } else if event.keystroke.key == "r" && event.keystroke.modifiers.platform {
+                    this.run_query(RunScope::StatementAtCursor, window, cx);
                 } else if event.keystroke.key == "e"
                     && event.keystroke.modifiers.platform
                     && event.keystroke.modifiers.shift {
@@ -903,6 +828,10 @@ impl Render for PostCommanderPage {
                     this.explain_analyze_query(window, cx);
                 } else if event.keystroke.key == "/" && event.keystroke.modifiers.platform {
                     this.toggle_comment(window, cx);
+                } else if event.keystroke.key == "f"
+                    && event.keystroke.modifiers.platform
+                    && event.keystroke.modifiers.alt {
+                    this.format_selection(window, cx);
                 } else if event.keystroke.key == "f"
                     && event.keystroke.modifiers.platform
                     && event.keystroke.modifiers.shift {
@@ -913,6 +842,9 @@ impl Render for PostCommanderPage {
                     let _ = this.save_query_to_file(cx);
                 } else if event.keystroke.key == "o" && event.keystroke.modifiers.platform {
                     this.open_sql_file(window, cx);
+                } else if event.keystroke.key == "p" && event.keystroke.modifiers.platform {
+                    let position = point(window.bounds().size.width / 2. - px(120.), px(80.));
+                    this.deploy_connection_switcher(position, window, cx);
                 } else if event.keystroke.key == "t" && event.keystroke.modifiers.platform {
                     this.add_tab(window, cx);
                 } else if event.keystroke.key == "w" && event.keystroke.modifiers.platform {
@@ -934,6 +866,9 @@ impl Render for PostCommanderPage {
                             .min_h_0()
                             .flex()
                             .flex_col()
+                            .when_some(protected_color, |el, color| {
+                                el.border_2().border_color(rgb(color))
+                            })
                             .child(self.render_tabs_bar(cx))
                             .when(has_tabs, |el| {
                                 el.child(
@@ -997,6 +932,26 @@ impl Render for PostCommanderPage {
                     .with_priority(1),
                 )
             })
+            .when_some(run_menu, |el, (menu, position)| {
+                let window_size = window.bounds().size;
+                el.child(
+                    deferred(
+                        anchored().child(
+                            div()
+                                .w(window_size.width)
+                                .h(window_size.height)
+                                .occlude()
+                                .child(
+                                    anchored()
+                                        .position(position)
+                                        .anchor(Corner::TopLeft)
+                                        .child(menu),
+                                ),
+                        ),
+                    )
+                    .with_priority(1),
+                )
+            })
             .when_some(cell_context_menu, |el, (menu, position)| {
                 let window_size = window.bounds().size;
                 el.child(
@@ -1037,12 +992,41 @@ impl Render for PostCommanderPage {
                     .with_priority(1),
                 )
             })
+            .when_some(connection_menu, |el, (menu, position)| {
+                let window_size = window.bounds().size;
+                el.child(
+                    deferred(
+                        anchored().child(
+                            div()
+                                .w(window_size.width)
+                                .h(window_size.height)
+                                .occlude()
+                                .child(
+                                    anchored()
+                                        .position(position)
+                                        .anchor(Corner::TopLeft)
+                                        .child(menu),
+                                ),
+                        ),
+                    )
+                    .with_priority(1),
+                )
+            })
             .when(show_safety_warning, |el| {
                 el.child(deferred(self.render_safety_warning_dialog(cx)).with_priority(3))
             })
+            .when(show_parameter_prompt, |el| {
+                el.child(deferred(self.render_parameter_dialog(cx)).with_priority(3))
+            })
+            .when(show_changes_review, |el| {
+                el.child(deferred(self.render_changes_review(cx)).with_priority(3))
+            })
             .when(show_save_dialog, |el| {
                 el.child(deferred(self.render_save_query_dialog(cx)).with_priority(3))
             })
+            .when(show_transaction_hold, |el| {
+                el.child(deferred(self.render_transaction_hold_dialog(cx)).with_priority(3))
+            })
             .when_some(self.temporary_message.as_ref().map(|(msg, _)| msg.clone()), |el, msg| {
                 el.child(deferred(self.render_temporary_message(&msg, cx)).with_priority(4))
             })
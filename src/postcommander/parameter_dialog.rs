@@ -0,0 +1,412 @@
+use crate::components::TextInput;
+use crate::icons::icon_sm;
+use crate::postcommander::query_execution::statement_run;
+use crate::postcommander::sql::{bind_placeholders, parameter_names, SqlStatement};
+use crate::postcommander::state::{ParameterField, ParameterPromptState};
+use crate::postcommander::types::{BoundParams, TabId};
+use crate::postcommander::PostCommanderPage;
+use crate::settings::{AppSettings, SavedQueryParameter};
+use crate::theme::ActiveTheme;
+use gpui::prelude::FluentBuilder;
+use gpui::*;
+use std::collections::HashMap;
+
+impl PostCommanderPage {
+    /// Asks the server for the types of the script's placeholders, then
+    /// prompts for their values.
+    pub(crate) fn prompt_parameters(
+        &mut self,
+        tab_id: TabId,
+        statements: Vec<SqlStatement>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return;
+        };
+        let Some(connection_id) = tab.connection_id.clone() else {
+            return;
+        };
+        let session_id = tab.transaction.as_ref().map(|t| t.session_id.clone());
+
+        let describes: Vec<_> = statements
+            .iter()
+            .map(|statement| {
+                (!parameter_names(&statement.text).is_empty()).then(|| {
+                    self.db_manager.describe_parameters(
+                        &connection_id,
+                        bind_placeholders(&statement.text, &[]),
+                        session_id.clone(),
+                    )
+                })
+            })
+            .collect();
+
+        cx.spawn(async move |this, cx| {
+            let mut statement_types = Vec::with_capacity(describes.len());
+            for rx in describes {
+                let types = match rx {
+                    Some(rx) => rx
+                        .await
+                        .unwrap_or_else(|_| Err(anyhow::anyhow!("Connection closed"))),
+                    None => Ok(vec![]),
+                };
+                statement_types.push(types);
+            }
+
+            let _ = this.update(cx, |this, cx| {
+                match statement_types.into_iter().collect::<anyhow::Result<Vec<_>>>() {
+                    Ok(types) => this.show_parameter_prompt(tab_id, statements, types, cx),
+                    Err(e) => {
+                        let message = format!("Could not prepare parameters: {}", e);
+                        this.set_tab_error(tab_id, &message, cx);
+                    }
+                }
+            });
+        })
+        .detach();
+    }
+
+    fn show_parameter_prompt(
+        &mut self,
+        tab_id: TabId,
+        statements: Vec<SqlStatement>,
+        statement_types: Vec<Vec<String>>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return;
+        };
+
+        // Values saved with the query win over the ones last typed in the tab.
+        let saved: Vec<SavedQueryParameter> = tab
+            .saved_query_id
+            .as_deref()
+            .and_then(|id| {
+                AppSettings::get_global(cx)
+                    .postcommander()
+                    .saved_queries
+                    .as_ref()?
+                    .get_entry(id)
+                    .map(|entry| entry.parameters.clone())
+            })
+            .unwrap_or_default();
+        let remembered = tab.parameter_values.clone();
+
+        let mut fields: Vec<ParameterField> = Vec::new();
+        for (statement, types) in statements.iter().zip(&statement_types) {
+            for (name, type_name) in parameter_names(&statement.text).into_iter().zip(types) {
+                if fields.iter().any(|f| f.name == name) {
+                    continue;
+                }
+                let value = saved
+                    .iter()
+                    .find(|p| p.name == name)
+                    .map(|p| p.value.clone())
+                    .or_else(|| remembered.get(&name).cloned());
+                let is_null = matches!(value, Some(None));
+                let text = value.flatten().unwrap_or_default();
+                let input = cx.new(|cx| {
+                    let mut input = TextInput::new(cx, type_name.clone());
+                    input.set_content(text);
+                    input
+                });
+                fields.push(ParameterField {
+                    name,
+                    type_name: type_name.clone(),
+                    input,
+                    is_null,
+                });
+            }
+        }
+
+        self.parameter_prompt = Some(ParameterPromptState {
+            tab_id,
+            statements,
+            statement_types,
+            fields,
+        });
+        cx.notify();
+    }
+
+    fn toggle_parameter_null(&mut self, index: usize, cx: &mut Context<Self>) {
+        if let Some(field) = self
+            .parameter_prompt
+            .as_mut()
+            .and_then(|prompt| prompt.fields.get_mut(index))
+        {
+            field.is_null = !field.is_null;
+            cx.notify();
+        }
+    }
+
+    fn cancel_parameter_prompt(&mut self, cx: &mut Context<Self>) {
+        self.parameter_prompt = None;
+        cx.notify();
+    }
+
+    /// Binds the entered values and runs the script.
+    fn confirm_parameter_prompt(&mut self, cx: &mut Context<Self>) {
+        let Some(prompt) = self.parameter_prompt.take() else {
+            return;
+        };
+
+        let values: Vec<(String, Option<String>)> = prompt
+            .fields
+            .iter()
+            .map(|f| {
+                let value = (!f.is_null).then(|| f.input.read(cx).content().to_string());
+                (f.name.clone(), value)
+            })
+            .collect();
+        let by_name: HashMap<&str, &Option<String>> =
+            values.iter().map(|(name, value)| (name.as_str(), value)).collect();
+
+        let runs = prompt
+            .statements
+            .iter()
+            .zip(&prompt.statement_types)
+            .map(|(statement, types)| {
+                let names = parameter_names(&statement.text);
+                let params = (!names.is_empty()).then(|| BoundParams {
+                    sql: bind_placeholders(&statement.text, types),
+                    values: names
+                        .iter()
+                        .map(|name| by_name.get(name.as_str()).and_then(|v| (*v).clone()))
+                        .collect(),
+                });
+                statement_run(&statement.text, Some(statement.start), params)
+            })
+            .collect();
+
+        self.remember_parameters(prompt.tab_id, &values, cx);
+        self.run_script(prompt.tab_id, runs, cx);
+    }
+
+    /// Keeps the values for the next run in the tab and, when the tab holds
+    /// a saved query, with that query.
+    fn remember_parameters(
+        &mut self,
+        tab_id: TabId,
+        values: &[(String, Option<String>)],
+        cx: &mut Context<Self>,
+    ) {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+        tab.parameter_values.extend(values.iter().cloned());
+
+        let Some(saved_id) = tab.saved_query_id.clone() else {
+            return;
+        };
+        AppSettings::update_global(cx, |settings| {
+            let Some(saved) = settings.postcommander_mut().saved_queries.as_mut() else {
+                return;
+            };
+            saved.update_entry(&saved_id, |entry| {
+                // The editor may have moved on from the saved text; only
+                // keep values for placeholders the saved query has.
+                let names = parameter_names(&entry.sql);
+                for (name, value) in values.iter().filter(|(name, _)| names.contains(name)) {
+                    match entry.parameters.iter_mut().find(|p| &p.name == name) {
+                        Some(param) => param.value = value.clone(),
+                        None => entry.parameters.push(SavedQueryParameter {
+                            name: name.clone(),
+                            value: value.clone(),
+                        }),
+                    }
+                }
+            });
+        });
+        AppSettings::get_global(cx).save();
+    }
+
+    pub(crate) fn render_parameter_dialog(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+        let text = colors.text;
+        let text_muted = colors.text_muted;
+        let surface = colors.surface;
+        let border = colors.border;
+        let border_variant = colors.border_variant;
+        let element = colors.element;
+        let element_hover = colors.element_hover;
+        let accent = colors.accent;
+        let accent_foreground = colors.accent_foreground;
+
+        let Some(prompt) = &self.parameter_prompt else {
+            return div().into_any_element();
+        };
+
+        for field in &prompt.fields {
+            field.input.update(cx, |input, _| {
+                input.set_colors(text, text_muted);
+            });
+        }
+
+        div()
+            .id("parameter-prompt-overlay")
+            .absolute()
+            .inset_0()
+            .flex()
+            .items_center()
+            .justify_center()
+            .bg(rgba(0x00000080))
+            .on_mouse_down(MouseButton::Left, |_, _, _| {})
+            .child(
+                div()
+                    .w(px(460.0))
+                    .bg(rgb(surface))
+                    .border_1()
+                    .border_color(rgb(border))
+                    .rounded_lg()
+                    .shadow_lg()
+                    .p_4()
+                    .flex()
+                    .flex_col()
+                    .gap_4()
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .gap_2()
+                            .child(icon_sm("braces", text_muted))
+                            .child(
+                                div()
+                                    .text_lg()
+                                    .font_weight(FontWeight::SEMIBOLD)
+                                    .text_color(rgb(text))
+                                    .child("Query Parameters"),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .id("parameter-fields")
+                            .max_h(px(360.))
+                            .overflow_y_scroll()
+                            .flex()
+                            .flex_col()
+                            .gap_3()
+                            .children(prompt.fields.iter().enumerate().map(|(index, field)| {
+                                let is_null = field.is_null;
+                                div()
+                                    .flex()
+                                    .flex_col()
+                                    .gap_1()
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .items_center()
+                                            .gap_2()
+                                            .child(
+                                                div()
+                                                    .text_sm()
+                                                    .font_family("monospace")
+                                                    .text_color(rgb(text))
+                                                    .child(field.name.clone()),
+                                            )
+                                            .child(
+                                                div()
+                                                    .text_xs()
+                                                    .text_color(rgb(text_muted))
+                                                    .child(field.type_name.to_lowercase()),
+                                            ),
+                                    )
+                                    .child(
+                                        div()
+                                            .flex()
+                                            .items_center()
+                                            .gap_2()
+                                            .child(
+                                                div()
+                                                    .id(SharedString::from(format!("parameter-input-{}", index)))
+                                                    .flex_1()
+                                                    .h(px(32.))
+                                                    .px_3()
+                                                    .flex()
+                                                    .items_center()
+                                                    .rounded_md()
+                                                    .bg(rgb(surface))
+                                                    .border_1()
+                                                    .border_color(rgb(border_variant))
+                                                    .when(is_null, |el| el.opacity(0.4))
+                                                    .child(
+                                                        div()
+                                                            .w_full()
+                                                            .text_sm()
+                                                            .line_height(px(20.))
+                                                            .when(!is_null, |el| el.child(field.input.clone()))
+                                                            .when(is_null, |el| {
+                                                                el.text_color(rgb(text_muted)).child("NULL")
+                                                            }),
+                                                    ),
+                                            )
+                                            .child(
+                                                div()
+                                                    .id(SharedString::from(format!("parameter-null-{}", index)))
+                                                    .h(px(32.))
+                                                    .px_2()
+                                                    .flex()
+                                                    .items_center()
+                                                    .gap_1()
+                                                    .rounded_md()
+                                                    .cursor_pointer()
+                                                    .hover(move |s| s.bg(rgb(element_hover)))
+                                                    .on_click(cx.listener(move |this, _, _, cx| {
+                                                        this.toggle_parameter_null(index, cx);
+                                                    }))
+                                                    .child(icon_sm(
+                                                        if is_null { "square-check" } else { "square" },
+                                                        text_muted,
+                                                    ))
+                                                    .child(
+                                                        div()
+                                                            .text_xs()
+                                                            .text_color(rgb(text_muted))
+                                                            .child("NULL"),
+                                                    ),
+                                            ),
+                                    )
+                            })),
+                    )
+                    .child(
+                        div()
+                            .flex()
+                            .justify_end()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .id("cancel-parameters-btn")
+                                    .px_3()
+                                    .py_1p5()
+                                    .rounded_md()
+                                    .bg(rgb(element))
+                                    .text_sm()
+                                    .text_color(rgb(text))
+                                    .cursor_pointer()
+                                    .hover(move |s| s.bg(rgb(element_hover)))
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.cancel_parameter_prompt(cx);
+                                    }))
+                                    .child("Cancel"),
+                            )
+                            .child(
+                                div()
+                                    .id("run-with-parameters-btn")
+                                    .px_3()
+                                    .py_1p5()
+                                    .rounded_md()
+                                    .bg(rgb(accent))
+                                    .text_sm()
+                                    .text_color(rgb(accent_foreground))
+                                    .cursor_pointer()
+                                    .hover(|s| s.opacity(0.9))
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.confirm_parameter_prompt(cx);
+                                    }))
+                                    .child("Run"),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,637 @@
+use crate::icons::icon_sm;
+use crate::postcommander::cell_edit::{build_row_delete, build_row_insert, build_row_update};
+use crate::postcommander::database::CellValue;
+use crate::postcommander::sql::inline_parameters;
+use crate::postcommander::state::ChangesReviewState;
+use crate::postcommander::types::{column_table, TabId};
+use crate::postcommander::PostCommanderPage;
+use crate::theme::ActiveTheme;
+use gpui::prelude::FluentBuilder;
+use gpui::*;
+use std::collections::BTreeMap;
+
+/// The grid row a pending statement writes.
+#[derive(Clone, Copy)]
+enum RowChange {
+    Update(usize),
+    Insert(usize),
+    Delete(usize),
+}
+
+struct PendingStatement {
+    change: RowChange,
+    sql: String,
+    params: Vec<Option<String>>,
+    /// The written table's column shown in each grid column, for placing
+    /// the returned row.
+    result_columns: Vec<Option<String>>,
+}
+
+impl PostCommanderPage {
+    /// Appends a new row to the grid showing each column's default. Cells
+    /// left untouched are not sent, so the server fills them in.
+    fn add_draft_row(&mut self, cx: &mut Context<Self>) {
+        let Some(tab) = self
+            .active_tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id))
+        else {
+            return;
+        };
+        let (Some(context), Some(result)) = (tab.row_table(), &tab.result) else {
+            return;
+        };
+        let structure = tab
+            .table_structures
+            .iter()
+            .find(|s| s.schema == context.schema && s.table == context.table);
+        let cells = (0..result.columns.len())
+            .map(|col_index| {
+                let name = context.source_column(col_index);
+                structure
+                    .and_then(|s| s.columns.iter().find(|c| Some(c.name.as_str()) == name))
+                    .and_then(|c| c.default_value.clone())
+                    .map(CellValue::Text)
+                    .unwrap_or(CellValue::Null)
+            })
+            .collect();
+        tab.table_state.update(cx, |state, cx| {
+            state.add_draft_row(cells, cx);
+        });
+        cx.notify();
+    }
+
+    fn delete_selected_rows(&mut self, cx: &mut Context<Self>) {
+        let Some(tab) = self
+            .active_tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id))
+        else {
+            return;
+        };
+        tab.table_state.update(cx, |state, cx| {
+            let rows = state.selected_rows();
+            state.mark_rows_deleted(&rows, cx);
+        });
+        cx.notify();
+    }
+
+    fn toggle_buffer_edits(&mut self, cx: &mut Context<Self>) {
+        let Some(tab) = self
+            .active_tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id))
+        else {
+            return;
+        };
+        tab.table_state.update(cx, |state, cx| {
+            // Turning buffering off would strand the staged edits.
+            if !state.has_pending_changes() {
+                let buffer = !state.buffers_edits();
+                state.set_buffer_edits(buffer, cx);
+            }
+        });
+        cx.notify();
+    }
+
+    /// One statement per changed row and table: UPDATEs of edited rows,
+    /// INSERTs of new rows and DELETEs of rows marked for deletion. Keys come from the
+    /// values as loaded, so the WHERE clause matches even when a key column
+    /// was edited. Every statement returns the row it wrote.
+    fn pending_statements(&self, tab_id: TabId, cx: &App) -> Result<Vec<PendingStatement>, String> {
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return Ok(vec![]);
+        };
+        let state = tab.table_state.read(cx);
+
+        let mut edited: Vec<(usize, Vec<(usize, Option<String>)>)> = Vec::new();
+        for ((row_index, col_index), edit) in state.pending_edits() {
+            match edited.last_mut() {
+                Some((last, changes)) if last == row_index => {
+                    changes.push((*col_index, edit.value.clone()));
+                }
+                _ => edited.push((*row_index, vec![(*col_index, edit.value.clone())])),
+            }
+        }
+
+        let mut statements = Vec::new();
+        for (row_index, changes) in &edited {
+            if state.is_draft_row(*row_index) || state.deleted_rows().contains(row_index) {
+                continue;
+            }
+            let row = state.original_row(*row_index).ok_or("Edited row no longer exists")?;
+            // A row of a join is written one table at a time.
+            let mut by_table: BTreeMap<usize, Vec<(usize, Option<String>)>> = BTreeMap::new();
+            for (col_index, value) in changes {
+                let table = column_table(&tab.table_contexts, *col_index)
+                    .map(|(table, _)| table)
+                    .ok_or("Edited column is not read from a table")?;
+                by_table.entry(table).or_default().push((*col_index, value.clone()));
+            }
+            for (table, changes) in by_table {
+                let update = build_row_update(tab, &row, changes)?;
+                statements.push(PendingStatement {
+                    change: RowChange::Update(*row_index),
+                    sql: format!("{} RETURNING *", update.sql),
+                    params: update.params,
+                    result_columns: tab.table_contexts[table].result_columns.clone(),
+                });
+            }
+        }
+        let row_columns = tab
+            .row_table()
+            .map(|context| context.result_columns.clone())
+            .unwrap_or_default();
+        for &row_index in state.draft_rows() {
+            let changes = edited
+                .iter()
+                .find(|(r, _)| *r == row_index)
+                .map(|(_, changes)| changes.clone())
+                .unwrap_or_default();
+            let (sql, params) = build_row_insert(tab, changes)?;
+            statements.push(PendingStatement {
+                change: RowChange::Insert(row_index),
+                sql,
+                params,
+                result_columns: row_columns.clone(),
+            });
+        }
+        for &row_index in state.deleted_rows() {
+            let row = state.original_row(row_index).ok_or("Deleted row no longer exists")?;
+            let (sql, params) = build_row_delete(tab, &row)?;
+            statements.push(PendingStatement {
+                change: RowChange::Delete(row_index),
+                sql,
+                params,
+                result_columns: row_columns.clone(),
+            });
+        }
+        Ok(statements)
+    }
+
+    fn open_changes_review(&mut self, cx: &mut Context<Self>) {
+        let Some(tab_id) = self.active_tab_id else {
+            return;
+        };
+        self.changes_review = Some(ChangesReviewState {
+            tab_id,
+            is_applying: false,
+            error: None,
+        });
+        cx.notify();
+    }
+
+    fn close_changes_review(&mut self, cx: &mut Context<Self>) {
+        if self.changes_review.as_ref().is_some_and(|r| r.is_applying) {
+            return;
+        }
+        self.changes_review = None;
+        cx.notify();
+    }
+
+    fn discard_pending_changes(&mut self, tab_id: TabId, cx: &mut Context<Self>) {
+        if self.changes_review.as_ref().is_some_and(|r| r.is_applying) {
+            return;
+        }
+        if let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) {
+            tab.table_state.update(cx, |state, cx| {
+                state.discard_pending_changes(cx);
+            });
+        }
+        self.changes_review = None;
+        cx.notify();
+    }
+
+    /// Writes every pending change in one transaction, or in the tab's own
+    /// when manual transactions are on. Nothing is kept unless all of them
+    /// succeed and each touches exactly one row.
+    fn apply_pending_changes(&mut self, cx: &mut Context<Self>) {
+        let Some(review) = self.changes_review.as_ref() else {
+            return;
+        };
+        if review.is_applying {
+            return;
+        }
+        let tab_id = review.tab_id;
+        let statements = self.pending_statements(tab_id, cx);
+        let Some((table_state, connection_id, session_id)) = self
+            .tabs
+            .iter()
+            .find(|t| t.id == tab_id)
+            .and_then(|t| {
+                let session_id = t.transaction.as_ref().map(|transaction| transaction.session_id.clone());
+                Some((t.table_state.clone(), t.connection_id.clone()?, session_id))
+            })
+        else {
+            return;
+        };
+        let Some(review) = self.changes_review.as_mut() else {
+            return;
+        };
+
+        let statements = match statements {
+            Ok(statements) if statements.is_empty() => {
+                self.changes_review = None;
+                cx.notify();
+                return;
+            }
+            Ok(statements) => statements,
+            Err(e) => {
+                review.error = Some(e);
+                cx.notify();
+                return;
+            }
+        };
+        review.is_applying = true;
+        review.error = None;
+        cx.notify();
+
+        let (changes, batch): (Vec<_>, Vec<_>) = statements
+            .into_iter()
+            .map(|s| ((s.change, s.result_columns), (s.sql, s.params)))
+            .unzip();
+        let rx = self.db_manager.execute_single_row_batch(&connection_id, batch, session_id);
+
+        cx.spawn(async move |this, cx| {
+            let result = rx.await;
+            let _ = this.update(cx, |this, cx| {
+                match result {
+                    Ok(Ok(returned)) => {
+                        // Show what the server wrote, such as generated ids
+                        // and trigger-set columns, in the grid columns that
+                        // read them.
+                        let written = changes
+                            .iter()
+                            .zip(&returned)
+                            .filter_map(|((change, result_columns), result)| {
+                                let row_index = match change {
+                                    RowChange::Update(row_index) | RowChange::Insert(row_index) => *row_index,
+                                    RowChange::Delete(_) => return None,
+                                };
+                                let row = result.rows.first()?;
+                                let cells = result_columns
+                                    .iter()
+                                    .enumerate()
+                                    .filter_map(|(col_index, name)| {
+                                        let name = name.as_ref()?;
+                                        let i = result.columns.iter().position(|c| &c.name == name)?;
+                                        Some((col_index, row.get(i)?.clone()))
+                                    })
+                                    .collect();
+                                Some((row_index, cells))
+                            })
+                            .collect();
+                        table_state.update(cx, |state, cx| {
+                            state.commit_pending_changes(written, cx);
+                        });
+                        this.note_grid_edit(tab_id);
+                        this.changes_review = None;
+                    }
+                    Ok(Err(e)) => {
+                        if let Some(review) = this.changes_review.as_mut() {
+                            review.error = Some(format!("{}. Nothing was saved.", e));
+                            review.is_applying = false;
+                        }
+                    }
+                    Err(_) => {
+                        if let Some(review) = this.changes_review.as_mut() {
+                            review.error = Some("Apply failed".to_string());
+                            review.is_applying = false;
+                        }
+                    }
+                }
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Row and buffered-edit controls for the results header, plus the
+    /// pending count, Review and Discard once there are changes.
+    pub(crate) fn render_edit_mode_controls(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+        let text_muted = colors.text_muted;
+        let element_hover = colors.element_hover;
+        let status_warning = colors.status_warning;
+
+        let Some(tab) = self
+            .active_tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id))
+        else {
+            return div();
+        };
+        if !tab.table_contexts.iter().any(|c| c.is_editable()) {
+            return div();
+        }
+        let can_add_rows = tab.row_table().is_some_and(|c| c.is_editable());
+        let tab_id = tab.id;
+        let state = tab.table_state.read(cx);
+        let buffers_edits = state.buffers_edits();
+        let pending_count = state.pending_change_count();
+        let has_selection = !state.selected_rows().is_empty();
+
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .when(can_add_rows, |el| {
+                el.child(
+                    div()
+                        .id("add-row-btn")
+                        .h(px(24.))
+                        .px_2()
+                        .flex()
+                        .items_center()
+                        .gap_1()
+                        .rounded_md()
+                        .cursor_pointer()
+                        .hover(move |s| s.bg(rgb(element_hover)))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.add_draft_row(cx);
+                        }))
+                        .child(icon_sm("plus", text_muted))
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(text_muted))
+                                .child("Add row"),
+                        ),
+                )
+            })
+            .when(can_add_rows && has_selection, |el| {
+                el.child(
+                    div()
+                        .id("delete-rows-btn")
+                        .h(px(24.))
+                        .px_2()
+                        .flex()
+                        .items_center()
+                        .gap_1()
+                        .rounded_md()
+                        .cursor_pointer()
+                        .hover(move |s| s.bg(rgb(element_hover)))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.delete_selected_rows(cx);
+                        }))
+                        .child(icon_sm("trash-2", text_muted))
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(text_muted))
+                                .child("Delete selected"),
+                        ),
+                )
+            })
+            .when(pending_count == 0, |el| {
+                el.child(
+                    div()
+                        .id("buffer-edits-btn")
+                        .h(px(24.))
+                        .px_2()
+                        .flex()
+                        .items_center()
+                        .gap_1()
+                        .rounded_md()
+                        .cursor_pointer()
+                        .hover(move |s| s.bg(rgb(element_hover)))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.toggle_buffer_edits(cx);
+                        }))
+                        .child(icon_sm(
+                            if buffers_edits { "square-check" } else { "square" },
+                            text_muted,
+                        ))
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(text_muted))
+                                .child("Buffer edits"),
+                        ),
+                )
+            })
+            .when(pending_count > 0, |el| {
+                el.child(
+                    div()
+                        .text_xs()
+                        .text_color(rgb(status_warning))
+                        .child(if pending_count == 1 {
+                            "1 pending change".to_string()
+                        } else {
+                            format!("{} pending changes", pending_count)
+                        }),
+                )
+                .child(
+                    div()
+                        .id("review-changes-btn")
+                        .h(px(24.))
+                        .px_2()
+                        .flex()
+                        .items_center()
+                        .gap_1()
+                        .rounded_md()
+                        .cursor_pointer()
+                        .hover(move |s| s.bg(rgb(element_hover)))
+                        .on_click(cx.listener(|this, _, _, cx| {
+                            this.open_changes_review(cx);
+                        }))
+                        .child(icon_sm("eye", text_muted))
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(text_muted))
+                                .child("Review"),
+                        ),
+                )
+                .child(
+                    div()
+                        .id("discard-changes-btn")
+                        .h(px(24.))
+                        .px_2()
+                        .flex()
+                        .items_center()
+                        .gap_1()
+                        .rounded_md()
+                        .cursor_pointer()
+                        .hover(move |s| s.bg(rgb(element_hover)))
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            this.discard_pending_changes(tab_id, cx);
+                        }))
+                        .child(icon_sm("undo", text_muted))
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(text_muted))
+                                .child("Discard"),
+                        ),
+                )
+            })
+    }
+
+    pub(crate) fn render_changes_review(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+        let text = colors.text;
+        let text_muted = colors.text_muted;
+        let surface = colors.surface;
+        let border = colors.border;
+        let border_variant = colors.border_variant;
+        let element = colors.element;
+        let element_hover = colors.element_hover;
+        let accent = colors.accent;
+        let accent_foreground = colors.accent_foreground;
+        let status_error = colors.status_error;
+
+        let Some(review) = &self.changes_review else {
+            return div().into_any_element();
+        };
+        let tab_id = review.tab_id;
+        let is_applying = review.is_applying;
+        let (statements, build_error) = match self.pending_statements(tab_id, cx) {
+            Ok(statements) => (
+                statements
+                    .iter()
+                    .map(|s| inline_parameters(&s.sql, &s.params))
+                    .collect::<Vec<_>>(),
+                None,
+            ),
+            Err(e) => (vec![], Some(e)),
+        };
+        let error = review.error.clone().or(build_error);
+        let note = if self.tabs.iter().any(|t| t.id == tab_id && t.transaction.is_some()) {
+            "These statements join the tab's transaction and are kept once it commits. If any fails, none are kept."
+        } else {
+            "These statements run in one transaction. If any fails, none are kept."
+        };
+
+        div()
+            .id("changes-review-overlay")
+            .absolute()
+            .inset_0()
+            .flex()
+            .items_center()
+            .justify_center()
+            .bg(rgba(0x00000080))
+            .on_mouse_down(MouseButton::Left, |_, _, _| {})
+            .child(
+                div()
+                    .w(px(640.0))
+                    .bg(rgb(surface))
+                    .border_1()
+                    .border_color(rgb(border))
+                    .rounded_lg()
+                    .shadow_lg()
+                    .p_4()
+                    .flex()
+                    .flex_col()
+                    .gap_4()
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .gap_2()
+                            .child(icon_sm("pencil", text_muted))
+                            .child(
+                                div()
+                                    .text_lg()
+                                    .font_weight(FontWeight::SEMIBOLD)
+                                    .text_color(rgb(text))
+                                    .child("Review Changes"),
+                            ),
+                    )
+                    .child(
+                        div()
+                            .text_sm()
+                            .text_color(rgb(text_muted))
+                            .child(note),
+                    )
+                    .child(
+                        div()
+                            .id("pending-statements")
+                            .max_h(px(360.))
+                            .overflow_y_scroll()
+                            .p_2()
+                            .rounded_md()
+                            .border_1()
+                            .border_color(rgb(border_variant))
+                            .flex()
+                            .flex_col()
+                            .gap_2()
+                            .children(statements.into_iter().enumerate().map(|(index, sql)| {
+                                div()
+                                    .flex()
+                                    .gap_2()
+                                    .text_xs()
+                                    .font_family("monospace")
+                                    .child(
+                                        div()
+                                            .flex_shrink_0()
+                                            .text_color(rgb(text_muted))
+                                            .child(format!("{}.", index + 1)),
+                                    )
+                                    .child(div().text_color(rgb(text)).child(sql))
+                            })),
+                    )
+                    .when_some(error, |el, error| {
+                        el.child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(status_error))
+                                .child(error),
+                        )
+                    })
+                    .child(
+                        div()
+                            .flex()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .id("discard-all-changes-btn")
+                                    .px_3()
+                                    .py_1p5()
+                                    .rounded_md()
+                                    .text_sm()
+                                    .text_color(rgb(text_muted))
+                                    .cursor_pointer()
+                                    .hover(move |s| s.bg(rgb(element_hover)))
+                                    .on_click(cx.listener(move |this, _, _, cx| {
+                                        this.discard_pending_changes(tab_id, cx);
+                                    }))
+                                    .child("Discard All"),
+                            )
+                            .child(div().flex_1())
+                            .child(
+                                div()
+                                    .id("close-review-btn")
+                                    .px_3()
+                                    .py_1p5()
+                                    .rounded_md()
+                                    .bg(rgb(element))
+                                    .text_sm()
+                                    .text_color(rgb(text))
+                                    .cursor_pointer()
+                                    .hover(move |s| s.bg(rgb(element_hover)))
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.close_changes_review(cx);
+                                    }))
+                                    .child("Close"),
+                            )
+                            .child(
+                                div()
+                                    .id("apply-changes-btn")
+                                    .px_3()
+                                    .py_1p5()
+                                    .rounded_md()
+                                    .bg(rgb(accent))
+                                    .text_sm()
+                                    .text_color(rgb(accent_foreground))
+                                    .cursor_pointer()
+                                    .when(!is_applying, |el| el.hover(|s| s.opacity(0.9)))
+                                    .when(is_applying, |el| el.opacity(0.6))
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.apply_pending_changes(cx);
+                                    }))
+                                    .child(if is_applying { "Applying..." } else { "Apply" }),
+                            ),
+                    ),
+            )
+            .into_any_element()
+    }
+}
@@ -0,0 +1,901 @@
+use crate::postcommander::database::CellValue;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use gpui::SharedString;
+use sqlx::postgres::{PgTypeInfo, PgTypeKind, PgValueFormat, PgValueRef};
+use sqlx::{TypeInfo, ValueRef};
+use std::fmt::Write;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+mod oid {
+    pub const BOOL: u32 = 16;
+    pub const BYTEA: u32 = 17;
+    pub const CHAR: u32 = 18;
+    pub const NAME: u32 = 19;
+    pub const INT8: u32 = 20;
+    pub const INT2: u32 = 21;
+    pub const INT2VECTOR: u32 = 22;
+    pub const INT4: u32 = 23;
+    pub const REGPROC: u32 = 24;
+    pub const TEXT: u32 = 25;
+    pub const OID: u32 = 26;
+    pub const TID: u32 = 27;
+    pub const XID: u32 = 28;
+    pub const CID: u32 = 29;
+    pub const OIDVECTOR: u32 = 30;
+    pub const JSON: u32 = 114;
+    pub const XML: u32 = 142;
+    pub const POINT: u32 = 600;
+    pub const LSEG: u32 = 601;
+    pub const PATH: u32 = 602;
+    pub const BOX: u32 = 603;
+    pub const POLYGON: u32 = 604;
+    pub const LINE: u32 = 628;
+    pub const CIDR: u32 = 650;
+    pub const FLOAT4: u32 = 700;
+    pub const FLOAT8: u32 = 701;
+    pub const UNKNOWN: u32 = 705;
+    pub const CIRCLE: u32 = 718;
+    pub const MACADDR8: u32 = 774;
+    pub const MONEY: u32 = 790;
+    pub const MACADDR: u32 = 829;
+    pub const INET: u32 = 869;
+    pub const BPCHAR: u32 = 1042;
+    pub const VARCHAR: u32 = 1043;
+    pub const DATE: u32 = 1082;
+    pub const TIME: u32 = 1083;
+    pub const TIMESTAMP: u32 = 1114;
+    pub const TIMESTAMPTZ: u32 = 1184;
+    pub const INTERVAL: u32 = 1186;
+    pub const TIMETZ: u32 = 1266;
+    pub const BIT: u32 = 1560;
+    pub const VARBIT: u32 = 1562;
+    pub const NUMERIC: u32 = 1700;
+    pub const REFCURSOR: u32 = 1790;
+    pub const REGPROCEDURE: u32 = 2202;
+    pub const REGOPER: u32 = 2203;
+    pub const REGOPERATOR: u32 = 2204;
+    pub const REGCLASS: u32 = 2205;
+    pub const REGTYPE: u32 = 2206;
+    pub const RECORD: u32 = 2249;
+    pub const CSTRING: u32 = 2275;
+    pub const VOID: u32 = 2278;
+    pub const UUID: u32 = 2950;
+    pub const PG_LSN: u32 = 3220;
+    pub const TSVECTOR: u32 = 3614;
+    pub const REGCONFIG: u32 = 3734;
+    pub const REGDICTIONARY: u32 = 3769;
+    pub const JSONB: u32 = 3802;
+    pub const INT4RANGE: u32 = 3904;
+    pub const NUMRANGE: u32 = 3906;
+    pub const TSRANGE: u32 = 3908;
+    pub const TSTZRANGE: u32 = 3910;
+    pub const DATERANGE: u32 = 3912;
+    pub const INT8RANGE: u32 = 3926;
+    pub const JSONPATH: u32 = 4072;
+    pub const REGNAMESPACE: u32 = 4089;
+    pub const REGROLE: u32 = 4096;
+    pub const REGCOLLATION: u32 = 4191;
+    pub const INT4MULTIRANGE: u32 = 4451;
+    pub const NUMMULTIRANGE: u32 = 4532;
+    pub const TSMULTIRANGE: u32 = 4533;
+    pub const TSTZMULTIRANGE: u32 = 4534;
+    pub const DATEMULTIRANGE: u32 = 4535;
+    pub const INT8MULTIRANGE: u32 = 4536;
+    pub const XID8: u32 = 5069;
+}
+
+/// Turns a raw column value into a `CellValue`. Built-in types are decoded
+/// from their wire format; anything unrecognised falls back to its text (or,
+/// failing that, its raw bytes) so a non-NULL value never reads as NULL.
+pub(crate) fn decode_value(value: PgValueRef<'_>) -> CellValue {
+    if value.is_null() {
+        return CellValue::Null;
+    }
+
+    let type_info = value.type_info().into_owned();
+    let format = value.format();
+    let Ok(bytes) = value.as_bytes() else {
+        return CellValue::Null;
+    };
+
+    match format {
+        PgValueFormat::Binary => decode_binary_typed(&type_info, bytes),
+        PgValueFormat::Text => decode_text(&type_info, bytes),
+    }
+}
+
+fn decode_binary_typed(type_info: &PgTypeInfo, bytes: &[u8]) -> CellValue {
+    let decoded = match type_info.kind() {
+        PgTypeKind::Domain(base) => return decode_binary_typed(base, bytes),
+        PgTypeKind::Enum(_) => None,
+        PgTypeKind::Array(_) => decode_array(bytes),
+        PgTypeKind::Composite(_) => decode_record(bytes),
+        PgTypeKind::Range(subtype) => subtype
+            .oid()
+            .and_then(|sub| decode_range(sub.0, bytes))
+            .map(text_value),
+        PgTypeKind::Simple | PgTypeKind::Pseudo => match type_info.oid() {
+            Some(type_oid) if is_builtin(type_oid.0) => decode_binary(type_oid.0, bytes),
+            _ => decode_extension(type_info.name(), bytes),
+        },
+    };
+
+    decoded.unwrap_or_else(|| fallback_value(bytes))
+}
+
+/// The cast that names the object behind a value of a reg* type, or an
+/// array of one: `regclass` turns 1259 into `pg_class`. The wire format
+/// only carries the OID, which is what `decode_value` returns.
+pub(crate) fn reg_type_cast(type_info: &PgTypeInfo) -> Option<&'static str> {
+    let type_info = match type_info.kind() {
+        PgTypeKind::Array(element) => element,
+        _ => type_info,
+    };
+    Some(match type_info.oid()?.0 {
+        oid::REGPROC => "regproc",
+        oid::REGPROCEDURE => "regprocedure",
+        oid::REGOPER => "regoper",
+        oid::REGOPERATOR => "regoperator",
+        oid::REGCLASS => "regclass",
+        oid::REGTYPE => "regtype",
+        oid::REGCONFIG => "regconfig",
+        oid::REGDICTIONARY => "regdictionary",
+        oid::REGNAMESPACE => "regnamespace",
+        oid::REGROLE => "regrole",
+        oid::REGCOLLATION => "regcollation",
+        _ => return None,
+    })
+}
+
+fn is_builtin(type_oid: u32) -> bool {
+    // OIDs below FirstNormalObjectId are assigned by initdb.
+    type_oid < 16384
+}
+
+/// Decodes a binary value of a built-in type. Returns `None` for types that
+/// have no dedicated decoder or for payloads that don't match the type.
+fn decode_binary(type_oid: u32, bytes: &[u8]) -> Option<CellValue> {
+    let mut r = Reader::new(bytes);
+    let value = match type_oid {
+        oid::BOOL => CellValue::Bool(r.u8()? != 0),
+        oid::INT2 => CellValue::Int(r.i16()? as i64),
+        oid::INT4 => CellValue::Int(r.i32()? as i64),
+        oid::INT8 => CellValue::Int(r.i64()?),
+        // The reg* types arrive as OIDs; see `reg_type_cast` for their names.
+        oid::OID | oid::XID | oid::CID | oid::REGPROC | oid::REGPROCEDURE | oid::REGOPER
+        | oid::REGOPERATOR | oid::REGCLASS | oid::REGTYPE | oid::REGCONFIG
+        | oid::REGDICTIONARY | oid::REGNAMESPACE | oid::REGROLE | oid::REGCOLLATION => {
+            CellValue::Int(r.u32()? as i64)
+        }
+        oid::XID8 => {
+            let v = r.u64()?;
+            i64::try_from(v)
+                .map(CellValue::Int)
+                .unwrap_or_else(|_| CellValue::Numeric(SharedString::from(v.to_string())))
+        }
+        // Widening would show float4's binary error (3.140000104904175), so
+        // go through its shortest decimal form, as Postgres prints it.
+        oid::FLOAT4 => CellValue::Float(r.f32()?.to_string().parse().ok()?),
+        oid::FLOAT8 => CellValue::Float(r.f64()?),
+        oid::NUMERIC => CellValue::Numeric(SharedString::from(decode_numeric(bytes)?)),
+        oid::MONEY => CellValue::Numeric(SharedString::from(format_money(r.i64()?))),
+        oid::TEXT | oid::VARCHAR | oid::BPCHAR | oid::NAME | oid::CHAR | oid::XML
+        | oid::UNKNOWN | oid::CSTRING | oid::REFCURSOR => {
+            text_value(std::str::from_utf8(bytes).ok()?.to_string())
+        }
+        oid::VOID => text_value(String::new()),
+        oid::BYTEA => CellValue::Bytes(Arc::new(bytes.to_vec())),
+        oid::UUID => CellValue::Uuid(uuid::Uuid::from_slice(bytes).ok()?),
+        oid::JSON => decode_json(std::str::from_utf8(bytes).ok()?),
+        oid::JSONB => {
+            if r.u8()? != 1 {
+                return None;
+            }
+            decode_json(std::str::from_utf8(r.rest()).ok()?)
+        }
+        oid::JSONPATH => {
+            if r.u8()? != 1 {
+                return None;
+            }
+            text_value(std::str::from_utf8(r.rest()).ok()?.to_string())
+        }
+        oid::DATE => match r.i32()? {
+            i32::MAX => text_value("infinity".to_string()),
+            i32::MIN => text_value("-infinity".to_string()),
+            days => CellValue::Date(pg_epoch().date().checked_add_signed(Duration::days(days as i64))?),
+        },
+        oid::TIME => {
+            let micros = r.i64()?;
+            time_from_micros(micros)
+                .map(CellValue::Time)
+                .unwrap_or_else(|| text_value(format_time_micros(micros)))
+        }
+        oid::TIMETZ => {
+            let micros = r.i64()?;
+            // The zone is sent as seconds west of UTC.
+            let offset = -r.i32()?;
+            text_value(format!("{}{}", format_time_micros(micros), format_utc_offset(offset)))
+        }
+        oid::TIMESTAMP => match r.i64()? {
+            i64::MAX => text_value("infinity".to_string()),
+            i64::MIN => text_value("-infinity".to_string()),
+            micros => CellValue::Timestamp(timestamp_from_micros(micros)?),
+        },
+        oid::TIMESTAMPTZ => match r.i64()? {
+            i64::MAX => text_value("infinity".to_string()),
+            i64::MIN => text_value("-infinity".to_string()),
+            micros => CellValue::TimestampTz(Utc.from_utc_datetime(&timestamp_from_micros(micros)?)),
+        },
+        oid::INTERVAL => {
+            let micros = r.i64()?;
+            let days = r.i32()?;
+            let months = r.i32()?;
+            text_value(format_interval(months, days, micros))
+        }
+        oid::INET | oid::CIDR => text_value(decode_inet(bytes)?),
+        oid::MACADDR => text_value(format_mac(r.take(6)?)),
+        oid::MACADDR8 => text_value(format_mac(r.take(8)?)),
+        oid::BIT | oid::VARBIT => {
+            let len = usize::try_from(r.i32()?).ok()?;
+            let data = r.rest();
+            let bits: String = (0..len)
+                .map(|i| {
+                    let byte = data.get(i / 8).copied().unwrap_or(0);
+                    if byte & (0x80 >> (i % 8)) != 0 { '1' } else { '0' }
+                })
+                .collect();
+            text_value(bits)
+        }
+        oid::TID => {
+            let block = r.u32()?;
+            let offset = r.u16()?;
+            text_value(format!("({},{})", block, offset))
+        }
+        oid::PG_LSN => {
+            let lsn = r.u64()?;
+            text_value(format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF))
+        }
+        oid::POINT => text_value(format_point(r.f64()?, r.f64()?)),
+        oid::LSEG => {
+            let (a, b) = (read_point(&mut r)?, read_point(&mut r)?);
+            text_value(format!("[{},{}]", a, b))
+        }
+        oid::BOX => {
+            let (a, b) = (read_point(&mut r)?, read_point(&mut r)?);
+            text_value(format!("{},{}", a, b))
+        }
+        oid::PATH => {
+            let closed = r.u8()? != 0;
+            let points = read_points(&mut r)?;
+            if closed {
+                text_value(format!("({})", points))
+            } else {
+                text_value(format!("[{}]", points))
+            }
+        }
+        oid::POLYGON => text_value(format!("({})", read_points(&mut r)?)),
+        oid::LINE => text_value(format!("{{{},{},{}}}", r.f64()?, r.f64()?, r.f64()?)),
+        oid::CIRCLE => {
+            let center = read_point(&mut r)?;
+            text_value(format!("<{},{}>", center, r.f64()?))
+        }
+        oid::TSVECTOR => text_value(decode_tsvector(bytes)?),
+        oid::INT2VECTOR | oid::OIDVECTOR => return decode_array(bytes),
+        oid::RECORD => return decode_record(bytes),
+        oid::INT4RANGE | oid::INT8RANGE | oid::NUMRANGE | oid::TSRANGE | oid::TSTZRANGE
+        | oid::DATERANGE => text_value(decode_range(range_subtype(type_oid)?, bytes)?),
+        oid::INT4MULTIRANGE | oid::INT8MULTIRANGE | oid::NUMMULTIRANGE | oid::TSMULTIRANGE
+        | oid::TSTZMULTIRANGE | oid::DATEMULTIRANGE => {
+            let subtype = range_subtype(type_oid)?;
+            let count = r.i32()?;
+            let mut ranges = Vec::new();
+            for _ in 0..count {
+                let len = usize::try_from(r.i32()?).ok()?;
+                ranges.push(decode_range(subtype, r.take(len)?)?);
+            }
+            text_value(format!("{{{}}}", ranges.join(",")))
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Decoders for common extension types, which have no fixed OID.
+fn decode_extension(type_name: &str, bytes: &[u8]) -> Option<CellValue> {
+    let mut r = Reader::new(bytes);
+    match type_name.to_lowercase().as_str() {
+        "citext" => Some(text_value(std::str::from_utf8(bytes).ok()?.to_string())),
+        "ltree" | "lquery" | "ltxtquery" => {
+            if r.u8()? != 1 {
+                return None;
+            }
+            Some(text_value(std::str::from_utf8(r.rest()).ok()?.to_string()))
+        }
+        "hstore" => {
+            let count = r.i32()?;
+            let mut pairs = Vec::new();
+            for _ in 0..count {
+                let key = read_optional_text(&mut r)?.unwrap_or_default();
+                let value = match read_optional_text(&mut r)? {
+                    Some(v) => format!("\"{}\"", escape_quoted(&v)),
+                    None => "NULL".to_string(),
+                };
+                pairs.push(format!("\"{}\"=>{}", escape_quoted(&key), value));
+            }
+            Some(text_value(pairs.join(", ")))
+        }
+        "vector" => {
+            let dim = r.u16()?;
+            r.u16()?;
+            let values: Option<Vec<String>> = (0..dim).map(|_| r.f32().map(|v| v.to_string())).collect();
+            Some(text_value(format!("[{}]", values?.join(","))))
+        }
+        "geometry" | "geography" => {
+            Some(text_value(bytes.iter().map(|b| format!("{:02X}", b)).collect()))
+        }
+        _ => None,
+    }
+}
+
+fn decode_text(type_info: &PgTypeInfo, bytes: &[u8]) -> CellValue {
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return CellValue::Bytes(Arc::new(bytes.to_vec()));
+    };
+
+    let parsed = match type_info.name() {
+        "BOOL" => match text {
+            "t" => Some(CellValue::Bool(true)),
+            "f" => Some(CellValue::Bool(false)),
+            _ => None,
+        },
+        "INT2" | "INT4" | "INT8" | "OID" => text.parse::<i64>().ok().map(CellValue::Int),
+        "FLOAT4" | "FLOAT8" => text.parse::<f64>().ok().map(CellValue::Float),
+        "NUMERIC" => Some(CellValue::Numeric(SharedString::from(text.to_string()))),
+        "UUID" => uuid::Uuid::parse_str(text).ok().map(CellValue::Uuid),
+        "JSON" | "JSONB" => Some(decode_json(text)),
+        _ => None,
+    };
+
+    parsed.unwrap_or_else(|| text_value(text.to_string()))
+}
+
+/// Shows undecodable values as text when they are valid UTF-8 (enums,
+/// text-like extension types) and as raw bytes otherwise.
+fn fallback_value(bytes: &[u8]) -> CellValue {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) => {
+            text_value(text.to_string())
+        }
+        _ => CellValue::Bytes(Arc::new(bytes.to_vec())),
+    }
+}
+
+fn text_value(text: String) -> CellValue {
+    CellValue::Text(SharedString::from(text))
+}
+
+fn decode_json(text: &str) -> CellValue {
+    serde_json::from_str(text)
+        .map(|v| CellValue::Json(Arc::new(v)))
+        .unwrap_or_else(|_| text_value(text.to_string()))
+}
+
+fn decode_numeric(bytes: &[u8]) -> Option<String> {
+    const POSITIVE: u16 = 0x0000;
+    const NEGATIVE: u16 = 0x4000;
+    const NAN: u16 = 0xC000;
+    const POS_INFINITY: u16 = 0xD000;
+    const NEG_INFINITY: u16 = 0xF000;
+
+    let mut r = Reader::new(bytes);
+    let ndigits = r.i16()?;
+    let weight = r.i16()? as i32;
+    let sign = r.u16()?;
+    let dscale = r.u16()? as usize;
+
+    match sign {
+        NAN => return Some("NaN".to_string()),
+        POS_INFINITY => return Some("Infinity".to_string()),
+        NEG_INFINITY => return Some("-Infinity".to_string()),
+        POSITIVE | NEGATIVE => {}
+        _ => return None,
+    }
+
+    let digits: Vec<i16> = (0..ndigits).map(|_| r.i16()).collect::<Option<_>>()?;
+    // Each digit is a base-10000 group; `weight` is the position of the
+    // first group relative to the decimal point.
+    let group = |index: i32| -> i16 {
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| digits.get(i).copied())
+            .unwrap_or(0)
+    };
+
+    let mut out = String::new();
+    if sign == NEGATIVE {
+        out.push('-');
+    }
+
+    if weight < 0 {
+        out.push('0');
+    } else {
+        for i in 0..=weight {
+            if i == 0 {
+                write!(out, "{}", group(i)).ok()?;
+            } else {
+                write!(out, "{:04}", group(i)).ok()?;
+            }
+        }
+    }
+
+    if dscale > 0 {
+        let mut fraction = String::new();
+        let mut i = weight + 1;
+        while fraction.len() < dscale {
+            write!(fraction, "{:04}", group(i)).ok()?;
+            i += 1;
+        }
+        fraction.truncate(dscale);
+        out.push('.');
+        out.push_str(&fraction);
+    }
+
+    Some(out)
+}
+
+fn format_money(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let abs = cents.unsigned_abs();
+    format!("{}{}.{:02}", sign, abs / 100, abs % 100)
+}
+
+fn pg_epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2000, 1, 1)
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .unwrap_or_default()
+}
+
+fn timestamp_from_micros(micros: i64) -> Option<NaiveDateTime> {
+    pg_epoch().checked_add_signed(Duration::microseconds(micros))
+}
+
+fn time_from_micros(micros: i64) -> Option<NaiveTime> {
+    let secs = u32::try_from(micros.div_euclid(1_000_000)).ok()?;
+    let nanos = u32::try_from(micros.rem_euclid(1_000_000) * 1000).ok()?;
+    NaiveTime::from_num_seconds_from_midnight_opt(secs, nanos)
+}
+
+/// Formats a time of day like Postgres, which also allows `24:00:00`.
+fn format_time_micros(micros: i64) -> String {
+    let negative = micros < 0;
+    let micros = micros.unsigned_abs();
+    let secs = micros / 1_000_000;
+    let fraction = micros % 1_000_000;
+    let mut out = format!(
+        "{}{:02}:{:02}:{:02}",
+        if negative { "-" } else { "" },
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    );
+    if fraction > 0 {
+        let digits = format!("{:06}", fraction);
+        out.push('.');
+        out.push_str(digits.trim_end_matches('0'));
+    }
+    out
+}
+
+fn format_utc_offset(offset_secs: i32) -> String {
+    let sign = if offset_secs < 0 { '-' } else { '+' };
+    let abs = offset_secs.unsigned_abs();
+    let (hours, minutes, seconds) = (abs / 3600, (abs / 60) % 60, abs % 60);
+    match (minutes, seconds) {
+        (0, 0) => format!("{}{:02}", sign, hours),
+        (_, 0) => format!("{}{:02}:{:02}", sign, hours, minutes),
+        _ => format!("{}{:02}:{:02}:{:02}", sign, hours, minutes, seconds),
+    }
+}
+
+fn format_interval(months: i32, days: i32, micros: i64) -> String {
+    let mut parts = Vec::new();
+    let (years, months) = (months / 12, months % 12);
+    let mut unit = |value: i64, singular: &str, plural: &str| {
+        if value != 0 {
+            let name = if value == 1 { singular } else { plural };
+            parts.push(format!("{} {}", value, name));
+        }
+    };
+    unit(years as i64, "year", "years");
+    unit(months as i64, "mon", "mons");
+    unit(days as i64, "day", "days");
+
+    if micros != 0 || parts.is_empty() {
+        parts.push(format_time_micros(micros));
+    }
+    parts.join(" ")
+}
+
+fn decode_inet(bytes: &[u8]) -> Option<String> {
+    const PGSQL_AF_INET: u8 = 2;
+    const PGSQL_AF_INET6: u8 = 3;
+
+    let mut r = Reader::new(bytes);
+    let family = r.u8()?;
+    let bits = r.u8()?;
+    let is_cidr = r.u8()? != 0;
+    let len = r.u8()? as usize;
+    let addr = r.take(len)?;
+
+    let (text, max_bits) = match family {
+        PGSQL_AF_INET => {
+            let octets: [u8; 4] = addr.try_into().ok()?;
+            (Ipv4Addr::from(octets).to_string(), 32)
+        }
+        PGSQL_AF_INET6 => {
+            let octets: [u8; 16] = addr.try_into().ok()?;
+            (Ipv6Addr::from(octets).to_string(), 128)
+        }
+        _ => return None,
+    };
+
+    if is_cidr || bits != max_bits {
+        Some(format!("{}/{}", text, bits))
+    } else {
+        Some(text)
+    }
+}
+
+fn format_mac(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+fn format_point(x: f64, y: f64) -> String {
+    format!("({},{})", x, y)
+}
+
+fn read_point(r: &mut Reader<'_>) -> Option<String> {
+    Some(format_point(r.f64()?, r.f64()?))
+}
+
+fn read_points(r: &mut Reader<'_>) -> Option<String> {
+    let count = r.i32()?;
+    let points: Option<Vec<String>> = (0..count).map(|_| read_point(r)).collect();
+    Some(points?.join(","))
+}
+
+fn decode_tsvector(bytes: &[u8]) -> Option<String> {
+    const WEIGHTS: [&str; 4] = ["", "C", "B", "A"];
+
+    let mut r = Reader::new(bytes);
+    let count = r.i32()?;
+    let mut lexemes = Vec::new();
+    for _ in 0..count {
+        let lexeme = r.cstring()?;
+        let npos = r.u16()?;
+        let mut entry = format!("'{}'", lexeme.replace('\'', "''"));
+        let positions: Option<Vec<String>> = (0..npos)
+            .map(|_| {
+                let pos = r.u16()?;
+                Some(format!("{}{}", pos & 0x3FFF, WEIGHTS[(pos >> 14) as usize]))
+            })
+            .collect();
+        let positions = positions?;
+        if !positions.is_empty() {
+            entry.push(':');
+            entry.push_str(&positions.join(","));
+        }
+        lexemes.push(entry);
+    }
+    Some(lexemes.join(" "))
+}
+
+fn range_subtype(range_oid: u32) -> Option<u32> {
+    match range_oid {
+        oid::INT4RANGE | oid::INT4MULTIRANGE => Some(oid::INT4),
+        oid::INT8RANGE | oid::INT8MULTIRANGE => Some(oid::INT8),
+        oid::NUMRANGE | oid::NUMMULTIRANGE => Some(oid::NUMERIC),
+        oid::TSRANGE | oid::TSMULTIRANGE => Some(oid::TIMESTAMP),
+        oid::TSTZRANGE | oid::TSTZMULTIRANGE => Some(oid::TIMESTAMPTZ),
+        oid::DATERANGE | oid::DATEMULTIRANGE => Some(oid::DATE),
+        _ => None,
+    }
+}
+
+fn decode_range(subtype: u32, bytes: &[u8]) -> Option<String> {
+    const EMPTY: u8 = 0x01;
+    const LB_INC: u8 = 0x02;
+    const UB_INC: u8 = 0x04;
+    const LB_INF: u8 = 0x08;
+    const UB_INF: u8 = 0x10;
+
+    let mut r = Reader::new(bytes);
+    let flags = r.u8()?;
+    if flags & EMPTY != 0 {
+        return Some("empty".to_string());
+    }
+
+    let mut bound = |infinite: bool| -> Option<String> {
+        if infinite {
+            return Some(String::new());
+        }
+        let len = usize::try_from(r.i32()?).ok()?;
+        let value = decode_element(subtype, r.take(len)?);
+        Some(quote_element(&value.display(), &['[', ']', '(', ')', ',']))
+    };
+    let lower = bound(flags & LB_INF != 0)?;
+    let upper = bound(flags & UB_INF != 0)?;
+
+    Some(format!(
+        "{}{},{}{}",
+        if flags & LB_INC != 0 { '[' } else { '(' },
+        lower,
+        upper,
+        if flags & UB_INC != 0 { ']' } else { ')' }
+    ))
+}
+
+fn decode_element(type_oid: u32, bytes: &[u8]) -> CellValue {
+    decode_binary(type_oid, bytes).unwrap_or_else(|| fallback_value(bytes))
+}
+
+/// Decodes a binary array, including multi-dimensional ones, into nested
+/// `CellValue::Array`s. The element type comes from the array header.
+fn decode_array(bytes: &[u8]) -> Option<CellValue> {
+    let mut r = Reader::new(bytes);
+    let ndim = usize::try_from(r.i32()?).ok()?;
+    let _has_nulls = r.i32()?;
+    let element_oid = r.u32()?;
+
+    let mut dims = Vec::with_capacity(ndim);
+    for _ in 0..ndim {
+        dims.push(usize::try_from(r.i32()?).ok()?);
+        let _lower_bound = r.i32()?;
+    }
+
+    if dims.is_empty() {
+        return Some(CellValue::Array(Arc::new(Vec::new())));
+    }
+
+    fn build(dims: &[usize], element_oid: u32, r: &mut Reader<'_>) -> Option<CellValue> {
+        let mut items = Vec::with_capacity(dims[0]);
+        for _ in 0..dims[0] {
+            if dims.len() > 1 {
+                items.push(build(&dims[1..], element_oid, r)?);
+            } else {
+                let len = r.i32()?;
+                if len < 0 {
+                    items.push(CellValue::Null);
+                } else {
+                    items.push(decode_element(element_oid, r.take(len as usize)?));
+                }
+            }
+        }
+        Some(CellValue::Array(Arc::new(items)))
+    }
+
+    build(&dims, element_oid, &mut r)
+}
+
+/// Decodes a binary composite value; each field carries its own type OID.
+fn decode_record(bytes: &[u8]) -> Option<CellValue> {
+    let mut r = Reader::new(bytes);
+    let count = r.i32()?;
+    let mut fields = Vec::new();
+    for _ in 0..count {
+        let field_oid = r.u32()?;
+        let len = r.i32()?;
+        if len < 0 {
+            fields.push(String::new());
+            continue;
+        }
+        let value = decode_element(field_oid, r.take(len as usize)?);
+        fields.push(quote_element(&value.display(), &['(', ')', ',']));
+    }
+    Some(text_value(format!("({})", fields.join(","))))
+}
+
+/// Double-quotes an element of a composite or range literal when it contains
+/// any of `specials`, whitespace, quotes or backslashes, or is empty.
+fn quote_element(text: &str, specials: &[char]) -> String {
+    let needs_quotes = text.is_empty()
+        || text
+            .chars()
+            .any(|c| c == '"' || c == '\\' || c.is_whitespace() || specials.contains(&c));
+    if needs_quotes {
+        format!("\"{}\"", escape_quoted(text))
+    } else {
+        text.to_string()
+    }
+}
+
+fn escape_quoted(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn read_optional_text(r: &mut Reader<'_>) -> Option<Option<String>> {
+    let len = r.i32()?;
+    if len < 0 {
+        return Some(None);
+    }
+    let bytes = r.take(len as usize)?;
+    Some(Some(std::str::from_utf8(bytes).ok()?.to_string()))
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < n {
+            return None;
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Some(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.buf)
+    }
+
+    fn cstring(&mut self) -> Option<String> {
+        let end = self.buf.iter().position(|&b| b == 0)?;
+        let text = std::str::from_utf8(&self.buf[..end]).ok()?.to_string();
+        self.buf = &self.buf[end + 1..];
+        Some(text)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.array().map(u16::from_be_bytes)
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        self.array().map(i16::from_be_bytes)
+    }
+
+    fn i32(&mut self) -> Option<i32> {
+        self.array().map(i32::from_be_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.array().map(u32::from_be_bytes)
+    }
+
+    fn i64(&mut self) -> Option<i64> {
+        self.array().map(i64::from_be_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.array().map(u64::from_be_bytes)
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        self.array().map(f32::from_be_bytes)
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        self.array().map(f64::from_be_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numeric(ndigits: i16, weight: i16, sign: u16, dscale: u16, digits: &[i16]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&ndigits.to_be_bytes());
+        bytes.extend_from_slice(&weight.to_be_bytes());
+        bytes.extend_from_slice(&sign.to_be_bytes());
+        bytes.extend_from_slice(&dscale.to_be_bytes());
+        for d in digits {
+            bytes.extend_from_slice(&d.to_be_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_numeric_keeps_full_precision() {
+        let bytes = numeric(5, 2, 0, 6, &[1234, 5678, 9012, 3456, 7800]);
+        assert_eq!(decode_numeric(&bytes).as_deref(), Some("123456789012.345678"));
+    }
+
+    #[test]
+    fn test_numeric_small_fraction_and_sign() {
+        let bytes = numeric(1, -2, 0x4000, 5, &[5000]);
+        assert_eq!(decode_numeric(&bytes).as_deref(), Some("-0.00005"));
+    }
+
+    #[test]
+    fn test_numeric_trailing_zero_groups() {
+        let bytes = numeric(1, 1, 0, 2, &[1]);
+        assert_eq!(decode_numeric(&bytes).as_deref(), Some("10000.00"));
+    }
+
+    #[test]
+    fn test_numeric_special_values() {
+        assert_eq!(decode_numeric(&numeric(0, 0, 0xC000, 0, &[])).as_deref(), Some("NaN"));
+        assert_eq!(decode_numeric(&numeric(0, 0, 0xF000, 0, &[])).as_deref(), Some("-Infinity"));
+    }
+
+    #[test]
+    fn test_interval_format() {
+        assert_eq!(format_interval(14, 3, 4 * 3_600_000_000 + 5_500_000), "1 year 2 mons 3 days 04:00:05.5");
+        assert_eq!(format_interval(0, 0, 0), "00:00:00");
+        assert_eq!(format_interval(0, -1, 0), "-1 days");
+    }
+
+    #[test]
+    fn test_inet_and_cidr() {
+        assert_eq!(decode_inet(&[2, 32, 0, 4, 192, 168, 0, 1]).as_deref(), Some("192.168.0.1"));
+        assert_eq!(decode_inet(&[2, 24, 1, 4, 10, 0, 0, 0]).as_deref(), Some("10.0.0.0/24"));
+    }
+
+    #[test]
+    fn test_two_dimensional_int_array_with_null() {
+        let mut bytes = Vec::new();
+        for v in [2i32, 1] {
+            bytes.extend_from_slice(&v.to_be_bytes());
+        }
+        bytes.extend_from_slice(&oid::INT4.to_be_bytes());
+        for v in [2i32, 1, 2, 1] {
+            bytes.extend_from_slice(&v.to_be_bytes());
+        }
+        for v in [Some(1i32), None, Some(3), Some(4)] {
+            match v {
+                Some(v) => {
+                    bytes.extend_from_slice(&4i32.to_be_bytes());
+                    bytes.extend_from_slice(&v.to_be_bytes());
+                }
+                None => bytes.extend_from_slice(&(-1i32).to_be_bytes()),
+            }
+        }
+
+        let value = decode_array(&bytes).expect("array should decode");
+        assert_eq!(value.display().as_ref(), "{{1,NULL},{3,4}}");
+    }
+
+    #[test]
+    fn test_range_bounds() {
+        let mut bytes = vec![0x02];
+        bytes.extend_from_slice(&4i32.to_be_bytes());
+        bytes.extend_from_slice(&1i32.to_be_bytes());
+        bytes.extend_from_slice(&4i32.to_be_bytes());
+        bytes.extend_from_slice(&10i32.to_be_bytes());
+        assert_eq!(decode_range(oid::INT4, &bytes).as_deref(), Some("[1,10)"));
+        assert_eq!(decode_range(oid::INT4, &[0x01]).as_deref(), Some("empty"));
+    }
+
+    #[test]
+    fn test_float4_keeps_its_precision() {
+        let value = decode_binary(oid::FLOAT4, &2.71f32.to_be_bytes()).unwrap();
+        assert_eq!(value.display().as_ref(), "2.71");
+    }
+
+    #[test]
+    fn test_time_end_of_day() {
+        let value = decode_binary(oid::TIME, &86_400_000_000i64.to_be_bytes()).unwrap();
+        assert_eq!(value.display().as_ref(), "24:00:00");
+    }
+
+    #[test]
+    fn test_unknown_binary_falls_back_to_text() {
+        assert_eq!(fallback_value(b"happy"), CellValue::Text(SharedString::from("happy")));
+        assert!(matches!(fallback_value(&[0, 159, 146, 150]), CellValue::Bytes(_)));
+    }
+}
@@ -0,0 +1,470 @@
+use crate::postcommander::database::{CellValue, QueryResult};
+use serde_json::Value;
+
+/// Share of the run (or of the total cost, without ANALYZE) at which a
+/// node counts as expensive.
+pub const EXPENSIVE_SHARE: f64 = 0.2;
+/// Factor between estimated and actual rows at which a node counts as
+/// misestimated.
+pub const MISESTIMATE_FACTOR: f64 = 10.0;
+
+/// A plan from `EXPLAIN (FORMAT JSON)`, with or without ANALYZE.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlan {
+    pub root: PlanNode,
+    pub planning_ms: Option<f64>,
+    pub execution_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanNode {
+    pub node_type: String,
+    /// Table, CTE or function the node reads.
+    pub relation: Option<String>,
+    /// As in the text plan: `Index Scan using users_pkey on users u`.
+    pub title: String,
+    /// Conditions and keys, one `Name: value` line each.
+    pub details: Vec<String>,
+    pub startup_cost: f64,
+    pub total_cost: f64,
+    /// Estimated rows per loop.
+    pub plan_rows: f64,
+    /// Actual rows per loop; set with ANALYZE.
+    pub actual_rows: Option<f64>,
+    pub loops: Option<f64>,
+    /// Time spent in this node and its children over all loops.
+    pub total_ms: Option<f64>,
+    /// Shared buffer blocks found in cache and read in, including children.
+    pub shared_hit: Option<u64>,
+    pub shared_read: Option<u64>,
+    pub children: Vec<PlanNode>,
+}
+
+/// Fields shown under a node, in this order.
+const DETAIL_KEYS: [&str; 10] = [
+    "Index Cond",
+    "Recheck Cond",
+    "Hash Cond",
+    "Merge Cond",
+    "Join Filter",
+    "Filter",
+    "Rows Removed by Filter",
+    "Sort Key",
+    "Group Key",
+    "Sort Method",
+];
+
+impl QueryPlan {
+    /// The plan in a result, when the result is the single `QUERY PLAN`
+    /// JSON cell that `EXPLAIN (FORMAT JSON)` returns.
+    pub fn from_result(result: &QueryResult) -> Option<Self> {
+        let [column] = result.columns.as_slice() else {
+            return None;
+        };
+        if column.name != "QUERY PLAN" {
+            return None;
+        }
+        match result.rows.as_slice() {
+            [row] => match row.first()? {
+                CellValue::Json(value) => Self::from_json(value),
+                CellValue::Text(text) => Self::from_json(&serde_json::from_str(text.as_ref()).ok()?),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        let explain = value.as_array()?.first()?;
+        Some(Self {
+            root: PlanNode::from_json(explain.get("Plan")?)?,
+            planning_ms: explain["Planning Time"].as_f64(),
+            execution_ms: explain["Execution Time"].as_f64(),
+        })
+    }
+
+    pub fn analyzed(&self) -> bool {
+        self.root.actual_rows.is_some()
+    }
+
+    /// Every node depth-first with its depth, in drawing order.
+    pub fn nodes(&self) -> Vec<(usize, &PlanNode)> {
+        fn walk<'a>(node: &'a PlanNode, depth: usize, out: &mut Vec<(usize, &'a PlanNode)>) {
+            out.push((depth, node));
+            for child in &node.children {
+                walk(child, depth + 1, out);
+            }
+        }
+        let mut nodes = Vec::new();
+        walk(&self.root, 0, &mut nodes);
+        nodes
+    }
+
+    /// Time for the whole statement; without ANALYZE there is none.
+    pub fn total_ms(&self) -> Option<f64> {
+        self.execution_ms.or(self.root.total_ms)
+    }
+
+    /// The node's own time as a share of the run.
+    pub fn time_share(&self, node: &PlanNode) -> Option<f64> {
+        let total = self.total_ms()?;
+        (total > 0.0).then(|| node.self_ms().unwrap_or(0.0) / total)
+    }
+
+    /// The node's own cost as a share of the plan's.
+    pub fn cost_share(&self, node: &PlanNode) -> f64 {
+        if self.root.total_cost > 0.0 {
+            node.self_cost() / self.root.total_cost
+        } else {
+            0.0
+        }
+    }
+
+    /// Judged by time when the plan was run, by cost otherwise.
+    pub fn is_expensive(&self, node: &PlanNode) -> bool {
+        let share = match self.time_share(node) {
+            Some(share) => share,
+            None => self.cost_share(node),
+        };
+        share >= EXPENSIVE_SHARE
+    }
+}
+
+impl PlanNode {
+    fn from_json(plan: &Value) -> Option<Self> {
+        let node_type = plan["Node Type"].as_str()?;
+        let loops = plan["Actual Loops"].as_f64();
+        let children = plan["Plans"]
+            .as_array()
+            .map(|plans| plans.iter().filter_map(Self::from_json).collect())
+            .unwrap_or_default();
+
+        let details = DETAIL_KEYS
+            .iter()
+            .filter_map(|key| {
+                let value = match &plan[*key] {
+                    Value::Null => return None,
+                    Value::String(s) => s.clone(),
+                    Value::Array(items) => items
+                        .iter()
+                        .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    other => other.to_string(),
+                };
+                Some(format!("{}: {}", key, value))
+            })
+            .collect();
+
+        let relation = plan["Relation Name"]
+            .as_str()
+            .or(plan["CTE Name"].as_str())
+            .or(plan["Function Name"].as_str());
+
+        Some(Self {
+            node_type: node_type.to_string(),
+            relation: relation.map(str::to_string),
+            title: title(node_type, relation, plan),
+            details,
+            startup_cost: plan["Startup Cost"].as_f64().unwrap_or(0.0),
+            total_cost: plan["Total Cost"].as_f64().unwrap_or(0.0),
+            plan_rows: plan["Plan Rows"].as_f64().unwrap_or(0.0),
+            actual_rows: plan["Actual Rows"].as_f64(),
+            loops,
+            total_ms: plan["Actual Total Time"]
+                .as_f64()
+                .map(|ms| ms * loops.unwrap_or(1.0)),
+            shared_hit: plan["Shared Hit Blocks"].as_u64(),
+            shared_read: plan["Shared Read Blocks"].as_u64(),
+            children,
+        })
+    }
+
+    /// Time not spent in children; parallel workers and init plans can make
+    /// the children add up to more, so it never goes below zero.
+    pub fn self_ms(&self) -> Option<f64> {
+        let children: f64 = self.children.iter().filter_map(|c| c.total_ms).sum();
+        Some((self.total_ms? - children).max(0.0))
+    }
+
+    pub fn self_cost(&self) -> f64 {
+        let children: f64 = self.children.iter().map(|c| c.total_cost).sum();
+        (self.total_cost - children).max(0.0)
+    }
+
+    /// How far the row estimate was off, as a factor of at least 1. Both
+    /// sides are per loop; zero counts as one row.
+    pub fn misestimate(&self) -> Option<f64> {
+        let actual = self.actual_rows?.max(1.0);
+        let planned = self.plan_rows.max(1.0);
+        Some((actual / planned).max(planned / actual))
+    }
+
+    pub fn is_misestimated(&self) -> bool {
+        self.misestimate().is_some_and(|f| f >= MISESTIMATE_FACTOR)
+    }
+}
+
+/// A node of one plan next to the node in the same place in another, or
+/// next to nothing when the other plan has no counterpart.
+#[derive(Debug, Clone, Copy)]
+pub struct PlanPair<'a> {
+    pub depth: usize,
+    pub before: Option<&'a PlanNode>,
+    pub after: Option<&'a PlanNode>,
+}
+
+impl PlanPair<'_> {
+    /// The planner did something different here, e.g. a Seq Scan that
+    /// became an Index Scan, or a node one plan does not have.
+    pub fn strategy_changed(&self) -> bool {
+        match (self.before, self.after) {
+            (Some(before), Some(after)) => before.node_type != after.node_type,
+            _ => true,
+        }
+    }
+}
+
+/// Lines two plans up node by node, in the drawing order of `after`. Nodes
+/// under the same parent are paired by the table they read where they have
+/// one, else in order; what is left over gets no counterpart.
+pub fn compare_plans<'a>(before: &'a QueryPlan, after: &'a QueryPlan) -> Vec<PlanPair<'a>> {
+    fn walk<'a>(
+        before: Option<&'a PlanNode>,
+        after: Option<&'a PlanNode>,
+        depth: usize,
+        out: &mut Vec<PlanPair<'a>>,
+    ) {
+        out.push(PlanPair { depth, before, after });
+        let before_children = before.map_or(&[][..], |n| n.children.as_slice());
+        let after_children = after.map_or(&[][..], |n| n.children.as_slice());
+        for (b, a) in pair_children(before_children, after_children) {
+            walk(b, a, depth + 1, out);
+        }
+    }
+    let mut pairs = Vec::new();
+    walk(Some(&before.root), Some(&after.root), 0, &mut pairs);
+    pairs
+}
+
+fn pair_children<'a>(
+    before: &'a [PlanNode],
+    after: &'a [PlanNode],
+) -> Vec<(Option<&'a PlanNode>, Option<&'a PlanNode>)> {
+    let mut used = vec![false; before.len()];
+    let mut matches: Vec<Option<usize>> = after
+        .iter()
+        .map(|a| {
+            let relation = a.relation.as_ref()?;
+            let i = (0..before.len()).find(|&i| !used[i] && before[i].relation.as_ref() == Some(relation))?;
+            used[i] = true;
+            Some(i)
+        })
+        .collect();
+    for slot in matches.iter_mut().filter(|m| m.is_none()) {
+        if let Some(i) = (0..before.len()).find(|&i| !used[i]) {
+            used[i] = true;
+            *slot = Some(i);
+        }
+    }
+
+    let mut pairs: Vec<_> = after
+        .iter()
+        .zip(matches)
+        .map(|(a, m)| (m.map(|i| &before[i]), Some(a)))
+        .collect();
+    pairs.extend(
+        before
+            .iter()
+            .zip(used)
+            .filter(|(_, used)| !used)
+            .map(|(b, _)| (Some(b), None)),
+    );
+    pairs
+}
+
+fn title(node_type: &str, target: Option<&str>, plan: &Value) -> String {
+    let mut title = node_type.to_string();
+    if let Some(join) = plan["Join Type"].as_str().filter(|j| *j != "Inner") {
+        title = match title.strip_suffix(" Join") {
+            Some(kind) => format!("{} {} Join", kind, join),
+            None => format!("{} {} Join", title, join),
+        };
+    }
+    if let Some(index) = plan["Index Name"].as_str() {
+        title.push_str(&format!(" using {}", index));
+    }
+    if let Some(target) = target {
+        title.push_str(&format!(" on {}", target));
+        if let Some(alias) = plan["Alias"].as_str().filter(|a| *a != target) {
+            title.push_str(&format!(" {}", alias));
+        }
+    }
+    if let Some(subplan) = plan["Subplan Name"].as_str() {
+        title = format!("{}: {}", subplan, title);
+    }
+    title
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn analyzed() -> Value {
+        json!([{
+            "Plan": {
+                "Node Type": "Hash Join",
+                "Join Type": "Left",
+                "Startup Cost": 1.0,
+                "Total Cost": 100.0,
+                "Plan Rows": 10,
+                "Actual Total Time": 9.0,
+                "Actual Rows": 500,
+                "Actual Loops": 1,
+                "Shared Hit Blocks": 12,
+                "Shared Read Blocks": 3,
+                "Hash Cond": "(o.user_id = u.id)",
+                "Plans": [
+                    {
+                        "Node Type": "Seq Scan",
+                        "Relation Name": "orders",
+                        "Alias": "o",
+                        "Startup Cost": 0.0,
+                        "Total Cost": 70.0,
+                        "Plan Rows": 500,
+                        "Actual Total Time": 6.0,
+                        "Actual Rows": 500,
+                        "Actual Loops": 1,
+                        "Filter": "(status = 'x'::text)",
+                        "Rows Removed by Filter": 20
+                    },
+                    {
+                        "Node Type": "Index Scan",
+                        "Index Name": "users_pkey",
+                        "Relation Name": "users",
+                        "Alias": "users",
+                        "Startup Cost": 0.0,
+                        "Total Cost": 0.5,
+                        "Plan Rows": 1,
+                        "Actual Total Time": 0.01,
+                        "Actual Rows": 1,
+                        "Actual Loops": 100
+                    }
+                ]
+            },
+            "Planning Time": 0.2,
+            "Execution Time": 10.0
+        }])
+    }
+
+    #[test]
+    fn test_titles() {
+        let plan = QueryPlan::from_json(&analyzed()).unwrap();
+        let titles: Vec<&str> = plan.nodes().iter().map(|(_, n)| n.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec!["Hash Left Join", "Seq Scan on orders o", "Index Scan using users_pkey on users"]
+        );
+    }
+
+    #[test]
+    fn test_details_and_depth() {
+        let plan = QueryPlan::from_json(&analyzed()).unwrap();
+        let nodes = plan.nodes();
+        assert_eq!(nodes[1].0, 1);
+        assert_eq!(
+            nodes[1].1.details,
+            vec!["Filter: (status = 'x'::text)", "Rows Removed by Filter: 20"]
+        );
+        assert_eq!(plan.root.shared_hit, Some(12));
+    }
+
+    #[test]
+    fn test_times_cover_all_loops() {
+        let plan = QueryPlan::from_json(&analyzed()).unwrap();
+        let index_scan = &plan.root.children[1];
+        assert!((index_scan.total_ms.unwrap() - 1.0).abs() < 1e-9);
+        assert!((plan.root.self_ms().unwrap() - 2.0).abs() < 1e-9);
+        assert!((plan.time_share(&plan.root.children[0]).unwrap() - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_highlights() {
+        let plan = QueryPlan::from_json(&analyzed()).unwrap();
+        assert!(plan.analyzed());
+        assert!(plan.root.is_misestimated());
+        assert!(!plan.root.children[0].is_misestimated());
+        assert!(plan.is_expensive(&plan.root.children[0]));
+        assert!(!plan.is_expensive(&plan.root.children[1]));
+    }
+
+    #[test]
+    fn test_plain_explain_uses_cost() {
+        let value = json!([{
+            "Plan": {
+                "Node Type": "Sort",
+                "Total Cost": 50.0,
+                "Plan Rows": 10,
+                "Sort Key": ["a", "b DESC"],
+                "Plans": [{ "Node Type": "Seq Scan", "Relation Name": "t", "Total Cost": 45.0, "Plan Rows": 10 }]
+            }
+        }]);
+        let plan = QueryPlan::from_json(&value).unwrap();
+        assert!(!plan.analyzed());
+        assert_eq!(plan.root.details, vec!["Sort Key: a, b DESC"]);
+        assert!(!plan.is_expensive(&plan.root));
+        assert!(plan.is_expensive(&plan.root.children[0]));
+        assert_eq!(plan.root.misestimate(), None);
+    }
+
+    #[test]
+    fn test_compare_finds_changed_scans() {
+        let before = QueryPlan::from_json(&analyzed()).unwrap();
+        let mut value = analyzed();
+        let plans = value[0]["Plan"]["Plans"].as_array_mut().unwrap();
+        plans.swap(0, 1);
+        plans[1]["Node Type"] = json!("Index Scan");
+        plans[1]["Index Name"] = json!("orders_status_idx");
+        let after = QueryPlan::from_json(&value).unwrap();
+
+        let pairs = compare_plans(&before, &after);
+        let changed: Vec<(&str, &str)> = pairs
+            .iter()
+            .filter(|p| p.strategy_changed())
+            .map(|p| (p.before.unwrap().title.as_str(), p.after.unwrap().title.as_str()))
+            .collect();
+        assert_eq!(
+            changed,
+            vec![("Seq Scan on orders o", "Index Scan using orders_status_idx on orders o")]
+        );
+        assert_eq!(pairs.len(), 3);
+        assert_eq!(pairs[2].depth, 1);
+    }
+
+    #[test]
+    fn test_compare_keeps_unmatched_nodes() {
+        let before = QueryPlan::from_json(&analyzed()).unwrap();
+        let mut value = analyzed();
+        value[0]["Plan"]["Plans"].as_array_mut().unwrap().truncate(1);
+        let after = QueryPlan::from_json(&value).unwrap();
+
+        let pairs = compare_plans(&before, &after);
+        assert_eq!(pairs.len(), 3);
+        assert!(pairs[2].after.is_none());
+        assert!(pairs[2].strategy_changed());
+        assert!(!pairs[1].strategy_changed());
+    }
+
+    #[test]
+    fn test_from_result_needs_a_plan_cell() {
+        let result = QueryResult {
+            columns: vec![],
+            rows: Default::default(),
+            rows_affected: 0,
+            execution_time_ms: 0,
+            truncated: None,
+        };
+        assert_eq!(QueryPlan::from_result(&result), None);
+    }
+}
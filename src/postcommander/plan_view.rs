@@ -0,0 +1,362 @@
+use crate::postcommander::page::PostCommanderPage;
+use crate::postcommander::plan::{compare_plans, PlanNode, PlanPair, QueryPlan};
+use crate::postcommander::types::TabId;
+use crate::theme::{ActiveTheme, ThemeColors};
+use gpui::prelude::FluentBuilder;
+use gpui::*;
+use std::sync::Arc;
+
+const INDENT: f32 = 18.;
+const BAR_WIDTH: f32 = 80.;
+
+impl PostCommanderPage {
+    /// The plan as an indented tree, one row per node. Nodes that take a
+    /// large share of the time (or of the cost, without ANALYZE) are marked
+    /// in red, row estimates that were far off in amber. With a baseline
+    /// picked from the tab's earlier plans, the two are shown side by side.
+    pub fn render_plan_view(
+        &self,
+        tab_id: TabId,
+        plan: Arc<QueryPlan>,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let colors = *cx.theme().colors();
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return div().id("plan-view");
+        };
+        let baseline = tab.plan_baseline.clone().filter(|b| **b != *plan);
+
+        let mut summary = Vec::new();
+        if let Some(baseline) = &baseline {
+            summary.push(format!(
+                "Time {}",
+                change(baseline.total_ms(), plan.total_ms(), format_ms)
+            ));
+            summary.push(format!(
+                "Rows {}",
+                change(baseline.root.actual_rows, plan.root.actual_rows, format_rows)
+            ));
+        } else {
+            if let Some(ms) = plan.planning_ms {
+                summary.push(format!("Planning {}", format_ms(ms)));
+            }
+            if let Some(ms) = plan.execution_ms {
+                summary.push(format!("Execution {}", format_ms(ms)));
+            }
+            if !plan.analyzed() {
+                summary.push(
+                    "Estimates only; Explain Analyze (Cmd+Shift+A) adds actual rows and times".to_string(),
+                );
+            }
+        }
+
+        let earlier: Vec<AnyElement> = tab
+            .plan_history
+            .iter()
+            .enumerate()
+            .filter(|(_, captured)| *captured.plan != *plan)
+            .map(|(index, captured)| {
+                let is_baseline = baseline.as_ref().is_some_and(|b| Arc::ptr_eq(b, &captured.plan));
+                let next = (!is_baseline).then(|| captured.plan.clone());
+                let label = match captured.plan.total_ms() {
+                    Some(ms) => format!("{} · {}", captured.captured_at.format("%H:%M:%S"), format_ms(ms)),
+                    None => captured.captured_at.format("%H:%M:%S").to_string(),
+                };
+                div()
+                    .id(SharedString::from(format!("plan-baseline-{}", index)))
+                    .px_2()
+                    .rounded_sm()
+                    .cursor_pointer()
+                    .text_color(rgb(if is_baseline { colors.accent } else { colors.text_muted }))
+                    .when(is_baseline, |el| el.bg(rgb(colors.element)))
+                    .hover(|s| s.bg(rgb(colors.element_hover)))
+                    .on_click(cx.listener(move |this, _, _, cx| {
+                        this.set_plan_baseline(tab_id, next.clone(), cx);
+                    }))
+                    .child(label)
+                    .into_any_element()
+            })
+            .collect();
+
+        let rows: Vec<AnyElement> = match &baseline {
+            Some(baseline) => compare_plans(baseline, &plan)
+                .iter()
+                .enumerate()
+                .map(|(index, pair)| comparison_row(index, pair, &colors))
+                .collect(),
+            None => plan
+                .nodes()
+                .into_iter()
+                .enumerate()
+                .map(|(index, (depth, node))| plan_row(&plan, index, depth, node, &colors))
+                .collect(),
+        };
+
+        div()
+            .id("plan-view")
+            .flex_1()
+            .min_h_0()
+            .overflow_y_scroll()
+            .flex()
+            .flex_col()
+            .child(
+                div()
+                    .px_3()
+                    .py_1()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .text_xs()
+                    .text_color(rgb(colors.text_muted))
+                    .bg(rgb(colors.panel_background))
+                    .border_b_1()
+                    .border_color(rgb(colors.border_variant))
+                    .child(div().flex_1().child(summary.join(" · ")))
+                    .when(!earlier.is_empty(), |el| {
+                        el.child("Compare with:").children(earlier)
+                    }),
+            )
+            .children(rows)
+    }
+
+    pub(crate) fn set_plan_baseline(
+        &mut self,
+        tab_id: TabId,
+        baseline: Option<Arc<QueryPlan>>,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) {
+            tab.plan_baseline = baseline;
+            cx.notify();
+        }
+    }
+}
+
+fn plan_row(
+    plan: &QueryPlan,
+    index: usize,
+    depth: usize,
+    node: &PlanNode,
+    colors: &ThemeColors,
+) -> AnyElement {
+    let expensive = plan.is_expensive(node);
+    let misestimated = node.is_misestimated();
+    let share = plan
+        .time_share(node)
+        .unwrap_or_else(|| plan.cost_share(node))
+        .clamp(0.0, 1.0);
+
+    div()
+        .id(SharedString::from(format!("plan-node-{}", index)))
+        .flex()
+        .items_start()
+        .gap_3()
+        .py_1()
+        .pr_3()
+        .pl(px(12. + depth as f32 * INDENT))
+        .border_b_1()
+        .border_color(rgb(colors.border_variant))
+        .when(expensive, |el| el.bg(rgb(colors.status_error_background)))
+        .child(node_label(
+            depth,
+            0.,
+            node,
+            if expensive { colors.status_error } else { colors.text },
+            colors,
+        ))
+        .child(
+            div()
+                .flex_none()
+                .w(px(130.))
+                .text_xs()
+                .text_color(rgb(colors.text_muted))
+                .child(format!("cost {:.2}..{:.2}", node.startup_cost, node.total_cost)),
+        )
+        .child(
+            div()
+                .flex_none()
+                .w(px(150.))
+                .text_xs()
+                .text_color(rgb(if misestimated { colors.status_warning } else { colors.text_muted }))
+                .child(rows_label(node)),
+        )
+        .child(
+            div()
+                .flex_none()
+                .w(px(BAR_WIDTH + 70.))
+                .flex()
+                .items_center()
+                .gap_2()
+                .child(
+                    div()
+                        .w(px(BAR_WIDTH))
+                        .h(px(6.))
+                        .rounded_sm()
+                        .bg(rgb(colors.element))
+                        .child(
+                            div()
+                                .h_full()
+                                .rounded_sm()
+                                .w(px(BAR_WIDTH * share as f32))
+                                .bg(rgb(if expensive { colors.status_error } else { colors.accent })),
+                        ),
+                )
+                .child(
+                    div()
+                        .text_xs()
+                        .text_color(rgb(colors.text_muted))
+                        .child(match node.self_ms() {
+                            Some(ms) => format_ms(ms),
+                            None => format!("{:.0}%", share * 100.0),
+                        }),
+                ),
+        )
+        .child(
+            div()
+                .flex_none()
+                .w(px(120.))
+                .text_xs()
+                .text_color(rgb(colors.text_muted))
+                .child(buffers_label(node)),
+        )
+        .into_any_element()
+}
+
+/// One node of the baseline next to its counterpart in the shown plan.
+/// Rows where the planner chose differently are marked in amber.
+fn comparison_row(index: usize, pair: &PlanPair, colors: &ThemeColors) -> AnyElement {
+    let changed = pair.strategy_changed();
+    let title_color = if changed { colors.status_warning } else { colors.text };
+    let indent = pair.depth as f32 * INDENT;
+    let side = |node: Option<&PlanNode>| match node {
+        Some(node) => node_label(pair.depth, indent, node, title_color, colors).into_any_element(),
+        None => div()
+            .flex_1()
+            .min_w_0()
+            .pl(px(indent))
+            .text_sm()
+            .text_color(rgb(colors.text_muted))
+            .child("—")
+            .into_any_element(),
+    };
+
+    div()
+        .id(SharedString::from(format!("plan-pair-{}", index)))
+        .flex()
+        .items_start()
+        .gap_3()
+        .py_1()
+        .px_3()
+        .border_b_1()
+        .border_color(rgb(colors.border_variant))
+        .when(changed, |el| el.bg(rgb(colors.status_warning_background)))
+        .child(side(pair.before))
+        .child(side(pair.after))
+        .child(
+            div()
+                .flex_none()
+                .w(px(200.))
+                .text_xs()
+                .text_color(rgb(colors.text_muted))
+                .child(format!(
+                    "time {}",
+                    change(
+                        pair.before.and_then(|n| n.total_ms),
+                        pair.after.and_then(|n| n.total_ms),
+                        format_ms,
+                    )
+                )),
+        )
+        .child(
+            div()
+                .flex_none()
+                .w(px(170.))
+                .text_xs()
+                .text_color(rgb(colors.text_muted))
+                .child(format!(
+                    "rows {}",
+                    change(
+                        pair.before.map(|n| n.actual_rows.unwrap_or(n.plan_rows)),
+                        pair.after.map(|n| n.actual_rows.unwrap_or(n.plan_rows)),
+                        format_rows,
+                    )
+                )),
+        )
+        .into_any_element()
+}
+
+fn node_label(depth: usize, indent: f32, node: &PlanNode, color: u32, colors: &ThemeColors) -> Div {
+    div()
+        .flex_1()
+        .min_w_0()
+        .flex()
+        .flex_col()
+        .pl(px(indent))
+        .child(
+            div()
+                .text_sm()
+                .text_color(rgb(color))
+                .child(if depth > 0 {
+                    format!("-> {}", node.title)
+                } else {
+                    node.title.clone()
+                }),
+        )
+        .children(node.details.iter().map(|detail| {
+            div()
+                .text_xs()
+                .text_color(rgb(colors.text_muted))
+                .child(detail.clone())
+        }))
+}
+
+fn format_ms(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2}s", ms / 1000.0)
+    } else {
+        format!("{:.2}ms", ms)
+    }
+}
+
+fn format_rows(rows: f64) -> String {
+    format!("{:.0}", rows)
+}
+
+/// `before → after (±n%)`, or whichever side is known.
+fn change(before: Option<f64>, after: Option<f64>, format: fn(f64) -> String) -> String {
+    match (before, after) {
+        (Some(before), Some(after)) if before > 0.0 => format!(
+            "{} → {} ({:+.0}%)",
+            format(before),
+            format(after),
+            (after - before) / before * 100.0
+        ),
+        (Some(before), Some(after)) => format!("{} → {}", format(before), format(after)),
+        (Some(before), None) => format!("{} → —", format(before)),
+        (None, Some(after)) => format!("— → {}", format(after)),
+        (None, None) => "—".to_string(),
+    }
+}
+
+fn rows_label(node: &PlanNode) -> String {
+    let mut label = match node.actual_rows {
+        Some(actual) => format!("rows {:.0} est, {:.0} actual", node.plan_rows, actual),
+        None => format!("rows {:.0} est", node.plan_rows),
+    };
+    if let Some(loops) = node.loops.filter(|l| *l > 1.0) {
+        label.push_str(&format!(" × {:.0}", loops));
+    }
+    if node.is_misestimated() {
+        if let Some(factor) = node.misestimate() {
+            label.push_str(&format!(" ({:.0}× off)", factor));
+        }
+    }
+    label
+}
+
+fn buffers_label(node: &PlanNode) -> String {
+    match (node.shared_hit, node.shared_read) {
+        (None, None) => String::new(),
+        (hit, read) => format!("hit {} · read {}", hit.unwrap_or(0), read.unwrap_or(0)),
+    }
+}
@@ -1,27 +1,304 @@
-use crate::components::{DataTableColumn, FkDataRequest};
-use crate::postcommander::sql::{analyze_sql, format_sql, SqlDangerLevel};
-use crate::postcommander::types::TableContext;
-use crate::postcommander::ui_helpers::parse_table_from_select;
+use crate::components::{DataTableColumn, FkDataRequest, TextInput};
+use crate::postcommander::database::{
+    is_query_cancelled, QueryColumn, QueryError, QueryResult, QueryStreamEvent,
+    QueryStreamSummary, ResultLimits, SourceTable,
+};
+use crate::postcommander::sql::{
+    analyze_script, count_query, error_range, format_with_style, parameter_names,
+    split_statements, statement_at, writes_data, SqlDangerLevel, SqlFinding, SqlLint, SqlStatement,
+};
+use crate::postcommander::types::{
+    BoundParams, CapturedPlan, RowImpact, RunScope, SafetyConfirm, SafetyWarning,
+    StatementOutcome, StatementRun, TabId, TableContext,
+};
+use crate::postcommander::ui_helpers::utf16_to_byte_offset;
+use crate::postcommander::plan::QueryPlan;
 use crate::postcommander::PostCommanderPage;
-use crate::settings::{AppSettings, QueryHistoryEntry, QueryHistorySettings, QueryHistoryStatus};
-use chrono::Utc;
+use crate::settings::{AppSettings, ConnectionAccess, QueryHistoryEntry, QueryHistorySettings, QueryHistoryStatus};
+use crate::theme::ActiveTheme;
+use chrono::{Local, Utc};
 use gpui::*;
 use gpui_component::input::Position;
 use std::sync::Arc;
 use std::time::Instant;
+use tokio::sync::mpsc;
+
+/// Planner estimates at or above this many rows get the safety dialog even
+/// when the statement has a WHERE clause.
+const LARGE_IMPACT_ROWS: i64 = 10_000;
+
+/// Explain runs whose plans each tab keeps for comparison.
+const PLAN_HISTORY_LIMIT: usize = 10;
 
 impl PostCommanderPage {
+    /// Runs the whole editor buffer as a script.
     pub(crate) fn execute_query(&mut self, cx: &mut Context<Self>) {
-        self.execute_query_internal(false, cx);
+        let Some(tab_id) = self.active_tab_id else {
+            return;
+        };
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return;
+        };
+        let sql = tab.editor.read(cx).value().to_string();
+        let statements = split_statements(&sql);
+        self.execute_statements(tab_id, statements, false, cx);
+    }
+
+    /// Runs the script, the statement under the cursor, or the selected
+    /// text. A run of the selection with nothing selected falls back to the
+    /// statement under the cursor.
+    pub(crate) fn run_query(&mut self, scope: RunScope, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(tab_id) = self.active_tab_id else {
+            return;
+        };
+        let statements = self.scoped_statements(tab_id, scope, window, cx);
+        self.execute_statements(tab_id, statements, false, cx);
+    }
+
+    fn scoped_statements(
+        &self,
+        tab_id: TabId,
+        scope: RunScope,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Vec<SqlStatement> {
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return Vec::new();
+        };
+
+        let sql = tab.editor.read(cx).value().to_string();
+        let selection = tab
+            .editor
+            .update(cx, |editor, cx| editor.selected_text_range(false, window, cx))
+            .map(|s| {
+                let start = utf16_to_byte_offset(&sql, s.range.start);
+                let end = utf16_to_byte_offset(&sql, s.range.end);
+                (start..end, if s.reversed { start } else { end })
+            });
+        let (selected, cursor) = selection.unwrap_or((0..0, 0));
+
+        match scope {
+            RunScope::Script => split_statements(&sql),
+            RunScope::Selection if !selected.is_empty() => split_statements(&sql[selected.clone()])
+                .into_iter()
+                .map(|s| SqlStatement {
+                    start: selected.start + s.start,
+                    end: selected.start + s.end,
+                    ..s
+                })
+                .collect(),
+            RunScope::Selection | RunScope::StatementAtCursor => statement_at(&sql, cursor).into_iter().collect(),
+        }
     }
 
     pub(crate) fn execute_query_force(&mut self, cx: &mut Context<Self>) {
+        if self.safety_warning.as_ref().is_some_and(|w| !w.is_confirmed(cx)) {
+            return;
+        }
         self.safety_warning = None;
-        self.execute_query_internal(true, cx);
+        if let Some((tab_id, statements)) = self.pending_script.take() {
+            self.execute_statements(tab_id, statements, true, cx);
+        }
+    }
+
+    /// Opens the safety dialog and starts estimating the rows each flagged
+    /// UPDATE or DELETE would touch.
+    fn show_safety_warning(
+        &mut self,
+        tab_id: TabId,
+        statements: Vec<SqlStatement>,
+        findings: Vec<SqlFinding>,
+        cx: &mut Context<Self>,
+    ) {
+        let impacts: Vec<Option<RowImpact>> = findings
+            .iter()
+            .map(|f| {
+                let plain = parameter_names(&f.statement).is_empty();
+                count_query(&f.statement).filter(|_| plain).map(RowImpact::new)
+            })
+            .collect();
+        let estimates: Vec<String> = findings
+            .iter()
+            .zip(&impacts)
+            .filter(|(_, impact)| impact.is_some())
+            .map(|(f, _)| f.statement.clone())
+            .collect();
+
+        self.safety_warning = Some(SafetyWarning {
+            findings,
+            impacts,
+            confirm: self.safety_confirm(tab_id, cx),
+        });
+        self.pending_script = Some((tab_id, statements));
+        cx.notify();
+
+        let Some((connection_id, session_id)) = self.tab_connection(tab_id) else {
+            return;
+        };
+        for sql in estimates {
+            let rx = self
+                .db_manager
+                .estimate_rows(&connection_id, sql.clone(), session_id.clone());
+            cx.spawn(async move |this, cx| {
+                let estimate = rx
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("Connection closed")))
+                    .map_err(|e| e.to_string());
+                let _ = this.update(cx, |this, cx| {
+                    let Some(warning) = this.safety_warning.as_mut() else {
+                        return;
+                    };
+                    for (finding, impact) in warning.findings.iter().zip(&mut warning.impacts) {
+                        if let Some(impact) = impact.as_mut().filter(|_| finding.statement == sql) {
+                            impact.estimated = Some(estimate.clone());
+                        }
+                    }
+                    cx.notify();
+                });
+            })
+            .detach();
+        }
+    }
+
+    /// Asks the planner how many rows each UPDATE and DELETE would touch.
+    /// The script runs straight away unless one of them looks large, in
+    /// which case the safety dialog asks first.
+    fn check_row_impact(
+        &mut self,
+        tab_id: TabId,
+        statements: Vec<SqlStatement>,
+        targets: Vec<String>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some((connection_id, session_id)) = self.tab_connection(tab_id) else {
+            return;
+        };
+        let estimates: Vec<_> = targets
+            .iter()
+            .map(|sql| {
+                self.db_manager
+                    .estimate_rows(&connection_id, sql.clone(), session_id.clone())
+            })
+            .collect();
+
+        cx.spawn(async move |this, cx| {
+            let mut findings = Vec::new();
+            let mut impacts = Vec::new();
+            for (sql, rx) in targets.into_iter().zip(estimates) {
+                // A statement that can't be explained runs as usual and
+                // reports its own error.
+                let Ok(Ok(rows)) = rx.await else {
+                    continue;
+                };
+                if rows < LARGE_IMPACT_ROWS {
+                    continue;
+                }
+                let mut impact = count_query(&sql).map(RowImpact::new);
+                if let Some(impact) = impact.as_mut() {
+                    impact.estimated = Some(Ok(rows));
+                }
+                findings.push(SqlFinding {
+                    level: SqlDangerLevel::Warning(format!(
+                        "This statement will touch about {} rows",
+                        rows
+                    )),
+                    statement: sql,
+                });
+                impacts.push(impact);
+            }
+
+            let _ = this.update(cx, |this, cx| {
+                if findings.is_empty() {
+                    this.execute_statements(tab_id, statements, true, cx);
+                } else {
+                    this.safety_warning = Some(SafetyWarning {
+                        findings,
+                        impacts,
+                        confirm: this.safety_confirm(tab_id, cx),
+                    });
+                    this.pending_script = Some((tab_id, statements));
+                    cx.notify();
+                }
+            });
+        })
+        .detach();
+    }
+
+    /// Runs the exact `count(*)` for a finding in the safety dialog.
+    pub(crate) fn count_impact_rows(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(tab_id) = self.pending_script.as_ref().map(|(id, _)| *id) else {
+            return;
+        };
+        let Some((connection_id, _)) = self.tab_connection(tab_id) else {
+            return;
+        };
+        let Some(impact) = self
+            .safety_warning
+            .as_mut()
+            .and_then(|w| w.impacts.get_mut(index))
+            .and_then(Option::as_mut)
+        else {
+            return;
+        };
+        if impact.counting {
+            return;
+        }
+        impact.counting = true;
+        let count_sql = impact.count_sql.clone();
+        let rx = self.db_manager.count_rows(&connection_id, count_sql.clone());
+        cx.notify();
+
+        cx.spawn(async move |this, cx| {
+            let count = rx
+                .await
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("Connection closed")))
+                .map_err(|e| e.to_string());
+            let _ = this.update(cx, |this, cx| {
+                let impact = this
+                    .safety_warning
+                    .as_mut()
+                    .and_then(|w| w.impacts.get_mut(index))
+                    .and_then(Option::as_mut)
+                    .filter(|impact| impact.count_sql == count_sql);
+                if let Some(impact) = impact {
+                    impact.counting = false;
+                    impact.counted = Some(count);
+                    cx.notify();
+                }
+            });
+        })
+        .detach();
+    }
+
+    /// On a protected connection, the input the database name is typed into.
+    fn safety_confirm(&self, tab_id: TabId, cx: &mut Context<Self>) -> Option<SafetyConfirm> {
+        let profile = self
+            .tab_profile(tab_id)
+            .filter(|p| p.access == ConnectionAccess::Protected)?;
+        let database = profile.connection.database.clone();
+        let colors = cx.theme().colors();
+        let (text, text_muted) = (colors.text, colors.text_muted);
+        let page = cx.entity();
+        let input = cx.new(|cx| {
+            let mut input = TextInput::new(cx, database.clone());
+            input.set_colors(text, text_muted);
+            input.set_on_change(move |_, _, cx| {
+                let _ = page.update(cx, |_, cx| cx.notify());
+            });
+            input
+        });
+        Some(SafetyConfirm { database, input })
+    }
+
+    fn tab_connection(&self, tab_id: TabId) -> Option<(String, Option<String>)> {
+        let tab = self.tabs.iter().find(|t| t.id == tab_id)?;
+        let session_id = tab.transaction.as_ref().map(|t| t.session_id.clone());
+        Some((tab.connection_id.clone()?, session_id))
     }
 
     pub(crate) fn cancel_dangerous_query(&mut self, cx: &mut Context<Self>) {
         self.safety_warning = None;
+        self.pending_script = None;
         cx.notify();
     }
 
@@ -44,78 +321,197 @@ impl PostCommanderPage {
             return;
         };
 
-        if !tab.is_loading {
+        if !tab.is_loading || tab.cancel_requested {
             return;
         }
 
-        let sql = tab.editor.read(cx).value().to_string();
-        let formatted_sql = format_sql(&sql);
-        let execution_ms = tab.query_start_time.map(|t| t.elapsed().as_millis() as u64);
-        let database = tab.database.clone();
+        let (Some(backend_pid), Some(connection_id)) = (tab.backend_pid, tab.connection_id.clone()) else {
+            // Nothing is running on the server yet, so dropping the task is enough.
+            self.abandon_query(tab_id, QueryHistoryStatus::Cancelled, cx);
+            return;
+        };
 
-        let entry = QueryHistoryEntry {
-            sql: formatted_sql,
-            timestamp: Utc::now().to_rfc3339(),
-            execution_ms,
-            status: QueryHistoryStatus::Cancelled,
-            database: Some(database),
+        tab.cancel_requested = true;
+        cx.notify();
+
+        // On success the stream ends with a query_canceled error, which
+        // finish_statement records. Only an unconfirmed cancel is handled here.
+        let rx = self.db_manager.cancel_backend(&connection_id, backend_pid);
+        cx.spawn(async move |this, cx| {
+            let confirmed = matches!(rx.await, Ok(Ok(true)));
+            if confirmed {
+                return;
+            }
+
+            let _ = this.update(cx, |this, cx| {
+                let still_running = this
+                    .tabs
+                    .iter()
+                    .any(|t| t.id == tab_id && t.is_loading && t.backend_pid == Some(backend_pid));
+                if still_running {
+                    this.abandon_query(
+                        tab_id,
+                        QueryHistoryStatus::ServerCancelled { confirmed: false },
+                        cx,
+                    );
+                }
+            });
+        })
+        .detach();
+    }
+
+    /// Stops waiting for the tab's script without a result from the server.
+    fn abandon_query(&mut self, tab_id: TabId, status: QueryHistoryStatus, cx: &mut Context<Self>) {
+        let database = self.tab_database(tab_id);
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
         };
 
-        AppSettings::update_global(cx, |settings| {
-            let pc = settings.postcommander_mut();
-            let history = pc.query_history.get_or_insert_with(QueryHistorySettings::default);
-            history.add_entry(entry);
-        });
-        AppSettings::get_global(cx).save();
+        let running = tab
+            .statements
+            .iter()
+            .position(|s| matches!(s.outcome, StatementOutcome::Running));
+        let execution_ms = tab.query_start_time.map(|t| t.elapsed().as_millis() as u64);
+        let error = match status {
+            QueryHistoryStatus::ServerCancelled { confirmed: false } => {
+                "Query cancelled locally; the server did not confirm the cancel"
+            }
+            _ => "Query cancelled",
+        };
+
+        if let Some(index) = running {
+            let entry = QueryHistoryEntry {
+                sql: tab.statements[index].sql.clone(),
+                timestamp: Utc::now().to_rfc3339(),
+                execution_ms,
+                status,
+                database,
+            };
+            AppSettings::update_global(cx, |settings| {
+                let pc = settings.postcommander_mut();
+                let history = pc.query_history.get_or_insert_with(QueryHistorySettings::default);
+                history.add_entry(entry);
+            });
+            AppSettings::get_global(cx).save();
+
+            tab.statements[index].outcome = StatementOutcome::Failed {
+                error: error.into(),
+                execution_time_ms: execution_ms.unwrap_or(0),
+            };
+            skip_remaining(&mut tab.statements[index + 1..]);
+            tab.active_statement = index;
+        }
 
         tab.query_task = None;
         tab.is_loading = false;
         tab.query_start_time = None;
-        tab.error = Some("Query cancelled".to_string());
+        tab.backend_pid = None;
+        tab.cancel_requested = false;
+        tab.result = None;
+        tab.plan = None;
+        tab.error = Some(error.into());
+        tab.table_state.update(cx, |state, cx| {
+            state.clear();
+            cx.notify();
+        });
         cx.notify();
     }
 
-    pub(crate) fn execute_query_internal(&mut self, force: bool, cx: &mut Context<Self>) {
-        let Some(tab_id) = self.active_tab_id.clone() else {
-            return;
-        };
-
+    /// Runs `statements` one after another on the tab's connection, stopping
+    /// at the first failure. Each gets its own result sub-tab.
+    fn execute_statements(
+        &mut self,
+        tab_id: TabId,
+        statements: Vec<SqlStatement>,
+        force: bool,
+        cx: &mut Context<Self>,
+    ) {
         let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
             return;
         };
-
-        let raw_sql = tab.editor.read(cx).value().to_string();
-        if raw_sql.trim().is_empty() {
+        if tab.is_loading || statements.is_empty() {
+            return;
+        }
+        if tab.connection_id.is_none() {
+            tab.error = Some("No connection selected. Pick one with ⌘P.".into());
+            cx.notify();
+            return;
+        }
+        if tab.transaction.as_ref().is_some_and(|t| t.is_busy) {
+            return;
+        }
+        let read_only = self
+            .tab_profile(tab_id)
+            .is_some_and(|p| p.access == ConnectionAccess::ReadOnly);
+        if read_only && statements.iter().any(|s| writes_data(&s.text)) {
+            self.set_tab_error(tab_id, "The connection is read-only, so the script was not run", cx);
             return;
         }
 
-        let sql = format_sql(&raw_sql);
+        // A sort kept from the last result is applied to the new rows here
+        // rather than written into the user's query.
+        if let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) {
+            tab.grid_rerun = None;
+            tab.table_state.update(cx, |state, cx| state.set_sort_on_server(false, cx));
+        }
 
         if !force {
-            let danger_level = analyze_sql(&sql);
-            match danger_level {
-                SqlDangerLevel::Safe => {}
-                SqlDangerLevel::Warning(ref msg) | SqlDangerLevel::Dangerous(ref msg) => {
-                    self.safety_warning = Some((danger_level.clone(), msg.clone()));
-                    cx.notify();
-                    return;
-                }
+            let findings: Vec<_> = statements.iter().flat_map(|s| analyze_script(&s.text)).collect();
+            if !findings.is_empty() {
+                self.show_safety_warning(tab_id, statements, findings, cx);
+                return;
             }
+            let targets: Vec<String> = statements
+                .iter()
+                .filter(|s| parameter_names(&s.text).is_empty() && count_query(&s.text).is_some())
+                .map(|s| s.text.clone())
+                .collect();
+            if !targets.is_empty() {
+                self.check_row_impact(tab_id, statements, targets, cx);
+                return;
+            }
+        }
+
+        if statements.iter().any(|s| !parameter_names(&s.text).is_empty()) {
+            self.prompt_parameters(tab_id, statements, cx);
+            return;
         }
 
+        let runs = statements
+            .iter()
+            .map(|s| statement_run(&s.text, Some(s.start), None))
+            .collect();
+        self.run_script(tab_id, runs, cx);
+    }
+
+    /// Starts running prepared statements in the tab.
+    pub(crate) fn run_script(&mut self, tab_id: TabId, runs: Vec<StatementRun>, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+        if tab.is_loading || runs.is_empty() {
+            return;
+        }
+
+        let statement_count = runs.len();
+        tab.statements = runs;
+        tab.grid_base_sql = None;
+        tab.active_statement = 0;
         tab.is_loading = true;
+        tab.backend_pid = None;
+        tab.cancel_requested = false;
         tab.error = None;
-        tab.table_context = None;
+        tab.error_diagnostic = None;
+        tab.result = None;
+        tab.plan = None;
+        tab.table_contexts.clear();
         tab.query_start_time = Some(Instant::now());
+        tab.table_state.update(cx, |state, cx| {
+            state.clear();
+            cx.notify();
+        });
         cx.notify();
 
-        let parsed_table = parse_table_from_select(&sql);
-        let rx = self.db_manager.execute(sql.clone());
-        let tab_id_clone = tab_id.clone();
-        let db_manager = self.db_manager.clone();
-        let sql_for_history = sql.clone();
-        let database_for_history = tab.database.clone();
-
         cx.spawn({
             let tab_id_for_refresh = tab_id.clone();
             async move |this, cx| {
@@ -139,160 +535,69 @@ impl PostCommanderPage {
         }).detach();
 
         let task = cx.spawn(async move |this, cx| {
-            let result = rx.await;
-            let _ = this.update(cx, |this, cx| {
-                match result {
-                    Ok(Ok(query_result)) => {
-                        if let Some(tab) = this.tabs.iter_mut().find(|t| t.id == tab_id_clone) {
-                            let columns: Vec<DataTableColumn> = query_result
-                                .columns
-                                .iter()
-                                .map(|c| {
-                                    DataTableColumn::new(c.name.clone())
-                                        .type_name(c.type_name.clone())
-                                })
-                                .collect();
-                            let rows = query_result.rows.clone();
-
-                            let execution_ms = tab.query_start_time.map(|t| t.elapsed().as_millis() as u64);
-
-                            let entry = QueryHistoryEntry {
-                                sql: sql_for_history.clone(),
-                                timestamp: Utc::now().to_rfc3339(),
-                                execution_ms,
-                                status: QueryHistoryStatus::Success,
-                                database: Some(database_for_history.clone()),
-                            };
-
-                            AppSettings::update_global(cx, |settings| {
-                                let pc = settings.postcommander_mut();
-                                let history = pc.query_history.get_or_insert_with(QueryHistorySettings::default);
-                                history.add_entry(entry);
+            for index in 0..statement_count {
+                let Ok(Some(mut rx)) = this.update(cx, |this, cx| this.start_statement(tab_id, index, cx))
+                else {
+                    break;
+                };
+                let started = Instant::now();
+                let mut columns: Option<Vec<QueryColumn>> = None;
+                let mut result = Err(anyhow::anyhow!("Query execution failed"));
+
+                while let Some(event) = rx.recv().await {
+                    match event {
+                        QueryStreamEvent::Started { backend_pid } => {
+                            let _ = this.update(cx, |this, _cx| {
+                                if let Some(tab) = this.tabs.iter_mut().find(|t| t.id == tab_id) {
+                                    tab.backend_pid = Some(backend_pid);
+                                }
                             });
-                            AppSettings::get_global(cx).save();
-
-                            tab.table_state.update(cx, |state, _cx| {
-                                state.set_columns(columns);
-                                state.set_rows(rows);
+                        }
+                        QueryStreamEvent::Columns(query_columns) => {
+                            columns = Some(query_columns.clone());
+                            let _ = this.update(cx, |this, cx| {
+                                this.begin_streamed_result(tab_id, query_columns, cx);
                             });
-                            tab.result = Some(query_result);
-                            tab.is_loading = false;
-                            tab.query_start_time = None;
-                            tab.query_task = None;
-                            tab.error = None;
-
-                            if let Some((schema, table)) = parsed_table.clone() {
-                                let tab_id_for_pk = tab_id_clone.clone();
-                                let pk_rx = db_manager.fetch_primary_keys(schema.clone(), table.clone());
-                                let fk_rx = db_manager.fetch_foreign_keys(schema.clone(), table.clone());
-                                let struct_rx = db_manager.fetch_table_structure(schema.clone(), table.clone());
-
-                                cx.spawn(async move |this, cx| {
-                                    let pk_result = pk_rx.await;
-                                    let fk_result = fk_rx.await;
-                                    let struct_result = struct_rx.await;
-
-                                    let _ = this.update(cx, |this, cx| {
-                                        let primary_keys = match pk_result {
-                                            Ok(Ok(pks)) => pks,
-                                            _ => vec![],
-                                        };
-
-                                        let foreign_keys = match fk_result {
-                                            Ok(Ok(fks)) => Arc::new(fks
-                                                .into_iter()
-                                                .map(|fk| (fk.column_name.clone(), fk))
-                                                .collect()),
-                                            _ => Arc::new(std::collections::HashMap::new()),
-                                        };
-
-                                        let context = TableContext {
-                                            schema: schema.clone(),
-                                            table: table.clone(),
-                                            primary_keys,
-                                            foreign_keys,
-                                        };
-
-                                        if let Some(tab) = this.tabs.iter_mut().find(|t| t.id == tab_id_for_pk) {
-                                            tab.table_context = Some(context.clone());
-                                            tab.table_state.update(cx, |state, _cx| {
-                                                state.set_table_context(Some(context));
-                                            });
-
-                                            if let Ok(Ok(structure)) = struct_result {
-                                                let key = format!("{}.{}", structure.schema, structure.table);
-                                                tab.table_structures = vec![structure.clone()];
-                                                tab.structure_expanded.insert(key, true);
-                                                *this.completion_structures.borrow_mut() = vec![structure];
-                                            }
-                                        }
-
+                        }
+                        QueryStreamEvent::Rows(rows) => {
+                            let _ = this.update(cx, |this, cx| {
+                                if let Some(tab) = this.tabs.iter().find(|t| t.id == tab_id) {
+                                    tab.table_state.update(cx, |state, cx| {
+                                        state.append_rows(rows);
                                         cx.notify();
                                     });
-                                }).detach();
-                            }
-                        }
-                    }
-                    Ok(Err(e)) => {
-                        if let Some(tab) = this.tabs.iter_mut().find(|t| t.id == tab_id_clone) {
-                            let execution_ms = tab.query_start_time.map(|t| t.elapsed().as_millis() as u64);
-
-                            let entry = QueryHistoryEntry {
-                                sql: sql_for_history.clone(),
-                                timestamp: Utc::now().to_rfc3339(),
-                                execution_ms,
-                                status: QueryHistoryStatus::Error(e.to_string()),
-                                database: Some(database_for_history.clone()),
-                            };
-
-                            AppSettings::update_global(cx, |settings| {
-                                let pc = settings.postcommander_mut();
-                                let history = pc.query_history.get_or_insert_with(QueryHistorySettings::default);
-                                history.add_entry(entry);
+                                }
                             });
-                            AppSettings::get_global(cx).save();
-
-                            tab.table_state.update(cx, |state, cx| {
-                                state.clear();
-                                cx.notify();
-                            });
-                            tab.error = Some(e.to_string());
-                            tab.is_loading = false;
-                            tab.query_start_time = None;
-                            tab.query_task = None;
                         }
-                    }
-                    Err(_) => {
-                        if let Some(tab) = this.tabs.iter_mut().find(|t| t.id == tab_id_clone) {
-                            let execution_ms = tab.query_start_time.map(|t| t.elapsed().as_millis() as u64);
-
-                            let entry = QueryHistoryEntry {
-                                sql: sql_for_history.clone(),
-                                timestamp: Utc::now().to_rfc3339(),
-                                execution_ms,
-                                status: QueryHistoryStatus::Error("Query execution failed".to_string()),
-                                database: Some(database_for_history.clone()),
-                            };
-
-                            AppSettings::update_global(cx, |settings| {
-                                let pc = settings.postcommander_mut();
-                                let history = pc.query_history.get_or_insert_with(QueryHistorySettings::default);
-                                history.add_entry(entry);
-                            });
-                            AppSettings::get_global(cx).save();
-
-                            tab.table_state.update(cx, |state, cx| {
-                                state.clear();
-                                cx.notify();
+                        QueryStreamEvent::Transaction(status) => {
+                            let _ = this.update(cx, |this, cx| {
+                                let tab = this.tabs.iter_mut().find(|t| t.id == tab_id);
+                                if let Some(transaction) = tab.and_then(|t| t.transaction.as_mut()) {
+                                    transaction.status = status;
+                                    cx.notify();
+                                }
                             });
-                            tab.error = Some("Query execution failed".to_string());
-                            tab.is_loading = false;
-                            tab.query_start_time = None;
-                            tab.query_task = None;
+                        }
+                        QueryStreamEvent::Finished(finished) => {
+                            result = finished;
+                            break;
                         }
                     }
                 }
-                cx.notify();
+
+                let elapsed_ms = started.elapsed().as_millis() as u64;
+                let keep_going = this
+                    .update(cx, |this, cx| {
+                        this.finish_statement(tab_id, index, columns.take(), result, elapsed_ms, cx)
+                    })
+                    .unwrap_or(false);
+                if !keep_going {
+                    break;
+                }
+            }
+
+            let _ = this.update(cx, |this, cx| {
+                this.finish_script(tab_id, cx);
             });
         });
 
@@ -301,17 +606,381 @@ impl PostCommanderPage {
         }
     }
 
+    /// Sends the statement at `index` to the server and points the grid at it.
+    fn start_statement(
+        &mut self,
+        tab_id: TabId,
+        index: usize,
+        cx: &mut Context<Self>,
+    ) -> Option<mpsc::UnboundedReceiver<QueryStreamEvent>> {
+        let limits = result_limits(cx);
+        let tab = self.tabs.iter_mut().find(|t| t.id == tab_id)?;
+        let connection_id = tab.connection_id.clone()?;
+        let session_id = tab.transaction.as_ref().map(|t| t.session_id.clone());
+        let statement = tab.statements.get_mut(index)?;
+        statement.outcome = StatementOutcome::Running;
+        let (sql, params) = match &statement.params {
+            Some(bound) => (bound.sql.clone(), bound.values.clone()),
+            None => (statement.sql.clone(), vec![]),
+        };
+
+        tab.active_statement = index;
+        tab.backend_pid = None;
+        tab.result = None;
+        tab.plan = None;
+        tab.table_state.update(cx, |state, cx| {
+            state.clear();
+            cx.notify();
+        });
+        cx.notify();
+
+        Some(self.db_manager.execute_streaming(&connection_id, sql, params, limits, session_id))
+    }
+
+    /// Swaps the tab over to a new result set as soon as its columns are
+    /// known, so rows can be shown while they are still arriving.
+    fn begin_streamed_result(
+        &mut self,
+        tab_id: TabId,
+        query_columns: Vec<QueryColumn>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+
+        let columns: Vec<DataTableColumn> = query_columns
+            .iter()
+            .map(|c| DataTableColumn::new(c.name.clone()).type_name(c.type_name.clone()))
+            .collect();
+
+        tab.table_state.update(cx, |state, _cx| {
+            state.clear();
+            state.set_columns(columns);
+        });
+        tab.result = Some(QueryResult {
+            columns: query_columns,
+            rows: Arc::new(vec![]),
+            rows_affected: 0,
+            execution_time_ms: 0,
+            truncated: None,
+        });
+        cx.notify();
+    }
+
+    /// Records a finished statement; returns whether the script should go on.
+    fn finish_statement(
+        &mut self,
+        tab_id: TabId,
+        index: usize,
+        columns: Option<Vec<QueryColumn>>,
+        result: anyhow::Result<QueryStreamSummary>,
+        elapsed_ms: u64,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let database = self.tab_database(tab_id);
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return false;
+        };
+        let Some(sql) = tab.statements.get(index).map(|s| s.sql.clone()) else {
+            return false;
+        };
+
+        let cancelled = result.as_ref().is_err_and(is_query_cancelled);
+        let status = match &result {
+            Ok(_) => QueryHistoryStatus::Success,
+            Err(_) if cancelled => QueryHistoryStatus::ServerCancelled { confirmed: true },
+            Err(e) => QueryHistoryStatus::Error(e.to_string()),
+        };
+
+        let entry = QueryHistoryEntry {
+            sql,
+            timestamp: Utc::now().to_rfc3339(),
+            execution_ms: Some(elapsed_ms),
+            status,
+            database,
+        };
+
+        AppSettings::update_global(cx, |settings| {
+            let pc = settings.postcommander_mut();
+            let history = pc.query_history.get_or_insert_with(QueryHistorySettings::default);
+            history.add_entry(entry);
+        });
+        AppSettings::get_global(cx).save();
+
+        tab.backend_pid = None;
+
+        match result {
+            Ok(summary) => {
+                let query_result = QueryResult {
+                    columns: columns.unwrap_or_default(),
+                    rows: tab.table_state.read(cx).rows().clone(),
+                    rows_affected: summary.row_count,
+                    execution_time_ms: summary.execution_time_ms,
+                    truncated: summary.truncated,
+                };
+                if let Some(plan) = QueryPlan::from_result(&query_result) {
+                    if tab.plan_history.len() >= PLAN_HISTORY_LIMIT {
+                        tab.plan_history.remove(0);
+                    }
+                    tab.plan_history.push(CapturedPlan {
+                        plan: Arc::new(plan),
+                        sql: tab.statements[index].sql.clone(),
+                        captured_at: Local::now(),
+                    });
+                }
+                tab.statements[index].outcome = StatementOutcome::Done(query_result);
+                true
+            }
+            Err(e) => {
+                let error = if cancelled {
+                    QueryError::from("Query cancelled")
+                } else {
+                    QueryError::from_error(&e)
+                };
+                // Positions count into the SQL sent, which only matches the
+                // editor text when no parameters were bound and the text
+                // where the statement was split from is still there.
+                let statement = &tab.statements[index];
+                tab.error_diagnostic = error
+                    .position
+                    .filter(|_| statement.params.is_none())
+                    .and_then(|position| {
+                        let text = tab.editor.read(cx).value();
+                        let start = statement.offset?;
+                        if text.get(start..start + statement.sql.len()) != Some(statement.sql.as_str()) {
+                            return None;
+                        }
+                        let range = error_range(&statement.sql, position)?;
+                        Some(SqlLint {
+                            range: start + range.start..start + range.end,
+                            message: error.message.clone(),
+                        })
+                    });
+                tab.statements[index].outcome = StatementOutcome::Failed {
+                    error,
+                    execution_time_ms: elapsed_ms,
+                };
+                skip_remaining(&mut tab.statements[index + 1..]);
+                false
+            }
+        }
+    }
+
+    /// Ends a run and shows the result most worth looking at: the failed
+    /// statement, else the last one that returned rows, else the last one.
+    fn finish_script(&mut self, tab_id: TabId, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+
+        tab.is_loading = false;
+        tab.query_start_time = None;
+        tab.query_task = None;
+        tab.backend_pid = None;
+        tab.cancel_requested = false;
+
+        let failed = tab
+            .statements
+            .iter()
+            .position(|s| matches!(s.outcome, StatementOutcome::Failed { .. }));
+        let with_rows = tab.statements.iter().rposition(|s| {
+            matches!(&s.outcome, StatementOutcome::Done(result) if !result.columns.is_empty())
+        });
+        let index = failed
+            .or(with_rows)
+            .unwrap_or(tab.statements.len().saturating_sub(1));
+
+        self.show_statement_result(tab_id, index, cx);
+        self.refresh_diagnostics(tab_id, cx);
+    }
+
+    /// Switches the grid to another statement's result sub-tab.
+    pub(crate) fn select_statement_result(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(tab_id) = self.active_tab_id else {
+            return;
+        };
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+        if tab.is_loading || index == tab.active_statement || index >= tab.statements.len() {
+            return;
+        }
+        if tab.table_state.read(cx).has_pending_changes() {
+            return;
+        }
+
+        // Keep edits made in the grid when coming back to this result.
+        let rows = tab.table_state.read(cx).rows().clone();
+        if let Some(StatementOutcome::Done(result)) =
+            tab.statements.get_mut(tab.active_statement).map(|s| &mut s.outcome)
+        {
+            result.rows = rows;
+        }
+
+        self.show_statement_result(tab_id, index, cx);
+    }
+
+    fn show_statement_result(&mut self, tab_id: TabId, index: usize, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+        let Some(statement) = tab.statements.get(index) else {
+            return;
+        };
+
+        let (result, error) = match &statement.outcome {
+            StatementOutcome::Done(result) => (Some(result.clone()), None),
+            StatementOutcome::Failed { error, .. } => (None, Some(error.clone())),
+            StatementOutcome::Skipped => {
+                (None, Some("Not run because an earlier statement failed".into()))
+            }
+            StatementOutcome::Pending | StatementOutcome::Running => (None, None),
+        };
+        let columns: Vec<DataTableColumn> = result
+            .iter()
+            .flat_map(|r| r.columns.iter())
+            .map(|c| DataTableColumn::new(c.name.clone()).type_name(c.type_name.clone()))
+            .collect();
+        let rows = result.as_ref().map(|r| r.rows.clone());
+
+        tab.active_statement = index;
+        tab.plan = result.as_ref().and_then(QueryPlan::from_result).map(Arc::new);
+        tab.result = result;
+        tab.error = error;
+        tab.table_contexts.clear();
+        tab.table_state.update(cx, |state, cx| {
+            state.clear();
+            state.set_columns(columns);
+            if let Some(rows) = rows {
+                state.set_rows(rows);
+            }
+            cx.notify();
+        });
+
+        let source_columns = tab.result.as_ref().map(|r| r.columns.clone());
+        if let (Some(columns), Some(connection_id)) = (source_columns, tab.connection_id.clone()) {
+            self.load_table_contexts(tab_id, connection_id, columns, cx);
+        }
+        cx.notify();
+    }
+
+    /// Database name recorded in history for queries run in the tab.
+    fn tab_database(&self, tab_id: TabId) -> Option<String> {
+        self.tabs
+            .iter()
+            .find(|t| t.id == tab_id)
+            .and_then(|t| t.connection_id.as_deref())
+            .and_then(|id| self.connection(id))
+            .map(|c| c.profile.connection.database.clone())
+    }
+
+    /// Finds the base tables the result's columns were read from, by the
+    /// table OID and column number the server reports for each, and fetches
+    /// their foreign keys and structure. This enables cell editing and FK
+    /// lookups in the grid.
+    fn load_table_contexts(
+        &mut self,
+        tab_id: TabId,
+        connection_id: String,
+        columns: Vec<QueryColumn>,
+        cx: &mut Context<Self>,
+    ) {
+        let mut table_oids: Vec<u32> = columns
+            .iter()
+            .filter_map(|c| c.source.map(|s| s.table_oid))
+            .collect();
+        table_oids.sort_unstable();
+        table_oids.dedup();
+        if table_oids.is_empty() {
+            return;
+        }
+        let sources_rx = self.db_manager.fetch_source_tables(&connection_id, table_oids);
+
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(tables)) = sources_rx.await else {
+                return;
+            };
+            let mut contexts = table_contexts(&columns, tables);
+            if contexts.is_empty() {
+                return;
+            }
+
+            let Ok(lookups) = this.update(cx, |this, _| {
+                let read_only = this
+                    .connection(&connection_id)
+                    .is_some_and(|c| c.profile.access == ConnectionAccess::ReadOnly);
+                for context in contexts.iter_mut() {
+                    context.read_only = read_only;
+                }
+                contexts
+                    .iter()
+                    .map(|c| {
+                        (
+                            this.db_manager.fetch_foreign_keys(&connection_id, c.schema.clone(), c.table.clone()),
+                            this.db_manager.fetch_table_structure(&connection_id, c.schema.clone(), c.table.clone()),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            }) else {
+                return;
+            };
+
+            let mut structures = Vec::new();
+            for (context, (fk_rx, struct_rx)) in contexts.iter_mut().zip(lookups) {
+                if let Ok(Ok(fks)) = fk_rx.await {
+                    context.foreign_keys = Arc::new(fks
+                        .into_iter()
+                        .map(|fk| (fk.column_name.clone(), fk))
+                        .collect());
+                }
+                if let Ok(Ok(structure)) = struct_rx.await {
+                    structures.push(structure);
+                }
+            }
+
+            let _ = this.update(cx, |this, cx| {
+                if let Some(tab) = this.tabs.iter_mut().find(|t| t.id == tab_id) {
+                    tab.table_contexts = contexts.clone();
+                    tab.table_state.update(cx, |state, _cx| {
+                        state.set_table_contexts(contexts);
+                    });
+
+                    if !structures.is_empty() {
+                        for structure in &structures {
+                            let key = format!("{}.{}", structure.schema, structure.table);
+                            tab.structure_expanded.insert(key, true);
+                        }
+                        tab.table_structures = structures.clone();
+                    }
+                }
+                this.cache_table_structures(&connection_id, structures, cx);
+
+                cx.notify();
+            });
+        }).detach();
+    }
+
     pub(crate) fn handle_fk_data_request(
         &mut self,
         table_state: Entity<crate::components::DataTableState>,
         event: &FkDataRequest,
         cx: &mut Context<Self>,
     ) {
+        let Some(connection_id) = self
+            .tabs
+            .iter()
+            .find(|t| t.table_state == table_state)
+            .and_then(|t| t.connection_id.clone())
+        else {
+            return;
+        };
         let db_manager = self.db_manager.clone();
         let fk_info = event.fk_info.clone();
         let cell_value = event.cell_value.to_string();
 
         let rx = db_manager.fetch_fk_referenced_row(
+            &connection_id,
             fk_info.referenced_schema.clone(),
             fk_info.referenced_table.clone(),
             fk_info.referenced_column.clone(),
@@ -338,53 +1007,29 @@ impl PostCommanderPage {
     }
 
     pub(crate) fn explain_query(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        let Some(tab_id) = self.active_tab_id else {
-            return;
-        };
-
-        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
-            return;
-        };
-
-        tab.editor.update(cx, |editor, cx| {
-            let current_text = editor.value().to_string();
-            let trimmed = current_text.trim();
-            if trimmed.is_empty() {
-                return;
-            }
-
-            let new_text = format!("EXPLAIN {}", trimmed);
-            let cursor_pos = new_text.len() as u32;
-            editor.set_value(new_text, window, cx);
-            editor.set_cursor_position(Position { line: 0, character: cursor_pos }, window, cx);
-        });
-
-        self.execute_query(cx);
+        self.explain("FORMAT JSON", window, cx);
     }
 
     pub(crate) fn explain_analyze_query(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.explain("ANALYZE, BUFFERS, FORMAT JSON", window, cx);
+    }
+
+    /// Explains the selected statement, or the one under the cursor, and
+    /// shows the plan tree. The editor text is left as it is.
+    fn explain(&mut self, options: &str, window: &mut Window, cx: &mut Context<Self>) {
         let Some(tab_id) = self.active_tab_id else {
             return;
         };
-
-        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+        let statements = self.scoped_statements(tab_id, RunScope::Selection, window, cx);
+        let Some(statement) = statements.into_iter().next() else {
             return;
         };
-
-        tab.editor.update(cx, |editor, cx| {
-            let current_text = editor.value().to_string();
-            let trimmed = current_text.trim();
-            if trimmed.is_empty() {
-                return;
-            }
-
-            let new_text = format!("EXPLAIN ANALYZE {}", trimmed);
-            let cursor_pos = new_text.len() as u32;
-            editor.set_value(new_text, window, cx);
-            editor.set_cursor_position(Position { line: 0, character: cursor_pos }, window, cx);
-        });
-
-        self.execute_query(cx);
+        // No longer the editor's text, so errors aren't marked there.
+        let explain = SqlStatement {
+            text: format!("EXPLAIN ({}) {}", options, statement.text),
+            ..statement
+        };
+        self.execute_statements(tab_id, vec![explain], false, cx);
     }
 
     pub(crate) fn toggle_comment(&mut self, window: &mut Window, cx: &mut Context<Self>) {
@@ -440,31 +1085,54 @@ impl PostCommanderPage {
     }
 
     pub(crate) fn format_query(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.format_editor(false, window, cx);
+    }
+
+    /// Formats the selected text, or the statement under the cursor when
+    /// nothing is selected, leaving the rest of the editor as it is.
+    pub(crate) fn format_selection(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.format_editor(true, window, cx);
+    }
+
+    fn format_editor(&mut self, selection_only: bool, window: &mut Window, cx: &mut Context<Self>) {
         let Some(tab_id) = self.active_tab_id else {
             return;
         };
-
-        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
             return;
         };
+        let style = AppSettings::get_global(cx)
+            .postcommander()
+            .formatter
+            .clone()
+            .unwrap_or_default();
 
         tab.editor.update(cx, |editor, cx| {
-            let current_text = editor.value().to_string();
-            if current_text.trim().is_empty() {
+            let mut text = editor.value().to_string();
+            let range = if selection_only {
+                let Some(selection) = editor.selected_text_range(false, window, cx) else {
+                    return;
+                };
+                let start = utf16_to_byte_offset(&text, selection.range.start);
+                let end = utf16_to_byte_offset(&text, selection.range.end);
+                if start < end {
+                    start..end
+                } else {
+                    let Some(statement) = statement_at(&text, end) else {
+                        return;
+                    };
+                    statement.start..statement.end
+                }
+            } else {
+                0..text.len()
+            };
+            if text[range.clone()].trim().is_empty() {
                 return;
             }
 
-            let formatted = sqlformat::format(
-                &current_text,
-                &sqlformat::QueryParams::None,
-                sqlformat::FormatOptions {
-                    indent: sqlformat::Indent::Spaces(2),
-                    uppercase: true,
-                    lines_between_queries: 1,
-                },
-            );
-
-            editor.set_value(formatted, window, cx);
+            let formatted = format_with_style(&text[range.clone()], &style);
+            text.replace_range(range, &formatted);
+            editor.set_value(text, window, cx);
         });
     }
 
@@ -483,3 +1151,84 @@ impl PostCommanderPage {
         cx.notify();
     }
 }
+
+fn result_limits(cx: &App) -> ResultLimits {
+    let settings = AppSettings::get_global(cx)
+        .postcommander()
+        .result_limits
+        .clone()
+        .unwrap_or_default();
+    ResultLimits {
+        max_rows: settings.max_rows,
+        max_bytes: settings.max_memory_mb.saturating_mul(1024 * 1024),
+    }
+}
+
+/// The statement as typed, which is what runs, what its result sub-tab
+/// shows and what history records, with what is needed to run it.
+pub(crate) fn statement_run(sql: &str, offset: Option<usize>, params: Option<BoundParams>) -> StatementRun {
+    StatementRun {
+        sql: sql.to_string(),
+        offset,
+        params,
+        outcome: StatementOutcome::Pending,
+    }
+}
+
+/// One context per base table the columns were read from, in the order
+/// its first column appears. A table joined to itself reports the same
+/// OID for both sides, so a table whose columns appear more than once is
+/// left out, and its columns stay read-only.
+fn table_contexts(columns: &[QueryColumn], tables: Vec<SourceTable>) -> Vec<TableContext> {
+    let mut contexts: Vec<(usize, TableContext)> = tables
+        .into_iter()
+        .filter(|t| t.is_base_table)
+        .filter_map(|table| {
+            let attnums: Vec<Option<i16>> = columns
+                .iter()
+                .map(|c| c.source.filter(|s| s.table_oid == table.oid).map(|s| s.attnum))
+                .collect();
+            let mut seen: Vec<i16> = attnums.iter().flatten().copied().collect();
+            let count = seen.len();
+            seen.sort_unstable();
+            seen.dedup();
+            if seen.len() != count {
+                return None;
+            }
+
+            let result_columns: Vec<Option<String>> = attnums
+                .iter()
+                .map(|attnum| {
+                    let attnum = (*attnum)?;
+                    table
+                        .columns
+                        .iter()
+                        .find(|(n, _)| *n == attnum)
+                        .map(|(_, name)| name.clone())
+                })
+                .collect();
+            let first = result_columns.iter().position(Option::is_some)?;
+            Some((
+                first,
+                TableContext {
+                    schema: table.schema,
+                    table: table.table,
+                    primary_keys: table.primary_key,
+                    foreign_keys: Arc::default(),
+                    result_columns,
+                    read_only: false,
+                },
+            ))
+        })
+        .collect();
+    contexts.sort_by_key(|(first, _)| *first);
+    contexts.into_iter().map(|(_, context)| context).collect()
+}
+
+fn skip_remaining(statements: &mut [StatementRun]) {
+    for statement in statements {
+        if matches!(statement.outcome, StatementOutcome::Pending) {
+            statement.outcome = StatementOutcome::Skipped;
+        }
+    }
+}
@@ -167,6 +167,13 @@ impl PostCommanderPage {
             QueryHistoryStatus::Success => ("check", 0x4ade80),
             QueryHistoryStatus::Error(_) => ("x", 0xf87171),
             QueryHistoryStatus::Cancelled => ("circle", text_muted),
+            QueryHistoryStatus::ServerCancelled { confirmed: true } => ("circle-x", text_muted),
+            QueryHistoryStatus::ServerCancelled { confirmed: false } => ("alert-triangle", 0xfbbf24),
+        };
+        let status_label = match &entry.status {
+            QueryHistoryStatus::ServerCancelled { confirmed: true } => Some("Cancelled on server"),
+            QueryHistoryStatus::ServerCancelled { confirmed: false } => Some("Cancel not confirmed"),
+            _ => None,
         };
 
         let entry_sql = entry.sql.clone();
@@ -219,6 +226,9 @@ impl PostCommanderPage {
                     .text_xs()
                     .text_color(rgb(text_muted))
                     .child(formatted_time)
+                    .when_some(status_label, |el, label| {
+                        el.child(div().text_color(rgb(status_color)).child(label))
+                    })
                     .child(execution_time),
             )
     }
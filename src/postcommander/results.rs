@@ -1,6 +1,10 @@
 use crate::components::DataTable;
-use crate::icons::icon_sm;
+use crate::icons::{icon, icon_sm};
+use crate::postcommander::database::{QueryError, TransactionStatus};
+use crate::postcommander::sql::conflicting_row_query;
 use crate::postcommander::page::PostCommanderPage;
+use crate::postcommander::types::{ConnectionState, StatementOutcome, TabId};
+use crate::settings::ConnectionAccess;
 use crate::theme::ActiveTheme;
 use gpui::prelude::FluentBuilder;
 use gpui::*;
@@ -28,6 +32,7 @@ impl PostCommanderPage {
         let editor = active_tab.map(|t| t.editor.clone());
         let is_loading = active_tab.map(|t| t.is_loading).unwrap_or(false);
         let query_start_time = active_tab.and_then(|t| t.query_start_time);
+        let cancel_requested = active_tab.map(|t| t.cancel_requested).unwrap_or(false);
 
         div()
             .flex_1()
@@ -83,6 +88,24 @@ impl PostCommanderPage {
                                     .child(button_text),
                             )
                     })
+                    .when(!is_loading, |el| {
+                        el.child(
+                            div()
+                                .id("run-menu-btn")
+                                .h(px(28.))
+                                .px_1()
+                                .flex()
+                                .items_center()
+                                .rounded_md()
+                                .bg(rgb(accent))
+                                .cursor_pointer()
+                                .hover(|s| s.opacity(0.9))
+                                .on_click(cx.listener(|this, event: &ClickEvent, window, cx| {
+                                    this.deploy_run_menu(event.position(), window, cx);
+                                }))
+                                .child(icon_sm("chevron-down", accent_foreground)),
+                        )
+                    })
                     .when(is_loading, |el| {
                         el.child(
                             div()
@@ -94,15 +117,28 @@ impl PostCommanderPage {
                                 .gap_2()
                                 .rounded_md()
                                 .bg(rgb(element))
-                                .cursor_pointer()
-                                .hover(move |s| s.bg(rgb(element_hover)))
-                                .on_click(cx.listener(|this, _, _, cx| {
-                                    this.cancel_query(cx);
-                                }))
-                                .child(icon_sm("square", text_muted))
-                                .child(div().text_sm().text_color(rgb(text_muted)).child("Cancel")),
+                                .when(!cancel_requested, |el| {
+                                    el.cursor_pointer()
+                                        .hover(move |s| s.bg(rgb(element_hover)))
+                                        .on_click(cx.listener(|this, _, _, cx| {
+                                            this.cancel_query(cx);
+                                        }))
+                                })
+                                .when(cancel_requested, |el| el.opacity(0.6))
+                                .child(icon_sm(
+                                    if cancel_requested { "loader-2" } else { "square" },
+                                    text_muted,
+                                ))
+                                .child(
+                                    div()
+                                        .text_sm()
+                                        .text_color(rgb(text_muted))
+                                        .child(if cancel_requested { "Cancelling..." } else { "Cancel" }),
+                                ),
                         )
                     })
+                    .child(self.render_connection_chip(cx))
+                    .child(self.render_transaction_controls(cx))
                     .child(
                         div()
                             .id("ai-btn")
@@ -146,9 +182,8 @@ impl PostCommanderPage {
         let text_muted = colors.text_muted;
         let element_hover = colors.element_hover;
         let status_success = colors.status_success;
+        let status_warning = colors.status_warning;
         let status_error = colors.status_error;
-        let status_error_background = colors.status_error_background;
-        let status_error_border = colors.status_error_border;
 
         let active_tab_id = self.active_tab_id.clone();
         let active_tab = active_tab_id
@@ -156,14 +191,24 @@ impl PostCommanderPage {
             .and_then(|id| self.tabs.iter().find(|t| &t.id == id));
 
         let result = active_tab.and_then(|t| t.result.clone());
+        let plan = active_tab.and_then(|t| t.plan.clone());
         let table_state = active_tab.map(|t| t.table_state.clone());
         let error = active_tab.and_then(|t| t.error.clone());
         let is_loading = active_tab.map(|t| t.is_loading).unwrap_or(false);
+        let has_script = active_tab.is_some_and(|t| t.statements.len() > 1);
 
-        let (execution_time, row_count) = result
+        let execution_time = result.as_ref().map(|r| r.execution_time_ms).unwrap_or(0);
+        let (row_count, shown_count) = table_state
             .as_ref()
-            .map(|r| (r.execution_time_ms, r.rows.len()))
+            .map(|state| {
+                let state = state.read(cx);
+                (state.rows().len(), state.visible_row_count())
+            })
             .unwrap_or((0, 0));
+        let truncation_message = result
+            .as_ref()
+            .and_then(|r| r.truncated)
+            .map(|t| t.message());
 
         div()
             .flex_1()
@@ -178,27 +223,29 @@ impl PostCommanderPage {
                 error.is_some(),
                 execution_time,
                 row_count,
+                shown_count,
+                truncation_message,
                 panel_background,
                 border_variant,
                 text_muted,
                 status_success,
+                status_warning,
                 status_error,
                 element_hover,
             ))
+            .when(has_script, |el| el.child(self.render_statement_tabs(cx)))
             .when_some(error.clone(), |el, err| {
                 el.child(
                     div()
                         .flex_1()
                         .p_3()
-                        .child(self.render_error_display(
-                            err,
-                            status_error,
-                            status_error_background,
-                            status_error_border,
-                        )),
+                        .child(self.render_error_display(active_tab_id, err, cx)),
                 )
             })
-            .when(result.is_some() && error.is_none(), |el| {
+            .when_some(active_tab_id.zip(plan.clone()).filter(|_| error.is_none()), |el, (tab_id, plan)| {
+                el.child(self.render_plan_view(tab_id, plan, cx))
+            })
+            .when(result.is_some() && error.is_none() && plan.is_none(), |el| {
                 el.when_some(table_state, |el, state| {
                     el.child(
                         div()
@@ -234,10 +281,13 @@ impl PostCommanderPage {
         has_error: bool,
         execution_time: u64,
         row_count: usize,
+        shown_count: usize,
+        truncation_message: Option<String>,
         panel_background: u32,
         border_variant: u32,
         text_muted: u32,
         status_success: u32,
+        status_warning: u32,
         status_error: u32,
         element_hover: u32,
     ) -> impl IntoElement {
@@ -251,7 +301,7 @@ impl PostCommanderPage {
             .border_t_1()
             .border_b_1()
             .border_color(rgb(border_variant))
-            .when(has_result, |el| {
+            .when(has_result && !is_loading, |el| {
                 el.child(
                     div()
                         .text_xs()
@@ -262,7 +312,11 @@ impl PostCommanderPage {
                     div()
                         .text_xs()
                         .text_color(rgb(status_success))
-                        .child(format!("{} rows", row_count)),
+                        .child(if shown_count == row_count {
+                            format!("{} rows", row_count)
+                        } else {
+                            format!("{} of {} rows", shown_count, row_count)
+                        }),
                 )
             })
             .when(is_loading, |el| {
@@ -270,7 +324,26 @@ impl PostCommanderPage {
                     div()
                         .text_xs()
                         .text_color(rgb(text_muted))
-                        .child("Executing query..."),
+                        .child(if has_result {
+                            "Fetching rows..."
+                        } else {
+                            "Executing query..."
+                        }),
+                )
+            })
+            .when_some(truncation_message.filter(|_| !is_loading), |el, message| {
+                el.child(
+                    div()
+                        .flex()
+                        .items_center()
+                        .gap_1()
+                        .child(icon_sm("alert-triangle", status_warning))
+                        .child(
+                            div()
+                                .text_xs()
+                                .text_color(rgb(status_warning))
+                                .child(message),
+                        ),
                 )
             })
             .when(has_error, |el| {
@@ -282,6 +355,12 @@ impl PostCommanderPage {
                 )
             })
             .child(div().flex_1())
+            .when(has_result || is_loading, |el| {
+                el.child(self.render_grid_query_controls(cx))
+            })
+            .when(has_result && !is_loading, |el| {
+                el.child(self.render_edit_mode_controls(cx))
+            })
             .child(
                 div()
                     .id("save-query-toolbar-btn")
@@ -331,19 +410,162 @@ impl PostCommanderPage {
             })
     }
 
+    /// One sub-tab per statement of the last script run.
+    fn render_statement_tabs(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+        let panel_background = colors.panel_background;
+        let border_variant = colors.border_variant;
+        let text = colors.text;
+        let text_muted = colors.text_muted;
+        let element_hover = colors.element_hover;
+        let accent = colors.accent;
+        let status_success = colors.status_success;
+        let status_error = colors.status_error;
+
+        let Some(tab) = self
+            .active_tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id))
+        else {
+            return div().id("statement-tabs");
+        };
+        // Switching results would drop edits still waiting to be applied.
+        let is_locked = tab.is_loading || tab.table_state.read(cx).has_pending_changes();
+
+        div()
+            .id("statement-tabs")
+            .h(px(30.))
+            .flex()
+            .items_center()
+            .overflow_x_scroll()
+            .bg(rgb(panel_background))
+            .border_b_1()
+            .border_color(rgb(border_variant))
+            .children(tab.statements.iter().enumerate().map(|(index, statement)| {
+                let is_active = index == tab.active_statement;
+                let (icon, icon_color, detail) = match &statement.outcome {
+                    StatementOutcome::Pending => ("circle", text_muted, "Pending".to_string()),
+                    StatementOutcome::Running => ("loader-2", accent, "Running...".to_string()),
+                    StatementOutcome::Done(result) => {
+                        let rows = if result.columns.is_empty() {
+                            format!("{} affected", result.rows_affected)
+                        } else {
+                            format!("{} rows", result.rows.len())
+                        };
+                        ("check", status_success, format!("{} · {}ms", rows, result.execution_time_ms))
+                    }
+                    StatementOutcome::Failed { execution_time_ms, .. } => {
+                        ("alert-circle", status_error, format!("Error · {}ms", execution_time_ms))
+                    }
+                    StatementOutcome::Skipped => ("circle", text_muted, "Not run".to_string()),
+                };
+                let preview: String = statement
+                    .sql
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .chars()
+                    .take(40)
+                    .collect();
+
+                div()
+                    .id(SharedString::from(format!("statement-tab-{}", index)))
+                    .h_full()
+                    .px_3()
+                    .flex()
+                    .flex_shrink_0()
+                    .items_center()
+                    .gap_2()
+                    .border_b_2()
+                    .when(is_active, |el| el.border_color(rgb(accent)))
+                    .when(!is_active, |el| el.border_color(transparent_black()))
+                    .when(!is_locked, |el| {
+                        el.cursor_pointer()
+                            .hover(move |s| s.bg(rgb(element_hover)))
+                            .on_click(cx.listener(move |this, _, _, cx| {
+                                this.select_statement_result(index, cx);
+                            }))
+                    })
+                    .child(icon_sm(icon, icon_color))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(if is_active { text } else { text_muted }))
+                            .child(format!("{}. {}", index + 1, preview)),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(text_muted))
+                            .child(detail),
+                    )
+            }))
+    }
+
     fn render_error_display(
         &self,
-        error: String,
-        status_error: u32,
-        status_error_background: u32,
-        status_error_border: u32,
+        tab_id: Option<TabId>,
+        error: QueryError,
+        cx: &mut Context<Self>,
     ) -> impl IntoElement {
+        let colors = *cx.theme().colors();
+        let status_error = colors.status_error;
+        let text = colors.text;
+        let text_muted = colors.text_muted;
+        let accent = colors.accent;
+        let element_hover = colors.element_hover;
+
+        let connection_id = tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id))
+            .and_then(|t| t.connection_id.clone());
+        let conflicting_row = match (&error.schema, &error.table, &error.detail) {
+            (Some(schema), Some(table), Some(detail)) if error.is_unique_violation() => {
+                conflicting_row_query(schema, table, detail)
+            }
+            _ => None,
+        };
+        let related_table = error.schema.clone().zip(error.table.clone());
+
+        let field = |label: &'static str, value: String| {
+            div()
+                .flex()
+                .gap_2()
+                .text_xs()
+                .child(div().w(px(72.)).text_color(rgb(text_muted)).child(label))
+                .child(div().flex_1().text_color(rgb(text)).child(value))
+        };
+        let table_link = |id: &'static str, value: String| {
+            let related = related_table.clone();
+            div()
+                .id(id)
+                .text_color(rgb(accent))
+                .cursor_pointer()
+                .hover(|s| s.underline())
+                .child(value)
+                .when_some(tab_id.zip(related), |el, (tab_id, (schema, table))| {
+                    el.on_click(cx.listener(move |this, _, _, cx| {
+                        this.show_table_structure(tab_id, schema.clone(), table.clone(), cx);
+                    }))
+                })
+        };
+        let link_field = |label: &'static str, link: Stateful<Div>| {
+            div()
+                .flex()
+                .gap_2()
+                .text_xs()
+                .child(div().w(px(72.)).text_color(rgb(text_muted)).child(label))
+                .child(link)
+        };
+
         div()
             .p_4()
             .rounded_md()
-            .bg(rgb(status_error_background))
+            .bg(rgb(colors.status_error_background))
             .border_1()
-            .border_color(rgb(status_error_border))
+            .border_color(rgb(colors.status_error_border))
+            .flex()
+            .flex_col()
+            .gap_2()
             .child(
                 div()
                     .flex()
@@ -355,9 +577,77 @@ impl PostCommanderPage {
                             .flex_1()
                             .text_sm()
                             .text_color(rgb(status_error))
-                            .child(error),
-                    ),
+                            .child(error.message.clone()),
+                    )
+                    .when_some(error.code.clone(), |el, code| {
+                        let label = match error.condition() {
+                            Some(condition) => format!("{} {}", code, condition),
+                            None => code,
+                        };
+                        el.child(
+                            div()
+                                .px_1()
+                                .rounded_sm()
+                                .border_1()
+                                .border_color(rgb(colors.status_error_border))
+                                .text_xs()
+                                .font_family("monospace")
+                                .text_color(rgb(status_error))
+                                .child(label),
+                        )
+                    }),
             )
+            .child(
+                div()
+                    .pl_6()
+                    .flex()
+                    .flex_col()
+                    .gap_1()
+                    .when_some(error.detail.clone(), |el, detail| el.child(field("Detail", detail)))
+                    .when_some(error.hint.clone(), |el, hint| el.child(field("Hint", hint)))
+                    .when_some(related_table.clone(), |el, (schema, table)| {
+                        el.child(link_field(
+                            "Table",
+                            table_link("error-table-link", format!("{}.{}", schema, table)),
+                        ))
+                    })
+                    .when_some(error.column.clone(), |el, column| el.child(field("Column", column)))
+                    .when_some(error.constraint.clone(), |el, constraint| {
+                        if related_table.is_some() {
+                            el.child(link_field("Constraint", table_link("error-constraint-link", constraint)))
+                        } else {
+                            el.child(field("Constraint", constraint))
+                        }
+                    }),
+            )
+            .when_some(connection_id.zip(conflicting_row), |el, (connection_id, sql)| {
+                el.child(
+                    div().pl_6().flex().child(
+                        div()
+                            .id("show-conflicting-row-btn")
+                            .h(px(24.))
+                            .px_2()
+                            .flex()
+                            .items_center()
+                            .gap_1()
+                            .rounded_md()
+                            .border_1()
+                            .border_color(rgb(colors.status_error_border))
+                            .cursor_pointer()
+                            .hover(move |s| s.bg(rgb(element_hover)))
+                            .on_click(cx.listener(move |this, _, window, cx| {
+                                this.show_conflicting_row(&connection_id, &sql, window, cx);
+                            }))
+                            .child(icon_sm("search", text))
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .text_color(rgb(text))
+                                    .child("Show conflicting row"),
+                            ),
+                    ),
+                )
+            })
     }
 
     pub fn render_tabs_bar(&self, cx: &mut Context<Self>) -> impl IntoElement {
@@ -391,7 +681,17 @@ impl PostCommanderPage {
                         let tab_id = tab.id;
                         let close_id = tab.id;
                         let tab_name = tab.name.clone();
-                        let tab_db = tab.database.clone();
+                        let tab_connection = tab
+                            .connection_id
+                            .as_deref()
+                            .and_then(|id| self.connection(id))
+                            .map(|c| {
+                                (
+                                    c.profile.name.clone(),
+                                    c.profile.environment.color(),
+                                    c.profile.access,
+                                )
+                            });
 
                         div()
                             .id(SharedString::from(format!("tab-{}", tab.id)))
@@ -406,13 +706,7 @@ impl PostCommanderPage {
                             .when(!is_active, |el| el.border_color(transparent_black()))
                             .hover(move |s| s.bg(rgb(element_hover)))
                             .on_click(cx.listener(move |this, _, window, cx| {
-                                this.active_tab_id = Some(tab_id);
-                                if let Some(tab) = this.tabs.iter().find(|t| t.id == tab_id) {
-                                    tab.editor.update(cx, |state, cx| {
-                                        state.focus(window, cx);
-                                    });
-                                }
-                                cx.notify();
+                                this.activate_tab(tab_id, window, cx);
                             }))
                             .child(
                                 div()
@@ -420,7 +714,22 @@ impl PostCommanderPage {
                                     .text_color(rgb(if is_active { text } else { text_muted }))
                                     .child(tab_name),
                             )
-                            .child(div().text_xs().text_color(rgb(text_muted)).child(tab_db))
+                            .when_some(tab_connection, |el, (name, color, access)| {
+                                el.child(
+                                    div()
+                                        .flex()
+                                        .items_center()
+                                        .gap_1()
+                                        .child(match access {
+                                            ConnectionAccess::ReadWrite => {
+                                                div().size(px(6.)).rounded_full().bg(rgb(color)).into_any_element()
+                                            }
+                                            ConnectionAccess::Protected => icon("shield-alert", px(12.), color).into_any_element(),
+                                            ConnectionAccess::ReadOnly => icon("lock", px(12.), color).into_any_element(),
+                                        })
+                                        .child(div().text_xs().text_color(rgb(text_muted)).child(name)),
+                                )
+                            })
                             .child(
                                 div()
                                     .id(SharedString::from(format!("close-{}", close_id)))
@@ -465,7 +774,7 @@ impl PostCommanderPage {
         let accent = colors.accent;
         let accent_foreground = colors.accent_foreground;
 
-        let is_connected = matches!(self.connection_state, super::types::ConnectionState::Connected);
+        let is_connected = self.connections.iter().any(|c| c.is_connected());
 
         div()
             .flex_1()
@@ -552,8 +861,7 @@ impl PostCommanderPage {
                                 .cursor_pointer()
                                 .hover(|s| s.opacity(0.9))
                                 .on_click(cx.listener(|this, _, _, cx| {
-                                    this.connection_dialog.is_visible = true;
-                                    cx.notify();
+                                    this.open_connection_dialog(None, cx);
                                 }))
                                 .child(
                                     div()
@@ -595,6 +903,7 @@ impl PostCommanderPage {
                                         .text_xs()
                                         .text_color(rgb(text_muted))
                                         .child("⌘T new")
+                                        .child("⌘P connection")
                                         .child("⌘O open")
                                         .child("⌘↵ run"),
                                 ),
@@ -614,23 +923,29 @@ impl PostCommanderPage {
         let status_warning = colors.status_warning;
         let status_error = colors.status_error;
 
-        let status_color = match &self.connection_state {
-            super::types::ConnectionState::Connected => status_success,
-            super::types::ConnectionState::Connecting => status_warning,
-            super::types::ConnectionState::Disconnected => text_muted,
-            super::types::ConnectionState::Error(_) => status_error,
+        let connection = self.current_connection();
+        let status_color = match connection.map(|c| &c.state) {
+            Some(ConnectionState::Connected) => status_success,
+            Some(ConnectionState::Connecting) => status_warning,
+            Some(ConnectionState::Error(_)) => status_error,
+            Some(ConnectionState::Disconnected) | None => text_muted,
         };
 
-        let is_connected = matches!(self.connection_state, super::types::ConnectionState::Connected);
+        let is_connected = connection.is_some_and(|c| c.is_connected());
 
-        let status_text = match &self.connection_state {
-            super::types::ConnectionState::Connected => format!("{}:{}", self.get_conn_host(), self.get_conn_port()),
-            super::types::ConnectionState::Connecting => "Connecting...".to_string(),
-            super::types::ConnectionState::Disconnected => "Not connected".to_string(),
-            super::types::ConnectionState::Error(e) => format!("Error: {}", e),
+        let status_text = match connection.map(|c| (&c.state, &c.profile)) {
+            Some((ConnectionState::Connected, profile)) => {
+                format!("{}:{}", profile.connection.host, profile.connection.port)
+            }
+            Some((ConnectionState::Connecting, _)) => "Connecting...".to_string(),
+            Some((ConnectionState::Error(e), _)) => format!("Error: {}", e),
+            Some((ConnectionState::Disconnected, _)) | None => "Not connected".to_string(),
         };
 
-        let database = self.get_conn_database().to_string();
+        let database = connection
+            .map(|c| c.profile.connection.database.clone())
+            .unwrap_or_default();
+        let environment = connection.map(|c| c.profile.environment);
 
         div()
             .h(px(24.))
@@ -646,6 +961,15 @@ impl PostCommanderPage {
                     .items_center()
                     .gap_2()
                     .child(div().size(px(8.)).rounded_full().bg(rgb(status_color)))
+                    .when_some(environment, |el, environment| {
+                        el.child(
+                            div()
+                                .text_xs()
+                                .font_weight(FontWeight::SEMIBOLD)
+                                .text_color(rgb(environment.color()))
+                                .child(environment.label().to_uppercase()),
+                        )
+                    })
                     .child(
                         div()
                             .text_xs()
@@ -664,6 +988,8 @@ impl PostCommanderPage {
                     }),
             )
             .child(div().flex_1())
+            .child(self.render_transaction_indicator(cx))
+            .child(self.render_fetch_progress(text_muted, cx))
             .child(self.render_export_message(text_muted, cx))
             .child(
                 div()
@@ -673,6 +999,168 @@ impl PostCommanderPage {
             )
     }
 
+    /// Shows which connection the active tab runs on; clicking opens the
+    /// quick switcher.
+    fn render_connection_chip(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+        let element = colors.element;
+        let element_hover = colors.element_hover;
+        let text = colors.text;
+        let text_muted = colors.text_muted;
+
+        let (label, color) = match self.current_connection() {
+            Some(c) => (
+                format!("{} · {}", c.profile.name, c.profile.environment.label()),
+                c.profile.environment.color(),
+            ),
+            None => ("No connection".to_string(), text_muted),
+        };
+
+        div()
+            .id("connection-chip")
+            .h(px(28.))
+            .px_3()
+            .flex()
+            .items_center()
+            .gap_2()
+            .rounded_md()
+            .bg(rgb(element))
+            .border_l_2()
+            .border_color(rgb(color))
+            .cursor_pointer()
+            .hover(move |s| s.bg(rgb(element_hover)))
+            .on_mouse_down(
+                MouseButton::Left,
+                cx.listener(|this, event: &MouseDownEvent, window, cx| {
+                    this.deploy_connection_switcher(event.position, window, cx);
+                }),
+            )
+            .child(icon_sm("database", color))
+            .child(div().text_sm().text_color(rgb(text)).child(label))
+            .child(icon_sm("chevron-down", text_muted))
+    }
+
+    /// Autocommit/manual toggle for the active tab, plus Commit and
+    /// Rollback while a transaction is open.
+    fn render_transaction_controls(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+        let element = colors.element;
+        let element_hover = colors.element_hover;
+        let text = colors.text;
+        let text_muted = colors.text_muted;
+        let status_warning = colors.status_warning;
+
+        let tab = self
+            .active_tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id));
+        let is_loading = tab.is_some_and(|t| t.is_loading);
+        let transaction = tab.and_then(|t| t.transaction.as_ref());
+        let manual = transaction.is_some();
+        let is_busy = transaction.is_some_and(|t| t.is_busy);
+        let status = transaction.map(|t| t.status).unwrap_or_default();
+        let can_end = manual && !is_busy && !is_loading && status != TransactionStatus::Idle;
+        let can_commit = can_end && status != TransactionStatus::Failed;
+
+        let button = move |id: &'static str, icon: &'static str, label: &'static str, enabled: bool| {
+            div()
+                .id(id)
+                .h(px(28.))
+                .px_3()
+                .flex()
+                .items_center()
+                .gap_2()
+                .rounded_md()
+                .bg(rgb(element))
+                .when(enabled, |el| {
+                    el.cursor_pointer().hover(move |s| s.bg(rgb(element_hover)))
+                })
+                .when(!enabled, |el| el.opacity(0.5))
+                .child(icon_sm(icon, text_muted))
+                .child(div().text_sm().text_color(rgb(text)).child(label))
+        };
+
+        div()
+            .flex()
+            .items_center()
+            .gap_2()
+            .child(
+                button(
+                    "transaction-mode-btn",
+                    if manual { "lock" } else { "unlock" },
+                    if manual { "Manual" } else { "Auto-commit" },
+                    !is_loading && !is_busy,
+                )
+                .when(manual, |el| el.border_1().border_color(rgb(status_warning)))
+                .on_click(cx.listener(|this, _, _, cx| {
+                    this.toggle_manual_transactions(cx);
+                })),
+            )
+            .when(manual, |el| {
+                el.child(
+                    button("commit-btn", "check", "Commit", can_commit).when(can_commit, |el| {
+                        el.on_click(cx.listener(|this, _, _, cx| {
+                            this.commit_transaction(cx);
+                        }))
+                    }),
+                )
+                .child(
+                    button("rollback-btn", "rotate-ccw", "Rollback", can_end).when(can_end, |el| {
+                        el.on_click(cx.listener(|this, _, _, cx| {
+                            this.rollback_transaction(cx);
+                        }))
+                    }),
+                )
+            })
+    }
+
+    fn render_transaction_indicator(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+
+        let status = self
+            .active_tab_id
+            .and_then(|id| self.tabs.iter().find(|t| t.id == id))
+            .and_then(|t| t.transaction.as_ref())
+            .map(|t| t.status);
+        let indicator = match status {
+            Some(TransactionStatus::Idle) => Some(("MANUAL COMMIT", colors.text_muted)),
+            Some(TransactionStatus::InTransaction) => Some(("IN TRANSACTION", colors.status_warning)),
+            Some(TransactionStatus::Failed) => Some(("FAILED TRANSACTION", colors.status_error)),
+            None => None,
+        };
+
+        div()
+            .mr_4()
+            .when_some(indicator, |el, (label, color)| {
+                el.flex()
+                    .items_center()
+                    .gap_1()
+                    .child(div().size(px(6.)).rounded_full().bg(rgb(color)))
+                    .child(
+                        div()
+                            .text_xs()
+                            .font_weight(FontWeight::SEMIBOLD)
+                            .text_color(rgb(color))
+                            .child(label),
+                    )
+            })
+    }
+
+    fn render_fetch_progress(&self, text_muted: u32, cx: &mut Context<Self>) -> impl IntoElement {
+        let fetched = self.active_tab_id.as_ref()
+            .and_then(|id| self.tabs.iter().find(|t| &t.id == id))
+            .filter(|tab| tab.is_loading && tab.result.is_some())
+            .map(|tab| tab.table_state.read(cx).rows().len());
+
+        div()
+            .text_xs()
+            .text_color(rgb(text_muted))
+            .mr_4()
+            .when_some(fetched, |el, rows| el.child(format!("{} rows fetched", rows)))
+    }
+
     fn render_export_message(&self, text_muted: u32, _cx: &mut Context<Self>) -> impl IntoElement {
         let export_msg = self.active_tab_id.as_ref()
             .and_then(|id| self.tabs.iter().find(|t| &t.id == id))
@@ -343,7 +343,7 @@ impl PostCommanderPage {
 
         let now = chrono::Utc::now().to_rfc3339();
 
-        if let Some(editing_id) = &self.save_query_dialog.editing_id {
+        let saved_id = if let Some(editing_id) = &self.save_query_dialog.editing_id {
             let id = editing_id.clone();
             AppSettings::update_global(cx, |settings| {
                 let pc = settings.postcommander_mut();
@@ -355,6 +355,7 @@ impl PostCommanderPage {
                     entry.sql = sql.clone();
                 });
             });
+            id
         } else {
             let entry = SavedQueryEntry {
                 id: Uuid::new_v4().to_string(),
@@ -364,17 +365,26 @@ impl PostCommanderPage {
                 description,
                 created_at: now,
                 last_used: None,
+                parameters: Vec::new(),
             };
+            let id = entry.id.clone();
 
             AppSettings::update_global(cx, |settings| {
                 let pc = settings.postcommander_mut();
                 let saved = pc.saved_queries.get_or_insert_with(SavedQueriesSettings::default);
                 saved.add_entry(entry);
             });
-        }
+            id
+        };
 
         AppSettings::get_global(cx).save();
 
+        if let Some(tab) = self.tabs.iter_mut().find(|t| Some(t.id) == self.active_tab_id) {
+            tab.saved_query_id = Some(saved_id);
+            let tab_id = tab.id;
+            self.refresh_diagnostics(tab_id, cx);
+        }
+
         self.save_query_dialog.is_visible = false;
         self.save_query_dialog.editing_id = None;
         cx.notify();
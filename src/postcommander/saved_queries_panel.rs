@@ -132,6 +132,7 @@ impl PostCommanderPage {
         };
 
         let entry_sql = entry.sql.clone();
+        let entry_id = entry.id.clone();
         let entry_sql_for_menu = entry.sql.clone();
         let entry_id_for_menu = entry.id.clone();
         let folder_display = entry.folder.clone();
@@ -155,10 +156,11 @@ impl PostCommanderPage {
             .when(!is_context_target, |el| el.hover(move |s| s.bg(rgb(element_hover))))
             .cursor_pointer()
             .on_click(cx.listener(move |this, event: &ClickEvent, window, cx| {
-                if let Some(tab) = this.tabs.iter().find(|t| Some(t.id) == this.active_tab_id) {
+                if let Some(tab) = this.tabs.iter_mut().find(|t| Some(t.id) == this.active_tab_id) {
                     tab.editor.update(cx, |editor, cx| {
                         editor.set_value(entry_sql.clone(), window, cx);
                     });
+                    tab.saved_query_id = Some(entry_id.clone());
                 }
 
                 if event.click_count() == 2 {
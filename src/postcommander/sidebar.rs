@@ -1,10 +1,10 @@
 use crate::icons::{icon_md, icon_sm};
+use crate::postcommander::connections::{connection_node_key, database_node_key};
 use crate::postcommander::page::PostCommanderPage;
-use crate::postcommander::types::{ConnectionState, SidebarTab};
+use crate::postcommander::types::{ConnectionSession, ConnectionState, SidebarTab};
 use crate::theme::ActiveTheme;
 use gpui::prelude::FluentBuilder;
 use gpui::*;
-use std::sync::Arc;
 
 impl PostCommanderPage {
     pub fn render_sidebar(&self, cx: &mut Context<Self>) -> impl IntoElement {
@@ -19,18 +19,28 @@ impl PostCommanderPage {
         let status_warning = colors.status_warning;
         let status_error = colors.status_error;
 
-        let status_color = match &self.connection_state {
-            ConnectionState::Connected => status_success,
-            ConnectionState::Connecting => status_warning,
-            ConnectionState::Disconnected => text_muted,
-            ConnectionState::Error(_) => status_error,
+        let connected = self.connections.iter().filter(|c| c.is_connected()).count();
+        let connecting = self
+            .connections
+            .iter()
+            .any(|c| c.state == ConnectionState::Connecting);
+
+        let status_color = if connected > 0 {
+            status_success
+        } else if connecting {
+            status_warning
+        } else if self.connections.iter().any(|c| matches!(c.state, ConnectionState::Error(_))) {
+            status_error
+        } else {
+            text_muted
         };
 
-        let status_text = match &self.connection_state {
-            ConnectionState::Connected => "Connected".to_string(),
-            ConnectionState::Connecting => "Connecting...".to_string(),
-            ConnectionState::Disconnected => "Disconnected".to_string(),
-            ConnectionState::Error(_) => "Error".to_string(),
+        let status_text = if self.connections.is_empty() {
+            "No connections".to_string()
+        } else if connecting && connected == 0 {
+            "Connecting...".to_string()
+        } else {
+            format!("{} of {} connected", connected, self.connections.len())
         };
 
         div()
@@ -160,18 +170,8 @@ impl PostCommanderPage {
         let text = colors.text;
         let element_hover = colors.element_hover;
         let accent = colors.accent;
-        let status_success = colors.status_success;
 
-        let is_connected = matches!(self.connection_state, ConnectionState::Connected);
-        let error_text = match &self.connection_state {
-            ConnectionState::Connecting => "Connecting...".to_string(),
-            ConnectionState::Error(e) => format!("Error: {}", e),
-            _ => "Not connected".to_string(),
-        };
-
-        let host = self.get_conn_host();
-        let port = self.get_conn_port();
-        let database = self.get_conn_database();
+        let has_connections = !self.connections.is_empty();
 
         div()
             .flex()
@@ -181,11 +181,11 @@ impl PostCommanderPage {
             .min_h_0()
             .child(self.render_sidebar_header(surface, border_variant, text_muted, text_placeholder))
             .child(self.render_connect_button(cx, element_hover, accent, text, text_muted))
-            .when(is_connected, |el| {
-                el.child(self.render_tree_view(cx, host.to_string(), port.to_string(), database.to_string(), element_hover, text_muted, text, accent, status_success))
+            .when(has_connections, |el| {
+                el.child(self.render_tree_view(cx, element_hover, text_muted, text))
             })
-            .when(!is_connected, |el| {
-                el.child(self.render_disconnected_state(text_muted, error_text))
+            .when(!has_connections, |el| {
+                el.child(self.render_disconnected_state(text_muted, "No connections".to_string()))
             })
     }
 
@@ -245,8 +245,7 @@ impl PostCommanderPage {
                     .cursor_pointer()
                     .hover(move |s| s.bg(rgb(element_hover)))
                     .on_click(cx.listener(|this, _, _, cx| {
-                        this.connection_dialog.is_visible = true;
-                        cx.notify();
+                        this.open_connection_dialog(None, cx);
                     }))
                     .child(icon_sm("plug", accent))
                     .child(
@@ -254,7 +253,7 @@ impl PostCommanderPage {
                             .flex_1()
                             .text_sm()
                             .text_color(rgb(text))
-                            .child("Connect to Server"),
+                            .child("New Connection"),
                     )
                     .child(icon_sm("plus", text_muted)),
             )
@@ -263,82 +262,146 @@ impl PostCommanderPage {
     fn render_tree_view(
         &self,
         cx: &mut Context<Self>,
-        host: String,
-        port: String,
-        database: String,
         element_hover: u32,
         text_muted: u32,
         text: u32,
-        accent: u32,
-        status_success: u32,
     ) -> impl IntoElement {
-        let server_expanded = self.expanded_nodes.contains("server");
-        let db_expanded = self.expanded_nodes.contains("database");
-        let schemas_loading = self.schemas_loading;
-        let schemas = self.schemas.clone();
-
         div()
             .id("sidebar-tree-scroll")
             .flex_1()
             .min_h_0()
             .overflow_y_scroll()
             .p_2()
+            .children(self.connections.iter().map(|session| {
+                self.render_connection_node(cx, session, element_hover, text_muted, text)
+            }))
+    }
+
+    fn render_connection_node(
+        &self,
+        cx: &mut Context<Self>,
+        session: &ConnectionSession,
+        element_hover: u32,
+        text_muted: u32,
+        text: u32,
+    ) -> impl IntoElement {
+        let theme = cx.theme();
+        let colors = theme.colors();
+        let status_success = colors.status_success;
+        let status_warning = colors.status_warning;
+        let status_error = colors.status_error;
+
+        let connection_id = session.id().to_string();
+        let node_key = connection_node_key(&connection_id);
+        let expanded = self.expanded_nodes.contains(&node_key);
+        let is_current = self.current_connection().map(|c| c.id()) == Some(session.id());
+        let environment = session.profile.environment;
+        let status_color = match &session.state {
+            ConnectionState::Connected => status_success,
+            ConnectionState::Connecting => status_warning,
+            ConnectionState::Disconnected => text_muted,
+            ConnectionState::Error(_) => status_error,
+        };
+        let detail = match &session.state {
+            ConnectionState::Connected => None,
+            ConnectionState::Connecting => Some("Connecting...".to_string()),
+            ConnectionState::Disconnected => Some("Disconnected".to_string()),
+            ConnectionState::Error(e) => Some(format!("Error: {}", e)),
+        };
+        let id_click = connection_id.clone();
+        let id_menu = connection_id.clone();
+
+        div()
             .child(
                 div()
-                    .id("server-node")
+                    .id(SharedString::from(format!("connection-{}", connection_id)))
                     .px_2()
                     .py_1()
                     .flex()
                     .items_center()
                     .gap_2()
                     .rounded_md()
+                    .when(is_current, |el| el.bg(rgb(element_hover)))
                     .hover(move |s| s.bg(rgb(element_hover)))
                     .cursor_pointer()
-                    .on_click(cx.listener(|this, _, _, cx| {
-                        this.toggle_node("server", cx);
+                    .on_click(cx.listener(move |this, event: &ClickEvent, _, cx| {
+                        if event.click_count() == 2 {
+                            this.switch_connection(&id_click, cx);
+                            return;
+                        }
+                        let disconnected = this
+                            .connection(&id_click)
+                            .is_some_and(|c| matches!(c.state, ConnectionState::Disconnected | ConnectionState::Error(_)));
+                        if disconnected {
+                            this.connect_profile(&id_click, cx);
+                        }
+                        this.toggle_node(&connection_node_key(&id_click), cx);
                     }))
+                    .on_mouse_down(
+                        MouseButton::Right,
+                        cx.listener(move |this, event: &MouseDownEvent, window, cx| {
+                            this.deploy_connection_context_menu(id_menu.clone(), event.position, window, cx);
+                        }),
+                    )
                     .child(icon_sm(
-                        if server_expanded { "chevron-down" } else { "chevron-right" },
+                        if expanded { "chevron-down" } else { "chevron-right" },
                         text_muted,
                     ))
-                    .child(icon_sm("server", accent))
+                    .child(icon_sm("server", environment.color()))
                     .child(
                         div()
                             .flex_1()
+                            .min_w_0()
+                            .overflow_hidden()
+                            .whitespace_nowrap()
+                            .text_ellipsis()
                             .text_sm()
                             .text_color(rgb(text))
-                            .child(format!("{}:{}", host, port)),
+                            .child(session.profile.name.clone()),
+                    )
+                    .child(
+                        div()
+                            .px_1()
+                            .rounded(px(3.))
+                            .border_1()
+                            .border_color(rgb(environment.color()))
+                            .text_xs()
+                            .text_color(rgb(environment.color()))
+                            .child(environment.label()),
                     )
-                    .child(div().size(px(8.)).rounded_full().bg(rgb(status_success))),
+                    .child(div().size(px(8.)).rounded_full().bg(rgb(status_color))),
             )
-            .when(server_expanded, |el| {
-                el.child(self.render_database_node(
-                    cx,
-                    database,
-                    db_expanded,
-                    schemas_loading,
-                    schemas,
-                    element_hover,
-                    text_muted,
-                    text,
-                ))
+            .when(expanded, |el| match detail {
+                Some(detail) => el.child(
+                    div()
+                        .pl_6()
+                        .px_2()
+                        .py_1()
+                        .text_xs()
+                        .text_color(rgb(text_muted))
+                        .child(detail),
+                ),
+                None => el.child(self.render_database_node(cx, session, element_hover, text_muted, text)),
             })
     }
 
     fn render_database_node(
         &self,
         cx: &mut Context<Self>,
-        database: String,
-        db_expanded: bool,
-        schemas_loading: bool,
-        schemas: Arc<super::types::SchemaMap>,
+        session: &ConnectionSession,
         element_hover: u32,
         text_muted: u32,
         text: u32,
     ) -> impl IntoElement {
+        let connection_id = session.id().to_string();
+        let db_key = database_node_key(&connection_id);
+        let db_expanded = self.expanded_nodes.contains(&db_key);
+        let schemas_loading = session.schemas_loading;
+        let schemas = session.schemas.clone();
+
         div().pl_4().child(
             div()
-                .id("database-node")
+                .id(SharedString::from(format!("database-{}", connection_id)))
                 .px_2()
                 .py_1()
                 .flex()
@@ -347,15 +410,20 @@ impl PostCommanderPage {
                 .rounded_md()
                 .hover(move |s| s.bg(rgb(element_hover)))
                 .cursor_pointer()
-                .on_click(cx.listener(|this, _, _, cx| {
-                    this.toggle_node("database", cx);
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.toggle_node(&db_key, cx);
                 }))
                 .child(icon_sm(
                     if db_expanded { "chevron-down" } else { "chevron-right" },
                     text_muted,
                 ))
                 .child(icon_sm("database", text_muted))
-                .child(div().text_sm().text_color(rgb(text)).child(database.clone())),
+                .child(
+                    div()
+                        .text_sm()
+                        .text_color(rgb(text))
+                        .child(session.profile.connection.database.clone()),
+                ),
         )
         .when(db_expanded, |el| {
             el.child(
@@ -382,7 +450,7 @@ impl PostCommanderPage {
                         )
                     })
                     .children(schemas.iter().map(|(schema_name, objects)| {
-                        self.render_schema_node(cx, schema_name, objects, element_hover, text_muted, text)
+                        self.render_schema_node(cx, &connection_id, schema_name, objects, element_hover, text_muted, text)
                     })),
             )
         })
@@ -391,28 +459,28 @@ impl PostCommanderPage {
     fn render_schema_node(
         &self,
         cx: &mut Context<Self>,
+        connection_id: &str,
         schema_name: &str,
         objects: &super::types::SchemaObjects,
         element_hover: u32,
         text_muted: u32,
         text: u32,
     ) -> impl IntoElement {
-        let schema_key = format!("schema:{}", schema_name);
+        let schema_key = format!("schema:{}:{}", connection_id, schema_name);
         let schema_expanded = self.expanded_nodes.contains(&schema_key);
-        let tables_key = format!("tables:{}", schema_name);
-        let views_key = format!("views:{}", schema_name);
+        let tables_key = format!("tables:{}:{}", connection_id, schema_name);
+        let views_key = format!("views:{}:{}", connection_id, schema_name);
         let tables_expanded = self.expanded_nodes.contains(&tables_key);
         let views_expanded = self.expanded_nodes.contains(&views_key);
         let tables = objects.tables.clone();
         let views = objects.views.clone();
-        let schema_name_clone = schema_name.to_string();
         let schema_name_tables = schema_name.to_string();
         let schema_name_views = schema_name.to_string();
 
         div()
             .child(
                 div()
-                    .id(SharedString::from(format!("schema-{}", schema_name)))
+                    .id(SharedString::from(format!("schema-{}-{}", connection_id, schema_name)))
                     .px_2()
                     .py_1()
                     .flex()
@@ -422,7 +490,7 @@ impl PostCommanderPage {
                     .hover(move |s| s.bg(rgb(element_hover)))
                     .cursor_pointer()
                     .on_click(cx.listener(move |this, _, _, cx| {
-                        this.toggle_node(&format!("schema:{}", schema_name_clone), cx);
+                        this.toggle_node(&schema_key, cx);
                     }))
                     .child(icon_sm(
                         if schema_expanded { "chevron-down" } else { "chevron-right" },
@@ -443,6 +511,7 @@ impl PostCommanderPage {
                         .when(!tables.is_empty(), |el| {
                             el.child(self.render_tables_node(
                                 cx,
+                                connection_id,
                                 &tables,
                                 tables_expanded,
                                 schema_name_tables,
@@ -454,6 +523,7 @@ impl PostCommanderPage {
                         .when(!views.is_empty(), |el| {
                             el.child(self.render_views_node(
                                 cx,
+                                connection_id,
                                 &views,
                                 views_expanded,
                                 schema_name_views,
@@ -469,6 +539,7 @@ impl PostCommanderPage {
     fn render_tables_node(
         &self,
         cx: &mut Context<Self>,
+        connection_id: &str,
         tables: &[String],
         tables_expanded: bool,
         schema_name: String,
@@ -476,7 +547,7 @@ impl PostCommanderPage {
         text_muted: u32,
         text: u32,
     ) -> impl IntoElement {
-        let schema_name_click = schema_name.clone();
+        let tables_key = format!("tables:{}:{}", connection_id, schema_name);
         let schema_name_for_items = schema_name.clone();
         let tables_clone = tables.to_vec();
         let context_menu_table = self
@@ -487,7 +558,7 @@ impl PostCommanderPage {
         div()
             .child(
                 div()
-                    .id(SharedString::from(format!("tables-{}", schema_name)))
+                    .id(SharedString::from(format!("tables-{}-{}", connection_id, schema_name)))
                     .px_2()
                     .py_1()
                     .flex()
@@ -497,7 +568,7 @@ impl PostCommanderPage {
                     .hover(move |s| s.bg(rgb(element_hover)))
                     .cursor_pointer()
                     .on_click(cx.listener(move |this, _, _, cx| {
-                        this.toggle_node(&format!("tables:{}", schema_name_click), cx);
+                        this.toggle_node(&tables_key, cx);
                     }))
                     .child(icon_sm(
                         if tables_expanded { "chevron-down" } else { "chevron-right" },
@@ -518,10 +589,13 @@ impl PostCommanderPage {
                         let table_name_dbl = table.clone();
                         let schema_for_menu = schema_name_for_items.clone();
                         let schema_for_dbl = schema_name_for_items.clone();
-                        let is_context_target = context_menu_table.as_ref() == Some(table);
+                        let connection_for_menu = connection_id.to_string();
+                        let connection_for_dbl = connection_id.to_string();
+                        let menu_key = format!("{}:{}.{}", connection_id, schema_for_menu, table);
+                        let is_context_target = context_menu_table.as_ref() == Some(&menu_key);
 
                         div()
-                            .id(SharedString::from(format!("table-{}-{}", schema_for_menu, table_name)))
+                            .id(SharedString::from(format!("table-{}", menu_key)))
                             .px_2()
                             .py_1()
                             .flex()
@@ -544,7 +618,7 @@ impl PostCommanderPage {
                             )
                             .on_click(cx.listener(move |this, event: &ClickEvent, window, cx| {
                                 if event.click_count() == 2 {
-                                    this.query_table(&schema_for_dbl, &table_name_dbl, window, cx);
+                                    this.query_table(&connection_for_dbl, &schema_for_dbl, &table_name_dbl, window, cx);
                                 }
                             }))
                             .on_mouse_down(
@@ -552,6 +626,7 @@ impl PostCommanderPage {
                                 cx.listener(move |this, event: &MouseDownEvent, window, cx| {
                                     this.deploy_table_context_menu(
                                         event.position,
+                                        connection_for_menu.clone(),
                                         schema_for_menu.clone(),
                                         table_name.clone(),
                                         window,
@@ -567,6 +642,7 @@ impl PostCommanderPage {
     fn render_views_node(
         &self,
         cx: &mut Context<Self>,
+        connection_id: &str,
         views: &[String],
         views_expanded: bool,
         schema_name: String,
@@ -574,14 +650,14 @@ impl PostCommanderPage {
         text_muted: u32,
         text: u32,
     ) -> impl IntoElement {
-        let schema_name_click = schema_name.clone();
+        let views_key = format!("views:{}:{}", connection_id, schema_name);
         let schema_name_for_items = schema_name.clone();
         let views_clone = views.to_vec();
 
         div()
             .child(
                 div()
-                    .id(SharedString::from(format!("views-{}", schema_name)))
+                    .id(SharedString::from(format!("views-{}-{}", connection_id, schema_name)))
                     .px_2()
                     .py_1()
                     .flex()
@@ -591,7 +667,7 @@ impl PostCommanderPage {
                     .hover(move |s| s.bg(rgb(element_hover)))
                     .cursor_pointer()
                     .on_click(cx.listener(move |this, _, _, cx| {
-                        this.toggle_node(&format!("views:{}", schema_name_click), cx);
+                        this.toggle_node(&views_key, cx);
                     }))
                     .child(icon_sm(
                         if views_expanded { "chevron-down" } else { "chevron-right" },
@@ -610,9 +686,10 @@ impl PostCommanderPage {
                     div().pl_4().children(views_clone.iter().map(|view| {
                         let view_name = view.clone();
                         let schema_for_view = schema_name_for_items.clone();
+                        let connection_for_view = connection_id.to_string();
 
                         div()
-                            .id(SharedString::from(format!("view-{}-{}", schema_for_view, view_name)))
+                            .id(SharedString::from(format!("view-{}:{}.{}", connection_id, schema_for_view, view_name)))
                             .px_2()
                             .py_1()
                             .flex()
@@ -634,7 +711,7 @@ impl PostCommanderPage {
                             )
                             .on_click(cx.listener(move |this, event: &ClickEvent, window, cx| {
                                 if event.click_count() == 2 {
-                                    this.query_table(&schema_for_view, &view_name, window, cx);
+                                    this.query_table(&connection_for_view, &schema_for_view, &view_name, window, cx);
                                 }
                             }))
                     })),
@@ -0,0 +1,351 @@
+use super::lexer::{tokenize, Token};
+
+/// A token, or a parenthesized group of them. Comments and whitespace are
+/// dropped, so neighbouring nodes are neighbouring code. A group's
+/// `start..end` covers its parentheses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node<'a> {
+    Token(Token<'a>),
+    Group {
+        start: usize,
+        end: usize,
+        children: Vec<Node<'a>>,
+    },
+}
+
+impl<'a> Node<'a> {
+    pub fn token(&self) -> Option<&Token<'a>> {
+        match self {
+            Node::Token(token) => Some(token),
+            Node::Group { .. } => None,
+        }
+    }
+
+    pub fn start(&self) -> usize {
+        match self {
+            Node::Token(token) => token.start,
+            Node::Group { start, .. } => *start,
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        match self {
+            Node::Token(token) => token.end(),
+            Node::Group { end, .. } => *end,
+        }
+    }
+
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.token().is_some_and(|t| t.is_keyword(keyword))
+    }
+
+    pub fn is_punct(&self, c: char) -> bool {
+        self.token().is_some_and(|t| t.is_punct(c))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Select,
+    Insert,
+    Update,
+    Delete,
+    Drop,
+    Truncate,
+    Alter,
+    Create,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Explain {
+    /// `EXPLAIN ANALYZE` runs the statement it explains.
+    pub analyze: bool,
+}
+
+/// A statement parsed far enough to tell what it does: the queries in its
+/// `WITH` clause, its command, and the command's nodes from its first
+/// keyword on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement<'a> {
+    pub sql: &'a str,
+    pub explain: Option<Explain>,
+    pub ctes: Vec<Statement<'a>>,
+    pub command: Command,
+    pub body: Vec<Node<'a>>,
+}
+
+impl<'a> Statement<'a> {
+    /// A keyword in the command itself, outside any parentheses.
+    pub fn has_keyword(&self, keyword: &str) -> bool {
+        self.body.iter().any(|n| n.is_keyword(keyword))
+    }
+
+    /// The source text from the first of `nodes` to the last.
+    pub fn text(&self, nodes: &[Node]) -> &'a str {
+        match (nodes.first(), nodes.last()) {
+            (Some(first), Some(last)) => &self.sql[first.start()..last.end()],
+            _ => "",
+        }
+    }
+
+    /// The top-level nodes after `keyword`, up to the first of `until`.
+    pub fn clause(&self, keyword: &str, until: &[&str]) -> Option<&[Node<'a>]> {
+        let start = self.body.iter().position(|n| n.is_keyword(keyword))? + 1;
+        let end = self.body[start..]
+            .iter()
+            .position(|n| until.iter().any(|k| n.is_keyword(k)))
+            .map_or(self.body.len(), |i| start + i);
+        Some(&self.body[start..end])
+    }
+
+    /// The condition of a top-level `WHERE`, up to `RETURNING`. A `WHERE`
+    /// inside a subquery belongs to the subquery, not to this command.
+    pub fn where_clause(&self) -> Option<&[Node<'a>]> {
+        self.clause("WHERE", &["RETURNING"])
+    }
+}
+
+/// Parses one statement; run `split_statements` on a script first.
+pub fn parse_statement(sql: &str) -> Statement<'_> {
+    statement(sql, &parse_nodes(sql))
+}
+
+/// Whether `sql` ends the open transaction, as COMMIT, ROLLBACK and their
+/// aliases do unless they chain a new one. ROLLBACK TO a savepoint keeps it
+/// open, and PREPARE TRANSACTION hands it off.
+pub fn ends_transaction(sql: &str) -> bool {
+    let nodes = parse_nodes(sql);
+    let is = |i: usize, keyword: &str| nodes.get(i).is_some_and(|n| n.is_keyword(keyword));
+    if is(0, "PREPARE") {
+        return is(1, "TRANSACTION");
+    }
+    let chains = nodes.last().is_some_and(|n| n.is_keyword("CHAIN")) && !nodes.iter().any(|n| n.is_keyword("NO"));
+    ["COMMIT", "END", "ROLLBACK", "ABORT"].iter().any(|keyword| is(0, keyword))
+        && !nodes.iter().any(|n| n.is_keyword("TO"))
+        && !chains
+}
+
+/// The statement's tokens with parenthesized parts grouped.
+pub fn parse_nodes(sql: &str) -> Vec<Node<'_>> {
+    let mut tokens = tokenize(sql).into_iter().filter(|t| !t.is_trivia());
+    nodes(&mut tokens, false).0
+}
+
+/// Nodes up to the `)` closing a group, and where that `)` ends. An
+/// unclosed group runs to the end of the input.
+fn nodes<'a>(tokens: &mut impl Iterator<Item = Token<'a>>, nested: bool) -> (Vec<Node<'a>>, Option<usize>) {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        if token.is_punct('(') {
+            let (children, close) = self::nodes(tokens, true);
+            let end = close
+                .or(children.last().map(Node::end))
+                .unwrap_or(token.end());
+            nodes.push(Node::Group {
+                start: token.start,
+                end,
+                children,
+            });
+        } else if token.is_punct(')') && nested {
+            return (nodes, Some(token.end()));
+        } else {
+            nodes.push(Node::Token(token));
+        }
+    }
+    (nodes, None)
+}
+
+fn statement<'a>(sql: &'a str, nodes: &[Node<'a>]) -> Statement<'a> {
+    let mut rest = nodes;
+
+    let mut explain = None;
+    if rest.first().is_some_and(|n| n.is_keyword("EXPLAIN")) {
+        rest = &rest[1..];
+        let mut analyze = false;
+        if let Some(Node::Group { children: options, .. }) = rest.first() {
+            // `EXPLAIN (ANALYZE [boolean], ...)`
+            analyze = options.iter().enumerate().any(|(i, n)| {
+                (n.is_keyword("ANALYZE") || n.is_keyword("ANALYSE"))
+                    && !options.get(i + 1).is_some_and(|value| {
+                        value.is_keyword("FALSE")
+                            || value.is_keyword("OFF")
+                            || value.token().is_some_and(|t| t.text == "0")
+                    })
+            });
+            rest = &rest[1..];
+        } else {
+            while let Some(option) = rest.first() {
+                if option.is_keyword("ANALYZE") || option.is_keyword("ANALYSE") {
+                    analyze = true;
+                } else if !option.is_keyword("VERBOSE") {
+                    break;
+                }
+                rest = &rest[1..];
+            }
+        }
+        explain = Some(Explain { analyze });
+    }
+
+    let mut ctes = Vec::new();
+    if rest.first().is_some_and(|n| n.is_keyword("WITH")) {
+        rest = &rest[1..];
+        if rest.first().is_some_and(|n| n.is_keyword("RECURSIVE")) {
+            rest = &rest[1..];
+        }
+        // name [ (columns) ] AS [ [ NOT ] MATERIALIZED ] ( query ) [ SEARCH ... ] [ CYCLE ... ]
+        while let Some((query, after)) = cte_query(rest) {
+            ctes.push(statement(sql, query));
+            rest = after;
+            let next = rest
+                .iter()
+                .position(|n| n.is_punct(',') || command(n) != Command::Other);
+            match next {
+                Some(i) if rest[i].is_punct(',') => rest = &rest[i + 1..],
+                Some(i) => {
+                    rest = &rest[i..];
+                    break;
+                }
+                None => {
+                    rest = &[];
+                    break;
+                }
+            }
+        }
+    }
+
+    Statement {
+        sql,
+        explain,
+        ctes,
+        command: rest.first().map_or(Command::Other, command),
+        body: rest.to_vec(),
+    }
+}
+
+/// The body of the next `WITH` query and the nodes after it.
+fn cte_query<'n, 'a>(nodes: &'n [Node<'a>]) -> Option<(&'n [Node<'a>], &'n [Node<'a>])> {
+    let mut i = nodes.iter().position(|n| n.is_keyword("AS"))? + 1;
+    while nodes.get(i).is_some_and(|n| n.is_keyword("NOT") || n.is_keyword("MATERIALIZED")) {
+        i += 1;
+    }
+    match nodes.get(i)? {
+        Node::Group { children, .. } => Some((children, &nodes[i + 1..])),
+        Node::Token(_) => None,
+    }
+}
+
+fn command(node: &Node) -> Command {
+    let token = match node {
+        Node::Token(token) => token,
+        // `(SELECT ...) UNION ...`
+        Node::Group { children, .. } => {
+            return match children.first() {
+                Some(first) => match command(first) {
+                    Command::Select => Command::Select,
+                    _ => Command::Other,
+                },
+                None => Command::Other,
+            }
+        }
+    };
+    [
+        ("SELECT", Command::Select),
+        ("VALUES", Command::Select),
+        ("TABLE", Command::Select),
+        ("INSERT", Command::Insert),
+        ("UPDATE", Command::Update),
+        ("DELETE", Command::Delete),
+        ("DROP", Command::Drop),
+        ("TRUNCATE", Command::Truncate),
+        ("ALTER", Command::Alter),
+        ("CREATE", Command::Create),
+    ]
+    .into_iter()
+    .find(|(keyword, _)| token.is_keyword(keyword))
+    .map_or(Command::Other, |(_, command)| command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_after_comment() {
+        let statement = parse_statement("-- note\n/* x */ drop table t");
+        assert_eq!(statement.command, Command::Drop);
+        assert!(statement.ctes.is_empty());
+    }
+
+    #[test]
+    fn test_cte_bodies_are_statements() {
+        let statement = parse_statement(
+            "WITH RECURSIVE a(x) AS (DELETE FROM t RETURNING *), b AS MATERIALIZED (SELECT 1) SELECT * FROM a",
+        );
+        let commands: Vec<Command> = statement.ctes.iter().map(|c| c.command).collect();
+        assert_eq!(commands, vec![Command::Delete, Command::Select]);
+        assert_eq!(statement.command, Command::Select);
+    }
+
+    #[test]
+    fn test_cte_with_search_clause() {
+        let statement = parse_statement(
+            "WITH RECURSIVE t AS (SELECT 1) SEARCH DEPTH FIRST BY id SET ord UPDATE u SET a = 1",
+        );
+        assert_eq!(statement.ctes.len(), 1);
+        assert_eq!(statement.command, Command::Update);
+    }
+
+    #[test]
+    fn test_where_clause_is_top_level() {
+        let statement = parse_statement("DELETE FROM t USING (SELECT id FROM u WHERE ok) s");
+        assert_eq!(statement.command, Command::Delete);
+        assert!(statement.where_clause().is_none());
+
+        let statement = parse_statement("UPDATE t SET a = 1 WHERE id = 2 RETURNING *");
+        assert_eq!(statement.where_clause().map(<[Node]>::len), Some(3));
+    }
+
+    #[test]
+    fn test_explain() {
+        let statement = parse_statement("EXPLAIN DELETE FROM t");
+        assert_eq!(statement.explain, Some(Explain { analyze: false }));
+        assert_eq!(statement.command, Command::Delete);
+
+        let statement = parse_statement("EXPLAIN ANALYZE VERBOSE DELETE FROM t");
+        assert_eq!(statement.explain, Some(Explain { analyze: true }));
+
+        let statement = parse_statement("EXPLAIN (ANALYZE, BUFFERS) DELETE FROM t");
+        assert_eq!(statement.explain, Some(Explain { analyze: true }));
+
+        let statement = parse_statement("EXPLAIN (ANALYZE off) DELETE FROM t");
+        assert_eq!(statement.explain, Some(Explain { analyze: false }));
+    }
+
+    #[test]
+    fn test_unbalanced_parentheses() {
+        assert_eq!(parse_statement("SELECT (1").command, Command::Select);
+        assert_eq!(parse_statement("DROP TABLE t)").command, Command::Drop);
+    }
+
+    #[test]
+    fn test_ends_transaction() {
+        for sql in ["COMMIT", "end work", "ROLLBACK TRANSACTION", "abort", "COMMIT AND NO CHAIN", "PREPARE TRANSACTION 'x'"] {
+            assert!(ends_transaction(sql), "{}", sql);
+        }
+        for sql in ["ROLLBACK TO SAVEPOINT s", "rollback to s", "COMMIT AND CHAIN", "BEGIN", "SELECT 'commit'", "PREPARE q AS SELECT 1"] {
+            assert!(!ends_transaction(sql), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_clause_text() {
+        let sql = "UPDATE t SET a = f(1, 2) FROM u WHERE t.id = u.id RETURNING *";
+        let statement = parse_statement(sql);
+        let from = statement.clause("FROM", &["WHERE", "RETURNING"]).unwrap();
+        assert_eq!(statement.text(from), "u");
+        let set = statement.clause("SET", &["FROM", "WHERE", "RETURNING"]).unwrap();
+        assert_eq!(statement.text(set), "a = f(1, 2)");
+        assert_eq!(statement.text(statement.where_clause().unwrap()), "t.id = u.id");
+    }
+}
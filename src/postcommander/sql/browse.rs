@@ -0,0 +1,176 @@
+use super::ast::{parse_statement, Command, Node};
+use super::dml::quote_ident;
+
+/// Keywords that make a SELECT more than a look at one table's rows.
+const NOT_A_BROWSE: &[&str] = &[
+    "DISTINCT", "JOIN", "GROUP", "HAVING", "WINDOW", "UNION", "INTERSECT", "EXCEPT", "FOR", "INTO", "FETCH",
+    "TABLESAMPLE",
+];
+
+/// Rewrites a plain `SELECT ... FROM table [WHERE] [ORDER BY] [LIMIT]
+/// [OFFSET]` so the server does the grid's sort and search: `order_by`
+/// lists result columns by index with whether they sort descending, and
+/// each word of `search` must appear in one of `search_columns`. An empty
+/// `order_by` keeps the query's own `ORDER BY`. Anything else, such as a
+/// join or an aggregate, gets `None`.
+pub fn browse_query(
+    sql: &str,
+    order_by: &[(usize, bool)],
+    search_columns: &[String],
+    search: &str,
+) -> Option<String> {
+    let statement = parse_statement(sql);
+    if statement.explain.is_some() || !statement.ctes.is_empty() || statement.command != Command::Select {
+        return None;
+    }
+    if NOT_A_BROWSE.iter().any(|k| statement.has_keyword(k)) {
+        return None;
+    }
+
+    let body = match statement.body.split_last() {
+        Some((last, rest)) if last.is_punct(';') => rest,
+        _ => &statement.body[..],
+    };
+    let from_index = body.iter().position(|n| n.is_keyword("FROM"))?;
+    let clause_end = |start: usize, until: &[&str]| {
+        body[start..]
+            .iter()
+            .position(|n| until.iter().any(|k| n.is_keyword(k)))
+            .map_or(body.len(), |i| start + i)
+    };
+
+    let columns = &body[1..from_index];
+    let from_end = clause_end(from_index + 1, &["WHERE", "ORDER", "LIMIT", "OFFSET"]);
+    let from = &body[from_index + 1..from_end];
+    // One named table; a comma list, subquery or table function isn't.
+    if columns.is_empty()
+        || from.is_empty()
+        || from.iter().any(|n| n.is_punct(',') || matches!(n, Node::Group { .. }))
+    {
+        return None;
+    }
+
+    let mut rest = from_end;
+    let mut condition = None;
+    if body.get(rest).is_some_and(|n| n.is_keyword("WHERE")) {
+        let end = clause_end(rest + 1, &["ORDER", "LIMIT", "OFFSET"]);
+        condition = Some(statement.text(&body[rest + 1..end])).filter(|c| !c.is_empty());
+        rest = end;
+    }
+    let mut own_order = None;
+    if body.get(rest).is_some_and(|n| n.is_keyword("ORDER")) {
+        let end = clause_end(rest + 1, &["LIMIT", "OFFSET"]);
+        own_order = Some(statement.text(&body[rest..end]));
+        rest = end;
+    }
+    let paging = statement.text(&body[rest..]);
+
+    let mut query = format!("SELECT {} FROM {}", statement.text(columns), statement.text(from));
+    match (condition, search_condition(search_columns, search)) {
+        (Some(condition), Some(search)) => {
+            query.push_str(&format!(" WHERE ({}) AND {}", condition, search))
+        }
+        (Some(condition), None) => query.push_str(&format!(" WHERE {}", condition)),
+        (None, Some(search)) => query.push_str(&format!(" WHERE {}", search)),
+        (None, None) => {}
+    }
+    if !order_by.is_empty() {
+        // By position, as result column names may be aliases or repeat.
+        let keys: Vec<String> = order_by
+            .iter()
+            .map(|(index, descending)| {
+                format!("{}{}", index + 1, if *descending { " DESC" } else { "" })
+            })
+            .collect();
+        query.push_str(&format!(" ORDER BY {}", keys.join(", ")));
+    } else if let Some(order) = own_order {
+        query.push(' ');
+        query.push_str(order);
+    }
+    if !paging.is_empty() {
+        query.push(' ');
+        query.push_str(paging);
+    }
+    Some(query)
+}
+
+/// `(a::text ILIKE '%word%' OR b::text ILIKE '%word%') AND ...` for each
+/// word of the search.
+fn search_condition(columns: &[String], search: &str) -> Option<String> {
+    if columns.is_empty() {
+        return None;
+    }
+    let terms: Vec<String> = search
+        .split_whitespace()
+        .map(|term| {
+            let pattern = term
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+                .replace('\'', "''");
+            let matches: Vec<String> = columns
+                .iter()
+                .map(|c| format!("{}::text ILIKE '%{}%'", quote_ident(c), pattern))
+                .collect();
+            format!("({})", matches.join(" OR "))
+        })
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" AND "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_by() {
+        assert_eq!(
+            browse_query("SELECT * FROM \"public\".\"users\"", &[(1, true), (0, false)], &[], "").as_deref(),
+            Some("SELECT * FROM \"public\".\"users\" ORDER BY 2 DESC, 1")
+        );
+        assert_eq!(
+            browse_query("select id, name from users u order by name limit 100;", &[(0, false)], &[], "")
+                .as_deref(),
+            Some("SELECT id, name FROM users u ORDER BY 1 limit 100")
+        );
+    }
+
+    #[test]
+    fn test_own_order_kept_without_sort() {
+        assert_eq!(
+            browse_query("SELECT * FROM t WHERE a > 1 ORDER BY a LIMIT 10 OFFSET 5", &[], &[], "").as_deref(),
+            Some("SELECT * FROM t WHERE a > 1 ORDER BY a LIMIT 10 OFFSET 5")
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let columns = vec!["name".to_string(), "email".to_string()];
+        assert_eq!(
+            browse_query("SELECT * FROM t", &[], &columns, "ann").as_deref(),
+            Some("SELECT * FROM t WHERE (\"name\"::text ILIKE '%ann%' OR \"email\"::text ILIKE '%ann%')")
+        );
+        assert_eq!(
+            browse_query("SELECT * FROM t WHERE a = 1 OR b = 2", &[], &columns[..1], "50%_o'k").as_deref(),
+            Some("SELECT * FROM t WHERE (a = 1 OR b = 2) AND (\"name\"::text ILIKE '%50\\%\\_o''k%')")
+        );
+    }
+
+    #[test]
+    fn test_not_a_browse() {
+        for sql in [
+            "SELECT * FROM a JOIN b ON a.id = b.a_id",
+            "SELECT * FROM a, b",
+            "SELECT count(*) FROM a GROUP BY b",
+            "SELECT DISTINCT a FROM t",
+            "SELECT * FROM (SELECT 1) s",
+            "SELECT * FROM generate_series(1, 3)",
+            "SELECT 1",
+            "WITH x AS (SELECT 1) SELECT * FROM x",
+            "SELECT * FROM t FOR UPDATE",
+            "UPDATE t SET a = 1",
+        ] {
+            assert_eq!(browse_query(sql, &[(0, false)], &[], ""), None, "{}", sql);
+        }
+    }
+}
@@ -3,16 +3,23 @@ use gpui::{Context, Task};
 use gpui_component::input::{CompletionProvider, InputState};
 use gpui_component::Rope;
 use lsp_types::{
-    CompletionContext, CompletionItem, CompletionItemKind, CompletionResponse,
+    CompletionContext, CompletionItem, CompletionItemKind, CompletionResponse, Documentation,
 };
 use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::postcommander::types::{SchemaMap, TableStructureInfo};
+use super::lexer::{tokenize, Token, TokenKind};
+use super::scope::{scope_at, DerivedTable, Scope, TableRef};
+use crate::postcommander::types::{
+    CompletionCatalog, FunctionKind, SchemaMap, TableColumn, TableStructureInfo,
+};
 
 pub struct SqlCompletionProvider {
     schemas: Rc<RefCell<SchemaMap>>,
     table_structures: Rc<RefCell<Vec<TableStructureInfo>>>,
+    catalog: Rc<RefCell<Arc<CompletionCatalog>>>,
 }
 
 impl SqlCompletionProvider {
@@ -20,9 +27,14 @@ impl SqlCompletionProvider {
         Self {
             schemas: Rc::new(RefCell::new(SchemaMap::new())),
             table_structures: Rc::new(RefCell::new(Vec::new())),
+            catalog: Rc::new(RefCell::new(Arc::new(CompletionCatalog::default()))),
         }
     }
 
+    pub fn catalog_ref(&self) -> Rc<RefCell<Arc<CompletionCatalog>>> {
+        self.catalog.clone()
+    }
+
     pub fn schemas_ref(&self) -> Rc<RefCell<SchemaMap>> {
         self.schemas.clone()
     }
@@ -38,109 +50,147 @@ enum SqlContext {
     AfterFrom,
     AfterJoin,
     AfterWhere,
+    AfterOn,
+    AfterComparison,
+    AfterCast,
     AfterDot,
     AfterComma,
+    InStringOrComment,
     Unknown,
 }
 
 struct SqlContextInfo {
     context: SqlContext,
+    /// The qualifier before the dot, or of the compared column.
     prefix: Option<String>,
+    /// The column a value is being compared with.
+    column: Option<String>,
     filter: String,
 }
 
 fn detect_sql_context(text: &str, offset: usize) -> SqlContextInfo {
     let before_cursor = &text[..offset.min(text.len())];
-    let before_upper = before_cursor.to_uppercase();
+    let tokens = tokenize(before_cursor);
 
-    let current_word_start = before_cursor
-        .rfind(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')')
-        .map(|i| i + 1)
-        .unwrap_or(0);
-    let current_word = &before_cursor[current_word_start..];
-
-    if before_cursor.ends_with('.') {
+    // Nothing to complete inside a string or comment.
+    if tokens
+        .last()
+        .is_some_and(|t| t.unterminated && t.kind != TokenKind::QuotedIdent)
+    {
         return SqlContextInfo {
-            context: SqlContext::AfterDot,
+            context: SqlContext::InStringOrComment,
             prefix: None,
+            column: None,
             filter: String::new(),
         };
     }
 
-    if current_word.contains('.') {
-        let parts: Vec<&str> = current_word.split('.').collect();
-        if parts.len() >= 2 {
-            let prefix = parts[0].trim_matches('"').to_string();
-            let filter = parts[1].trim_matches('"').to_string();
-            return SqlContextInfo {
-                context: SqlContext::AfterDot,
-                prefix: Some(prefix),
-                filter,
-            };
-        }
+    let mut code: Vec<&Token> = tokens.iter().filter(|t| !t.is_trivia()).collect();
+
+    // The word being typed, if the cursor is right after one.
+    let current = code
+        .last()
+        .filter(|t| {
+            matches!(t.kind, TokenKind::Word | TokenKind::QuotedIdent)
+                && t.end() == before_cursor.len()
+        })
+        .copied();
+    if current.is_some() {
+        code.pop();
     }
-
-    if before_cursor.ends_with(',') || before_cursor.ends_with(", ") {
-        if before_upper.rfind("SELECT").is_some() {
-            if let Some(from_pos) = before_upper.rfind("FROM") {
-                if let Some(select_pos) = before_upper.rfind("SELECT") {
-                    if select_pos > from_pos {
-                        return SqlContextInfo {
-                            context: SqlContext::AfterComma,
-                            prefix: None,
-                            filter: String::new(),
-                        };
-                    }
-                }
-            } else {
-                return SqlContextInfo {
-                    context: SqlContext::AfterComma,
-                    prefix: None,
-                    filter: String::new(),
-                };
-            }
-        }
+    let filter = current.map(|t| t.ident()).unwrap_or_default();
+
+    if let Some(dot) = code.last().filter(|t| t.is_punct('.')) {
+        let prefix = code
+            .iter()
+            .rev()
+            .nth(1)
+            .filter(|t| {
+                matches!(t.kind, TokenKind::Word | TokenKind::QuotedIdent) && t.end() == dot.start
+            })
+            .map(|t| t.ident());
+        return SqlContextInfo {
+            context: SqlContext::AfterDot,
+            prefix,
+            column: None,
+            filter,
+        };
     }
 
-    let words: Vec<&str> = before_cursor.split_whitespace().collect();
-    let filter = words.last().map(|s| s.to_string()).unwrap_or_default();
+    let Some(previous) = code.last() else {
+        return SqlContextInfo { context: SqlContext::Unknown, prefix: None, column: None, filter };
+    };
 
-    if let Some(last_word) = words.last() {
-        let upper = last_word.to_uppercase();
-        match upper.as_str() {
-            "SELECT" => return SqlContextInfo { context: SqlContext::AfterSelect, prefix: None, filter: String::new() },
-            "FROM" => return SqlContextInfo { context: SqlContext::AfterFrom, prefix: None, filter: String::new() },
-            "JOIN" | "INNER" | "LEFT" | "RIGHT" | "OUTER" | "CROSS" => {
-                return SqlContextInfo { context: SqlContext::AfterJoin, prefix: None, filter: String::new() }
-            }
-            "WHERE" | "AND" | "OR" => return SqlContextInfo { context: SqlContext::AfterWhere, prefix: None, filter: String::new() },
-            _ => {}
+    if previous.is_punct(',') {
+        let last_select = code.iter().rposition(|t| t.is_keyword("SELECT"));
+        let last_from = code.iter().rposition(|t| t.is_keyword("FROM"));
+        if last_select.is_some() && last_select > last_from {
+            return SqlContextInfo { context: SqlContext::AfterComma, prefix: None, column: None, filter };
         }
     }
 
-    if words.len() >= 2 {
-        let second_last = words[words.len() - 2].to_uppercase();
-        match second_last.as_str() {
-            "SELECT" => return SqlContextInfo { context: SqlContext::AfterSelect, prefix: None, filter },
-            "FROM" => return SqlContextInfo { context: SqlContext::AfterFrom, prefix: None, filter },
-            "JOIN" => return SqlContextInfo { context: SqlContext::AfterJoin, prefix: None, filter },
-            "WHERE" | "AND" | "OR" => return SqlContextInfo { context: SqlContext::AfterWhere, prefix: None, filter },
-            _ => {}
+    // `column = `, `column <> ` or `column IN (`
+    let comparison = if previous.kind == TokenKind::Operator && ["=", "<>", "!="].contains(&previous.text) {
+        Some(code.len() - 1)
+    } else if previous.is_punct('(') && code.len() >= 2 && code[code.len() - 2].is_keyword("IN") {
+        Some(code.len() - 2)
+    } else {
+        None
+    };
+    if let Some(at) = comparison {
+        let is_name = |t: &&Token| matches!(t.kind, TokenKind::Word | TokenKind::QuotedIdent);
+        if let Some(column) = at.checked_sub(1).map(|i| code[i]).filter(is_name) {
+            let qualifier = at
+                .checked_sub(3)
+                .filter(|&i| code[i + 1].is_punct('.'))
+                .map(|i| code[i])
+                .filter(is_name)
+                .map(|t| t.ident());
+            return SqlContextInfo {
+                context: SqlContext::AfterComparison,
+                prefix: qualifier,
+                column: Some(column.ident()),
+                filter,
+            };
         }
     }
 
-    SqlContextInfo { context: SqlContext::Unknown, prefix: None, filter }
-}
-
-fn get_identifier_before_dot(text: &str, offset: usize) -> Option<String> {
-    let before_cursor = &text[..offset.min(text.len())];
-    if !before_cursor.ends_with('.') {
-        return None;
+    // `value::` or `CAST(value AS `
+    let mut open = Vec::new();
+    for (i, token) in code.iter().enumerate() {
+        if token.is_punct('(') {
+            open.push(i);
+        } else if token.is_punct(')') {
+            open.pop();
+        }
+    }
+    let in_cast = open
+        .last()
+        .and_then(|&i| i.checked_sub(1))
+        .is_some_and(|i| code[i].is_keyword("CAST"));
+    if (previous.kind == TokenKind::Operator && previous.text == "::")
+        || (previous.is_keyword("AS") && in_cast)
+    {
+        return SqlContextInfo { context: SqlContext::AfterCast, prefix: None, column: None, filter };
     }
 
-    let without_dot = &before_cursor[..before_cursor.len() - 1];
-    let words: Vec<&str> = without_dot.split_whitespace().collect();
-    words.last().map(|s| s.trim_matches('"').to_string())
+    let context = if previous.is_keyword("SELECT") {
+        SqlContext::AfterSelect
+    } else if previous.is_keyword("FROM") {
+        SqlContext::AfterFrom
+    } else if ["JOIN", "INNER", "LEFT", "RIGHT", "OUTER", "CROSS"]
+        .iter()
+        .any(|k| previous.is_keyword(k))
+    {
+        SqlContext::AfterJoin
+    } else if ["WHERE", "AND", "OR"].iter().any(|k| previous.is_keyword(k)) {
+        SqlContext::AfterWhere
+    } else if previous.is_keyword("ON") {
+        SqlContext::AfterOn
+    } else {
+        SqlContext::Unknown
+    };
+    SqlContextInfo { context, prefix: None, column: None, filter }
 }
 
 fn is_schema_name(name: &str, schemas: &SchemaMap) -> bool {
@@ -159,18 +209,367 @@ const SQL_KEYWORDS: &[&str] = &[
     "DEFAULT", "CHECK", "CASCADE", "NULLS", "FIRST", "LAST",
 ];
 
-const SQL_FUNCTIONS: &[&str] = &[
-    "COUNT", "SUM", "AVG", "MIN", "MAX", "COALESCE", "NULLIF", "GREATEST",
-    "LEAST", "NOW", "CURRENT_TIMESTAMP", "CURRENT_DATE", "CURRENT_TIME",
-    "DATE_TRUNC", "DATE_PART", "EXTRACT", "AGE", "INTERVAL",
-    "UPPER", "LOWER", "TRIM", "LTRIM", "RTRIM", "LENGTH", "SUBSTRING",
-    "CONCAT", "REPLACE", "SPLIT_PART", "REGEXP_REPLACE", "REGEXP_MATCHES",
-    "ROUND", "FLOOR", "CEIL", "ABS", "POWER", "SQRT", "MOD",
-    "ARRAY_AGG", "STRING_AGG", "JSON_AGG", "JSONB_AGG",
-    "ROW_NUMBER", "RANK", "DENSE_RANK", "LAG", "LEAD", "FIRST_VALUE",
-    "GEN_RANDOM_UUID", "RANDOM",
+/// Built-in functions and their signatures, shown as completion detail.
+const SQL_FUNCTIONS: &[(&str, &str)] = &[
+    ("COUNT", "count(expression) → bigint"),
+    ("SUM", "sum(expression) → numeric"),
+    ("AVG", "avg(expression) → numeric"),
+    ("MIN", "min(expression) → same as input"),
+    ("MAX", "max(expression) → same as input"),
+    ("COALESCE", "coalesce(value, ...) → first non-null value"),
+    ("NULLIF", "nullif(value1, value2) → value1 or null"),
+    ("GREATEST", "greatest(value, ...) → largest value"),
+    ("LEAST", "least(value, ...) → smallest value"),
+    ("NOW", "now() → timestamptz"),
+    ("CURRENT_TIMESTAMP", "current_timestamp → timestamptz"),
+    ("CURRENT_DATE", "current_date → date"),
+    ("CURRENT_TIME", "current_time → timetz"),
+    ("DATE_TRUNC", "date_trunc(field text, source timestamp) → timestamp"),
+    ("DATE_PART", "date_part(field text, source timestamp) → double precision"),
+    ("EXTRACT", "extract(field FROM source) → numeric"),
+    ("AGE", "age(timestamp, timestamp) → interval"),
+    ("INTERVAL", "interval 'value' → interval"),
+    ("UPPER", "upper(text) → text"),
+    ("LOWER", "lower(text) → text"),
+    ("TRIM", "trim([both] [characters] FROM text) → text"),
+    ("LTRIM", "ltrim(text [, characters text]) → text"),
+    ("RTRIM", "rtrim(text [, characters text]) → text"),
+    ("LENGTH", "length(text) → integer"),
+    ("SUBSTRING", "substring(text [FROM start] [FOR count]) → text"),
+    ("CONCAT", "concat(value, ...) → text"),
+    ("REPLACE", "replace(text, from text, to text) → text"),
+    ("SPLIT_PART", "split_part(text, delimiter text, n integer) → text"),
+    ("REGEXP_REPLACE", "regexp_replace(text, pattern text, replacement text [, flags text]) → text"),
+    ("REGEXP_MATCHES", "regexp_matches(text, pattern text [, flags text]) → setof text[]"),
+    ("ROUND", "round(numeric [, scale integer]) → numeric"),
+    ("FLOOR", "floor(numeric) → numeric"),
+    ("CEIL", "ceil(numeric) → numeric"),
+    ("ABS", "abs(numeric) → numeric"),
+    ("POWER", "power(a numeric, b numeric) → numeric"),
+    ("SQRT", "sqrt(numeric) → numeric"),
+    ("MOD", "mod(y, x) → same as input"),
+    ("ARRAY_AGG", "array_agg(expression) → array"),
+    ("STRING_AGG", "string_agg(value text, delimiter text) → text"),
+    ("JSON_AGG", "json_agg(expression) → json"),
+    ("JSONB_AGG", "jsonb_agg(expression) → jsonb"),
+    ("ROW_NUMBER", "row_number() → bigint"),
+    ("RANK", "rank() → bigint"),
+    ("DENSE_RANK", "dense_rank() → bigint"),
+    ("LAG", "lag(value [, offset integer [, default]]) → same as value"),
+    ("LEAD", "lead(value [, offset integer [, default]]) → same as value"),
+    ("FIRST_VALUE", "first_value(value) → same as value"),
+    ("GEN_RANDOM_UUID", "gen_random_uuid() → uuid"),
+    ("RANDOM", "random() → double precision"),
 ];
 
+/// Functions from the server's catalog, one item per name with its
+/// overloads' signatures as detail; the built-in list until the catalog
+/// has loaded. Functions outside `pg_catalog` and `public` are offered
+/// schema-qualified.
+fn function_items(catalog: &CompletionCatalog, matches: &dyn Fn(&str) -> bool) -> Vec<CompletionItem> {
+    if catalog.functions.is_empty() {
+        return SQL_FUNCTIONS
+            .iter()
+            .filter(|(func, _)| matches(func))
+            .map(|(func, signature)| CompletionItem {
+                label: format!("{}()", func),
+                insert_text: Some(format!("{}()", func)),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(signature.to_string()),
+                sort_text: Some(format!("5_{}", func.to_lowercase())),
+                ..Default::default()
+            })
+            .collect();
+    }
+
+    let mut overloads: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut user_defined = HashSet::new();
+    for function in &catalog.functions {
+        let name = if function.schema == "pg_catalog" || function.schema == "public" {
+            function.name.clone()
+        } else {
+            format!("{}.{}", function.schema, function.name)
+        };
+        if !matches(&name) {
+            continue;
+        }
+        if function.schema != "pg_catalog" {
+            user_defined.insert(name.clone());
+        }
+        let mut signature = function.signature();
+        match function.kind {
+            FunctionKind::Aggregate => signature.push_str(" (aggregate)"),
+            FunctionKind::Window => signature.push_str(" (window)"),
+            FunctionKind::Normal => {}
+        }
+        overloads.entry(name).or_default().push(signature);
+    }
+
+    overloads
+        .into_iter()
+        .map(|(name, signatures)| {
+            let detail = match signatures.len() {
+                1 => signatures[0].clone(),
+                n => format!("{} (+{} more)", signatures[0], n - 1),
+            };
+            let priority = if user_defined.contains(&name) { "4" } else { "5" };
+            CompletionItem {
+                label: format!("{}()", name),
+                insert_text: Some(format!("{}()", name)),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(detail),
+                documentation: (signatures.len() > 1)
+                    .then(|| Documentation::String(signatures.join("\n"))),
+                sort_text: Some(format!("{}_{}", priority, name)),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn keyword_items(catalog: &CompletionCatalog, matches: &dyn Fn(&str) -> bool) -> Vec<CompletionItem> {
+    let keywords: Vec<String> = if catalog.keywords.is_empty() {
+        SQL_KEYWORDS.iter().map(|k| k.to_string()).collect()
+    } else {
+        catalog.keywords.iter().map(|k| k.to_uppercase()).collect()
+    };
+    keywords
+        .into_iter()
+        .filter(|kw| matches(kw))
+        .map(|kw| CompletionItem {
+            sort_text: Some(format!("9_{}", kw.to_lowercase())),
+            label: kw,
+            kind: Some(CompletionItemKind::KEYWORD),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn type_items(catalog: &CompletionCatalog, matches: &dyn Fn(&str) -> bool) -> Vec<CompletionItem> {
+    catalog
+        .types
+        .iter()
+        .filter(|t| matches(&t.display_name) || matches(&t.name))
+        .map(|t| {
+            let builtin = t.schema == "pg_catalog";
+            let detail = if !t.enum_values.is_empty() {
+                format!("enum in {}", t.schema)
+            } else if t.display_name != t.name {
+                t.name.clone()
+            } else {
+                format!("type in {}", t.schema)
+            };
+            CompletionItem {
+                label: t.display_name.clone(),
+                kind: Some(CompletionItemKind::TYPE_PARAMETER),
+                detail: Some(detail),
+                sort_text: Some(format!("{}_{}", if builtin { "2" } else { "1" }, t.display_name)),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// The column compared in `column = ` or `qualifier.column IN (`.
+fn compared_column<'s>(
+    qualifier: Option<&str>,
+    column: &str,
+    scope: &Scope,
+    structures: &'s [TableStructureInfo],
+) -> Option<&'s TableColumn> {
+    let find = |structure: &'s TableStructureInfo| {
+        structure.columns.iter().find(|c| c.name.eq_ignore_ascii_case(column))
+    };
+    match qualifier {
+        Some(qualifier) => {
+            let table = scope.resolve(qualifier)?;
+            structure_for(structures, table).and_then(find)
+        }
+        None => scope
+            .base_tables()
+            .filter_map(|t| structure_for(structures, t))
+            .find_map(find),
+    }
+}
+
+/// The loaded structure of a table in scope.
+fn structure_for<'s>(
+    structures: &'s [TableStructureInfo],
+    table: &TableRef,
+) -> Option<&'s TableStructureInfo> {
+    structures.iter().find(|s| {
+        s.table.eq_ignore_ascii_case(&table.name)
+            && table
+                .schema
+                .as_ref()
+                .is_none_or(|schema| s.schema.eq_ignore_ascii_case(schema))
+    })
+}
+
+fn column_items(
+    structure: &TableStructureInfo,
+    source: Option<&str>,
+    matches: &dyn Fn(&str) -> bool,
+) -> Vec<CompletionItem> {
+    structure
+        .columns
+        .iter()
+        .filter(|col| matches(&col.name))
+        .map(|col| {
+            let priority = if col.is_primary_key {
+                "1"
+            } else if col.is_foreign_key {
+                "2"
+            } else {
+                "3"
+            };
+
+            let mut detail = col.data_type.clone();
+            if col.is_primary_key {
+                detail = format!("PK {}", detail);
+            }
+            if col.is_foreign_key {
+                if let Some(ref fk_ref) = col.references {
+                    detail = format!("FK → {}.{} ({})", fk_ref.table, fk_ref.column, detail);
+                }
+            }
+            if let Some(source) = source {
+                detail = format!("{} · {}", source, detail);
+            }
+
+            CompletionItem {
+                label: col.name.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(detail),
+                sort_text: Some(format!("{}_{}", priority, col.name.to_lowercase())),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Columns of `qualifier` in `qualifier.`, which may be an alias, a table
+/// in scope, or a `WITH` query.
+fn qualified_columns(
+    qualifier: &str,
+    scope: &Scope,
+    structures: &[TableStructureInfo],
+    matches: &dyn Fn(&str) -> bool,
+) -> Option<Vec<CompletionItem>> {
+    let fallback = TableRef {
+        schema: None,
+        name: qualifier.to_lowercase(),
+        alias: None,
+        span: 0..0,
+    };
+    let table = scope.resolve(qualifier).unwrap_or(&fallback);
+    if let Some(derived) = scope.derived(&table.name).filter(|_| table.schema.is_none()) {
+        return Some(derived_items(derived, None, matches));
+    }
+    structure_for(structures, table).map(|s| column_items(s, None, matches))
+}
+
+fn derived_items(
+    derived: &DerivedTable,
+    source: Option<&str>,
+    matches: &dyn Fn(&str) -> bool,
+) -> Vec<CompletionItem> {
+    derived
+        .columns
+        .iter()
+        .filter(|c| matches(c))
+        .map(|c| CompletionItem {
+            label: c.clone(),
+            kind: Some(CompletionItemKind::FIELD),
+            detail: Some(source.unwrap_or(&derived.name).to_string()),
+            sort_text: Some(format!("3_{}", c.to_lowercase())),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Columns of every table in scope, or of every loaded table when the
+/// statement names none.
+fn scope_columns(
+    scope: &Scope,
+    structures: &[TableStructureInfo],
+    matches: &dyn Fn(&str) -> bool,
+) -> Vec<CompletionItem> {
+    if scope.tables.is_empty() {
+        return structures
+            .iter()
+            .flat_map(|s| column_items(s, None, matches))
+            .collect();
+    }
+    let several = scope.tables.len() > 1;
+    scope
+        .tables
+        .iter()
+        .flat_map(|table| {
+            let source = several.then(|| table.qualifier());
+            match scope.derived(&table.name).filter(|_| table.schema.is_none()) {
+                Some(derived) => derived_items(derived, source, matches),
+                None => structure_for(structures, table)
+                    .map(|s| column_items(s, source, matches))
+                    .unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+/// `ON` conditions joining the table just joined to the ones before it,
+/// along foreign keys in either direction.
+fn join_conditions(
+    scope: &Scope,
+    structures: &[TableStructureInfo],
+    matches: &dyn Fn(&str) -> bool,
+) -> Vec<CompletionItem> {
+    let Some((joined, earlier)) = scope.tables.split_last() else {
+        return Vec::new();
+    };
+    let Some(joined_structure) = structure_for(structures, joined) else {
+        return Vec::new();
+    };
+
+    let mut conditions = Vec::new();
+    for other in earlier {
+        let Some(other_structure) = structure_for(structures, other) else {
+            continue;
+        };
+        for (from, from_structure, to, to_structure) in [
+            (joined, joined_structure, other, other_structure),
+            (other, other_structure, joined, joined_structure),
+        ] {
+            for col in &from_structure.columns {
+                let Some(fk) = &col.references else {
+                    continue;
+                };
+                if fk.table == to_structure.table && fk.schema == to_structure.schema {
+                    conditions.push(format!(
+                        "{}.{} = {}.{}",
+                        from.qualifier(),
+                        col.name,
+                        to.qualifier(),
+                        fk.column
+                    ));
+                }
+            }
+        }
+    }
+
+    conditions
+        .into_iter()
+        .filter(|c| matches(c))
+        .map(|condition| CompletionItem {
+            label: condition.clone(),
+            kind: Some(CompletionItemKind::REFERENCE),
+            detail: Some("Foreign key".to_string()),
+            sort_text: Some(format!("0_{}", condition.to_lowercase())),
+            ..Default::default()
+        })
+        .collect()
+}
+
 impl CompletionProvider for SqlCompletionProvider {
     fn completions(
         &self,
@@ -184,7 +583,9 @@ impl CompletionProvider for SqlCompletionProvider {
         let ctx_info = detect_sql_context(&text_str, offset);
         let schemas = self.schemas.borrow();
         let structures = self.table_structures.borrow();
+        let catalog = self.catalog.borrow().clone();
         let filter_lower = ctx_info.filter.to_lowercase();
+        let scope = scope_at(&text_str, offset);
 
         let matches = |label: &str| -> bool {
             if filter_lower.is_empty() {
@@ -208,59 +609,26 @@ impl CompletionProvider for SqlCompletionProvider {
                     });
                 }
 
-                for structure in structures.iter() {
-                    for col in &structure.columns {
-                        if !matches(&col.name) {
-                            continue;
-                        }
-                        let priority = if col.is_primary_key {
-                            "1"
-                        } else if col.is_foreign_key {
-                            "2"
-                        } else {
-                            "3"
-                        };
+                items.extend(scope_columns(&scope, &structures, &matches));
+                items.extend(function_items(&catalog, &matches));
+                items
+            }
 
-                        let mut detail = col.data_type.clone();
-                        if col.is_primary_key {
-                            detail = format!("PK {}", detail);
-                        }
-                        if col.is_foreign_key {
-                            if let Some(ref fk_ref) = col.references {
-                                detail = format!("FK → {}.{} ({})", fk_ref.table, fk_ref.column, detail);
-                            }
-                        }
+            SqlContext::AfterFrom | SqlContext::AfterJoin => {
+                let mut items = Vec::new();
 
+                for derived in &scope.derived {
+                    if matches(&derived.name) {
                         items.push(CompletionItem {
-                            label: col.name.clone(),
-                            kind: Some(CompletionItemKind::FIELD),
-                            detail: Some(detail),
-                            sort_text: Some(format!("{}_{}", priority, col.name.to_lowercase())),
+                            label: derived.name.clone(),
+                            kind: Some(CompletionItemKind::CLASS),
+                            detail: Some("WITH query".to_string()),
+                            sort_text: Some(format!("0_{}", derived.name.to_lowercase())),
                             ..Default::default()
                         });
                     }
                 }
 
-                for func in SQL_FUNCTIONS {
-                    if !matches(func) {
-                        continue;
-                    }
-                    items.push(CompletionItem {
-                        label: format!("{}()", func),
-                        insert_text: Some(format!("{}()", func)),
-                        kind: Some(CompletionItemKind::FUNCTION),
-                        detail: Some("Function".to_string()),
-                        sort_text: Some(format!("5_{}", func.to_lowercase())),
-                        ..Default::default()
-                    });
-                }
-
-                items
-            }
-
-            SqlContext::AfterFrom | SqlContext::AfterJoin => {
-                let mut items = Vec::new();
-
                 for (schema_name, objects) in schemas.iter() {
                     for table in &objects.tables {
                         let qualified = if schema_name == "public" {
@@ -305,9 +673,12 @@ impl CompletionProvider for SqlCompletionProvider {
             SqlContext::AfterDot => {
                 let mut items = Vec::new();
 
-                let name_before_dot = ctx_info.prefix.or_else(|| get_identifier_before_dot(&text_str, offset));
-                if let Some(name_before_dot) = name_before_dot {
-                    if is_schema_name(&name_before_dot, &schemas) {
+                if let Some(name_before_dot) = ctx_info.prefix {
+                    if let Some(columns) =
+                        qualified_columns(&name_before_dot, &scope, &structures, &matches)
+                    {
+                        items = columns;
+                    } else if is_schema_name(&name_before_dot, &schemas) {
                         let schema_key = schemas
                             .keys()
                             .find(|k| k.eq_ignore_ascii_case(&name_before_dot))
@@ -340,73 +711,51 @@ impl CompletionProvider for SqlCompletionProvider {
                                 }
                             }
                         }
-                    } else {
-                        for structure in structures.iter() {
-                            if structure.table.eq_ignore_ascii_case(&name_before_dot) {
-                                for col in &structure.columns {
-                                    if !matches(&col.name) {
-                                        continue;
-                                    }
-                                    let priority = if col.is_primary_key {
-                                        "0"
-                                    } else if col.is_foreign_key {
-                                        "1"
-                                    } else {
-                                        "2"
-                                    };
-
-                                    let mut detail = col.data_type.clone();
-                                    if col.is_primary_key {
-                                        detail = format!("PK {}", detail);
-                                    }
-                                    if col.is_foreign_key {
-                                        if let Some(ref fk_ref) = col.references {
-                                            detail = format!("FK → {}.{} ({})", fk_ref.table, fk_ref.column, detail);
-                                        }
-                                    }
-
-                                    items.push(CompletionItem {
-                                        label: col.name.clone(),
-                                        kind: Some(CompletionItemKind::FIELD),
-                                        detail: Some(detail),
-                                        sort_text: Some(format!("{}_{}", priority, col.name.to_lowercase())),
-                                        ..Default::default()
-                                    });
-                                }
-                                break;
-                            }
-                        }
                     }
                 }
 
                 items
             }
 
-            SqlContext::AfterWhere => {
-                let mut items = Vec::new();
+            SqlContext::AfterOn => {
+                // Only the tables joined so far, so the last one is the
+                // table this ON belongs to.
+                let joined = scope_at(&text_str[..offset.min(text_str.len())], offset);
+                let mut items = join_conditions(&joined, &structures, &matches);
+                items.extend(scope_columns(&joined, &structures, &matches));
+                items
+            }
 
-                for structure in structures.iter() {
-                    for col in &structure.columns {
-                        if !matches(&col.name) {
-                            continue;
-                        }
-                        let priority = if col.is_primary_key {
-                            "0"
-                        } else if col.is_foreign_key {
-                            "1"
-                        } else {
-                            "2"
-                        };
+            SqlContext::AfterComparison => {
+                let column = ctx_info.column.as_deref().and_then(|column| {
+                    compared_column(ctx_info.prefix.as_deref(), column, &scope, &structures)
+                });
+                let mut items: Vec<CompletionItem> = column
+                    .and_then(|c| Some((c, catalog.enum_values(&c.data_type)?)))
+                    .map(|(c, values)| {
+                        values
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, v)| matches(v))
+                            .map(|(i, value)| CompletionItem {
+                                label: format!("'{}'", value.replace('\'', "''")),
+                                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                                detail: Some(c.data_type.clone()),
+                                sort_text: Some(format!("0_{:04}", i)),
+                                ..Default::default()
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                items.extend(scope_columns(&scope, &structures, &matches));
+                items.extend(function_items(&catalog, &matches));
+                items
+            }
 
-                        items.push(CompletionItem {
-                            label: col.name.clone(),
-                            kind: Some(CompletionItemKind::FIELD),
-                            detail: Some(col.data_type.clone()),
-                            sort_text: Some(format!("{}_{}", priority, col.name.to_lowercase())),
-                            ..Default::default()
-                        });
-                    }
-                }
+            SqlContext::AfterCast => type_items(&catalog, &matches),
+
+            SqlContext::AfterWhere => {
+                let mut items = scope_columns(&scope, &structures, &matches);
 
                 let operators = ["=", "<>", "<", ">", "<=", ">=", "LIKE", "ILIKE", "IN", "BETWEEN", "IS NULL", "IS NOT NULL"];
                 for op in operators {
@@ -423,22 +772,9 @@ impl CompletionProvider for SqlCompletionProvider {
                 items
             }
 
-            SqlContext::Unknown => {
-                let mut items = Vec::new();
+            SqlContext::InStringOrComment => Vec::new(),
 
-                for kw in SQL_KEYWORDS {
-                    if matches(kw) {
-                        items.push(CompletionItem {
-                            label: kw.to_string(),
-                            kind: Some(CompletionItemKind::KEYWORD),
-                            sort_text: Some(format!("9_{}", kw.to_lowercase())),
-                            ..Default::default()
-                        });
-                    }
-                }
-
-                items
-            }
+            SqlContext::Unknown => keyword_items(&catalog, &matches),
         })))
     }
 
@@ -0,0 +1,284 @@
+use super::params::cast_type;
+
+/// A table column and the text bound for it; `None` binds NULL. The value
+/// is cast to `type_name` on the server, so any type with a text input
+/// form can be written.
+pub struct ColumnValue<'a> {
+    pub name: &'a str,
+    pub type_name: &'a str,
+    pub value: Option<String>,
+}
+
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Builds `UPDATE schema.table SET ... WHERE ...` with every value bound as
+/// a parameter, in `set` then `key` order.
+pub fn update_statement(
+    schema: &str,
+    table: &str,
+    set: &[ColumnValue],
+    key: &[ColumnValue],
+) -> (String, Vec<Option<String>>) {
+    let mut params = Vec::with_capacity(set.len() + key.len());
+    let assignments = set
+        .iter()
+        .map(|c| format!("{} = {}", quote_ident(c.name), bind(c, &mut params)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let conditions = key
+        .iter()
+        .map(|c| format!("{} = {}", quote_ident(c.name), bind(c, &mut params)))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+
+    let sql = format!(
+        "UPDATE {}.{} SET {} WHERE {}",
+        quote_ident(schema),
+        quote_ident(table),
+        assignments,
+        conditions
+    );
+    (sql, params)
+}
+
+/// Builds `INSERT INTO schema.table (...) VALUES (...) RETURNING *`.
+/// Columns left out get their defaults; with none at all the row is
+/// inserted with `DEFAULT VALUES`.
+pub fn insert_statement(
+    schema: &str,
+    table: &str,
+    values: &[ColumnValue],
+) -> (String, Vec<Option<String>>) {
+    let target = format!("{}.{}", quote_ident(schema), quote_ident(table));
+    if values.is_empty() {
+        return (format!("INSERT INTO {} DEFAULT VALUES RETURNING *", target), vec![]);
+    }
+
+    let mut params = Vec::with_capacity(values.len());
+    let columns = values
+        .iter()
+        .map(|c| quote_ident(c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let placeholders = values
+        .iter()
+        .map(|c| bind(c, &mut params))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({}) RETURNING *",
+        target, columns, placeholders
+    );
+    (sql, params)
+}
+
+/// Builds `DELETE FROM schema.table WHERE ... RETURNING *`.
+pub fn delete_statement(
+    schema: &str,
+    table: &str,
+    key: &[ColumnValue],
+) -> (String, Vec<Option<String>>) {
+    let mut params = Vec::with_capacity(key.len());
+    let conditions = key
+        .iter()
+        .map(|c| format!("{} = {}", quote_ident(c.name), bind(c, &mut params)))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let sql = format!(
+        "DELETE FROM {}.{} WHERE {} RETURNING *",
+        quote_ident(schema),
+        quote_ident(table),
+        conditions
+    );
+    (sql, params)
+}
+
+/// Builds `SELECT * FROM schema.table WHERE ...` for the row a unique
+/// violation ran into, from the server's `Key (columns)=(values) already
+/// exists.` detail. Key columns may be expressions and are kept as the
+/// server wrote them. `None` when the values can't be told apart, as with
+/// text holding `, ` in a key of several columns.
+pub fn conflicting_row_query(schema: &str, table: &str, detail: &str) -> Option<String> {
+    let key = detail.strip_prefix("Key (")?;
+    let (columns, values) = key.split_once(")=(")?;
+    let values = values.strip_suffix(") already exists.")?;
+
+    let mut depth = 0;
+    let mut start = 0;
+    let mut columns_split = Vec::new();
+    for (i, c) in columns.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                columns_split.push(columns[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    columns_split.push(columns[start..].trim());
+
+    let values: Vec<&str> = if columns_split.len() == 1 {
+        vec![values]
+    } else {
+        values.split(", ").collect()
+    };
+    if values.len() != columns_split.len() {
+        return None;
+    }
+
+    let conditions = columns_split
+        .iter()
+        .zip(values)
+        .map(|(column, value)| format!("{} = '{}'", column, value.replace('\'', "''")))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    Some(format!(
+        "SELECT * FROM {}.{} WHERE {}",
+        quote_ident(schema),
+        quote_ident(table),
+        conditions
+    ))
+}
+
+fn bind(column: &ColumnValue, params: &mut Vec<Option<String>>) -> String {
+    params.push(column.value.clone());
+    format!("${}::{}", params.len(), cast_type(column.type_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn col<'a>(name: &'a str, type_name: &'a str, value: Option<&str>) -> ColumnValue<'a> {
+        ColumnValue {
+            name,
+            type_name,
+            value: value.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("users"), "\"users\"");
+        assert_eq!(quote_ident("we\"ird"), "\"we\"\"ird\"");
+    }
+
+    #[test]
+    fn test_update_binds_value_and_key() {
+        let (sql, params) = update_statement(
+            "public",
+            "users",
+            &[col("email", "VARCHAR", Some("a@b.c"))],
+            &[col("id", "INT4", Some("7"))],
+        );
+        assert_eq!(
+            sql,
+            "UPDATE \"public\".\"users\" SET \"email\" = $1::VARCHAR WHERE \"id\" = $2::INT4"
+        );
+        assert_eq!(params, vec![Some("a@b.c".to_string()), Some("7".to_string())]);
+    }
+
+    #[test]
+    fn test_update_fixed_length_columns() {
+        let (sql, _) = update_statement(
+            "public",
+            "codes",
+            &[col("label", "CHAR", Some("world")), col("flags", "BIT", Some("1010"))],
+            &[col("code", "CHAR", Some("abc"))],
+        );
+        assert_eq!(
+            sql,
+            "UPDATE \"public\".\"codes\" SET \"label\" = $1::BPCHAR, \"flags\" = $2::VARBIT WHERE \"code\" = $3::BPCHAR"
+        );
+    }
+
+    #[test]
+    fn test_update_null_value() {
+        let (_, params) = update_statement(
+            "public",
+            "users",
+            &[col("deleted_at", "TIMESTAMPTZ", None)],
+            &[col("id", "INT4", Some("1"))],
+        );
+        assert_eq!(params, vec![None, Some("1".to_string())]);
+    }
+
+    #[test]
+    fn test_update_composite_key() {
+        let (sql, _) = update_statement(
+            "app",
+            "order items",
+            &[col("qty", "INT4", Some("2"))],
+            &[col("order_id", "INT8", Some("1")), col("line", "INT2", Some("3"))],
+        );
+        assert_eq!(
+            sql,
+            "UPDATE \"app\".\"order items\" SET \"qty\" = $1::INT4 WHERE \"order_id\" = $2::INT8 AND \"line\" = $3::INT2"
+        );
+    }
+
+    #[test]
+    fn test_insert_binds_values() {
+        let (sql, params) = insert_statement(
+            "public",
+            "users",
+            &[col("name", "TEXT", Some("Ann")), col("age", "INT4", None)],
+        );
+        assert_eq!(
+            sql,
+            "INSERT INTO \"public\".\"users\" (\"name\", \"age\") VALUES ($1::TEXT, $2::INT4) RETURNING *"
+        );
+        assert_eq!(params, vec![Some("Ann".to_string()), None]);
+    }
+
+    #[test]
+    fn test_insert_all_defaults() {
+        let (sql, params) = insert_statement("public", "events", &[]);
+        assert_eq!(sql, "INSERT INTO \"public\".\"events\" DEFAULT VALUES RETURNING *");
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn test_delete_by_key() {
+        let (sql, params) = delete_statement(
+            "public",
+            "users",
+            &[col("id", "INT8", Some("42"))],
+        );
+        assert_eq!(
+            sql,
+            "DELETE FROM \"public\".\"users\" WHERE \"id\" = $1::INT8 RETURNING *"
+        );
+        assert_eq!(params, vec![Some("42".to_string())]);
+    }
+
+    #[test]
+    fn test_update_value_is_never_inlined() {
+        let (sql, params) = update_statement(
+            "public",
+            "t",
+            &[col("note", "TEXT", Some("'; DROP TABLE t; --"))],
+            &[col("id", "INT4", Some("1"))],
+        );
+        assert!(!sql.contains("DROP"));
+        assert_eq!(params[0].as_deref(), Some("'; DROP TABLE t; --"));
+    }
+
+    #[test]
+    fn test_conflicting_row_query() {
+        assert_eq!(
+            conflicting_row_query("public", "users", "Key (email)=(o'neil@x.io, jr) already exists.").as_deref(),
+            Some("SELECT * FROM \"public\".\"users\" WHERE email = 'o''neil@x.io, jr'")
+        );
+        assert_eq!(
+            conflicting_row_query("s", "t", "Key (a, lower(b::text))=(1, x) already exists.").as_deref(),
+            Some("SELECT * FROM \"s\".\"t\" WHERE a = '1' AND lower(b::text) = 'x'")
+        );
+        assert_eq!(conflicting_row_query("s", "t", "Key (a, b)=(1, x, y) already exists."), None);
+        assert_eq!(conflicting_row_query("s", "t", "Failing row contains (1)."), None);
+    }
+}
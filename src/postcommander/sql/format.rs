@@ -1,4 +1,5 @@
-use std::collections::HashSet;
+use super::lexer::{split_statements, tokenize, Token, TokenKind};
+use crate::settings::{CommaPlacement, FormatterSettings, LetterCase};
 
 const SQL_KEYWORDS: &[&str] = &[
     "select", "from", "where", "and", "or", "not", "in", "like", "ilike",
@@ -12,50 +13,124 @@ const SQL_KEYWORDS: &[&str] = &[
     "default", "check", "cascade", "nulls", "first", "last", "returning",
     "with", "recursive", "over", "partition", "rows", "range", "unbounded",
     "preceding", "following", "current", "row", "coalesce", "nullif",
+    "using", "natural", "full", "lateral", "only", "fetch", "filter",
+    "within", "interval", "any", "some", "similar", "for", "to", "do",
+    "nothing", "conflict", "window", "explain", "analyze", "begin", "commit",
+    "rollback", "grant", "revoke", "if",
 ];
 
-fn get_keywords_set() -> HashSet<&'static str> {
-    SQL_KEYWORDS.iter().copied().collect()
+/// Lays the script out with sqlformat, then applies what it has no
+/// options for: statements short enough to fit `line_width` are joined back
+/// onto one line, commas are moved to the start of lines, and keywords and
+/// unquoted identifiers get their configured case.
+pub fn format_with_style(sql: &str, style: &FormatterSettings) -> String {
+    let laid_out = sqlformat::format(
+        sql,
+        &sqlformat::QueryParams::None,
+        sqlformat::FormatOptions {
+            indent: sqlformat::Indent::Spaces(style.indent_width),
+            uppercase: false,
+            lines_between_queries: 1,
+        },
+    );
+    let mut formatted = join_short_statements(&laid_out, style.line_width);
+    if style.comma_placement == CommaPlacement::Leading {
+        formatted = leading_commas(&formatted);
+    }
+    apply_case(&formatted, style)
 }
 
-pub fn format_sql(sql: &str) -> String {
-    let keywords = get_keywords_set();
-    let mut result = String::with_capacity(sql.len());
-    let mut word_start = 0;
-    let mut in_string = false;
-    let mut string_char = ' ';
-    let chars: Vec<char> = sql.chars().collect();
-
-    for (i, &c) in chars.iter().enumerate() {
-        if !in_string && (c == '\'' || c == '"') {
-            in_string = true;
-            string_char = c;
-        } else if in_string && c == string_char {
-            in_string = false;
+fn join_short_statements(sql: &str, line_width: usize) -> String {
+    if line_width == 0 {
+        return sql.to_string();
+    }
+    let mut result = sql.to_string();
+    for statement in split_statements(sql).into_iter().rev() {
+        let terminator = usize::from(sql[statement.end..].trim_start().starts_with(';'));
+        if let Some(line) = single_line(&statement.text) {
+            if line.chars().count() + terminator <= line_width {
+                result.replace_range(statement.start..statement.end, &line);
+            }
         }
+    }
+    result
+}
 
-        let is_word_char = c.is_alphanumeric() || c == '_';
-
-        if !is_word_char || i == chars.len() - 1 {
-            let end = if is_word_char { i + 1 } else { i };
-            if end > word_start {
-                let word: String = chars[word_start..end].iter().collect();
-                let lower = word.to_lowercase();
+/// The statement with each run of whitespace collapsed to one space, or
+/// `None` when it can't be put on one line: a line comment would swallow
+/// the rest, and multi-line literals must keep their breaks.
+fn single_line(sql: &str) -> Option<String> {
+    let mut line = String::with_capacity(sql.len());
+    let mut space = false;
+    for token in tokenize(sql) {
+        if token.kind == TokenKind::Whitespace {
+            space = true;
+            continue;
+        }
+        if token.kind == TokenKind::LineComment || token.text.contains('\n') {
+            return None;
+        }
+        let tight = token.is_punct(',') || token.is_punct(')') || line.ends_with('(');
+        if space && !tight && !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(token.text);
+        space = false;
+    }
+    Some(line)
+}
 
-                if !in_string && keywords.contains(lower.as_str()) {
-                    result.push_str(&word.to_uppercase());
-                } else {
-                    result.push_str(&word);
-                }
-            }
+/// Moves each comma that ends a line to the start of the next one.
+fn leading_commas(sql: &str) -> String {
+    let tokens = tokenize(sql);
+    let mut result = String::with_capacity(sql.len());
+    let mut moved = false;
+    for (i, token) in tokens.iter().enumerate() {
+        let breaks_after = token.is_punct(',')
+            && tokens.get(i + 1).is_some_and(|t| t.kind == TokenKind::Whitespace && t.text.contains('\n'))
+            && tokens.get(i + 2).is_some_and(|t| !t.is_trivia());
+        if breaks_after {
+            moved = true;
+            continue;
+        }
+        if moved && !token.is_trivia() {
+            result.push_str(", ");
+            moved = false;
+        }
+        result.push_str(token.text);
+    }
+    result
+}
 
-            if !is_word_char {
-                result.push(c);
+/// Keywords take the keyword case and other unquoted words the identifier
+/// case. A word after `.` is always a name, and the word of a named `:name`
+/// placeholder is left alone so it still matches its bound value.
+fn apply_case(sql: &str, style: &FormatterSettings) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut previous: Option<Token> = None;
+    for token in tokenize(sql) {
+        if token.kind == TokenKind::Word {
+            let after = |c: char| previous.as_ref().is_some_and(|p| p.is_punct(c));
+            let is_keyword = !after('.') && capitalize_sql_keyword(token.text).is_some();
+            let case = if after(':') {
+                LetterCase::Preserve
+            } else if is_keyword {
+                style.keyword_case
+            } else {
+                style.identifier_case
+            };
+            match case {
+                LetterCase::Upper => result.push_str(&token.text.to_uppercase()),
+                LetterCase::Lower => result.push_str(&token.text.to_lowercase()),
+                LetterCase::Preserve => result.push_str(token.text),
             }
-            word_start = i + 1;
+        } else {
+            result.push_str(token.text);
+        }
+        if !token.is_trivia() {
+            previous = Some(token);
         }
     }
-
     result
 }
 
@@ -68,33 +143,26 @@ pub fn capitalize_sql_keyword(word: &str) -> Option<String> {
     }
 }
 
+/// When `trigger_char` ends a keyword typed in lowercase, returns its byte
+/// range and uppercase form. Words in strings, quoted identifiers, comments
+/// or after a `.` are left alone.
 pub fn maybe_capitalize_last_word(text: &str, trigger_char: char) -> Option<(usize, usize, String)> {
     if !matches!(trigger_char, ' ' | '\n' | '\t' | ',' | '(' | ')' | ';') {
         return None;
     }
 
     let text_before = text.trim_end_matches(trigger_char);
-    if text_before.is_empty() {
+    let tokens = tokenize(text_before);
+    let (word, before) = tokens.split_last()?;
+    if word.kind != TokenKind::Word || before.last().is_some_and(|t| t.is_punct('.')) {
         return None;
     }
 
-    let word_start = text_before
-        .rfind(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')' || c == ';')
-        .map(|i| i + 1)
-        .unwrap_or(0);
-
-    let word = &text_before[word_start..];
-    if word.is_empty() {
+    if word.text == word.text.to_uppercase() {
         return None;
     }
 
-    if word == word.to_uppercase() {
-        return None;
-    }
-
-    capitalize_sql_keyword(word).map(|upper| {
-        (word_start, word_start + word.len(), upper)
-    })
+    capitalize_sql_keyword(word.text).map(|upper| (word.start, word.end(), upper))
 }
 
 #[cfg(test)]
@@ -145,4 +213,71 @@ mod tests {
         let result = maybe_capitalize_last_word("SELECT * FROM users ", ' ');
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn test_maybe_capitalize_skips_string_and_comment() {
+        assert_eq!(maybe_capitalize_last_word("SELECT 'select ", ' '), None);
+        assert_eq!(maybe_capitalize_last_word("SELECT 1 -- from ", ' '), None);
+        assert_eq!(maybe_capitalize_last_word("SELECT \"from\" ", ' '), None);
+    }
+
+    #[test]
+    fn test_maybe_capitalize_skips_qualified_name() {
+        assert_eq!(maybe_capitalize_last_word("SELECT t.desc ", ' '), None);
+    }
+
+    fn style() -> FormatterSettings {
+        FormatterSettings::default()
+    }
+
+    #[test]
+    fn test_style_breaks_long_statements() {
+        let style = FormatterSettings { line_width: 0, ..style() };
+        assert_eq!(
+            format_with_style("select id, name from users where id = 1", &style),
+            "SELECT\n  id,\n  name\nFROM\n  users\nWHERE\n  id = 1"
+        );
+    }
+
+    #[test]
+    fn test_style_keeps_short_statements_on_one_line() {
+        assert_eq!(
+            format_with_style("select id,\n name from users where id in (1, 2);\nselect count(*) from t", &style()),
+            "SELECT id, name FROM users WHERE id IN (1, 2);\nSELECT count(*) FROM t"
+        );
+    }
+
+    #[test]
+    fn test_style_leading_commas_and_indent() {
+        let style = FormatterSettings {
+            indent_width: 4,
+            comma_placement: CommaPlacement::Leading,
+            line_width: 0,
+            ..style()
+        };
+        assert_eq!(
+            format_with_style("select id, name from users", &style),
+            "SELECT\n    id\n    , name\nFROM\n    users"
+        );
+    }
+
+    #[test]
+    fn test_style_cases() {
+        let style = FormatterSettings {
+            keyword_case: LetterCase::Lower,
+            identifier_case: LetterCase::Upper,
+            ..style()
+        };
+        assert_eq!(
+            format_with_style("SELECT u.first, \"Name\", 'abc' FROM users u WHERE id = :userId", &style),
+            "select U.FIRST, \"Name\", 'abc' from USERS U where ID = :userId"
+        );
+    }
+
+    #[test]
+    fn test_style_keeps_line_comments() {
+        let formatted = format_with_style("select id -- the key\nfrom t", &style());
+        assert!(formatted.contains("-- the key\n"));
+        assert!(formatted.contains("FROM"));
+    }
 }
@@ -0,0 +1,103 @@
+use super::ast::{parse_statement, Command};
+
+/// `SELECT count(*)` over the rows a plain UPDATE or DELETE would touch,
+/// built from its target, its `FROM`/`USING` list and its `WHERE`. A target
+/// row joined to several rows of that list is still touched once, so the
+/// join goes in an `EXISTS`. Anything else, including statements with
+/// `WITH` queries or `WHERE CURRENT OF`, gets `None`.
+pub fn count_query(sql: &str) -> Option<String> {
+    let statement = parse_statement(sql);
+    if statement.explain.is_some() || !statement.ctes.is_empty() {
+        return None;
+    }
+
+    let (target, from) = match statement.command {
+        Command::Delete => (
+            statement.clause("FROM", &["USING", "WHERE", "RETURNING"])?,
+            statement.clause("USING", &["WHERE", "RETURNING"]),
+        ),
+        Command::Update => (
+            statement.clause("UPDATE", &["SET"])?,
+            statement.clause("FROM", &["WHERE", "RETURNING"]),
+        ),
+        _ => return None,
+    };
+    if target.is_empty() {
+        return None;
+    }
+
+    let condition = match statement.where_clause() {
+        Some(c) if c.is_empty() || c.first().is_some_and(|n| n.is_keyword("CURRENT")) => return None,
+        Some(c) => Some(format!(" WHERE {}", statement.text(c))),
+        None => None,
+    };
+
+    let mut query = format!("SELECT count(*) FROM {}", statement.text(target));
+    match from.filter(|f| !f.is_empty()) {
+        Some(from) => {
+            query.push_str(" WHERE EXISTS (SELECT 1 FROM ");
+            query.push_str(statement.text(from));
+            query.push_str(condition.as_deref().unwrap_or_default());
+            query.push(')');
+        }
+        None => query.push_str(condition.as_deref().unwrap_or_default()),
+    }
+    Some(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete() {
+        assert_eq!(
+            count_query("DELETE FROM orders WHERE status = 'x'").as_deref(),
+            Some("SELECT count(*) FROM orders WHERE status = 'x'")
+        );
+        assert_eq!(
+            count_query("delete from only public.orders o").as_deref(),
+            Some("SELECT count(*) FROM only public.orders o")
+        );
+    }
+
+    #[test]
+    fn test_delete_using() {
+        assert_eq!(
+            count_query("DELETE FROM a USING b WHERE a.id = b.id RETURNING a.*").as_deref(),
+            Some("SELECT count(*) FROM a WHERE EXISTS (SELECT 1 FROM b WHERE a.id = b.id)")
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        assert_eq!(
+            count_query("UPDATE t AS x SET a = (SELECT 1 FROM u WHERE u.id = 2) WHERE x.b > 3").as_deref(),
+            Some("SELECT count(*) FROM t AS x WHERE x.b > 3")
+        );
+        assert_eq!(
+            count_query("UPDATE t SET a = u.a FROM u WHERE t.id = u.id").as_deref(),
+            Some("SELECT count(*) FROM t WHERE EXISTS (SELECT 1 FROM u WHERE t.id = u.id)")
+        );
+        assert_eq!(
+            count_query("UPDATE t SET a = 1 FROM u").as_deref(),
+            Some("SELECT count(*) FROM t WHERE EXISTS (SELECT 1 FROM u)")
+        );
+    }
+
+    #[test]
+    fn test_comments_are_kept_out_of_the_clauses() {
+        assert_eq!(
+            count_query("-- purge\nDELETE FROM t /* all */ WHERE done -- old\n").as_deref(),
+            Some("SELECT count(*) FROM t WHERE done")
+        );
+    }
+
+    #[test]
+    fn test_other_statements() {
+        assert_eq!(count_query("SELECT * FROM t"), None);
+        assert_eq!(count_query("WITH x AS (SELECT 1) DELETE FROM t"), None);
+        assert_eq!(count_query("EXPLAIN DELETE FROM t"), None);
+        assert_eq!(count_query("DELETE FROM t WHERE CURRENT OF c"), None);
+    }
+}
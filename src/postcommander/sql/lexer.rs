@@ -0,0 +1,667 @@
+/// What a token is. Keywords are plain words; see `Token::is_keyword`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Word,
+    /// `"name"` or `U&"name"`.
+    QuotedIdent,
+    /// `'...'`, including `E''`, `B''`, `X''`, `N''` and `U&''` forms.
+    String,
+    /// `$$...$$` or `$tag$...$tag$`.
+    DollarString,
+    Number,
+    /// `$1`. Named `:name` placeholders are a `:` followed by a word.
+    Parameter,
+    Operator,
+    Punct,
+    LineComment,
+    BlockComment,
+    Whitespace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub start: usize,
+    /// The token runs to the end of the input without being closed, as a
+    /// string or comment still being typed does.
+    pub unterminated: bool,
+}
+
+impl Token<'_> {
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment
+        )
+    }
+
+    /// An unquoted word matching `keyword` in any case. Quoted identifiers
+    /// never match, as in PostgreSQL.
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    pub fn is_punct(&self, c: char) -> bool {
+        self.kind == TokenKind::Punct && self.text.len() == 1 && self.text.starts_with(c)
+    }
+
+    /// The name a word or quoted identifier stands for. Unquoted words are
+    /// returned as written, not case-folded.
+    pub fn ident(&self) -> String {
+        match self.kind {
+            TokenKind::QuotedIdent => {
+                let body = self.text.trim_start_matches(['U', 'u', '&']);
+                let body = body.strip_prefix('"').unwrap_or(body);
+                let body = if self.unterminated {
+                    body
+                } else {
+                    body.strip_suffix('"').unwrap_or(body)
+                };
+                body.replace("\"\"", "\"")
+            }
+            _ => self.text.to_string(),
+        }
+    }
+}
+
+/// Splits SQL into tokens the way PostgreSQL's lexer does, covering the
+/// source with no gaps. Unterminated strings and comments run to the end.
+pub fn tokenize(sql: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < sql.len() {
+        let (kind, end, unterminated) = next_token(sql, i);
+        tokens.push(Token {
+            kind,
+            text: &sql[i..end],
+            start: i,
+            unterminated,
+        });
+        i = end;
+    }
+    tokens
+}
+
+/// One statement of a script. `start..end` is its byte range in the source,
+/// trimmed of surrounding whitespace and without the closing semicolon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlStatement {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Splits a script on top-level semicolons. Semicolons inside string
+/// literals (including E'' and dollar-quoted bodies), quoted identifiers
+/// and comments don't end a statement. Pieces holding only comments are
+/// dropped.
+pub fn split_statements(sql: &str) -> Vec<SqlStatement> {
+    let mut statements = Vec::new();
+    let mut segment_start = 0;
+    let mut has_code = false;
+
+    for token in tokenize(sql) {
+        if token.is_punct(';') {
+            if has_code {
+                statements.push(statement(sql, segment_start, token.start));
+            }
+            segment_start = token.end();
+            has_code = false;
+        } else if !token.is_trivia() {
+            has_code = true;
+        }
+    }
+
+    if has_code {
+        statements.push(statement(sql, segment_start, sql.len()));
+    }
+    statements
+}
+
+/// The statement the cursor is in. A cursor in the gap after a statement
+/// belongs to that statement, so running right after typing `;` works.
+pub fn statement_at(sql: &str, offset: usize) -> Option<SqlStatement> {
+    let statements = split_statements(sql);
+    let index = statements
+        .iter()
+        .rposition(|s| s.start <= offset)
+        .unwrap_or(0);
+    statements.into_iter().nth(index)
+}
+
+fn statement(sql: &str, start: usize, end: usize) -> SqlStatement {
+    let piece = &sql[start..end];
+    let leading = piece.len() - piece.trim_start().len();
+    let start = start + leading;
+    let end = start + piece.trim().len();
+    SqlStatement {
+        text: sql[start..end].to_string(),
+        start,
+        end,
+    }
+}
+
+/// Kind, end and whether it is unterminated, for the token at `i`. Every
+/// token boundary falls on an ASCII byte, so slicing stays on char
+/// boundaries.
+fn next_token(sql: &str, i: usize) -> (TokenKind, usize, bool) {
+    let bytes = sql.as_bytes();
+    let c = bytes[i];
+    let next = bytes.get(i + 1).copied();
+
+    match c {
+        c if c.is_ascii_whitespace() => (
+            TokenKind::Whitespace,
+            scan(bytes, i, |c| c.is_ascii_whitespace()),
+            false,
+        ),
+        b'-' if next == Some(b'-') => match sql[i..].find('\n') {
+            Some(n) => (TokenKind::LineComment, i + n, false),
+            None => (TokenKind::LineComment, bytes.len(), true),
+        },
+        b'/' if next == Some(b'*') => {
+            let (end, closed) = skip_block_comment(bytes, i);
+            (TokenKind::BlockComment, end, !closed)
+        }
+        b'\'' => {
+            let (end, closed) = skip_quoted(bytes, i + 1, b'\'', false);
+            (TokenKind::String, end, !closed)
+        }
+        b'"' => {
+            let (end, closed) = skip_quoted(bytes, i + 1, b'"', false);
+            (TokenKind::QuotedIdent, end, !closed)
+        }
+        b'$' if next.is_some_and(|c| c.is_ascii_digit()) => (
+            TokenKind::Parameter,
+            scan(bytes, i + 1, |c| c.is_ascii_digit()),
+            false,
+        ),
+        b'$' => match dollar_tag(sql, i) {
+            Some(tag) => {
+                let body = i + tag.len();
+                match sql[body..].find(tag) {
+                    Some(n) => (TokenKind::DollarString, body + n + tag.len(), false),
+                    None => (TokenKind::DollarString, bytes.len(), true),
+                }
+            }
+            None => (TokenKind::Punct, i + 1, false),
+        },
+        b'0'..=b'9' => (TokenKind::Number, skip_number(bytes, i), false),
+        b'.' if next.is_some_and(|c| c.is_ascii_digit()) => {
+            (TokenKind::Number, skip_number(bytes, i), false)
+        }
+        b'E' | b'e' | b'B' | b'b' | b'X' | b'x' | b'N' | b'n' if next == Some(b'\'') => {
+            let escapes = c.eq_ignore_ascii_case(&b'e');
+            let (end, closed) = skip_quoted(bytes, i + 2, b'\'', escapes);
+            (TokenKind::String, end, !closed)
+        }
+        b'U' | b'u' if next == Some(b'&') && matches!(bytes.get(i + 2), Some(b'\'' | b'"')) => {
+            let quote = bytes[i + 2];
+            let (end, closed) = skip_quoted(bytes, i + 3, quote, false);
+            let kind = if quote == b'"' {
+                TokenKind::QuotedIdent
+            } else {
+                TokenKind::String
+            };
+            (kind, end, !closed)
+        }
+        c if is_ident_start(c) => (TokenKind::Word, scan(bytes, i, is_ident_byte), false),
+        b':' if next == Some(b':') => (TokenKind::Operator, i + 2, false),
+        c if is_operator_byte(c) => {
+            let mut end = i + 1;
+            while end < bytes.len()
+                && is_operator_byte(bytes[end])
+                && !matches!(&bytes[end..], [b'-', b'-', ..] | [b'/', b'*', ..])
+            {
+                end += 1;
+            }
+            // `=-1` is `=` then `-1`: a trailing + or - only belongs to the
+            // operator when it holds one of the less common characters.
+            let exotic = bytes[i..end]
+                .iter()
+                .any(|c| matches!(c, b'~' | b'!' | b'@' | b'#' | b'%' | b'^' | b'&' | b'|' | b'`' | b'?'));
+            while !exotic && end - i > 1 && matches!(bytes[end - 1], b'+' | b'-') {
+                end -= 1;
+            }
+            (TokenKind::Operator, end, false)
+        }
+        _ => (TokenKind::Punct, i + 1, false),
+    }
+}
+
+fn is_ident_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_' || c >= 0x80
+}
+
+fn is_ident_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80
+}
+
+fn is_operator_byte(c: u8) -> bool {
+    matches!(
+        c,
+        b'+' | b'-' | b'*' | b'/' | b'<' | b'>' | b'=' | b'~' | b'!' | b'@' | b'#' | b'%' | b'^'
+            | b'&' | b'|' | b'`' | b'?'
+    )
+}
+
+fn scan(bytes: &[u8], mut i: usize, matches: impl Fn(u8) -> bool) -> usize {
+    while i < bytes.len() && matches(bytes[i]) {
+        i += 1;
+    }
+    i
+}
+
+/// Digits with an optional fraction and exponent, plus the `0x`, `1_000`
+/// and similar forms, which all stay within alphanumerics.
+fn skip_number(bytes: &[u8], i: usize) -> usize {
+    let mut end = scan(bytes, i, |c| c.is_ascii_alphanumeric() || c == b'_' || c == b'.');
+    if matches!(bytes[end - 1], b'e' | b'E')
+        && matches!(bytes.get(end), Some(b'+' | b'-'))
+        && bytes.get(end + 1).is_some_and(|c| c.is_ascii_digit())
+    {
+        end = scan(bytes, end + 1, |c| c.is_ascii_digit());
+    }
+    end
+}
+
+/// Returns the index just past the closing quote and whether there was
+/// one. A doubled quote stands for the quote itself.
+fn skip_quoted(bytes: &[u8], mut i: usize, quote: u8, backslash_escapes: bool) -> (usize, bool) {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if backslash_escapes => i += 2,
+            c if c == quote && bytes.get(i + 1) == Some(&quote) => i += 2,
+            c if c == quote => return (i + 1, true),
+            _ => i += 1,
+        }
+    }
+    (bytes.len(), false)
+}
+
+/// Block comments nest in PostgreSQL.
+fn skip_block_comment(bytes: &[u8], mut i: usize) -> (usize, bool) {
+    let mut depth = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            depth += 1;
+            i += 2;
+        } else if bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/') {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return (i, true);
+            }
+        } else {
+            i += 1;
+        }
+    }
+    (bytes.len(), false)
+}
+
+/// Reads a dollar-quote opener such as `$$` or `$body$` at `start`.
+fn dollar_tag(sql: &str, start: usize) -> Option<&str> {
+    let rest = &sql[start + 1..];
+    let tag_len = rest.find('$')?;
+    let tag = &rest[..tag_len];
+    let valid = tag.is_empty()
+        || (!tag.starts_with(|c: char| c.is_ascii_digit())
+            && tag.chars().all(|c| c.is_alphanumeric() || c == '_'));
+    valid.then(|| &sql[start..start + tag_len + 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(sql: &str) -> Vec<String> {
+        split_statements(sql).into_iter().map(|s| s.text).collect()
+    }
+
+    /// Non-trivia tokens as (kind, text).
+    fn lex(sql: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(sql)
+            .into_iter()
+            .filter(|t| t.kind != TokenKind::Whitespace)
+            .map(|t| (t.kind, t.text))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokens_cover_source() {
+        let sql = "SELECT e'\\'', \"a\"\"b\", $x$ ' $x$ -- c\n/* d */ FROM t";
+        let joined: String = tokenize(sql).iter().map(|t| t.text).collect();
+        assert_eq!(joined, sql);
+    }
+
+    #[test]
+    fn test_lex_basic() {
+        use TokenKind::*;
+        assert_eq!(
+            lex("SELECT a.b, 1.5e-3 FROM t WHERE x >= $1;"),
+            vec![
+                (Word, "SELECT"),
+                (Word, "a"),
+                (Punct, "."),
+                (Word, "b"),
+                (Punct, ","),
+                (Number, "1.5e-3"),
+                (Word, "FROM"),
+                (Word, "t"),
+                (Word, "WHERE"),
+                (Word, "x"),
+                (Operator, ">="),
+                (Parameter, "$1"),
+                (Punct, ";"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_doubled_quotes_stay_in_one_token() {
+        use TokenKind::*;
+        assert_eq!(
+            lex("'it''s' \"say \"\"hi\"\"\""),
+            vec![(String, "'it''s'"), (QuotedIdent, "\"say \"\"hi\"\"\"")]
+        );
+    }
+
+    #[test]
+    fn test_lex_escape_string() {
+        use TokenKind::*;
+        assert_eq!(
+            lex(r"E'a\'b' e'\\' x"),
+            vec![(String, r"E'a\'b'"), (String, r"e'\\'"), (Word, "x")]
+        );
+    }
+
+    #[test]
+    fn test_lex_backslash_in_standard_string() {
+        use TokenKind::*;
+        assert_eq!(lex(r"'a\' b"), vec![(String, r"'a\'"), (Word, "b")]);
+    }
+
+    #[test]
+    fn test_lex_prefixed_strings() {
+        use TokenKind::*;
+        assert_eq!(
+            lex("B'101' X'ff' N'n' U&'\\0041' U&\"d\\0061t\""),
+            vec![
+                (String, "B'101'"),
+                (String, "X'ff'"),
+                (String, "N'n'"),
+                (String, "U&'\\0041'"),
+                (QuotedIdent, "U&\"d\\0061t\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_word_ending_in_e_is_not_a_prefix() {
+        use TokenKind::*;
+        assert_eq!(
+            lex(r"name'\' date '2024-01-01'"),
+            vec![
+                (Word, "name"),
+                (String, r"'\'"),
+                (Word, "date"),
+                (String, "'2024-01-01'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_dollar_quotes() {
+        use TokenKind::*;
+        assert_eq!(
+            lex("$$ it's $$ $fn$ $$ $fn$ $1 a$b"),
+            vec![
+                (DollarString, "$$ it's $$"),
+                (DollarString, "$fn$ $$ $fn$"),
+                (Parameter, "$1"),
+                (Word, "a$b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_comments() {
+        use TokenKind::*;
+        assert_eq!(
+            lex("a -- 'x\n/* b /* c */ ' */ d"),
+            vec![
+                (Word, "a"),
+                (LineComment, "-- 'x"),
+                (BlockComment, "/* b /* c */ ' */"),
+                (Word, "d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_operator_stops_before_comment() {
+        use TokenKind::*;
+        assert_eq!(
+            lex("a+-- c\n1"),
+            vec![(Word, "a"), (Operator, "+"), (LineComment, "-- c"), (Number, "1")]
+        );
+    }
+
+    #[test]
+    fn test_lex_trailing_sign_is_not_part_of_operator() {
+        use TokenKind::*;
+        assert_eq!(
+            lex("a=-1 b->>'k'"),
+            vec![
+                (Word, "a"),
+                (Operator, "="),
+                (Operator, "-"),
+                (Number, "1"),
+                (Word, "b"),
+                (Operator, "->>"),
+                (String, "'k'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_casts_and_named_placeholders() {
+        use TokenKind::*;
+        assert_eq!(
+            lex(":id::int"),
+            vec![(Punct, ":"), (Word, "id"), (Operator, "::"), (Word, "int")]
+        );
+    }
+
+    #[test]
+    fn test_lex_unterminated() {
+        let tokens = tokenize("SELECT 'abc");
+        assert!(tokens.last().unwrap().unterminated);
+        let tokens = tokenize("SELECT 1 -- note");
+        assert!(tokens.last().unwrap().unterminated);
+        let tokens = tokenize("SELECT 'abc'");
+        assert!(!tokens.last().unwrap().unterminated);
+    }
+
+    #[test]
+    fn test_ident() {
+        let tokens = tokenize("\"My \"\"Table\"\"\" users \"open");
+        assert_eq!(tokens[0].ident(), "My \"Table\"");
+        assert_eq!(tokens[2].ident(), "users");
+        assert_eq!(tokens[4].ident(), "open");
+    }
+
+    #[test]
+    fn test_keyword_ignores_quoted() {
+        let tokens = tokenize("select \"select\"");
+        assert!(tokens[0].is_keyword("SELECT"));
+        assert!(!tokens[2].is_keyword("SELECT"));
+    }
+
+    #[test]
+    fn test_split_simple() {
+        assert_eq!(texts("SELECT 1; SELECT 2;"), vec!["SELECT 1", "SELECT 2"]);
+    }
+
+    #[test]
+    fn test_split_without_trailing_semicolon() {
+        assert_eq!(texts("SELECT 1;\nSELECT 2"), vec!["SELECT 1", "SELECT 2"]);
+    }
+
+    #[test]
+    fn test_split_single_statement() {
+        assert_eq!(texts("  SELECT * FROM users  "), vec!["SELECT * FROM users"]);
+    }
+
+    #[test]
+    fn test_split_skips_empty_statements() {
+        assert_eq!(texts(";;SELECT 1;; ;"), vec!["SELECT 1"]);
+    }
+
+    #[test]
+    fn test_split_semicolon_in_string() {
+        assert_eq!(
+            texts("SELECT 'a;b'; SELECT 2"),
+            vec!["SELECT 'a;b'", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_doubled_quote_in_string() {
+        assert_eq!(
+            texts("SELECT 'it''s; fine'; SELECT 2"),
+            vec!["SELECT 'it''s; fine'", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_escape_string() {
+        assert_eq!(
+            texts(r"SELECT E'a\';b'; SELECT 2"),
+            vec![r"SELECT E'a\';b'", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_backslash_in_standard_string() {
+        assert_eq!(
+            texts(r"SELECT 'a\'; SELECT 2"),
+            vec![r"SELECT 'a\'", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_identifier_ending_in_e() {
+        assert_eq!(
+            texts(r"SELECT name'\'; SELECT 2"),
+            vec![r"SELECT name'\'", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_quoted_identifier() {
+        assert_eq!(
+            texts(r#"SELECT "a;b" FROM t; SELECT 2"#),
+            vec![r#"SELECT "a;b" FROM t"#, "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_line_comment() {
+        assert_eq!(
+            texts("SELECT 1 -- done; really\n; SELECT 2"),
+            vec!["SELECT 1 -- done; really", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_nested_block_comment() {
+        assert_eq!(
+            texts("SELECT /* a /* b; */ c; */ 1; SELECT 2"),
+            vec!["SELECT /* a /* b; */ c; */ 1", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_drops_comment_only_pieces() {
+        assert_eq!(
+            texts("SELECT 1;\n-- trailing note\n/* block */"),
+            vec!["SELECT 1"]
+        );
+    }
+
+    #[test]
+    fn test_split_dollar_quoted_body() {
+        let sql = "CREATE FUNCTION f() RETURNS int AS $$ BEGIN RETURN 1; END; $$ LANGUAGE plpgsql; SELECT f()";
+        assert_eq!(
+            texts(sql),
+            vec![
+                "CREATE FUNCTION f() RETURNS int AS $$ BEGIN RETURN 1; END; $$ LANGUAGE plpgsql",
+                "SELECT f()",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_tagged_dollar_quote() {
+        let sql = "DO $body$ BEGIN PERFORM '$$;'; END $body$; SELECT 2";
+        assert_eq!(
+            texts(sql),
+            vec!["DO $body$ BEGIN PERFORM '$$;'; END $body$", "SELECT 2"]
+        );
+    }
+
+    #[test]
+    fn test_split_positional_parameter_is_not_dollar_quote() {
+        assert_eq!(
+            texts("SELECT $1; SELECT $2"),
+            vec!["SELECT $1", "SELECT $2"]
+        );
+    }
+
+    #[test]
+    fn test_split_unterminated_string() {
+        assert_eq!(texts("SELECT 'abc; SELECT 2"), vec!["SELECT 'abc; SELECT 2"]);
+    }
+
+    #[test]
+    fn test_split_offsets() {
+        let sql = "  SELECT 1 ;\n\nSELECT 2";
+        let statements = split_statements(sql);
+        assert_eq!(&sql[statements[0].start..statements[0].end], "SELECT 1");
+        assert_eq!(&sql[statements[1].start..statements[1].end], "SELECT 2");
+    }
+
+    #[test]
+    fn test_split_non_ascii() {
+        assert_eq!(
+            texts("SELECT 'héllo;'; SELECT 'ø'"),
+            vec!["SELECT 'héllo;'", "SELECT 'ø'"]
+        );
+    }
+
+    #[test]
+    fn test_statement_at_cursor() {
+        let sql = "SELECT 1;\nSELECT 2;\nSELECT 3";
+        assert_eq!(statement_at(sql, 0).unwrap().text, "SELECT 1");
+        assert_eq!(statement_at(sql, 12).unwrap().text, "SELECT 2");
+        assert_eq!(statement_at(sql, sql.len()).unwrap().text, "SELECT 3");
+    }
+
+    #[test]
+    fn test_statement_at_cursor_after_semicolon() {
+        let sql = "SELECT 1;\nSELECT 2;";
+        assert_eq!(statement_at(sql, sql.len()).unwrap().text, "SELECT 2");
+        assert_eq!(statement_at(sql, 9).unwrap().text, "SELECT 1");
+    }
+
+    #[test]
+    fn test_statement_at_empty() {
+        assert_eq!(statement_at("  -- nothing here", 3), None);
+    }
+}
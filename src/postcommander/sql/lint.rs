@@ -0,0 +1,384 @@
+use super::ast::{parse_nodes, parse_statement, Command, Node};
+use super::lexer::{split_statements, tokenize, Token, TokenKind};
+use super::scope::{statement_scope, Scope, TableRef};
+use crate::postcommander::types::{SchemaMap, TableStructureInfo};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// A likely mistake found before the script is run, at a byte range of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlLint {
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+/// What the lints check names against. Tables are only checked once the
+/// schema list has loaded, and columns only of tables whose structure has.
+pub struct LintContext<'a> {
+    pub schemas: &'a SchemaMap,
+    /// Loaded structures by `schema.table`.
+    pub structures: &'a HashMap<String, TableStructureInfo>,
+    /// Whether the script is a saved query, which should name its columns.
+    pub saved_query: bool,
+}
+
+/// Columns every table has without listing them.
+const SYSTEM_COLUMNS: &[&str] = &["ctid", "xmin", "xmax", "cmin", "cmax", "tableoid", "oid"];
+
+/// Words that end a FROM list or the table part of a JOIN.
+const CLAUSE_END: &[&str] = &[
+    "WHERE", "GROUP", "HAVING", "WINDOW", "ORDER", "LIMIT", "OFFSET", "FETCH", "FOR", "UNION",
+    "INTERSECT", "EXCEPT", "RETURNING", "ON", "USING", "SET", "VALUES", "SELECT",
+];
+
+const JOIN_KINDS: &[&str] = &["LEFT", "RIGHT", "FULL", "INNER", "OUTER"];
+
+pub fn lint_script(sql: &str, context: &LintContext) -> Vec<SqlLint> {
+    let mut lints = Vec::new();
+    let mut created = HashSet::new();
+    for statement in split_statements(sql) {
+        let parsed = parse_statement(&statement.text);
+        let at = |range: Range<usize>| statement.start + range.start..statement.start + range.end;
+
+        if parsed.command == Command::Create {
+            if let Some(name) = created_name(&parsed.body) {
+                created.insert(name);
+            }
+        }
+        let queries = matches!(
+            parsed.command,
+            Command::Select | Command::Insert | Command::Update | Command::Delete
+        );
+        if queries && !context.schemas.is_empty() {
+            let scope = statement_scope(&statement.text);
+            for (range, message) in unknown_names(&statement.text, &scope, &created, context) {
+                lints.push(SqlLint { range: at(range), message });
+            }
+        }
+
+        let nodes = parse_nodes(&statement.text);
+        let mut found = Vec::new();
+        each_level(&nodes, &mut |level| {
+            join_conditions(level, &mut found);
+            if context.saved_query {
+                select_star(level, &mut found);
+            }
+        });
+        lints.extend(found.into_iter().map(|(range, message)| SqlLint { range: at(range), message }));
+    }
+    lints.sort_by_key(|l| l.range.start);
+    lints
+}
+
+/// Where the server's error position points in the statement it ran:
+/// `position` counts characters from 1, one past the end meaning the end
+/// of input. Returns the byte range of the token found there.
+pub fn error_range(statement: &str, position: usize) -> Option<Range<usize>> {
+    let tokens: Vec<Token> = tokenize(statement).into_iter().filter(|t| !t.is_trivia()).collect();
+    let offset = position
+        .checked_sub(1)
+        .and_then(|p| statement.char_indices().nth(p))
+        .map(|(byte, _)| byte);
+    let token = match offset {
+        Some(offset) => tokens.iter().find(|t| t.start <= offset && offset < t.end()),
+        None => tokens.last(),
+    };
+    match (token, offset) {
+        (Some(token), _) => Some(token.start..token.end()),
+        (None, Some(offset)) => {
+            let len = statement[offset..].chars().next().map_or(1, char::len_utf8);
+            Some(offset..offset + len)
+        }
+        (None, None) => None,
+    }
+}
+
+fn each_level<'a>(nodes: &[Node<'a>], f: &mut dyn FnMut(&[Node<'a>])) {
+    f(nodes);
+    for node in nodes {
+        if let Node::Group { children, .. } = node {
+            each_level(children, f);
+        }
+    }
+}
+
+/// `CREATE [ OR REPLACE ] [ TEMP | UNLOGGED | MATERIALIZED ] TABLE | VIEW
+/// [ IF NOT EXISTS ] [ schema. ] name`, folded.
+fn created_name(body: &[Node]) -> Option<String> {
+    let kind = body
+        .iter()
+        .position(|n| n.is_keyword("TABLE") || n.is_keyword("VIEW"))?;
+    let mut i = kind + 1;
+    if body.get(i).is_some_and(|n| n.is_keyword("IF")) {
+        i += 3;
+    }
+    let mut name = body.get(i)?.token()?;
+    if body.get(i + 1).is_some_and(|n| n.is_punct('.')) {
+        name = body.get(i + 2)?.token()?;
+    }
+    matches!(name.kind, TokenKind::Word | TokenKind::QuotedIdent).then(|| folded(name))
+}
+
+/// Tables that are in no loaded schema, and `alias.column` references to
+/// columns a loaded table doesn't have.
+fn unknown_names(
+    sql: &str,
+    scope: &Scope,
+    created: &HashSet<String>,
+    context: &LintContext,
+) -> Vec<(Range<usize>, String)> {
+    let mut found = Vec::new();
+    for table in scope.base_tables() {
+        let system = table.name.starts_with("pg_")
+            || table
+                .schema
+                .as_ref()
+                .is_some_and(|s| s.starts_with("pg_") || s == "information_schema");
+        if system || created.contains(&table.name) || schema_of(table, context.schemas).is_some() {
+            continue;
+        }
+        let message = match &table.schema {
+            Some(schema) if !context.schemas.contains_key(schema) => {
+                format!("Unknown schema \"{}\"", schema)
+            }
+            Some(schema) => format!("Unknown table or view \"{}.{}\"", schema, table.name),
+            None => format!("Unknown table or view \"{}\"", table.name),
+        };
+        found.push((table.span.clone(), message));
+    }
+
+    let tokens: Vec<Token> = tokenize(sql).into_iter().filter(|t| !t.is_trivia()).collect();
+    for (i, window) in tokens.windows(3).enumerate() {
+        let [qualifier, dot, column] = window else {
+            continue;
+        };
+        let is_name = |t: &Token| matches!(t.kind, TokenKind::Word | TokenKind::QuotedIdent);
+        let chained = (i > 0 && tokens[i - 1].is_punct('.'))
+            || tokens.get(i + 3).is_some_and(|t| t.is_punct('.') || t.is_punct('('));
+        if !dot.is_punct('.') || !is_name(qualifier) || !is_name(column) || chained {
+            continue;
+        }
+        let Some(table) = scope.resolve(&folded(qualifier)) else {
+            continue;
+        };
+        if table.schema.is_none() && scope.derived(&table.name).is_some() {
+            continue;
+        }
+        let Some(schema) = schema_of(table, context.schemas) else {
+            continue;
+        };
+        let Some(structure) = context.structures.get(&format!("{}.{}", schema, table.name)) else {
+            continue;
+        };
+        let name = folded(column);
+        let known = SYSTEM_COLUMNS.contains(&name.as_str())
+            || structure.columns.iter().any(|c| c.name == name);
+        if !known {
+            found.push((
+                column.start..column.end(),
+                format!("Column \"{}\" not found in \"{}\"", name, table.name),
+            ));
+        }
+    }
+    found
+}
+
+/// The loaded schema holding the table, trying `public` first for an
+/// unqualified name.
+fn schema_of<'s>(table: &TableRef, schemas: &'s SchemaMap) -> Option<&'s str> {
+    let holds = |schema: &str| {
+        schemas.get(schema).is_some_and(|objects| {
+            objects.tables.contains(&table.name) || objects.views.contains(&table.name)
+        })
+    };
+    match &table.schema {
+        Some(schema) => schemas.get_key_value(schema).filter(|(k, _)| holds(k)).map(|(k, _)| k.as_str()),
+        None if holds("public") => schemas.get_key_value("public").map(|(k, _)| k.as_str()),
+        None => schemas.keys().find(|k| holds(k)).map(String::as_str),
+    }
+}
+
+/// A JOIN with neither ON nor USING, and a FROM list of two or more
+/// tables with no WHERE at all. Both pair every row with every row.
+fn join_conditions(nodes: &[Node], found: &mut Vec<(Range<usize>, String)>) {
+    let has_where = nodes.iter().any(|n| n.is_keyword("WHERE"));
+    for (i, node) in nodes.iter().enumerate() {
+        if node.is_keyword("JOIN") {
+            let mut first = i;
+            while first > 0 && JOIN_KINDS.iter().any(|k| nodes[first - 1].is_keyword(k)) {
+                first -= 1;
+            }
+            if first > 0 && (nodes[first - 1].is_keyword("CROSS") || nodes[first - 1].is_keyword("NATURAL")) {
+                continue;
+            }
+            let end = nodes[i + 1..]
+                .iter()
+                .position(|n| n.is_keyword("JOIN") || n.is_punct(',') || ends_clause(n))
+                .map_or(nodes.len(), |e| i + 1 + e);
+            let condition = nodes
+                .get(end)
+                .is_some_and(|n| n.is_keyword("ON") || n.is_keyword("USING"));
+            if end > i + 1 && !condition {
+                found.push((
+                    nodes[first].start()..node.end(),
+                    "JOIN without ON or USING".to_string(),
+                ));
+            }
+        } else if node.is_keyword("FROM") && !has_where {
+            let end = nodes[i + 1..]
+                .iter()
+                .position(|n| ends_clause(n) || n.is_keyword("JOIN"))
+                .map_or(nodes.len(), |e| i + 1 + e);
+            let items: Vec<&[Node]> = nodes[i + 1..end].split(|n| n.is_punct(',')).collect();
+            let plain = items.iter().all(|item| {
+                item.first().and_then(Node::token).is_some_and(|t| {
+                    matches!(t.kind, TokenKind::Word | TokenKind::QuotedIdent) && !t.is_keyword("LATERAL")
+                }) && !item.iter().any(|n| matches!(n, Node::Group { .. }))
+            });
+            if items.len() > 1 && plain {
+                found.push((
+                    nodes[i + 1].start()..nodes[end - 1].end(),
+                    "Tables listed without a join condition; every row of one pairs with every row of the other"
+                        .to_string(),
+                ));
+            }
+        }
+    }
+}
+
+fn ends_clause(node: &Node) -> bool {
+    CLAUSE_END.iter().any(|k| node.is_keyword(k))
+}
+
+/// `SELECT *` and `SELECT DISTINCT *`.
+fn select_star(nodes: &[Node], found: &mut Vec<(Range<usize>, String)>) {
+    for (i, node) in nodes.iter().enumerate() {
+        if !node.is_keyword("SELECT") {
+            continue;
+        }
+        let mut next = i + 1;
+        if nodes.get(next).is_some_and(|n| n.is_keyword("DISTINCT") || n.is_keyword("ALL")) {
+            next += 1;
+        }
+        if let Some(star) = nodes.get(next).and_then(Node::token).filter(|t| t.text == "*") {
+            found.push((
+                star.start..star.end(),
+                "SELECT * in a saved query returns different columns when the table changes".to_string(),
+            ));
+        }
+    }
+}
+
+fn folded(token: &Token) -> String {
+    match token.kind {
+        TokenKind::QuotedIdent => token.ident(),
+        _ => token.text.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postcommander::types::{SchemaObjects, TableColumn};
+
+    fn schemas() -> SchemaMap {
+        let mut schemas = SchemaMap::new();
+        schemas.insert(
+            "public".to_string(),
+            SchemaObjects {
+                tables: vec!["users".to_string(), "orders".to_string()],
+                views: vec!["active_users".to_string()],
+            },
+        );
+        schemas.insert("audit".to_string(), SchemaObjects { tables: vec!["log".to_string()], views: vec![] });
+        schemas
+    }
+
+    fn structures() -> HashMap<String, TableStructureInfo> {
+        let column = |name: &str| TableColumn {
+            name: name.to_string(),
+            data_type: "integer".to_string(),
+            nullable: false,
+            default_value: None,
+            is_primary_key: false,
+            is_foreign_key: false,
+            references: None,
+        };
+        let users = TableStructureInfo {
+            schema: "public".to_string(),
+            table: "users".to_string(),
+            columns: vec![column("id"), column("email")],
+        };
+        HashMap::from([("public.users".to_string(), users)])
+    }
+
+    fn lint(sql: &str, saved_query: bool) -> Vec<(String, String)> {
+        let schemas = schemas();
+        let structures = structures();
+        let context = LintContext { schemas: &schemas, structures: &structures, saved_query };
+        lint_script(sql, &context)
+            .into_iter()
+            .map(|l| (sql[l.range].to_string(), l.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_unknown_tables() {
+        let found = lint(
+            "SELECT * FROM users u JOIN ordrs o ON o.id = u.id; SELECT * FROM audit.log, nope.t WHERE true",
+            false,
+        );
+        assert_eq!(
+            found,
+            vec![
+                ("ordrs".to_string(), "Unknown table or view \"ordrs\"".to_string()),
+                ("nope.t".to_string(), "Unknown schema \"nope\"".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_known_and_created_tables() {
+        let sql = "CREATE TABLE scratch (id int); INSERT INTO scratch SELECT id FROM active_users; \
+                   WITH x AS (SELECT 1) SELECT * FROM x, pg_class WHERE true; SELECT * FROM information_schema.tables";
+        assert!(lint(sql, false).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_columns() {
+        let found = lint(
+            "SELECT u.id, u.emial, u.ctid, o.anything, s.x FROM users u JOIN orders o USING (id), (SELECT 1) s WHERE lower(u.email) = 'a'",
+            false,
+        );
+        assert_eq!(found, vec![("emial".to_string(), "Column \"emial\" not found in \"users\"".to_string())]);
+    }
+
+    #[test]
+    fn test_missing_join_conditions() {
+        let found = lint(
+            "SELECT * FROM users LEFT JOIN orders WHERE true; SELECT * FROM users, orders; \
+             SELECT * FROM users CROSS JOIN orders NATURAL JOIN active_users; SELECT * FROM users, generate_series(1, 2)",
+            false,
+        );
+        let ranges: Vec<&str> = found.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(ranges, vec!["LEFT JOIN", "users, orders"]);
+    }
+
+    #[test]
+    fn test_select_star_in_saved_query() {
+        let sql = "SELECT * FROM users WHERE id IN (SELECT DISTINCT * FROM orders); SELECT count(*), u.* FROM users u";
+        assert!(lint(sql, false).is_empty());
+        let found = lint(sql, true);
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|(text, _)| text == "*"));
+    }
+
+    #[test]
+    fn test_error_range() {
+        let sql = "SELECT idd FROM users";
+        assert_eq!(error_range(sql, 8).map(|r| &sql[r]), Some("idd"));
+        assert_eq!(error_range(sql, 22).map(|r| &sql[r]), Some("users"));
+        let sql = "SELECT 'é', frm";
+        assert_eq!(error_range(sql, 13).map(|r| &sql[r]), Some("frm"));
+    }
+}
@@ -1,7 +1,25 @@
+mod ast;
+mod browse;
 mod completion;
+mod dml;
 mod format;
+mod impact;
+mod lexer;
+mod lint;
+mod params;
 mod safety;
+mod scope;
 
+pub use ast::ends_transaction;
+pub use browse::browse_query;
 pub use completion::SqlCompletionProvider;
-pub use format::{format_sql, maybe_capitalize_last_word};
-pub use safety::{analyze_sql, SqlDangerLevel};
+pub use dml::{
+    conflicting_row_query, delete_statement, insert_statement, update_statement, ColumnValue,
+};
+pub use format::{format_with_style, maybe_capitalize_last_word};
+pub use impact::count_query;
+pub use lexer::{split_statements, statement_at, SqlStatement};
+pub use lint::{error_range, lint_script, LintContext, SqlLint};
+pub use params::{bind_placeholders, inline_parameters, parameter_names};
+pub use safety::{analyze_script, writes_data, SqlDangerLevel, SqlFinding};
+pub use scope::statement_scope;
@@ -0,0 +1,266 @@
+use super::lexer::{tokenize, TokenKind};
+
+/// A `$n` or `:name` placeholder at `start..end` in the source.
+struct Placeholder {
+    name: String,
+    start: usize,
+    end: usize,
+}
+
+/// Distinct placeholder names in binding order: `$n` by number, then
+/// `:name` in order of first use. Placeholders inside literals, quoted
+/// identifiers and comments are ignored, as are `::type` casts.
+pub fn parameter_names(sql: &str) -> Vec<String> {
+    let found = placeholders(sql);
+
+    let mut positional: Vec<u32> = found
+        .iter()
+        .filter_map(|p| p.name.strip_prefix('$')?.parse().ok())
+        .collect();
+    positional.sort_unstable();
+    positional.dedup();
+
+    let mut names: Vec<String> = positional.iter().map(|n| format!("${}", n)).collect();
+    for p in found.iter().filter(|p| p.name.starts_with(':')) {
+        if !names.contains(&p.name) {
+            names.push(p.name.clone());
+        }
+    }
+    names
+}
+
+/// Rewrites every placeholder to `$n`, its position in `parameter_names`,
+/// so the statement can be prepared with bind parameters. When `types` is
+/// given, each one is cast to its type, which lets values be bound as text.
+pub fn bind_placeholders(sql: &str, types: &[String]) -> String {
+    let names = parameter_names(sql);
+    let mut result = String::with_capacity(sql.len());
+    let mut last = 0;
+
+    for p in placeholders(sql) {
+        let Some(index) = names.iter().position(|n| *n == p.name) else {
+            continue;
+        };
+        result.push_str(&sql[last..p.start]);
+        result.push_str(&format!("${}", index + 1));
+        if let Some(type_name) = types.get(index) {
+            result.push_str("::");
+            result.push_str(&cast_type(type_name));
+        }
+        last = p.end;
+    }
+    result.push_str(&sql[last..]);
+    result
+}
+
+/// Writes bound values back into the `$n` placeholders as literals, for
+/// showing a statement to the user. The statement is still run with binds.
+pub fn inline_parameters(sql: &str, values: &[Option<String>]) -> String {
+    let mut result = String::with_capacity(sql.len());
+    let mut last = 0;
+
+    for p in placeholders(sql) {
+        let value = p
+            .name
+            .strip_prefix('$')
+            .and_then(|n| n.parse::<usize>().ok())
+            .and_then(|n| values.get(n.checked_sub(1)?));
+        let Some(value) = value else {
+            continue;
+        };
+        result.push_str(&sql[last..p.start]);
+        match value {
+            Some(text) => {
+                result.push('\'');
+                result.push_str(&text.replace('\'', "''"));
+                result.push('\'');
+            }
+            None => result.push_str("NULL"),
+        }
+        last = p.end;
+    }
+    result.push_str(&sql[last..]);
+    result
+}
+
+fn placeholders(sql: &str) -> Vec<Placeholder> {
+    let tokens = tokenize(sql);
+    let mut found = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::Parameter => {
+                // `$01` and `$1` are the same parameter.
+                let number: u32 = token.text[1..].parse().unwrap_or(0);
+                found.push(Placeholder {
+                    name: format!("${}", number),
+                    start: token.start,
+                    end: token.end(),
+                });
+            }
+            TokenKind::Punct if token.text == ":" => {
+                // `a[1:n]` is an array slice, not a placeholder.
+                let after_value = i > 0
+                    && tokens[i - 1].end() == token.start
+                    && matches!(
+                        tokens[i - 1].kind,
+                        TokenKind::Word | TokenKind::Number | TokenKind::Parameter
+                    );
+                let name = tokens
+                    .get(i + 1)
+                    .filter(|t| t.kind == TokenKind::Word && t.start == token.end());
+                if let (false, Some(name)) = (after_value, name) {
+                    found.push(Placeholder {
+                        name: format!(":{}", name.text),
+                        start: token.start,
+                        end: name.end(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Type names come back from the server as `INT4`, `TEXT[]` or, for user
+/// types, as stored. Anything that would not survive case folding is quoted.
+/// `CHAR` and `BIT` drop the length, and as casts would mean a length of
+/// one, so the unconstrained types are used and the column applies its own.
+pub(super) fn cast_type(type_name: &str) -> String {
+    let (base, array) = match type_name.strip_suffix("[]") {
+        Some(base) => (base, "[]"),
+        None => (type_name, ""),
+    };
+    let base = match base {
+        "CHAR" => "BPCHAR",
+        "BIT" => "VARBIT",
+        // The one-byte internal type.
+        "\"CHAR\"" => return format!("\"char\"{}", array),
+        other => other,
+    };
+    let plain = base.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_')
+        && !(base.bytes().any(|c| c.is_ascii_lowercase()) && base.bytes().any(|c| c.is_ascii_uppercase()));
+    if plain {
+        format!("{}{}", base, array)
+    } else {
+        format!("\"{}\"{}", base.replace('"', "\"\""), array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positional_parameters() {
+        assert_eq!(
+            parameter_names("SELECT * FROM t WHERE a = $2 AND b = $1 OR c = $2"),
+            vec!["$1", "$2"]
+        );
+    }
+
+    #[test]
+    fn test_named_parameters() {
+        assert_eq!(
+            parameter_names("SELECT * FROM t WHERE id = :id AND name = :name OR id = :id"),
+            vec![":id", ":name"]
+        );
+    }
+
+    #[test]
+    fn test_positional_before_named() {
+        assert_eq!(
+            parameter_names("SELECT :b, $1, :a"),
+            vec!["$1", ":b", ":a"]
+        );
+    }
+
+    #[test]
+    fn test_casts_are_not_parameters() {
+        assert_eq!(parameter_names("SELECT '1'::int, x::text FROM t"), Vec::<String>::new());
+        assert_eq!(parameter_names("SELECT :id::int"), vec![":id"]);
+    }
+
+    #[test]
+    fn test_array_slices_are_not_parameters() {
+        assert_eq!(parameter_names("SELECT a[1:n], a[i:j] FROM t"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_placeholders_in_literals_are_ignored() {
+        let sql = "SELECT ':no', \":no\", $$ $1 $$ -- :no\n/* $2 */ FROM t WHERE x = :yes";
+        assert_eq!(parameter_names(sql), vec![":yes"]);
+    }
+
+    #[test]
+    fn test_leading_zero_positional() {
+        assert_eq!(parameter_names("SELECT $01, $1"), vec!["$1"]);
+    }
+
+    #[test]
+    fn test_bind_renumbers_named() {
+        assert_eq!(
+            bind_placeholders("SELECT :a, $1, :b, :a", &[]),
+            "SELECT $2, $1, $3, $2"
+        );
+    }
+
+    #[test]
+    fn test_bind_closes_positional_gaps() {
+        assert_eq!(bind_placeholders("SELECT $3, $1", &[]), "SELECT $2, $1");
+    }
+
+    #[test]
+    fn test_bind_adds_casts() {
+        let types = vec!["INT4".to_string(), "TIMESTAMPTZ".to_string()];
+        assert_eq!(
+            bind_placeholders("SELECT * FROM t WHERE id = :id AND at > :since", &types),
+            "SELECT * FROM t WHERE id = $1::INT4 AND at > $2::TIMESTAMPTZ"
+        );
+    }
+
+    #[test]
+    fn test_bind_leaves_literals_alone() {
+        assert_eq!(
+            bind_placeholders("SELECT ':id', :id", &["TEXT".to_string()]),
+            "SELECT ':id', $1::TEXT"
+        );
+    }
+
+    #[test]
+    fn test_inline_parameters() {
+        let values = vec![Some("it's".to_string()), None];
+        assert_eq!(
+            inline_parameters("UPDATE t SET a = $1::TEXT WHERE b = $2::INT4", &values),
+            "UPDATE t SET a = 'it''s'::TEXT WHERE b = NULL::INT4"
+        );
+    }
+
+    #[test]
+    fn test_inline_parameters_leaves_unbound_and_quoted() {
+        let values = vec![Some("x".to_string())];
+        assert_eq!(
+            inline_parameters("SELECT \"$1\", $1, $2", &values),
+            "SELECT \"$1\", 'x', $2"
+        );
+    }
+
+    #[test]
+    fn test_cast_type_quoting() {
+        assert_eq!(cast_type("INT4"), "INT4");
+        assert_eq!(cast_type("INT4[]"), "INT4[]");
+        assert_eq!(cast_type("mood"), "mood");
+        assert_eq!(cast_type("MyType"), "\"MyType\"");
+        assert_eq!(cast_type("my type[]"), "\"my type\"[]");
+    }
+
+    #[test]
+    fn test_cast_type_keeps_length() {
+        assert_eq!(cast_type("CHAR"), "BPCHAR");
+        assert_eq!(cast_type("CHAR[]"), "BPCHAR[]");
+        assert_eq!(cast_type("BIT"), "VARBIT");
+        assert_eq!(cast_type("VARBIT"), "VARBIT");
+        assert_eq!(cast_type("\"CHAR\""), "\"char\"");
+    }
+}
@@ -1,3 +1,6 @@
+use super::ast::{parse_statement, Command, Node, Statement};
+use super::lexer::{split_statements, tokenize, TokenKind};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum SqlDangerLevel {
     Safe,
@@ -5,51 +8,160 @@ pub enum SqlDangerLevel {
     Dangerous(String),
 }
 
-pub fn analyze_sql(sql: &str) -> SqlDangerLevel {
-    let sql_upper = sql.to_uppercase();
-    let sql_trimmed = sql_upper.trim();
+/// Something risky in one statement of a script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlFinding {
+    pub level: SqlDangerLevel,
+    pub statement: String,
+}
 
-    if sql_trimmed.starts_with("DROP ") {
-        return SqlDangerLevel::Dangerous("DROP statement will permanently delete database objects".to_string());
+/// Every finding in every statement of `sql`, in order. Each statement is
+/// parsed, so keywords in strings, quoted identifiers and comments are
+/// ignored, and data-modifying queries inside `WITH` are checked too.
+pub fn analyze_script(sql: &str) -> Vec<SqlFinding> {
+    let mut findings = Vec::new();
+    for statement in split_statements(sql) {
+        let mut levels = Vec::new();
+        check(&parse_statement(&statement.text), &mut levels);
+        findings.extend(levels.into_iter().map(|level| SqlFinding {
+            level,
+            statement: statement.text.clone(),
+        }));
     }
+    findings
+}
+
+/// Whether any statement in `sql` might change data, schema or the
+/// session. Only plain reads pass: queries without row locks or `INTO`,
+/// `SHOW`, and `EXPLAIN` that doesn't run what it explains. Anything else,
+/// `SET` and `BEGIN` included, could write or lift the connection's
+/// read-only default, so it counts as a write.
+pub fn writes_data(sql: &str) -> bool {
+    split_statements(sql)
+        .iter()
+        .any(|s| !reads_only(&parse_statement(&s.text)))
+}
 
-    if sql_trimmed.starts_with("TRUNCATE ") {
-        return SqlDangerLevel::Dangerous("TRUNCATE will permanently delete all rows from the table".to_string());
+fn reads_only(statement: &Statement) -> bool {
+    // `SELECT set_config('default_transaction_read_only', 'off', false)`
+    if names_read_only_setting(statement.sql) {
+        return false;
     }
+    if statement.explain.is_some_and(|e| !e.analyze) {
+        return true;
+    }
+    let reads = match statement.command {
+        Command::Select => !statement.has_keyword("FOR") && !statement.has_keyword("INTO"),
+        Command::Other => statement.body.first().is_some_and(|n| n.is_keyword("SHOW")),
+        _ => false,
+    };
+    reads && statement.ctes.iter().all(reads_only)
+}
+
+fn names_read_only_setting(sql: &str) -> bool {
+    tokenize(sql).iter().any(|token| {
+        let name = match token.kind {
+            TokenKind::Word => token.text,
+            TokenKind::QuotedIdent => token.text.trim_matches('"'),
+            _ => return false,
+        };
+        ["set_config", "default_transaction_read_only", "transaction_read_only"]
+            .iter()
+            .any(|setting| name.eq_ignore_ascii_case(setting))
+    })
+}
 
-    if sql_trimmed.starts_with("ALTER ") && sql_trimmed.contains("DROP ") {
-        return SqlDangerLevel::Dangerous("ALTER...DROP will permanently remove columns or constraints".to_string());
+fn check(statement: &Statement, levels: &mut Vec<SqlDangerLevel>) {
+    if statement.explain.is_some_and(|e| !e.analyze) {
+        return;
+    }
+    for cte in &statement.ctes {
+        check(cte, levels);
     }
 
-    if sql_trimmed.starts_with("DELETE ") {
-        if !sql_upper.contains(" WHERE ") {
-            return SqlDangerLevel::Warning("DELETE without WHERE clause will delete ALL rows".to_string());
-        }
-        if sql_upper.contains(" WHERE 1=1") || sql_upper.contains(" WHERE TRUE") {
-            return SqlDangerLevel::Warning("DELETE with always-true condition will delete ALL rows".to_string());
+    match statement.command {
+        Command::Drop => levels.push(SqlDangerLevel::Dangerous(
+            "DROP statement will permanently delete database objects".to_string(),
+        )),
+        Command::Truncate => levels.push(SqlDangerLevel::Dangerous(
+            "TRUNCATE will permanently delete all rows from the table".to_string(),
+        )),
+        Command::Alter if statement.has_keyword("DROP") => levels.push(SqlDangerLevel::Dangerous(
+            "ALTER...DROP will permanently remove columns or constraints".to_string(),
+        )),
+        Command::Delete => match statement.where_clause() {
+            None => levels.push(SqlDangerLevel::Warning(
+                "DELETE without WHERE clause will delete ALL rows".to_string(),
+            )),
+            Some(condition) if is_always_true(condition) => levels.push(SqlDangerLevel::Warning(
+                "DELETE with always-true condition will delete ALL rows".to_string(),
+            )),
+            Some(_) => {}
+        },
+        Command::Update => match statement.where_clause() {
+            None => levels.push(SqlDangerLevel::Warning(
+                "UPDATE without WHERE clause will update ALL rows".to_string(),
+            )),
+            Some(condition) if is_always_true(condition) => levels.push(SqlDangerLevel::Warning(
+                "UPDATE with always-true condition will update ALL rows".to_string(),
+            )),
+            Some(_) => {}
+        },
+        Command::Create if creates_table_like_including_all(statement) => {
+            levels.push(SqlDangerLevel::Warning(
+                "CREATE TABLE...LIKE INCLUDING ALL will copy table structure".to_string(),
+            ))
         }
+        _ => {}
     }
+}
 
-    if sql_trimmed.starts_with("UPDATE ") {
-        if !sql_upper.contains(" WHERE ") {
-            return SqlDangerLevel::Warning("UPDATE without WHERE clause will update ALL rows".to_string());
-        }
-        if sql_upper.contains(" WHERE 1=1") || sql_upper.contains(" WHERE TRUE") {
-            return SqlDangerLevel::Warning("UPDATE with always-true condition will update ALL rows".to_string());
+/// `WHERE TRUE` or `WHERE 1 = 1`, with any number.
+fn is_always_true(condition: &[Node]) -> bool {
+    let tokens: Vec<_> = condition.iter().map(Node::token).collect();
+    match tokens.as_slice() {
+        [Some(cond), ..] if cond.is_keyword("TRUE") => true,
+        [Some(left), Some(op), Some(right), ..] => {
+            left.kind == TokenKind::Number
+                && op.text == "="
+                && right.kind == TokenKind::Number
+                && left.text == right.text
         }
+        _ => false,
     }
+}
 
-    if sql_trimmed.starts_with("CREATE TABLE ") && sql_upper.contains("LIKE") && sql_upper.contains("INCLUDING ALL") {
-        return SqlDangerLevel::Warning("CREATE TABLE...LIKE INCLUDING ALL will copy table structure".to_string());
+/// `CREATE TABLE t (LIKE s INCLUDING ALL)`; the `LIKE` is inside the
+/// column list.
+fn creates_table_like_including_all(statement: &Statement) -> bool {
+    if !statement.body.get(1).is_some_and(|n| n.is_keyword("TABLE")) {
+        return false;
     }
-
-    SqlDangerLevel::Safe
+    statement.body.iter().any(|node| match node {
+        Node::Group { children, .. } => {
+            children.iter().any(|n| n.is_keyword("LIKE"))
+                && children
+                    .windows(2)
+                    .any(|pair| pair[0].is_keyword("INCLUDING") && pair[1].is_keyword("ALL"))
+        }
+        Node::Token(_) => false,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// The most severe finding in `sql`, or `Safe`.
+    fn analyze_sql(sql: &str) -> SqlDangerLevel {
+        let findings = analyze_script(sql);
+        findings
+            .iter()
+            .find(|f| matches!(f.level, SqlDangerLevel::Dangerous(_)))
+            .or(findings.first())
+            .map_or(SqlDangerLevel::Safe, |f| f.level.clone())
+    }
+
     #[test]
     fn test_safe_select() {
         assert_eq!(analyze_sql("SELECT * FROM users"), SqlDangerLevel::Safe);
@@ -102,4 +214,158 @@ mod tests {
             SqlDangerLevel::Safe
         );
     }
+
+    #[test]
+    fn test_leading_comment_does_not_hide_drop() {
+        match analyze_sql("-- cleanup\n/* old */ DROP TABLE users") {
+            SqlDangerLevel::Dangerous(_) => {}
+            _ => panic!("DROP after a comment should be dangerous"),
+        }
+    }
+
+    #[test]
+    fn test_where_in_comment_or_string_does_not_count() {
+        match analyze_sql("DELETE FROM users -- WHERE id = 1") {
+            SqlDangerLevel::Warning(_) => {}
+            _ => panic!("WHERE in a comment should not count"),
+        }
+        match analyze_sql("UPDATE users SET note = ' where '") {
+            SqlDangerLevel::Warning(_) => {}
+            _ => panic!("WHERE in a string should not count"),
+        }
+    }
+
+    #[test]
+    fn test_drop_in_quoted_identifier_is_not_alter_drop() {
+        assert_eq!(
+            analyze_sql("ALTER TABLE t RENAME COLUMN a TO \"drop\""),
+            SqlDangerLevel::Safe
+        );
+    }
+
+    #[test]
+    fn test_always_true_condition() {
+        match analyze_sql("DELETE FROM users WHERE 1 = 1") {
+            SqlDangerLevel::Warning(_) => {}
+            _ => panic!("WHERE 1 = 1 should be warning"),
+        }
+        assert_eq!(
+            analyze_sql("DELETE FROM users WHERE 1 = id"),
+            SqlDangerLevel::Safe
+        );
+    }
+
+    #[test]
+    fn test_delete_inside_cte() {
+        match analyze_sql("with x as (delete from t returning *) select 1") {
+            SqlDangerLevel::Warning(_) => {}
+            _ => panic!("DELETE in a CTE should be warning"),
+        }
+        assert_eq!(
+            analyze_sql("WITH x AS (DELETE FROM t WHERE id = 1 RETURNING *) SELECT * FROM x"),
+            SqlDangerLevel::Safe
+        );
+    }
+
+    #[test]
+    fn test_every_statement_is_checked() {
+        let findings = analyze_script("select 1; drop table t; update u set a = 1");
+        assert_eq!(findings.len(), 2);
+        assert!(matches!(findings[0].level, SqlDangerLevel::Dangerous(_)));
+        assert_eq!(findings[0].statement, "drop table t");
+        assert!(matches!(findings[1].level, SqlDangerLevel::Warning(_)));
+        assert_eq!(findings[1].statement, "update u set a = 1");
+    }
+
+    #[test]
+    fn test_most_severe_finding_wins() {
+        match analyze_sql("DELETE FROM a; TRUNCATE b") {
+            SqlDangerLevel::Dangerous(_) => {}
+            _ => panic!("TRUNCATE should outrank the DELETE warning"),
+        }
+    }
+
+    #[test]
+    fn test_subquery_where_does_not_count() {
+        match analyze_sql("DELETE FROM t WHERE id IN (SELECT id FROM u)") {
+            SqlDangerLevel::Safe => {}
+            _ => panic!("outer WHERE should count"),
+        }
+        match analyze_sql("UPDATE t SET a = (SELECT b FROM u WHERE u.id = 1)") {
+            SqlDangerLevel::Warning(_) => {}
+            _ => panic!("WHERE in a subquery should not count for the UPDATE"),
+        }
+    }
+
+    #[test]
+    fn test_explain_runs_only_with_analyze() {
+        assert_eq!(analyze_sql("EXPLAIN DELETE FROM t"), SqlDangerLevel::Safe);
+        match analyze_sql("EXPLAIN ANALYZE DELETE FROM t") {
+            SqlDangerLevel::Warning(_) => {}
+            _ => panic!("EXPLAIN ANALYZE runs the DELETE"),
+        }
+    }
+
+    #[test]
+    fn test_create_table_like_including_all() {
+        match analyze_sql("CREATE TABLE b (LIKE a INCLUDING ALL)") {
+            SqlDangerLevel::Warning(_) => {}
+            _ => panic!("LIKE INCLUDING ALL should be warning"),
+        }
+    }
+
+    #[test]
+    fn test_writes_data() {
+        assert!(!writes_data("SELECT * FROM t; EXPLAIN DELETE FROM t"));
+        assert!(writes_data("SELECT 1; insert into t values (1)"));
+        assert!(writes_data("WITH x AS (UPDATE t SET a = 1 RETURNING *) SELECT * FROM x"));
+        assert!(writes_data("/* ddl */ CREATE INDEX ON t (a)"));
+    }
+
+    #[test]
+    fn test_reads_pass() {
+        assert!(!writes_data("select 1; values (1), (2); table t; show search_path"));
+        assert!(!writes_data("WITH x AS (SELECT 1) SELECT * FROM x"));
+        assert!(!writes_data("EXPLAIN ANALYZE SELECT * FROM t"));
+        assert!(!writes_data("EXPLAIN UPDATE t SET a = 1"));
+        assert!(!writes_data("SELECT substring(a FOR 2) FROM t"));
+    }
+
+    #[test]
+    fn test_read_only_default_cannot_be_lifted() {
+        for sql in [
+            "SET default_transaction_read_only = off",
+            "set \"default_transaction_read_only\" to false",
+            "SET SESSION CHARACTERISTICS AS TRANSACTION READ WRITE",
+            "SET TRANSACTION READ WRITE",
+            "BEGIN READ WRITE",
+            "START TRANSACTION READ WRITE",
+            "RESET default_transaction_read_only",
+            "SELECT set_config('default_transaction_read_only', 'off', false)",
+            "SELECT 1 FROM t WHERE pg_catalog.SET_CONFIG('transaction_read_only', 'off', true) = 'x'",
+        ] {
+            assert!(writes_data(sql), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn test_other_commands_count_as_writes() {
+        for sql in [
+            "MERGE INTO t USING s ON t.id = s.id WHEN MATCHED THEN DELETE",
+            "COPY t FROM '/tmp/t.csv'",
+            "DO $$ BEGIN DELETE FROM t; END $$",
+            "CALL purge()",
+            "GRANT ALL ON t TO public",
+            "REVOKE SELECT ON t FROM public",
+            "VACUUM t",
+            "REFRESH MATERIALIZED VIEW v",
+            "COMMENT ON TABLE t IS 'x'",
+            "SELECT * FROM t FOR UPDATE",
+            "SELECT * INTO copy FROM t",
+            "EXPLAIN ANALYZE DELETE FROM t",
+            "BEGIN",
+        ] {
+            assert!(writes_data(sql), "{}", sql);
+        }
+    }
 }
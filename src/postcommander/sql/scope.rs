@@ -0,0 +1,403 @@
+use super::ast::{parse_nodes, Node};
+use super::lexer::{statement_at, Token, TokenKind};
+use std::ops::Range;
+
+/// A table named in a statement: in a FROM list or JOIN, or as the target
+/// of UPDATE, DELETE or INSERT. Names are case-folded the way the server
+/// folds them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRef {
+    pub schema: Option<String>,
+    pub name: String,
+    pub alias: Option<String>,
+    /// Where the name is written in the statement, schema included.
+    pub span: Range<usize>,
+}
+
+impl TableRef {
+    /// Whether `qualifier.column` refers to this table: by its alias when
+    /// it has one, else by its name.
+    pub fn answers_to(&self, qualifier: &str) -> bool {
+        self.alias.as_deref().unwrap_or(&self.name).eq_ignore_ascii_case(qualifier)
+    }
+
+    /// What to write before a column of this table.
+    pub fn qualifier(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// A `WITH` query or a subquery in FROM, with the names of its output
+/// columns where they can be told from the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivedTable {
+    pub name: String,
+    pub columns: Vec<String>,
+}
+
+/// The tables a statement reads or writes, at any depth, and the `WITH`
+/// queries and subqueries it names.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Scope {
+    pub tables: Vec<TableRef>,
+    pub derived: Vec<DerivedTable>,
+}
+
+impl Scope {
+    pub fn resolve(&self, qualifier: &str) -> Option<&TableRef> {
+        self.tables.iter().find(|t| t.answers_to(qualifier))
+    }
+
+    pub fn derived(&self, name: &str) -> Option<&DerivedTable> {
+        self.derived.iter().find(|d| d.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Tables that live in the database, leaving out references to `WITH`
+    /// queries.
+    pub fn base_tables(&self) -> impl Iterator<Item = &TableRef> {
+        self.tables
+            .iter()
+            .filter(|t| t.schema.is_some() || self.derived(&t.name).is_none())
+    }
+}
+
+/// Words that end a table reference instead of naming or aliasing it.
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "AS", "CROSS", "DEFAULT", "DO", "EXCEPT", "FETCH", "FOR", "FROM", "FULL", "GROUP",
+    "HAVING", "INNER", "INTERSECT", "JOIN", "LATERAL", "LEFT", "LIMIT", "NATURAL", "OFFSET",
+    "ON", "ONLY", "ORDER", "OUTER", "OVERRIDING", "RETURNING", "RIGHT", "SELECT", "SET",
+    "TABLESAMPLE", "UNION", "USING", "VALUES", "WHERE", "WINDOW", "WITH",
+];
+
+/// Scope of the statement the cursor is in.
+pub fn scope_at(sql: &str, offset: usize) -> Scope {
+    statement_at(sql, offset)
+        .map(|s| statement_scope(&s.text))
+        .unwrap_or_default()
+}
+
+/// Scope of one statement; run `split_statements` on a script first.
+pub fn statement_scope(sql: &str) -> Scope {
+    let mut scope = Scope::default();
+    collect(&parse_nodes(sql), &mut scope);
+    scope
+}
+
+fn collect(nodes: &[Node], scope: &mut Scope) {
+    let mut i = 0;
+    while i < nodes.len() {
+        let node = &nodes[i];
+        i += 1;
+        if let Node::Group { children, .. } = node {
+            // FROM in the arguments of a call, as in `extract(year FROM ts)`,
+            // names no table, though a subquery among them still can.
+            let call = i >= 2 && nodes.get(i - 2).and_then(name_token).is_some();
+            let query = children
+                .first()
+                .is_some_and(|n| n.is_keyword("SELECT") || n.is_keyword("WITH") || n.is_keyword("VALUES"));
+            if call && !query {
+                for (j, child) in children.iter().enumerate() {
+                    if matches!(child, Node::Group { .. }) {
+                        collect(&children[j.saturating_sub(1)..=j], scope);
+                    }
+                }
+            } else {
+                collect(children, scope);
+            }
+        } else if node.is_keyword("WITH") {
+            if nodes.get(i).is_some_and(|n| n.is_keyword("RECURSIVE")) {
+                i += 1;
+            }
+            i = with_queries(nodes, i, scope);
+        } else if node.is_keyword("FROM") || node.is_keyword("USING") {
+            i = table_list(nodes, i, true, scope);
+        } else if node.is_keyword("JOIN") || node.is_keyword("UPDATE") {
+            i = table_list(nodes, i, false, scope);
+        } else if node.is_keyword("INTO") {
+            // `INSERT INTO t (columns)`: read the name before the list.
+            let end = nodes[i..]
+                .iter()
+                .position(|n| matches!(n, Node::Group { .. }))
+                .map_or(nodes.len(), |e| i + e);
+            table_list(&nodes[..end], i, false, scope);
+        }
+    }
+}
+
+/// `name [ (columns) ] AS [ [ NOT ] MATERIALIZED ] ( query ) [, ...]`
+fn with_queries(nodes: &[Node], mut i: usize, scope: &mut Scope) -> usize {
+    while let Some(name) = nodes.get(i).and_then(name_token) {
+        i += 1;
+        let listed = match nodes.get(i) {
+            Some(Node::Group { children, .. }) => {
+                i += 1;
+                Some(children.iter().filter_map(name_token).map(folded).collect())
+            }
+            _ => None,
+        };
+        if !nodes.get(i).is_some_and(|n| n.is_keyword("AS")) {
+            break;
+        }
+        i += 1;
+        while nodes.get(i).is_some_and(|n| n.is_keyword("NOT") || n.is_keyword("MATERIALIZED")) {
+            i += 1;
+        }
+        let Some(Node::Group { children, .. }) = nodes.get(i) else {
+            break;
+        };
+        i += 1;
+        collect(children, scope);
+        scope.derived.push(DerivedTable {
+            name: folded(name),
+            columns: listed.unwrap_or_else(|| output_columns(children)),
+        });
+        if !nodes.get(i).is_some_and(|n| n.is_punct(',')) {
+            break;
+        }
+        i += 1;
+    }
+    i
+}
+
+/// Table references from `i` on; a FROM or USING list goes on past commas.
+fn table_list(nodes: &[Node], mut i: usize, list: bool, scope: &mut Scope) -> usize {
+    loop {
+        while nodes.get(i).is_some_and(|n| n.is_keyword("ONLY") || n.is_keyword("LATERAL")) {
+            i += 1;
+        }
+        match nodes.get(i) {
+            Some(Node::Group { children, .. }) => {
+                i += 1;
+                collect(children, scope);
+                let at = i + usize::from(nodes.get(i).is_some_and(|n| n.is_keyword("AS")));
+                if let Some(alias) = alias(nodes, &mut i) {
+                    scope.derived.push(DerivedTable {
+                        name: alias.clone(),
+                        columns: output_columns(children),
+                    });
+                    scope.tables.push(TableRef {
+                        schema: None,
+                        name: alias,
+                        alias: None,
+                        span: nodes[at].start()..nodes[at].end(),
+                    });
+                }
+            }
+            Some(node) => {
+                let Some(first) = name_token(node) else {
+                    return i;
+                };
+                i += 1;
+                let mut span = first.start..first.end();
+                let mut schema = None;
+                let mut name = folded(first);
+                if nodes.get(i).is_some_and(|n| n.is_punct('.')) {
+                    if let Some(second) = nodes.get(i + 1).and_then(name_token) {
+                        schema = Some(name);
+                        name = folded(second);
+                        span.end = second.end();
+                        i += 2;
+                    }
+                }
+                // A function in FROM, such as `generate_series(1, 10) g`.
+                if let Some(Node::Group { children, .. }) = nodes.get(i) {
+                    i += 1;
+                    collect(children, scope);
+                    alias(nodes, &mut i);
+                } else {
+                    let alias = alias(nodes, &mut i);
+                    scope.tables.push(TableRef { schema, name, alias, span });
+                }
+            }
+            None => return i,
+        }
+        if !(list && nodes.get(i).is_some_and(|n| n.is_punct(','))) {
+            return i;
+        }
+        i += 1;
+    }
+}
+
+/// `[ AS ] alias [ (columns) ]`
+fn alias(nodes: &[Node], i: &mut usize) -> Option<String> {
+    let explicit = nodes.get(*i).is_some_and(|n| n.is_keyword("AS"));
+    if explicit {
+        *i += 1;
+    }
+    let alias = nodes.get(*i).and_then(name_token).map(folded)?;
+    *i += 1;
+    if matches!(nodes.get(*i), Some(Node::Group { .. })) {
+        *i += 1;
+    }
+    Some(alias)
+}
+
+/// Output column names of a query, from the select list of its first
+/// SELECT: the alias of each item, else the column or function it names.
+fn output_columns(nodes: &[Node]) -> Vec<String> {
+    let Some(select) = nodes.iter().position(|n| n.is_keyword("SELECT")) else {
+        return Vec::new();
+    };
+    let mut start = select + 1;
+    if nodes.get(start).is_some_and(|n| n.is_keyword("ALL")) {
+        start += 1;
+    } else if nodes.get(start).is_some_and(|n| n.is_keyword("DISTINCT")) {
+        start += 1;
+        if nodes.get(start).is_some_and(|n| n.is_keyword("ON")) {
+            start += 2;
+        }
+    }
+    let end = nodes[start.min(nodes.len())..]
+        .iter()
+        .position(|n| {
+            ["FROM", "INTO", "WHERE", "GROUP", "HAVING", "WINDOW", "ORDER", "LIMIT", "UNION", "INTERSECT", "EXCEPT"]
+                .iter()
+                .any(|k| n.is_keyword(k))
+        })
+        .map_or(nodes.len(), |e| start + e);
+    if start >= end {
+        return Vec::new();
+    }
+
+    nodes[start..end]
+        .split(|n| n.is_punct(','))
+        .filter_map(|item| match item {
+            [.., last] if item.len() >= 2 && item[item.len() - 2].is_keyword("AS") => {
+                last.token().map(folded)
+            }
+            [.., function, Node::Group { .. }] => name_token(function).map(folded),
+            [.., last] => {
+                let token = last.token().filter(|t| {
+                    matches!(t.kind, TokenKind::Word | TokenKind::QuotedIdent)
+                })?;
+                Some(folded(token))
+            }
+            [] => None,
+        })
+        .collect()
+}
+
+/// A word that can be a name here, or a quoted identifier.
+fn name_token<'n, 'a>(node: &'n Node<'a>) -> Option<&'n Token<'a>> {
+    let token = node.token()?;
+    match token.kind {
+        TokenKind::QuotedIdent => Some(token),
+        TokenKind::Word if !CLAUSE_KEYWORDS.iter().any(|k| token.is_keyword(k)) => Some(token),
+        _ => None,
+    }
+}
+
+fn folded(token: &Token) -> String {
+    match token.kind {
+        TokenKind::QuotedIdent => token.ident(),
+        _ => token.text.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(schema: Option<&str>, name: &str, alias: Option<&str>) -> TableRef {
+        TableRef {
+            schema: schema.map(str::to_string),
+            name: name.to_string(),
+            alias: alias.map(str::to_string),
+            span: 0..0,
+        }
+    }
+
+    /// The scope's tables with their spans cleared, to compare by name.
+    fn tables(scope: &Scope) -> Vec<TableRef> {
+        scope
+            .tables
+            .iter()
+            .map(|t| TableRef { span: 0..0, ..t.clone() })
+            .collect()
+    }
+
+    #[test]
+    fn test_from_and_joins() {
+        let scope = statement_scope(
+            "SELECT * FROM Users u, public.orders AS o LEFT JOIN \"Items\" i ON i.order_id = o.id WHERE u.",
+        );
+        assert_eq!(
+            tables(&scope),
+            vec![
+                table(None, "users", Some("u")),
+                table(Some("public"), "orders", Some("o")),
+                table(None, "Items", Some("i")),
+            ]
+        );
+        assert_eq!(scope.resolve("U").map(|t| t.name.as_str()), Some("users"));
+        assert_eq!(scope.resolve("users"), None);
+    }
+
+    #[test]
+    fn test_dml_targets() {
+        let scope = statement_scope("UPDATE accounts a SET x = 1 FROM owners WHERE a.id = owners.id");
+        assert_eq!(
+            tables(&scope),
+            vec![table(None, "accounts", Some("a")), table(None, "owners", None)]
+        );
+        let scope = statement_scope("INSERT INTO logs (a, b) SELECT a, b FROM src");
+        assert_eq!(tables(&scope), vec![table(None, "logs", None), table(None, "src", None)]);
+        let scope = statement_scope("DELETE FROM only t USING u WHERE t.id = u.id");
+        assert_eq!(tables(&scope), vec![table(None, "t", None), table(None, "u", None)]);
+    }
+
+    #[test]
+    fn test_with_queries() {
+        let scope = statement_scope(
+            "WITH recent(id, at) AS (SELECT id, created_at FROM events), totals AS MATERIALIZED (SELECT user_id, count(*) AS n, max(x) FROM orders GROUP BY 1) SELECT * FROM recent r JOIN totals t ON ",
+        );
+        assert_eq!(scope.derived("RECENT").unwrap().columns, vec!["id", "at"]);
+        assert_eq!(scope.derived("totals").unwrap().columns, vec!["user_id", "n", "max"]);
+        let base: Vec<&str> = scope.base_tables().map(|t| t.name.as_str()).collect();
+        assert_eq!(base, vec!["events", "orders"]);
+        assert_eq!(scope.resolve("r").map(|t| t.name.as_str()), Some("recent"));
+    }
+
+    #[test]
+    fn test_subqueries_and_functions() {
+        let scope = statement_scope(
+            "SELECT * FROM (SELECT o.id, o.total sum FROM orders o) AS s, generate_series(1, 3) g JOIN lateral (SELECT 1) x ON true",
+        );
+        assert_eq!(scope.derived("s").unwrap().columns, vec!["id", "sum"]);
+        let names: Vec<&str> = scope.tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["orders", "s", "x"]);
+        let base: Vec<&str> = scope.base_tables().map(|t| t.name.as_str()).collect();
+        assert_eq!(base, vec!["orders"]);
+    }
+
+    #[test]
+    fn test_scope_at_picks_the_statement() {
+        let sql = "SELECT * FROM a; SELECT * FROM b x WHERE x.";
+        let scope = scope_at(sql, sql.len());
+        assert_eq!(tables(&scope), vec![table(None, "b", Some("x"))]);
+    }
+
+    #[test]
+    fn test_incomplete_statement() {
+        let scope = statement_scope("SELECT * FROM users u JOIN");
+        assert_eq!(tables(&scope), vec![table(None, "users", Some("u"))]);
+        let scope = statement_scope("SELECT * FROM ");
+        assert!(scope.tables.is_empty());
+    }
+
+    #[test]
+    fn test_spans() {
+        let sql = "SELECT * FROM public.users u JOIN (SELECT 1) AS s ON true";
+        let scope = statement_scope(sql);
+        let spans: Vec<&str> = scope.tables.iter().map(|t| &sql[t.span.clone()]).collect();
+        assert_eq!(spans, vec!["public.users", "s"]);
+    }
+
+    #[test]
+    fn test_from_in_call_arguments() {
+        let scope = statement_scope(
+            "SELECT extract(year FROM created_at), substring(name FROM 2), coalesce((SELECT max(id) FROM t), 0) FROM users",
+        );
+        assert_eq!(tables(&scope), vec![table(None, "t", None), table(None, "users", None)]);
+    }
+}
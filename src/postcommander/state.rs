@@ -1,5 +1,9 @@
 use gpui::{Entity, Pixels, Point, Subscription};
-use crate::components::TextInput;
+use crate::components::{SelectedCells, TextInput};
+use crate::postcommander::database::CellValue;
+use crate::postcommander::sql::SqlStatement;
+use crate::postcommander::types::TabId;
+use crate::settings::{ConnectionAccess, ConnectionEnvironment};
 use gpui_component::menu::PopupMenu;
 
 /// Resize state for sidebar, editor, and structure panel
@@ -46,6 +50,11 @@ impl ResizeState {
 /// Connection dialog form state
 pub(crate) struct ConnectionDialogState {
     pub is_visible: bool,
+    /// Profile being edited; `None` when creating a new one.
+    pub editing_id: Option<String>,
+    pub environment: ConnectionEnvironment,
+    pub access: ConnectionAccess,
+    pub input_name: Entity<TextInput>,
     pub input_host: Entity<TextInput>,
     pub input_port: Entity<TextInput>,
     pub input_database: Entity<TextInput>,
@@ -55,6 +64,7 @@ pub(crate) struct ConnectionDialogState {
 
 impl ConnectionDialogState {
     pub fn new(
+        input_name: Entity<TextInput>,
         input_host: Entity<TextInput>,
         input_port: Entity<TextInput>,
         input_database: Entity<TextInput>,
@@ -63,6 +73,10 @@ impl ConnectionDialogState {
     ) -> Self {
         Self {
             is_visible: false,
+            editing_id: None,
+            environment: ConnectionEnvironment::default(),
+            access: ConnectionAccess::default(),
+            input_name,
             input_host,
             input_port,
             input_database,
@@ -96,10 +110,34 @@ impl SaveQueryDialogState {
     }
 }
 
+/// Values to bind before a script with placeholders runs.
+pub(crate) struct ParameterPromptState {
+    pub tab_id: TabId,
+    pub statements: Vec<SqlStatement>,
+    /// Server-inferred types of each statement's placeholders, in
+    /// `parameter_names` order.
+    pub statement_types: Vec<Vec<String>>,
+    pub fields: Vec<ParameterField>,
+}
+
+pub(crate) struct ParameterField {
+    pub name: String,
+    pub type_name: String,
+    pub input: Entity<TextInput>,
+    pub is_null: bool,
+}
+
+/// The review dialog for a tab's buffered grid edits.
+pub(crate) struct ChangesReviewState {
+    pub tab_id: TabId,
+    pub is_applying: bool,
+    pub error: Option<String>,
+}
+
 pub(crate) struct PendingCellContextMenu {
     pub col_index: usize,
-    pub column_names: Vec<String>,
-    pub row_data: Vec<gpui::SharedString>,
+    pub row_data: Vec<CellValue>,
+    pub selection: SelectedCells,
     pub position: Point<Pixels>,
     pub table_name: Option<String>,
 }
@@ -108,9 +146,11 @@ pub(crate) struct PendingCellContextMenu {
 pub(crate) struct ActiveOverlays {
     pub context_menu: Option<(Entity<PopupMenu>, Point<Pixels>, String, Subscription)>,
     pub export_menu: Option<(Entity<gpui_component::menu::PopupMenu>, Point<Pixels>, Subscription)>,
+    pub run_menu: Option<(Entity<PopupMenu>, Point<Pixels>, Subscription)>,
     pub cell_context_menu: Option<(Entity<gpui_component::menu::PopupMenu>, Point<Pixels>, Subscription)>,
     pub pending_cell_context_menu: Option<PendingCellContextMenu>,
     pub saved_query_menu: Option<(Entity<PopupMenu>, Point<Pixels>, String, Subscription)>,
+    pub connection_menu: Option<(Entity<PopupMenu>, Point<Pixels>, Subscription)>,
 }
 
 impl Default for ActiveOverlays {
@@ -118,9 +158,11 @@ impl Default for ActiveOverlays {
         Self {
             context_menu: None,
             export_menu: None,
+            run_menu: None,
             cell_context_menu: None,
             pending_cell_context_menu: None,
             saved_query_menu: None,
+            connection_menu: None,
         }
     }
 }
@@ -1,6 +1,6 @@
 use crate::icons::icon_sm;
 use crate::postcommander::page::PostCommanderPage;
-use crate::postcommander::types::{TableColumn, TableStructureInfo};
+use crate::postcommander::types::{TabId, TableColumn, TableStructureInfo};
 use crate::theme::ActiveTheme;
 use gpui::prelude::FluentBuilder;
 use gpui::*;
@@ -306,4 +306,56 @@ impl PostCommanderPage {
                 }),
             )
     }
+
+    /// Puts a table at the top of the tab's structure panel, fetching it
+    /// when the connection hasn't loaded it yet.
+    pub(crate) fn show_table_structure(
+        &mut self,
+        tab_id: TabId,
+        schema: String,
+        table: String,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(connection_id) = self
+            .tabs
+            .iter()
+            .find(|t| t.id == tab_id)
+            .and_then(|t| t.connection_id.clone())
+        else {
+            return;
+        };
+
+        let key = format!("{}.{}", schema, table);
+        let cached = self
+            .connection(&connection_id)
+            .and_then(|s| s.table_structures.get(&key).cloned());
+        if let Some(structure) = cached {
+            self.pin_table_structure(tab_id, structure, cx);
+            return;
+        }
+
+        let rx = self.db_manager.fetch_table_structure(&connection_id, schema, table);
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(structure)) = rx.await else {
+                return;
+            };
+            let _ = this.update(cx, |this, cx| {
+                this.cache_table_structures(&connection_id, vec![structure.clone()], cx);
+                this.pin_table_structure(tab_id, structure, cx);
+            });
+        })
+        .detach();
+    }
+
+    fn pin_table_structure(&mut self, tab_id: TabId, structure: TableStructureInfo, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+        tab.table_structures
+            .retain(|s| s.schema != structure.schema || s.table != structure.table);
+        tab.structure_expanded
+            .insert(format!("{}.{}", structure.schema, structure.table), true);
+        tab.table_structures.insert(0, structure);
+        cx.notify();
+    }
 }
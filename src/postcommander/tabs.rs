@@ -1,7 +1,7 @@
-use crate::components::DataTableState;
+use crate::components::{DataTableState, TextInput};
 use crate::postcommander::page::PostCommanderPage;
 use crate::postcommander::sql::maybe_capitalize_last_word;
-use crate::postcommander::types::{QueryTab, TabId};
+use crate::postcommander::types::{QueryTab, TabId, TransactionHold};
 use gpui::*;
 use gpui_component::input::{InputEvent, InputState};
 use std::collections::HashMap;
@@ -9,23 +9,47 @@ use std::rc::Rc;
 
 impl PostCommanderPage {
     pub(crate) fn add_tab(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let connection_id = self.current_connection().map(|c| c.id().to_string());
+        self.add_tab_for_connection(connection_id, window, cx);
+    }
+
+    pub(crate) fn add_tab_for_connection(
+        &mut self,
+        connection_id: Option<String>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let name = format!("Query {}", self.tabs.len() + 1);
+        self.open_tab(connection_id, name, "SELECT * FROM ", window, cx);
+    }
+
+    /// Creates a query tab with its editor and result grid wired up, and
+    /// makes it the active tab.
+    fn open_tab(
+        &mut self,
+        connection_id: Option<String>,
+        name: String,
+        sql: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> TabId {
         let id = TabId::new();
         let tab_id = id;
-        let database = self.get_conn_database().to_string();
+        let cursor_pos = sql.len() as u32;
 
         let editor = cx.new(|cx| {
             InputState::new(window, cx)
                 .code_editor("sql".to_string())
                 .line_number(true)
                 .soft_wrap(true)
-                .default_value("SELECT * FROM ")
+                .default_value(sql)
                 .placeholder("Enter SQL query...")
         });
 
         let provider = self.completion_provider.clone();
         editor.update(cx, |editor, cx| {
             editor.set_cursor_position(
-                gpui_component::input::Position { line: 0, character: 14 },
+                gpui_component::input::Position { line: 0, character: cursor_pos },
                 window,
                 cx,
             );
@@ -56,38 +80,85 @@ impl PostCommanderPage {
         self._subscriptions.push(sub3);
         let sub4 = cx.subscribe(&table_state, Self::handle_cell_context_menu);
         self._subscriptions.push(sub4);
+        let sub5 = cx.subscribe(&table_state, Self::handle_grid_query_changed);
+        self._subscriptions.push(sub5);
+        let sub6 = cx.subscribe(&table_state, Self::handle_copy_selection);
+        self._subscriptions.push(sub6);
+
+        let table_state_for_search = table_state.clone();
+        let grid_search_input = cx.new(|cx| {
+            let mut input = TextInput::new(cx, "Search rows...");
+            input.set_on_change(move |value, _, cx| {
+                table_state_for_search.update(cx, |state, cx| {
+                    state.set_filter(value.to_string(), cx);
+                });
+            });
+            input
+        });
 
         let tab = QueryTab {
             id,
-            name: format!("Query {}", self.tabs.len() + 1),
-            database,
+            name,
+            connection_id,
             editor,
             table_state,
-            table_context: None,
+            table_contexts: vec![],
             result: None,
+            plan: None,
+            plan_history: vec![],
+            plan_baseline: None,
             error: None,
+            error_diagnostic: None,
             is_loading: false,
             query_start_time: None,
             query_task: None,
+            backend_pid: None,
+            cancel_requested: false,
+            transaction: None,
+            statements: vec![],
+            active_statement: 0,
+            saved_query_id: None,
+            parameter_values: HashMap::new(),
             last_export_message: None,
             table_structures: vec![],
             structure_loading: false,
             structure_expanded: HashMap::new(),
+            grid_search_input,
+            grid_base_sql: None,
+            grid_rerun: None,
         };
         self.tabs.push(tab);
-        self.active_tab_id = Some(id);
-        if let Some(tab) = self.tabs.last() {
-            tab.editor.update(cx, |state, cx| {
-                state.focus(window, cx);
-            });
+        self.activate_tab(id, window, cx);
+        id
+    }
+
+    pub(crate) fn activate_tab(&mut self, tab_id: TabId, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return;
+        };
+        tab.editor.update(cx, |state, cx| {
+            state.focus(window, cx);
+        });
+        if let Some(connection_id) = tab.connection_id.clone() {
+            self.active_connection_id = Some(connection_id);
         }
+        self.active_tab_id = Some(tab_id);
+        self.sync_completion_schemas();
         cx.notify();
     }
 
-    pub(crate) fn query_table(&mut self, schema: &str, table: &str, window: &mut Window, cx: &mut Context<Self>) {
+    pub(crate) fn query_table(
+        &mut self,
+        connection_id: &str,
+        schema: &str,
+        table: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let connection_id = connection_id.to_string();
         let schema = schema.to_string();
         let table = table.to_string();
-        let pk_rx = self.db_manager.fetch_primary_keys(schema.clone(), table.clone());
+        let pk_rx = self.db_manager.fetch_primary_keys(&connection_id, schema.clone(), table.clone());
 
         cx.spawn_in(window, async move |this, cx| {
             let primary_keys = match pk_rx.await {
@@ -96,7 +167,7 @@ impl PostCommanderPage {
             };
 
             let _ = this.update_in(cx, |this, window, cx| {
-                this.query_table_with_pks(&schema, &table, primary_keys, window, cx);
+                this.query_table_with_pks(connection_id, &schema, &table, primary_keys, window, cx);
             });
         })
         .detach();
@@ -104,16 +175,13 @@ impl PostCommanderPage {
 
     fn query_table_with_pks(
         &mut self,
+        connection_id: String,
         schema: &str,
         table: &str,
         primary_keys: Vec<String>,
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        let id = TabId::new();
-        let tab_id = id;
-        let database = self.get_conn_database().to_string();
-
         let order_by = if primary_keys.is_empty() {
             String::new()
         } else {
@@ -124,166 +192,54 @@ impl PostCommanderPage {
             "SELECT * FROM \"{}\".\"{}\"{} LIMIT 100;",
             schema, table, order_by
         );
-        let cursor_pos = sql.len() as u32;
-
-        let editor = cx.new(|cx| {
-            InputState::new(window, cx)
-                .code_editor("sql".to_string())
-                .line_number(true)
-                .soft_wrap(true)
-                .default_value(&sql)
-                .placeholder("Enter SQL query...")
-        });
-
-        let provider = self.completion_provider.clone();
-        editor.update(cx, |editor, cx| {
-            editor.set_cursor_position(
-                gpui_component::input::Position { line: 0, character: cursor_pos },
-                window,
-                cx,
-            );
-            editor.lsp.completion_provider = Some(Rc::clone(&provider) as Rc<dyn gpui_component::input::CompletionProvider>);
-        });
-
-        let editor_sub = cx.subscribe(&editor, move |this, editor_entity, event: &InputEvent, cx| {
-            match event {
-                InputEvent::Change => {
-                    Self::handle_editor_change(this, tab_id, editor_entity, cx);
-                }
-                InputEvent::PressEnter { secondary: true } => {
-                    this.pending_undo_newline = Some(tab_id);
-                    this.execute_query(cx);
-                }
-                _ => {}
-            }
-        });
-        self._subscriptions.push(editor_sub);
-
-        let table_state = cx.new(|cx| DataTableState::new(cx));
-
-        let sub = cx.subscribe(&table_state, Self::handle_cell_save);
-        self._subscriptions.push(sub);
-        let sub2 = cx.subscribe(&table_state, Self::handle_cell_double_click);
-        self._subscriptions.push(sub2);
-        let sub3 = cx.subscribe(&table_state, Self::handle_fk_data_request);
-        self._subscriptions.push(sub3);
-        let sub4 = cx.subscribe(&table_state, Self::handle_cell_context_menu);
-        self._subscriptions.push(sub4);
-
-        let tab = QueryTab {
-            id,
-            name: format!("{}.{}", schema, table),
-            database,
-            editor,
-            table_state,
-            table_context: None,
-            result: None,
-            error: None,
-            is_loading: false,
-            query_start_time: None,
-            query_task: None,
-            last_export_message: None,
-            table_structures: vec![],
-            structure_loading: false,
-            structure_expanded: HashMap::new(),
-        };
-        self.tabs.push(tab);
-        self.active_tab_id = Some(id);
-        if let Some(tab) = self.tabs.last() {
-            tab.editor.update(cx, |state, cx| {
-                state.focus(window, cx);
-            });
-        }
-        cx.notify();
 
+        self.open_tab(Some(connection_id), format!("{}.{}", schema, table), &sql, window, cx);
         self.execute_query(cx);
     }
 
-    pub(crate) fn count_table_rows(&mut self, schema: &str, table: &str, window: &mut Window, cx: &mut Context<Self>) {
-        let id = TabId::new();
-        let tab_id = id;
-        let database = self.get_conn_database().to_string();
+    pub(crate) fn count_table_rows(
+        &mut self,
+        connection_id: &str,
+        schema: &str,
+        table: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
         let sql = format!("SELECT COUNT(*) FROM \"{}\".\"{}\"", schema, table);
-        let cursor_pos = sql.len() as u32;
 
-        let editor = cx.new(|cx| {
-            InputState::new(window, cx)
-                .code_editor("sql".to_string())
-                .line_number(true)
-                .soft_wrap(true)
-                .default_value(&sql)
-                .placeholder("Enter SQL query...")
-        });
-
-        let provider = self.completion_provider.clone();
-        editor.update(cx, |editor, cx| {
-            editor.set_cursor_position(
-                gpui_component::input::Position { line: 0, character: cursor_pos },
-                window,
-                cx,
-            );
-            editor.lsp.completion_provider = Some(Rc::clone(&provider) as Rc<dyn gpui_component::input::CompletionProvider>);
-        });
-
-        let editor_sub = cx.subscribe(&editor, move |this, editor_entity, event: &InputEvent, cx| {
-            match event {
-                InputEvent::Change => {
-                    Self::handle_editor_change(this, tab_id, editor_entity, cx);
-                }
-                InputEvent::PressEnter { secondary: true } => {
-                    this.pending_undo_newline = Some(tab_id);
-                    this.execute_query(cx);
-                }
-                _ => {}
-            }
-        });
-        self._subscriptions.push(editor_sub);
-
-        let table_state = cx.new(|cx| DataTableState::new(cx));
-
-        let sub = cx.subscribe(&table_state, Self::handle_cell_save);
-        self._subscriptions.push(sub);
-        let sub2 = cx.subscribe(&table_state, Self::handle_cell_double_click);
-        self._subscriptions.push(sub2);
-        let sub3 = cx.subscribe(&table_state, Self::handle_fk_data_request);
-        self._subscriptions.push(sub3);
-        let sub4 = cx.subscribe(&table_state, Self::handle_cell_context_menu);
-        self._subscriptions.push(sub4);
-
-        let tab = QueryTab {
-            id,
-            name: format!("Count {}.{}", schema, table),
-            database,
-            editor,
-            table_state,
-            table_context: None,
-            result: None,
-            error: None,
-            is_loading: false,
-            query_start_time: None,
-            query_task: None,
-            last_export_message: None,
-            table_structures: vec![],
-            structure_loading: false,
-            structure_expanded: HashMap::new(),
-        };
-        self.tabs.push(tab);
-        self.active_tab_id = Some(id);
-        if let Some(tab) = self.tabs.last() {
-            tab.editor.update(cx, |state, cx| {
-                state.focus(window, cx);
-            });
-        }
-        cx.notify();
+        let name = format!("Count {}.{}", schema, table);
+        self.open_tab(Some(connection_id.to_string()), name, &sql, window, cx);
+        self.execute_query(cx);
+    }
 
+    /// Opens a tab that selects the existing row a unique violation ran into.
+    pub(crate) fn show_conflicting_row(
+        &mut self,
+        connection_id: &str,
+        sql: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.open_tab(Some(connection_id.to_string()), "Conflicting row".to_string(), sql, window, cx);
         self.execute_query(cx);
     }
 
-    pub(crate) fn generate_select_statement(&mut self, schema: &str, table: &str, window: &mut Window, cx: &mut Context<Self>) {
+    pub(crate) fn generate_select_statement(
+        &mut self,
+        connection_id: &str,
+        schema: &str,
+        table: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
         let sql = format!("SELECT * FROM \"{}\".\"{}\"", schema, table);
 
         if let Some(active_id) = self.active_tab_id {
-            if let Some(tab) = self.tabs.iter().find(|t| t.id == active_id) {
+            let active_tab = self
+                .tabs
+                .iter()
+                .find(|t| t.id == active_id && t.connection_id.as_deref() == Some(connection_id));
+            if let Some(tab) = active_tab {
                 tab.editor.update(cx, |editor, cx| {
                     let current_text = editor.value().to_string();
                     let new_text = if current_text.trim().is_empty() {
@@ -304,160 +260,24 @@ impl PostCommanderPage {
             }
         }
 
-        let id = TabId::new();
-        let tab_id = id;
-        let database = self.get_conn_database().to_string();
-        let cursor_pos = sql.len() as u32;
-
-        let editor = cx.new(|cx| {
-            InputState::new(window, cx)
-                .code_editor("sql".to_string())
-                .line_number(true)
-                .soft_wrap(true)
-                .default_value(&sql)
-                .placeholder("Enter SQL query...")
-        });
-
-        let provider = self.completion_provider.clone();
-        editor.update(cx, |editor, cx| {
-            editor.set_cursor_position(
-                gpui_component::input::Position { line: 0, character: cursor_pos },
-                window,
-                cx,
-            );
-            editor.lsp.completion_provider = Some(Rc::clone(&provider) as Rc<dyn gpui_component::input::CompletionProvider>);
-        });
-
-        let editor_sub = cx.subscribe(&editor, move |this, editor_entity, event: &InputEvent, cx| {
-            match event {
-                InputEvent::Change => {
-                    Self::handle_editor_change(this, tab_id, editor_entity, cx);
-                }
-                InputEvent::PressEnter { secondary: true } => {
-                    this.pending_undo_newline = Some(tab_id);
-                    this.execute_query(cx);
-                }
-                _ => {}
-            }
-        });
-        self._subscriptions.push(editor_sub);
-
-        let table_state = cx.new(|cx| DataTableState::new(cx));
-
-        let sub = cx.subscribe(&table_state, Self::handle_cell_save);
-        self._subscriptions.push(sub);
-        let sub2 = cx.subscribe(&table_state, Self::handle_cell_double_click);
-        self._subscriptions.push(sub2);
-        let sub3 = cx.subscribe(&table_state, Self::handle_fk_data_request);
-        self._subscriptions.push(sub3);
-        let sub4 = cx.subscribe(&table_state, Self::handle_cell_context_menu);
-        self._subscriptions.push(sub4);
-
-        let tab = QueryTab {
-            id,
-            name: format!("{}.{}", schema, table),
-            database,
-            editor,
-            table_state,
-            table_context: None,
-            result: None,
-            error: None,
-            is_loading: false,
-            query_start_time: None,
-            query_task: None,
-            last_export_message: None,
-            table_structures: vec![],
-            structure_loading: false,
-            structure_expanded: HashMap::new(),
-        };
-        self.tabs.push(tab);
-        self.active_tab_id = Some(id);
-        if let Some(tab) = self.tabs.last() {
-            tab.editor.update(cx, |state, cx| {
-                state.focus(window, cx);
-            });
-        }
-        cx.notify();
+        let name = format!("{}.{}", schema, table);
+        self.open_tab(Some(connection_id.to_string()), name, &sql, window, cx);
     }
 
     pub(crate) fn create_tab_from_file(&mut self, filename: String, content: String, window: &mut Window, cx: &mut Context<Self>) {
-        let id = TabId::new();
-        let tab_id = id;
-        let database = self.get_conn_database().to_string();
-        let cursor_pos = content.len() as u32;
-
-        let editor = cx.new(|cx| {
-            InputState::new(window, cx)
-                .code_editor("sql".to_string())
-                .line_number(true)
-                .soft_wrap(true)
-                .default_value(&content)
-                .placeholder("Enter SQL query...")
-        });
-
-        let provider = self.completion_provider.clone();
-        editor.update(cx, |editor, cx| {
-            editor.set_cursor_position(
-                gpui_component::input::Position { line: 0, character: cursor_pos },
-                window,
-                cx,
-            );
-            editor.lsp.completion_provider = Some(Rc::clone(&provider) as Rc<dyn gpui_component::input::CompletionProvider>);
-        });
-
-        let editor_sub = cx.subscribe(&editor, move |this, editor_entity, event: &InputEvent, cx| {
-            match event {
-                InputEvent::Change => {
-                    Self::handle_editor_change(this, tab_id, editor_entity, cx);
-                }
-                InputEvent::PressEnter { secondary: true } => {
-                    this.pending_undo_newline = Some(tab_id);
-                    this.execute_query(cx);
-                }
-                _ => {}
-            }
-        });
-        self._subscriptions.push(editor_sub);
-
-        let table_state = cx.new(|cx| DataTableState::new(cx));
-
-        let sub = cx.subscribe(&table_state, Self::handle_cell_save);
-        self._subscriptions.push(sub);
-        let sub2 = cx.subscribe(&table_state, Self::handle_cell_double_click);
-        self._subscriptions.push(sub2);
-        let sub3 = cx.subscribe(&table_state, Self::handle_fk_data_request);
-        self._subscriptions.push(sub3);
-        let sub4 = cx.subscribe(&table_state, Self::handle_cell_context_menu);
-        self._subscriptions.push(sub4);
+        let connection_id = self.current_connection().map(|c| c.id().to_string());
+        self.open_tab(connection_id, filename, &content, window, cx);
+    }
 
-        let tab = QueryTab {
-            id,
-            name: filename,
-            database,
-            editor,
-            table_state,
-            table_context: None,
-            result: None,
-            error: None,
-            is_loading: false,
-            query_start_time: None,
-            query_task: None,
-            last_export_message: None,
-            table_structures: vec![],
-            structure_loading: false,
-            structure_expanded: HashMap::new(),
-        };
-        self.tabs.push(tab);
-        self.active_tab_id = Some(id);
-        if let Some(tab) = self.tabs.last() {
-            tab.editor.update(cx, |state, cx| {
-                state.focus(window, cx);
-            });
+    pub(crate) fn close_tab(&mut self, tab_id: TabId, window: &mut Window, cx: &mut Context<Self>) {
+        if self.hold_for_transactions(TransactionHold::CloseTab(tab_id), cx) {
+            return;
         }
-        cx.notify();
+        self.remove_tab(tab_id, window, cx);
     }
 
-    pub(crate) fn close_tab(&mut self, tab_id: TabId, window: &mut Window, cx: &mut Context<Self>) {
+    pub(crate) fn remove_tab(&mut self, tab_id: TabId, window: &mut Window, cx: &mut Context<Self>) {
+        self.close_transaction_session(tab_id, cx);
         let closed_index = self.tabs.iter().position(|t| t.id == tab_id);
         self.tabs.retain(|t| t.id != tab_id);
         if self.active_tab_id == Some(tab_id) {
@@ -472,11 +292,7 @@ impl PostCommanderPage {
                 .map(|t| t.id);
         }
         if let Some(active_id) = self.active_tab_id {
-            if let Some(tab) = self.tabs.iter().find(|t| t.id == active_id) {
-                tab.editor.update(cx, |state, cx| {
-                    state.focus(window, cx);
-                });
-            }
+            self.activate_tab(active_id, window, cx);
         }
         cx.notify();
     }
@@ -487,17 +303,20 @@ impl PostCommanderPage {
         editor_entity: Entity<InputState>,
         cx: &mut Context<Self>,
     ) {
-        let Some(tab) = this.tabs.iter().find(|t| t.id == tab_id) else {
+        let Some(tab) = this.tabs.iter_mut().find(|t| t.id == tab_id) else {
             return;
         };
         if tab.editor != editor_entity {
             return;
         }
+        tab.error_diagnostic = None;
+        this.refresh_diagnostics(tab_id, cx);
 
         let text = editor_entity.read(cx).value().to_string();
         if text.is_empty() {
             return;
         }
+        this.load_completion_structures(tab_id, &text, cx);
 
         let last_char = text.chars().last().unwrap_or(' ');
         if let Some((start, end, replacement)) = maybe_capitalize_last_word(&text, last_char) {
@@ -0,0 +1,318 @@
+use crate::postcommander::database::{QueryError, TransactionStatus};
+use crate::postcommander::page::PostCommanderPage;
+use crate::postcommander::types::{TabId, TabTransaction, TransactionHold};
+use gpui::*;
+use uuid::Uuid;
+
+impl PostCommanderPage {
+    /// Switches the active tab between autocommit and a pinned connection
+    /// with manual COMMIT/ROLLBACK.
+    pub(crate) fn toggle_manual_transactions(&mut self, cx: &mut Context<Self>) {
+        let Some(tab_id) = self.active_tab_id else {
+            return;
+        };
+        let Some(tab) = self.tabs.iter().find(|t| t.id == tab_id) else {
+            return;
+        };
+        if tab.is_loading {
+            return;
+        }
+
+        match tab.transaction.as_ref().map(|t| (t.is_busy, t.status)) {
+            Some((true, _)) => {}
+            Some((false, TransactionStatus::Idle)) => self.close_transaction_session(tab_id, cx),
+            Some((false, _)) => {
+                self.set_tab_error(tab_id, "Commit or roll back before leaving manual mode", cx);
+            }
+            None => self.open_transaction_session(tab_id, cx),
+        }
+    }
+
+    pub(crate) fn open_transaction_session(&mut self, tab_id: TabId, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+        let Some(connection_id) = tab.connection_id.clone() else {
+            tab.error = Some("No connection selected. Pick one with ⌘P.".into());
+            cx.notify();
+            return;
+        };
+
+        let session_id = Uuid::new_v4().to_string();
+        tab.transaction = Some(TabTransaction {
+            session_id: session_id.clone(),
+            status: TransactionStatus::Idle,
+            is_busy: true,
+        });
+        cx.notify();
+
+        let rx = self.db_manager.open_session(&connection_id, session_id.clone());
+        cx.spawn(async move |this, cx| {
+            let result = rx.await;
+            let _ = this.update(cx, |this, cx| {
+                let Some(tab) = this.tabs.iter_mut().find(|t| t.id == tab_id) else {
+                    return;
+                };
+                if tab.transaction.as_ref().map(|t| &t.session_id) != Some(&session_id) {
+                    return;
+                }
+                match result {
+                    Ok(Ok(())) => {
+                        if let Some(transaction) = tab.transaction.as_mut() {
+                            transaction.is_busy = false;
+                        }
+                    }
+                    Ok(Err(e)) => {
+                        tab.transaction = None;
+                        tab.error = Some(format!("Could not start manual transactions: {}", e).into());
+                    }
+                    Err(_) => {
+                        tab.transaction = None;
+                        tab.error = Some("Could not start manual transactions".into());
+                    }
+                }
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Drops the tab's pinned connection; the server rolls back anything
+    /// left uncommitted.
+    pub(crate) fn close_transaction_session(&mut self, tab_id: TabId, cx: &mut Context<Self>) {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return;
+        };
+        let Some(transaction) = tab.transaction.take() else {
+            return;
+        };
+        if let Some(connection_id) = tab.connection_id.as_deref() {
+            self.db_manager.close_session(connection_id, transaction.session_id);
+        }
+        cx.notify();
+    }
+
+    /// Forgets the sessions of tabs on a connection whose pool is going
+    /// away; the database side drops them along with the pool.
+    pub(crate) fn forget_transaction_sessions(&mut self, connection_id: &str) {
+        for tab in self.tabs.iter_mut() {
+            if tab.connection_id.as_deref() == Some(connection_id) {
+                tab.transaction = None;
+            }
+        }
+    }
+
+    /// Grid edits in manual mode join the tab's transaction, opening one
+    /// if none was open.
+    pub(crate) fn note_grid_edit(&mut self, tab_id: TabId) {
+        let tab = self.tabs.iter_mut().find(|t| t.id == tab_id);
+        if let Some(transaction) = tab.and_then(|t| t.transaction.as_mut()) {
+            transaction.status = TransactionStatus::InTransaction;
+        }
+    }
+
+    pub(crate) fn commit_transaction(&mut self, cx: &mut Context<Self>) {
+        self.end_transaction(true, cx);
+    }
+
+    pub(crate) fn rollback_transaction(&mut self, cx: &mut Context<Self>) {
+        self.end_transaction(false, cx);
+    }
+
+    fn end_transaction(&mut self, commit: bool, cx: &mut Context<Self>) {
+        let Some(tab_id) = self.active_tab_id else {
+            return;
+        };
+        let Some(rx) = self.request_end_transaction(tab_id, commit, cx) else {
+            return;
+        };
+        cx.spawn(async move |this, cx| {
+            let result = rx.await;
+            let _ = this.update(cx, |this, cx| {
+                this.apply_end_transaction(tab_id, result, cx);
+            });
+        })
+        .detach();
+    }
+
+    fn request_end_transaction(
+        &mut self,
+        tab_id: TabId,
+        commit: bool,
+        cx: &mut Context<Self>,
+    ) -> Option<tokio::sync::oneshot::Receiver<anyhow::Result<TransactionStatus>>> {
+        let tab = self.tabs.iter_mut().find(|t| t.id == tab_id)?;
+        if tab.is_loading {
+            return None;
+        }
+        let connection_id = tab.connection_id.clone()?;
+        let transaction = tab.transaction.as_mut().filter(|t| !t.is_busy)?;
+        if commit && transaction.status == TransactionStatus::Failed {
+            return None;
+        }
+
+        transaction.is_busy = true;
+        let session_id = transaction.session_id.clone();
+        cx.notify();
+        Some(self.db_manager.end_transaction(&connection_id, session_id, commit))
+    }
+
+    /// Records the outcome of a COMMIT/ROLLBACK; returns whether it succeeded.
+    fn apply_end_transaction(
+        &mut self,
+        tab_id: TabId,
+        result: Result<anyhow::Result<TransactionStatus>, tokio::sync::oneshot::error::RecvError>,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) else {
+            return false;
+        };
+        let Some(transaction) = tab.transaction.as_mut() else {
+            return false;
+        };
+        transaction.is_busy = false;
+
+        let succeeded = match result {
+            Ok(Ok(status)) => {
+                transaction.status = status;
+                tab.error = None;
+                true
+            }
+            Ok(Err(e)) => {
+                tab.error = Some(QueryError::from_error(&e));
+                false
+            }
+            Err(_) => {
+                tab.error = Some("Transaction session closed".into());
+                false
+            }
+        };
+        cx.notify();
+        succeeded
+    }
+
+    /// Whether closing the tab would throw away an open transaction.
+    pub(crate) fn has_open_transaction(&self, tab_id: TabId) -> bool {
+        self.tabs
+            .iter()
+            .find(|t| t.id == tab_id)
+            .and_then(|t| t.transaction.as_ref())
+            .is_some_and(|t| t.status != TransactionStatus::Idle)
+    }
+
+    /// Tabs whose open transaction the held action would end.
+    pub(crate) fn held_transaction_tabs(&self, hold: &TransactionHold) -> Vec<TabId> {
+        match hold {
+            TransactionHold::CloseTab(tab_id) => vec![*tab_id],
+            TransactionHold::Reconnect(connection_id)
+            | TransactionHold::Disconnect(connection_id)
+            | TransactionHold::DeleteConnection(connection_id) => self
+                .tabs
+                .iter()
+                .filter(|t| t.connection_id.as_ref() == Some(connection_id))
+                .map(|t| t.id)
+                .collect(),
+        }
+        .into_iter()
+        .filter(|&tab_id| self.has_open_transaction(tab_id))
+        .collect()
+    }
+
+    /// Holds `hold` back when it would end open transactions, so they can be
+    /// committed or rolled back first. Returns whether it was held.
+    pub(crate) fn hold_for_transactions(&mut self, hold: TransactionHold, cx: &mut Context<Self>) -> bool {
+        if self.held_transaction_tabs(&hold).is_empty() {
+            return false;
+        }
+        self.transaction_hold = Some(hold);
+        cx.notify();
+        true
+    }
+
+    /// Finishes an action that was held back by open transactions, after
+    /// committing them or letting it roll them back.
+    pub(crate) fn confirm_transaction_hold(
+        &mut self,
+        commit: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(hold) = self.transaction_hold.clone() else {
+            return;
+        };
+        if !commit {
+            self.transaction_hold = None;
+            self.release_transaction_hold(hold, window, cx);
+            return;
+        }
+
+        // A failed or busy transaction can't commit yet; the dialog stays so
+        // the action can still go ahead with a rollback.
+        let tab_ids = self.held_transaction_tabs(&hold);
+        if !tab_ids.iter().all(|&tab_id| self.can_commit(tab_id)) {
+            return;
+        }
+        let receivers: Vec<_> = tab_ids
+            .into_iter()
+            .filter_map(|tab_id| Some((tab_id, self.request_end_transaction(tab_id, true, cx)?)))
+            .collect();
+        self.transaction_hold = None;
+        cx.spawn_in(window, async move |this, cx| {
+            let mut committed = true;
+            for (tab_id, rx) in receivers {
+                let result = rx.await;
+                committed &= this
+                    .update_in(cx, |this, _window, cx| this.apply_end_transaction(tab_id, result, cx))
+                    .unwrap_or(false);
+            }
+            if committed {
+                let _ = this.update_in(cx, |this, window, cx| {
+                    this.release_transaction_hold(hold, window, cx);
+                });
+            }
+        })
+        .detach();
+    }
+
+    fn can_commit(&self, tab_id: TabId) -> bool {
+        self.tabs.iter().find(|t| t.id == tab_id).is_some_and(|tab| {
+            !tab.is_loading
+                && tab
+                    .transaction
+                    .as_ref()
+                    .is_some_and(|t| !t.is_busy && t.status != TransactionStatus::Failed)
+        })
+    }
+
+    /// Carries out a held action. Sessions still open are dropped with the
+    /// tab or the pool, which rolls them back.
+    fn release_transaction_hold(&mut self, hold: TransactionHold, window: &mut Window, cx: &mut Context<Self>) {
+        match hold {
+            TransactionHold::CloseTab(tab_id) => self.remove_tab(tab_id, window, cx),
+            TransactionHold::Reconnect(connection_id) => {
+                self.forget_transaction_sessions(&connection_id);
+                self.connect_profile(&connection_id, cx);
+            }
+            TransactionHold::Disconnect(connection_id) => {
+                self.forget_transaction_sessions(&connection_id);
+                self.disconnect_profile(&connection_id, cx);
+            }
+            TransactionHold::DeleteConnection(connection_id) => {
+                self.forget_transaction_sessions(&connection_id);
+                self.delete_connection_profile(&connection_id, cx);
+            }
+        }
+    }
+
+    pub(crate) fn cancel_transaction_hold(&mut self, cx: &mut Context<Self>) {
+        self.transaction_hold = None;
+        cx.notify();
+    }
+
+    pub(crate) fn set_tab_error(&mut self, tab_id: TabId, error: &str, cx: &mut Context<Self>) {
+        if let Some(tab) = self.tabs.iter_mut().find(|t| t.id == tab_id) {
+            tab.error = Some(error.into());
+            cx.notify();
+        }
+    }
+}
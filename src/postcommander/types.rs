@@ -1,8 +1,12 @@
-use crate::components::DataTableState;
-use crate::postcommander::database::QueryResult;
-use gpui::{Entity, SharedString, Task};
+use crate::components::{DataTableState, TextInput};
+use crate::postcommander::database::{CellValue, QueryError, QueryResult, TransactionStatus};
+use crate::postcommander::plan::QueryPlan;
+use crate::postcommander::sql::{browse_query, SqlFinding, SqlLint};
+use crate::settings::ConnectionProfile;
+use chrono::{DateTime, Local};
+use gpui::{App, Entity, SharedString, Task};
 use gpui_component::input::InputState;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -63,19 +67,108 @@ pub struct TableStructureInfo {
     pub columns: Vec<TableColumn>,
 }
 
+/// Functions, types and keywords of a server, for completion.
+#[derive(Clone, Debug, Default)]
+pub struct CompletionCatalog {
+    pub functions: Vec<CatalogFunction>,
+    pub types: Vec<CatalogType>,
+    /// Lower-case, as `pg_get_keywords()` lists them.
+    pub keywords: Vec<String>,
+}
+
+impl CompletionCatalog {
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty() && self.keywords.is_empty()
+    }
+
+    /// Labels of an enum type, by its name as a column's type.
+    pub fn enum_values(&self, type_name: &str) -> Option<&[String]> {
+        self.types
+            .iter()
+            .find(|t| !t.enum_values.is_empty() && (t.name == type_name || t.display_name == type_name))
+            .map(|t| t.enum_values.as_slice())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CatalogFunction {
+    pub schema: String,
+    pub name: String,
+    /// Argument list as `pg_get_function_arguments` prints it.
+    pub arguments: String,
+    /// Return type as `pg_get_function_result` prints it.
+    pub result: String,
+    pub kind: FunctionKind,
+}
+
+impl CatalogFunction {
+    pub fn signature(&self) -> String {
+        format!("{}({}) → {}", self.name, self.arguments, self.result)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FunctionKind {
+    Normal,
+    Aggregate,
+    Window,
+}
+
+#[derive(Clone, Debug)]
+pub struct CatalogType {
+    pub schema: String,
+    /// Name in `pg_type`, such as `int4`.
+    pub name: String,
+    /// Name as SQL spells it, such as `integer`.
+    pub display_name: String,
+    /// Labels in sort order when the type is an enum.
+    pub enum_values: Vec<String>,
+}
+
+/// A base table some of a result's columns were read from. Keys and
+/// foreign keys are by table column name.
 #[derive(Clone, Debug, Default)]
 pub struct TableContext {
     pub schema: String,
     pub table: String,
     pub primary_keys: Vec<String>,
     pub foreign_keys: Arc<HashMap<String, ForeignKeyInfo>>,
+    /// For each result column, the column of this table it shows, which
+    /// may differ from the result's name for it when aliased.
+    pub result_columns: Vec<Option<String>>,
+    /// Read over a read-only connection, so never written from the grid.
+    pub read_only: bool,
 }
 
 impl TableContext {
-    #[allow(dead_code)]
+    /// Rows can be told apart only when the whole primary key is in the
+    /// result.
     pub fn is_editable(&self) -> bool {
-        !self.primary_keys.is_empty()
+        !self.read_only
+            && !self.primary_keys.is_empty()
+            && self
+                .primary_keys
+                .iter()
+                .all(|pk| self.result_index(pk).is_some())
     }
+
+    pub fn source_column(&self, col_index: usize) -> Option<&str> {
+        self.result_columns.get(col_index)?.as_deref()
+    }
+
+    pub fn result_index(&self, column: &str) -> Option<usize> {
+        self.result_columns
+            .iter()
+            .position(|c| c.as_deref() == Some(column))
+    }
+}
+
+/// The table a result column was read from.
+pub fn column_table(contexts: &[TableContext], col_index: usize) -> Option<(usize, &TableContext)> {
+    contexts
+        .iter()
+        .enumerate()
+        .find(|(_, c)| c.source_column(col_index).is_some())
 }
 
 #[derive(Clone)]
@@ -83,7 +176,7 @@ pub struct CellEditState {
     pub row_index: usize,
     pub col_index: usize,
     pub column_name: SharedString,
-    pub original_value: SharedString,
+    pub original_value: CellValue,
     pub editor: Option<Entity<InputState>>,
     pub is_saving: bool,
     pub error: Option<String>,
@@ -92,19 +185,180 @@ pub struct CellEditState {
 pub struct QueryTab {
     pub id: TabId,
     pub name: String,
-    pub database: String,
+    /// Profile the tab runs against; `None` until a connection is picked.
+    pub connection_id: Option<String>,
     pub editor: Entity<InputState>,
     pub table_state: Entity<DataTableState>,
-    pub table_context: Option<TableContext>,
+    /// Tables the result's columns were read from; empty when no column
+    /// maps to a base table.
+    pub table_contexts: Vec<TableContext>,
     pub result: Option<QueryResult>,
-    pub error: Option<String>,
+    /// Plan tree shown instead of the grid when the result is an
+    /// `EXPLAIN (FORMAT JSON)` plan.
+    pub plan: Option<Arc<QueryPlan>>,
+    /// Plans from the tab's recent explain runs, oldest first.
+    pub plan_history: Vec<CapturedPlan>,
+    /// Earlier plan the shown one is compared against.
+    pub plan_baseline: Option<Arc<QueryPlan>>,
+    pub error: Option<QueryError>,
+    /// The last run's error, placed where the server pointed in the
+    /// editor text. Dropped once the text changes.
+    pub error_diagnostic: Option<SqlLint>,
     pub is_loading: bool,
     pub query_start_time: Option<Instant>,
     pub query_task: Option<Task<()>>,
+    /// Backend PID of the connection running the tab's statement.
+    pub backend_pid: Option<i32>,
+    pub cancel_requested: bool,
+    /// Pinned session for manual transactions; `None` means autocommit.
+    pub transaction: Option<TabTransaction>,
+    /// Statements of the last run, one result sub-tab each.
+    pub statements: Vec<StatementRun>,
+    /// Statement whose result the grid shows.
+    pub active_statement: usize,
+    /// Saved query last loaded into the editor; its parameter values are
+    /// remembered with it.
+    pub saved_query_id: Option<String>,
+    /// Last values bound to each placeholder, by name (`$1`, `:id`).
+    pub parameter_values: HashMap<String, Option<String>>,
     pub last_export_message: Option<String>,
     pub table_structures: Vec<TableStructureInfo>,
     pub structure_loading: bool,
     pub structure_expanded: HashMap<String, bool>,
+    /// Searches the rows of the grid.
+    pub grid_search_input: Entity<TextInput>,
+    /// The query as the user ran it, while the grid shows it re-run with
+    /// its sort and search applied on the server.
+    pub grid_base_sql: Option<String>,
+    /// Pending re-run for a changed server-side sort or search.
+    pub grid_rerun: Option<Task<()>>,
+}
+
+/// A plan kept so a later run of the query can be compared with it.
+#[derive(Clone)]
+pub struct CapturedPlan {
+    pub plan: Arc<QueryPlan>,
+    pub sql: String,
+    pub captured_at: DateTime<Local>,
+}
+
+impl QueryTab {
+    /// The table whole rows of the result belong to, which rows can be
+    /// inserted into and deleted from; `None` when the columns come from
+    /// several tables or none.
+    pub fn row_table(&self) -> Option<&TableContext> {
+        match self.table_contexts.as_slice() {
+            [context] => Some(context),
+            _ => None,
+        }
+    }
+
+    /// The query the grid's sort and search are applied to when they run on
+    /// the server: the tab's one statement, if it reads one table's rows.
+    pub fn browse_sql(&self) -> Option<&str> {
+        self.row_table()?;
+        let sql = match (&self.grid_base_sql, self.statements.as_slice()) {
+            (Some(sql), _) => sql.as_str(),
+            (None, [statement]) if statement.params.is_none() => statement.sql.as_str(),
+            _ => return None,
+        };
+        browse_query(sql, &[], &[], "").map(|_| sql)
+    }
+}
+
+/// What part of the editor a run executes.
+#[derive(Clone, Copy, PartialEq)]
+pub enum RunScope {
+    Script,
+    StatementAtCursor,
+    Selection,
+}
+
+pub struct StatementRun {
+    pub sql: String,
+    /// Where `sql` starts in the editor, when it was run from there.
+    pub offset: Option<usize>,
+    pub params: Option<BoundParams>,
+    pub outcome: StatementOutcome,
+}
+
+/// A statement with its placeholders rewritten to `$n` casts, and the
+/// values bound to them.
+pub struct BoundParams {
+    pub sql: String,
+    pub values: Vec<Option<String>>,
+}
+
+pub enum StatementOutcome {
+    Pending,
+    Running,
+    Done(QueryResult),
+    Failed { error: QueryError, execution_time_ms: u64 },
+    /// Not run because an earlier statement failed.
+    Skipped,
+}
+
+/// What the safety dialog shows before a risky script runs.
+pub struct SafetyWarning {
+    pub findings: Vec<SqlFinding>,
+    /// For findings on a plain UPDATE or DELETE, the rows it would touch.
+    pub impacts: Vec<Option<RowImpact>>,
+    /// Set on protected connections, where the database name has to be
+    /// typed before the script runs.
+    pub confirm: Option<SafetyConfirm>,
+}
+
+impl SafetyWarning {
+    pub fn is_confirmed(&self, cx: &App) -> bool {
+        self.confirm
+            .as_ref()
+            .is_none_or(|c| c.input.read(cx).content().trim() == c.database)
+    }
+}
+
+pub struct SafetyConfirm {
+    pub database: String,
+    pub input: Entity<TextInput>,
+}
+
+pub struct RowImpact {
+    /// The `SELECT count(*)` behind an exact count.
+    pub count_sql: String,
+    /// From the planner; `None` while EXPLAIN runs.
+    pub estimated: Option<Result<i64, String>>,
+    /// Run only on request.
+    pub counted: Option<Result<i64, String>>,
+    pub counting: bool,
+}
+
+impl RowImpact {
+    pub fn new(count_sql: String) -> Self {
+        Self {
+            count_sql,
+            estimated: None,
+            counted: None,
+            counting: false,
+        }
+    }
+}
+
+/// An action that would end open transactions, held back until the user
+/// commits or rolls them back.
+#[derive(Clone, PartialEq)]
+pub enum TransactionHold {
+    CloseTab(TabId),
+    /// Connecting again drops every session on the connection, as
+    /// disconnecting does.
+    Reconnect(String),
+    Disconnect(String),
+    DeleteConnection(String),
+}
+
+pub struct TabTransaction {
+    pub session_id: String,
+    pub status: TransactionStatus,
+    /// Set while the session is being opened or a COMMIT/ROLLBACK is in flight.
+    pub is_busy: bool,
 }
 
 #[derive(Clone, PartialEq)]
@@ -115,6 +369,45 @@ pub enum ConnectionState {
     Error(String),
 }
 
+/// Live state for one connection profile.
+pub struct ConnectionSession {
+    pub profile: ConnectionProfile,
+    pub state: ConnectionState,
+    pub schemas: Arc<SchemaMap>,
+    pub schemas_loading: bool,
+    /// Table structures fetched for completion, by `schema.table`.
+    pub table_structures: HashMap<String, TableStructureInfo>,
+    /// Tables whose structure was asked for, so each is fetched once.
+    pub structures_requested: HashSet<String>,
+    pub catalog: Arc<CompletionCatalog>,
+    /// Bumped by every connect and disconnect, so the answer to an earlier
+    /// connect can tell it was overtaken.
+    pub attempt: u64,
+}
+
+impl ConnectionSession {
+    pub fn new(profile: ConnectionProfile) -> Self {
+        Self {
+            profile,
+            state: ConnectionState::Disconnected,
+            schemas: Arc::new(SchemaMap::new()),
+            schemas_loading: false,
+            table_structures: HashMap::new(),
+            structures_requested: HashSet::new(),
+            catalog: Arc::new(CompletionCatalog::default()),
+            attempt: 0,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.profile.id
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state == ConnectionState::Connected
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct SchemaObjects {
     pub tables: Vec<String>,
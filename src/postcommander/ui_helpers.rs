@@ -3,54 +3,16 @@ use crate::postcommander::database::QueryResult;
 use gpui::prelude::FluentBuilder;
 use gpui::*;
 
-pub fn parse_table_from_select(sql: &str) -> Option<(String, String)> {
-    let sql_upper = sql.to_uppercase();
-    let sql_trimmed = sql.trim();
-
-    if !sql_upper.trim_start().starts_with("SELECT") {
-        return None;
-    }
-
-    let from_pos = sql_upper.find(" FROM ")?;
-    let after_from = &sql_trimmed[from_pos + 6..];
-    let after_from = after_from.trim_start();
-
-    let end_pos = after_from
-        .to_uppercase()
-        .find(" WHERE ")
-        .or_else(|| after_from.to_uppercase().find(" ORDER "))
-        .or_else(|| after_from.to_uppercase().find(" LIMIT "))
-        .or_else(|| after_from.to_uppercase().find(" GROUP "))
-        .or_else(|| after_from.to_uppercase().find(" HAVING "))
-        .or_else(|| after_from.to_uppercase().find(" JOIN "))
-        .or_else(|| after_from.find(';'))
-        .unwrap_or(after_from.len());
-
-    let table_part = after_from[..end_pos].trim();
-
-    if table_part.to_uppercase().contains(" JOIN ") || table_part.contains(',') {
-        return None;
-    }
-
-    let (schema, table) = if table_part.contains('.') {
-        let parts: Vec<&str> = table_part.splitn(2, '.').collect();
-        if parts.len() == 2 {
-            (
-                parts[0].trim().trim_matches('"').to_string(),
-                parts[1].trim().trim_matches('"').to_string(),
-            )
-        } else {
-            return None;
+/// Converts a UTF-16 offset, as reported by text inputs, into a byte offset.
+pub fn utf16_to_byte_offset(text: &str, utf16_offset: usize) -> usize {
+    let mut utf16_count = 0;
+    for (byte_index, c) in text.char_indices() {
+        if utf16_count >= utf16_offset {
+            return byte_index;
         }
-    } else {
-        ("public".to_string(), table_part.trim_matches('"').to_string())
-    };
-
-    if table.is_empty() {
-        return None;
+        utf16_count += c.len_utf16();
     }
-
-    Some((schema, table))
+    text.len()
 }
 
 #[allow(dead_code)]
@@ -168,7 +130,7 @@ pub fn render_results_table(
                 .border_b_1()
                 .border_color(rgb(border_variant))
                 .children(row.iter().map(|cell| {
-                    let is_null = cell.as_ref() == "NULL";
+                    let is_null = cell.is_null();
 
                     div()
                         .flex_1()
@@ -184,7 +146,7 @@ pub fn render_results_table(
                                 .text_sm()
                                 .text_color(rgb(if is_null { text_muted } else { text }))
                                 .when(is_null, |el| el.italic())
-                                .child(cell.clone()),
+                                .child(cell.display()),
                         )
                 }))
         }))
@@ -48,11 +48,91 @@ impl ConnectionSettings {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ConnectionEnvironment {
+    Local,
+    #[default]
+    Development,
+    Staging,
+    Production,
+}
+
+impl ConnectionEnvironment {
+    pub const ALL: [ConnectionEnvironment; 4] = [
+        ConnectionEnvironment::Local,
+        ConnectionEnvironment::Development,
+        ConnectionEnvironment::Staging,
+        ConnectionEnvironment::Production,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConnectionEnvironment::Local => "Local",
+            ConnectionEnvironment::Development => "Dev",
+            ConnectionEnvironment::Staging => "Staging",
+            ConnectionEnvironment::Production => "Prod",
+        }
+    }
+
+    pub fn color(&self) -> u32 {
+        match self {
+            ConnectionEnvironment::Local => 0x94a3b8,
+            ConnectionEnvironment::Development => 0x4ade80,
+            ConnectionEnvironment::Staging => 0xfbbf24,
+            ConnectionEnvironment::Production => 0xf87171,
+        }
+    }
+}
+
+/// How far a connection guards against changes.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ConnectionAccess {
+    #[default]
+    ReadWrite,
+    /// Anything the safety check flags needs the database name typed to run.
+    Protected,
+    /// Sessions start with `default_transaction_read_only`, and writes are
+    /// refused before they reach the server.
+    ReadOnly,
+}
+
+impl ConnectionAccess {
+    pub const ALL: [ConnectionAccess; 3] = [
+        ConnectionAccess::ReadWrite,
+        ConnectionAccess::Protected,
+        ConnectionAccess::ReadOnly,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConnectionAccess::ReadWrite => "Read/write",
+            ConnectionAccess::Protected => "Protected",
+            ConnectionAccess::ReadOnly => "Read-only",
+        }
+    }
+}
+
+/// A named, saved connection. Several profiles can be connected at once.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionProfile {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub environment: ConnectionEnvironment,
+    #[serde(default)]
+    pub access: ConnectionAccess,
+    pub connection: ConnectionSettings,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum QueryHistoryStatus {
     Success,
     Error(String),
+    /// Cancelled locally before the statement reached the server.
     Cancelled,
+    /// A cancel was sent to the server; `confirmed` is false when the server
+    /// did not acknowledge it and the query was only abandoned locally.
+    ServerCancelled { confirmed: bool },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -109,6 +189,16 @@ pub struct SavedQueryEntry {
     pub description: Option<String>,
     pub created_at: String,
     pub last_used: Option<String>,
+    /// Values last bound to the query's placeholders.
+    #[serde(default)]
+    pub parameters: Vec<SavedQueryParameter>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedQueryParameter {
+    pub name: String,
+    /// `None` binds NULL.
+    pub value: Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -182,11 +272,98 @@ impl SavedQueriesSettings {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResultLimitSettings {
+    pub max_rows: Option<usize>,
+    pub max_memory_mb: usize,
+}
+
+impl Default for ResultLimitSettings {
+    fn default() -> Self {
+        Self {
+            max_rows: Some(100_000),
+            max_memory_mb: 256,
+        }
+    }
+}
+
+/// Letter case the formatter gives keywords or identifiers.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LetterCase {
+    #[default]
+    Upper,
+    Lower,
+    /// Left as written.
+    Preserve,
+}
+
+impl LetterCase {
+    pub const ALL: [LetterCase; 3] = [LetterCase::Upper, LetterCase::Lower, LetterCase::Preserve];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LetterCase::Upper => "UPPER",
+            LetterCase::Lower => "lower",
+            LetterCase::Preserve => "As typed",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CommaPlacement {
+    #[default]
+    Trailing,
+    /// At the start of the next line, as in `, name`.
+    Leading,
+}
+
+impl CommaPlacement {
+    pub const ALL: [CommaPlacement; 2] = [CommaPlacement::Trailing, CommaPlacement::Leading];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CommaPlacement::Trailing => "Trailing",
+            CommaPlacement::Leading => "Leading",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FormatterSettings {
+    pub indent_width: u8,
+    pub keyword_case: LetterCase,
+    /// Applies to unquoted names only; quoted identifiers keep their case.
+    pub identifier_case: LetterCase,
+    pub comma_placement: CommaPlacement,
+    /// Statements that fit within this many characters stay on one line.
+    /// Zero always breaks them up.
+    pub line_width: usize,
+}
+
+impl Default for FormatterSettings {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            keyword_case: LetterCase::Upper,
+            identifier_case: LetterCase::Preserve,
+            comma_placement: CommaPlacement::Trailing,
+            line_width: 80,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PostCommanderSettings {
+    /// Single connection saved by older versions, migrated into a profile.
     #[serde(default)]
     pub connection: Option<ConnectionSettings>,
     #[serde(default)]
+    pub connection_profiles: Option<Vec<ConnectionProfile>>,
+    /// Profile ids that were connected when the app last ran.
+    #[serde(default)]
+    pub open_connections: Option<Vec<String>>,
+    #[serde(default)]
     pub expanded_nodes: Option<Vec<String>>,
     #[serde(default)]
     pub sidebar_width: Option<f32>,
@@ -198,6 +375,62 @@ pub struct PostCommanderSettings {
     pub query_history: Option<QueryHistorySettings>,
     #[serde(default)]
     pub saved_queries: Option<SavedQueriesSettings>,
+    #[serde(default)]
+    pub result_limits: Option<ResultLimitSettings>,
+    #[serde(default)]
+    pub formatter: Option<FormatterSettings>,
+}
+
+impl PostCommanderSettings {
+    pub fn profiles(&self) -> &[ConnectionProfile] {
+        self.connection_profiles.as_deref().unwrap_or(&[])
+    }
+
+    /// Turns the legacy single connection into a profile that reconnects on
+    /// startup, as the old connection did. Returns whether anything changed.
+    pub fn migrate_legacy_connection(&mut self) -> bool {
+        let Some(conn) = self.connection.take() else {
+            return false;
+        };
+        let id = uuid::Uuid::new_v4().to_string();
+        let name = if conn.database.is_empty() {
+            conn.host.clone()
+        } else {
+            format!("{}@{}", conn.database, conn.host)
+        };
+        self.connection_profiles.get_or_insert_with(Vec::new).push(ConnectionProfile {
+            id: id.clone(),
+            name,
+            environment: ConnectionEnvironment::default(),
+            access: ConnectionAccess::default(),
+            connection: conn,
+        });
+        self.open_connections.get_or_insert_with(Vec::new).push(id);
+        true
+    }
+
+    pub fn upsert_profile(&mut self, profile: ConnectionProfile) {
+        let profiles = self.connection_profiles.get_or_insert_with(Vec::new);
+        match profiles.iter_mut().find(|p| p.id == profile.id) {
+            Some(existing) => *existing = profile,
+            None => profiles.push(profile),
+        }
+    }
+
+    pub fn remove_profile(&mut self, id: &str) {
+        if let Some(profiles) = self.connection_profiles.as_mut() {
+            profiles.retain(|p| p.id != id);
+        }
+        self.set_connection_open(id, false);
+    }
+
+    pub fn set_connection_open(&mut self, id: &str, open: bool) {
+        let open_ids = self.open_connections.get_or_insert_with(Vec::new);
+        open_ids.retain(|existing| existing != id);
+        if open {
+            open_ids.push(id.to_string());
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -275,12 +508,16 @@ impl AppSettings {
     pub fn postcommander(&self) -> &PostCommanderSettings {
         static DEFAULT: PostCommanderSettings = PostCommanderSettings {
             connection: None,
+            connection_profiles: None,
+            open_connections: None,
             expanded_nodes: None,
             sidebar_width: None,
             editor_height: None,
             structure_panel_width: None,
             query_history: None,
             saved_queries: None,
+            result_limits: None,
+            formatter: None,
         };
         self.postcommander.as_ref().unwrap_or(&DEFAULT)
     }
@@ -1,9 +1,24 @@
 use crate::icons::icon_sm;
-use crate::settings::AppSettings;
+use crate::settings::{
+    AppSettings, CommaPlacement, FormatterSettings, LetterCase, ResultLimitSettings,
+};
 use crate::theme::{ActiveTheme, GlobalTheme, ThemeMeta, ThemeRegistry};
 use gpui::prelude::FluentBuilder;
 use gpui::*;
 
+const ROW_LIMIT_OPTIONS: &[(Option<usize>, &str)] = &[
+    (Some(10_000), "10,000"),
+    (Some(100_000), "100,000"),
+    (Some(1_000_000), "1,000,000"),
+    (None, "No limit"),
+];
+
+const MEMORY_LIMIT_OPTIONS: &[(usize, &str)] = &[(64, "64 MB"), (256, "256 MB"), (1024, "1 GB")];
+
+const INDENT_OPTIONS: &[u8] = &[2, 4];
+
+const LINE_WIDTH_OPTIONS: &[(usize, &str)] = &[(0, "Always break"), (80, "80"), (120, "120")];
+
 struct SettingsWindowHandle(Option<WindowHandle<SettingsWindow>>);
 
 impl Global for SettingsWindowHandle {}
@@ -74,6 +89,32 @@ impl SettingsWindow {
         cx.notify();
     }
 
+    fn update_result_limits(
+        &mut self,
+        f: impl FnOnce(&mut ResultLimitSettings),
+        cx: &mut Context<Self>,
+    ) {
+        AppSettings::update_global(cx, |settings| {
+            f(settings
+                .postcommander_mut()
+                .result_limits
+                .get_or_insert_with(ResultLimitSettings::default));
+        });
+        AppSettings::get_global(cx).save();
+        cx.notify();
+    }
+
+    fn update_formatter(&mut self, f: impl FnOnce(&mut FormatterSettings), cx: &mut Context<Self>) {
+        AppSettings::update_global(cx, |settings| {
+            f(settings
+                .postcommander_mut()
+                .formatter
+                .get_or_insert_with(FormatterSettings::default));
+        });
+        AppSettings::get_global(cx).save();
+        cx.notify();
+    }
+
     fn toggle_theme_dropdown(&mut self, cx: &mut Context<Self>) {
         self.show_theme_dropdown = !self.show_theme_dropdown;
         cx.notify();
@@ -172,7 +213,6 @@ impl SettingsWindow {
             )
     }
 
-    #[allow(dead_code)]
     fn render_section_header(&self, title: &str, cx: &Context<Self>) -> impl IntoElement {
         let theme = cx.theme();
         let colors = theme.colors();
@@ -193,7 +233,6 @@ impl SettingsWindow {
             )
     }
 
-    #[allow(dead_code)]
     fn render_setting_row(
         &self,
         label: &str,
@@ -233,37 +272,213 @@ impl SettingsWindow {
             .child(control)
     }
 
-    fn render_general_section(&self, cx: &Context<Self>) -> impl IntoElement {
+    fn render_option_button(
+        &self,
+        id: SharedString,
+        label: &str,
+        is_selected: bool,
+        cx: &Context<Self>,
+    ) -> Stateful<Div> {
         let theme = cx.theme();
         let colors = theme.colors();
+        let text_color = colors.text;
         let text_muted = colors.text_muted;
-        let border_variant = colors.border_variant;
+        let border_color = colors.border;
+        let element = colors.element;
+        let element_hover = colors.element_hover;
+        let element_selected = colors.element_selected;
+
+        div()
+            .id(id)
+            .h(px(28.))
+            .px(px(10.))
+            .flex()
+            .items_center()
+            .rounded_sm()
+            .border_1()
+            .border_color(rgb(border_color))
+            .cursor_pointer()
+            .text_sm()
+            .when(is_selected, |el| el.bg(rgb(element_selected)).text_color(rgb(text_color)))
+            .when(!is_selected, |el| el.bg(rgb(element)).text_color(rgb(text_muted)))
+            .hover(move |style| style.bg(rgb(element_hover)))
+            .child(label.to_string())
+    }
+
+    fn render_general_section(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let limits = AppSettings::get_global(cx)
+            .postcommander()
+            .result_limits
+            .clone()
+            .unwrap_or_default();
+
+        let row_limit_buttons: Vec<_> = ROW_LIMIT_OPTIONS
+            .iter()
+            .enumerate()
+            .map(|(idx, (max_rows, label))| {
+                let max_rows = *max_rows;
+                self.render_option_button(
+                    SharedString::from(format!("row-limit-{}", idx)),
+                    label,
+                    limits.max_rows == max_rows,
+                    cx,
+                )
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_result_limits(|limits| limits.max_rows = max_rows, cx);
+                }))
+            })
+            .collect();
+
+        let memory_limit_buttons: Vec<_> = MEMORY_LIMIT_OPTIONS
+            .iter()
+            .enumerate()
+            .map(|(idx, (max_memory_mb, label))| {
+                let max_memory_mb = *max_memory_mb;
+                self.render_option_button(
+                    SharedString::from(format!("memory-limit-{}", idx)),
+                    label,
+                    limits.max_memory_mb == max_memory_mb,
+                    cx,
+                )
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_result_limits(|limits| limits.max_memory_mb = max_memory_mb, cx);
+                }))
+            })
+            .collect();
+
+        let formatter = AppSettings::get_global(cx)
+            .postcommander()
+            .formatter
+            .clone()
+            .unwrap_or_default();
+
+        let indent_buttons: Vec<_> = INDENT_OPTIONS
+            .iter()
+            .map(|indent_width| {
+                let indent_width = *indent_width;
+                self.render_option_button(
+                    SharedString::from(format!("indent-{}", indent_width)),
+                    &format!("{} spaces", indent_width),
+                    formatter.indent_width == indent_width,
+                    cx,
+                )
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_formatter(|f| f.indent_width = indent_width, cx);
+                }))
+            })
+            .collect();
+
+        let keyword_case_buttons: Vec<_> = LetterCase::ALL
+            .iter()
+            .map(|case| {
+                let case = *case;
+                self.render_option_button(
+                    SharedString::from(format!("keyword-case-{:?}", case)),
+                    case.label(),
+                    formatter.keyword_case == case,
+                    cx,
+                )
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_formatter(|f| f.keyword_case = case, cx);
+                }))
+            })
+            .collect();
+
+        let identifier_case_buttons: Vec<_> = LetterCase::ALL
+            .iter()
+            .map(|case| {
+                let case = *case;
+                self.render_option_button(
+                    SharedString::from(format!("identifier-case-{:?}", case)),
+                    case.label(),
+                    formatter.identifier_case == case,
+                    cx,
+                )
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_formatter(|f| f.identifier_case = case, cx);
+                }))
+            })
+            .collect();
+
+        let comma_buttons: Vec<_> = CommaPlacement::ALL
+            .iter()
+            .map(|placement| {
+                let placement = *placement;
+                self.render_option_button(
+                    SharedString::from(format!("commas-{:?}", placement)),
+                    placement.label(),
+                    formatter.comma_placement == placement,
+                    cx,
+                )
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_formatter(|f| f.comma_placement = placement, cx);
+                }))
+            })
+            .collect();
+
+        let line_width_buttons: Vec<_> = LINE_WIDTH_OPTIONS
+            .iter()
+            .map(|(line_width, label)| {
+                let line_width = *line_width;
+                self.render_option_button(
+                    SharedString::from(format!("line-width-{}", line_width)),
+                    label,
+                    formatter.line_width == line_width,
+                    cx,
+                )
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_formatter(|f| f.line_width = line_width, cx);
+                }))
+            })
+            .collect();
 
         div()
             .flex()
             .flex_col()
-            .child(
-                div()
-                    .flex()
-                    .items_center()
-                    .pb(px(8.))
-                    .mb(px(8.))
-                    .border_b_1()
-                    .border_color(rgb(border_variant))
-                    .child(
-                        div()
-                            .text_sm()
-                            .font_weight(FontWeight::MEDIUM)
-                            .text_color(rgb(text_muted))
-                            .child("General"),
-                    ),
-            )
-            .child(
-                div()
-                    .text_sm()
-                    .text_color(rgb(text_muted))
-                    .child("General settings will be available here."),
-            )
+            .child(self.render_section_header("Query Results", cx))
+            .child(self.render_setting_row(
+                "Row limit",
+                "Stop fetching once a result reaches this many rows.",
+                div().flex().gap(px(4.)).children(row_limit_buttons),
+                cx,
+            ))
+            .child(self.render_setting_row(
+                "Memory limit",
+                "Stop fetching once a result uses roughly this much memory.",
+                div().flex().gap(px(4.)).children(memory_limit_buttons),
+                cx,
+            ))
+            .child(self.render_section_header("SQL Formatting", cx))
+            .child(self.render_setting_row(
+                "Indent",
+                "Spaces per indent level.",
+                div().flex().gap(px(4.)).children(indent_buttons),
+                cx,
+            ))
+            .child(self.render_setting_row(
+                "Keyword case",
+                "How keywords such as SELECT and FROM are written.",
+                div().flex().gap(px(4.)).children(keyword_case_buttons),
+                cx,
+            ))
+            .child(self.render_setting_row(
+                "Identifier case",
+                "How unquoted table, column and function names are written.",
+                div().flex().gap(px(4.)).children(identifier_case_buttons),
+                cx,
+            ))
+            .child(self.render_setting_row(
+                "Commas",
+                "Whether list commas end a line or start the next one.",
+                div().flex().gap(px(4.)).children(comma_buttons),
+                cx,
+            ))
+            .child(self.render_setting_row(
+                "Line width",
+                "Statements shorter than this stay on one line.",
+                div().flex().gap(px(4.)).children(line_width_buttons),
+                cx,
+            ))
     }
 
     fn render_appearance_section(&self, cx: &mut Context<Self>) -> impl IntoElement {
@@ -468,8 +683,12 @@ impl SettingsWindow {
             )
             .child(
                 div()
+                    .id("settings-content")
                     .flex_1()
+                    .min_h_0()
+                    .overflow_y_scroll()
                     .px(px(32.))
+                    .pb(px(16.))
                     .child(content),
             )
     }